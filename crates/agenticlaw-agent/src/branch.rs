@@ -0,0 +1,197 @@
+//! Session branches — forking a session at a user turn, and the lineage
+//! tree of a workspace's sessions
+//!
+//! A fork is an ordinary .ctx file whose header names its parent and the
+//! last user turn it kept. User turns are counted from 1 over the `<up>`
+//! messages, steering and follow-ups included, the same count
+//! `Session::turn` keeps for checkpoints; tool results don't start a turn.
+
+use crate::ctx_file;
+use agenticlaw_ctx::{Event, ForkPoint, Header, StreamParser};
+use serde::Serialize;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Write a new .ctx file for `session_id` next to `parent`, holding the
+/// parent's events through the end of user turn `turn`. Returns its path.
+pub fn fork_ctx(parent: &Path, turn: usize, session_id: &str) -> std::io::Result<PathBuf> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+    let mut events = agenticlaw_ctx::parse(&fs::read_to_string(parent)?)
+        .events
+        .into_iter();
+    let Some(Event::Header(parent_header)) = events.next() else {
+        return Err(invalid(format!(
+            "{} has no session header",
+            parent.display()
+        )));
+    };
+    let events: Vec<Event> = events.collect();
+    let turns = user_turns(&events);
+    if turn > turns {
+        return Err(invalid(format!(
+            "turn {} is past the end of session {} ({} turns)",
+            turn, parent_header.id, turns
+        )));
+    }
+
+    let mut header = Header::new(
+        session_id,
+        &ctx_file::now_timestamp(),
+        parent_header.cwd.as_deref(),
+    );
+    header.forked_from = Some(ForkPoint {
+        session: parent_header.id,
+        turn,
+    });
+    let mut kept = vec![Event::Header(header)];
+    kept.extend(through_turn(events, turn));
+
+    let path = parent.with_file_name(ctx_file::ctx_file_name(session_id));
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    file.write_all(agenticlaw_ctx::render(&kept).as_bytes())?;
+    Ok(path)
+}
+
+/// Number of user turns among `events`.
+pub fn user_turns(events: &[Event]) -> usize {
+    events
+        .iter()
+        .filter(|e| matches!(e, Event::User { .. }))
+        .count()
+}
+
+/// The events before user turn `turn + 1`.
+fn through_turn(events: Vec<Event>, turn: usize) -> Vec<Event> {
+    let mut seen = 0;
+    events
+        .into_iter()
+        .take_while(|e| {
+            if matches!(e, Event::User { .. }) {
+                seen += 1;
+            }
+            seen <= turn
+        })
+        .collect()
+}
+
+/// One .ctx file in a lineage tree, with the sessions forked from it.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionNode {
+    pub session: String,
+    pub path: PathBuf,
+    pub started: String,
+    /// Parent session id, for forks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Last parent turn the fork kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fork_turn: Option<usize>,
+    pub children: Vec<SessionNode>,
+}
+
+/// The sessions under a workspace's `.agenticlaw/sessions`, oldest first,
+/// with forks nested under their parents. A fork whose parent file is gone
+/// is listed at the top level.
+pub fn session_tree(workspace: &Path) -> std::io::Result<Vec<SessionNode>> {
+    let dir = ctx_file::sessions_dir(workspace);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut nodes = Vec::new();
+    for entry in fs::read_dir(&dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().is_none_or(|e| e != "ctx") {
+            continue;
+        }
+        let Some(header) = read_header(&path)? else {
+            continue;
+        };
+        let (parent, fork_turn) = match header.forked_from {
+            Some(fork) => (Some(fork.session), Some(fork.turn)),
+            None => (None, None),
+        };
+        nodes.push(SessionNode {
+            session: header.id,
+            path,
+            started: header.started,
+            parent,
+            fork_turn,
+            children: Vec::new(),
+        });
+    }
+    nodes.sort_by(|a, b| (&a.started, &a.path).cmp(&(&b.started, &b.path)));
+
+    // When several files share the parent's id, the latest one wins
+    let mut parents: Vec<Option<usize>> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let id = node.parent.as_ref()?;
+            nodes
+                .iter()
+                .enumerate()
+                .rfind(|(j, n)| *j != i && &n.session == id)
+                .map(|(j, _)| j)
+        })
+        .collect();
+    // Hand-edited headers can name each other; break any loop
+    for i in 0..nodes.len() {
+        let mut at = parents[i];
+        for _ in 0..nodes.len() {
+            match at {
+                Some(p) if p == i => {
+                    parents[i] = None;
+                    break;
+                }
+                Some(p) => at = parents[p],
+                None => break,
+            }
+        }
+    }
+
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    for (i, parent) in parents.iter().enumerate() {
+        if let Some(p) = parent {
+            children[*p].push(i);
+        }
+    }
+    let mut slots: Vec<Option<SessionNode>> = nodes.into_iter().map(Some).collect();
+    let roots: Vec<usize> = (0..slots.len()).filter(|&i| parents[i].is_none()).collect();
+    Ok(roots
+        .into_iter()
+        .map(|i| nest(i, &mut slots, &children))
+        .collect())
+}
+
+fn nest(i: usize, slots: &mut [Option<SessionNode>], children: &[Vec<usize>]) -> SessionNode {
+    let mut node = slots[i].take().expect("each node has one parent");
+    node.children = children[i]
+        .iter()
+        .map(|&c| nest(c, slots, children))
+        .collect();
+    node
+}
+
+/// Read just the header of a .ctx file. `None` if it doesn't start with one.
+pub fn read_header(path: &Path) -> std::io::Result<Option<Header>> {
+    let mut parser = StreamParser::new();
+    let mut events = Vec::new();
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        let line = line?;
+        let end = line.is_empty();
+        events.extend(parser.feed(&line));
+        events.extend(parser.feed("\n"));
+        if end {
+            break;
+        }
+    }
+    events.extend(parser.finish());
+    Ok(match events.into_iter().next() {
+        Some(Event::Header(header)) => Some(header),
+        _ => None,
+    })
+}
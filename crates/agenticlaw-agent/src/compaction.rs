@@ -0,0 +1,272 @@
+//! Context compaction — summarising the oldest turns of a session as it
+//! nears its model's context window
+//!
+//! Everything before a cut point is replaced by an LLM-written summary and
+//! the turns after it are kept as they are. A cut never falls between a
+//! tool call and its result: the kept history always starts at an assistant
+//! turn, so the summary becomes the user message it answers. The summary is
+//! written to the .ctx file as a compaction block, which resuming replays.
+
+use crate::context::ContextManager;
+use crate::session::Session;
+use agenticlaw_llm::{
+    ContentBlock, LlmContent, LlmMessage, LlmProvider, LlmRequest, StreamDelta, Usage,
+};
+use futures::StreamExt;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Opens the user message standing in for the compacted turns.
+const SUMMARY_INTRO: &str =
+    "[Earlier turns of this conversation were compacted. Summary of what happened:]";
+
+const SUMMARY_SYSTEM: &str = "You summarise the start of a conversation between a user and a \
+tool-using agent so the agent can carry on without it. Keep the user's goals and constraints, \
+decisions made, files and commands involved with their outcomes, and anything still unresolved. \
+Be specific and concise. Reply with the summary only.";
+
+/// Longest tool result quoted to the summariser, in bytes.
+const RESULT_CHARS: usize = 4000;
+
+/// When to compact and how much history to keep verbatim.
+#[derive(Clone, Debug)]
+pub struct CompactionPolicy {
+    /// Compact before a request once the context passes this share of the window.
+    pub threshold_pct: f64,
+    /// Share of the window kept verbatim after the summary.
+    pub keep_recent_pct: f64,
+    /// Output allowance for the summary.
+    pub summary_tokens: u32,
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self {
+            threshold_pct: 0.8,
+            keep_recent_pct: 0.25,
+            summary_tokens: 4096,
+        }
+    }
+}
+
+impl CompactionPolicy {
+    pub fn should_compact(&self, tokens: usize, context_window: usize) -> bool {
+        tokens as f64 > self.threshold_pct * context_window as f64
+    }
+
+    pub fn threshold_tokens(&self, context_window: usize) -> usize {
+        (self.threshold_pct * context_window as f64) as usize
+    }
+
+    pub fn keep_tokens(&self, context_window: usize) -> usize {
+        (self.keep_recent_pct * context_window as f64) as usize
+    }
+}
+
+/// Outcome of one compaction.
+#[derive(Clone, Debug, Serialize)]
+pub struct CompactionReport {
+    /// Messages replaced by the summary.
+    pub summarized: usize,
+    /// Messages kept after it.
+    pub kept: usize,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+}
+
+/// Index of the first message to keep: the earliest assistant turn after
+/// the first message whose tail fits in `keep_tokens`, or failing that the
+/// last one. `None` when there is no assistant turn to cut at.
+pub fn split_point(messages: &[LlmMessage], keep_tokens: usize) -> Option<usize> {
+    let mut tail = vec![0; messages.len() + 1];
+    for i in (0..messages.len()).rev() {
+        tail[i] = tail[i + 1] + ContextManager::message_tokens(&messages[i]);
+    }
+    let cuts: Vec<usize> = (1..messages.len())
+        .filter(|&i| messages[i].role == "assistant")
+        .collect();
+    cuts.iter()
+        .copied()
+        .find(|&i| tail[i] <= keep_tokens)
+        .or(cuts.last().copied())
+}
+
+/// The user message a summary is sent as.
+pub fn summary_message(summary: &str) -> LlmMessage {
+    LlmMessage {
+        role: "user".to_string(),
+        content: LlmContent::Text(format!("{}\n\n{}", SUMMARY_INTRO, summary)),
+    }
+}
+
+/// `messages` as plain text for the summariser: tool calls with their
+/// arguments, long results cut short and media as placeholders.
+pub fn transcript(messages: &[LlmMessage]) -> String {
+    let mut out = String::new();
+    for message in messages {
+        let speaker = if message.role == "assistant" {
+            "Assistant"
+        } else {
+            "User"
+        };
+        out.push_str(speaker);
+        out.push_str(":\n");
+        match &message.content {
+            LlmContent::Text(text) => push_line(&mut out, text),
+            LlmContent::Blocks(blocks) => {
+                for block in blocks {
+                    push_block(&mut out, block);
+                }
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn push_block(out: &mut String, block: &ContentBlock) {
+    match block {
+        ContentBlock::Text { text } => push_line(out, text),
+        ContentBlock::ToolUse { name, input, .. } => {
+            push_line(out, &format!("[tool:{}] {}", name, input))
+        }
+        ContentBlock::ToolResult {
+            content, is_error, ..
+        } => {
+            let tag = if *is_error == Some(true) {
+                "[result error]"
+            } else {
+                "[result]"
+            };
+            let text = match content {
+                LlmContent::Text(text) => text.clone(),
+                LlmContent::Blocks(blocks) => {
+                    let mut inner = String::new();
+                    for block in blocks {
+                        push_block(&mut inner, block);
+                    }
+                    inner
+                }
+            };
+            push_line(out, &format!("{} {}", tag, cut_short(&text)));
+        }
+        ContentBlock::Image { .. } => push_line(out, "[image]"),
+        ContentBlock::Document { .. } => push_line(out, "[document]"),
+        ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {}
+    }
+}
+
+fn push_line(out: &mut String, text: &str) {
+    out.push_str(text.trim_end());
+    out.push('\n');
+}
+
+fn cut_short(text: &str) -> String {
+    if text.len() <= RESULT_CHARS {
+        return text.to_string();
+    }
+    let mut end = RESULT_CHARS;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}… ({} bytes cut)", &text[..end], text.len() - end)
+}
+
+/// Ask `model` to summarise `span`. Returns the summary and what the
+/// request cost; cancelling `cancel` abandons the request.
+pub async fn summarize(
+    provider: &dyn LlmProvider,
+    model: &str,
+    span: &[LlmMessage],
+    max_tokens: u32,
+    cancel: &CancellationToken,
+) -> Result<(String, Option<Usage>), String> {
+    let request = LlmRequest {
+        model: model.to_string(),
+        messages: vec![LlmMessage {
+            role: "user".to_string(),
+            content: LlmContent::Text(format!(
+                "Summarise this conversation:\n\n{}",
+                transcript(span)
+            )),
+        }],
+        max_tokens: Some(max_tokens),
+        system: Some(SUMMARY_SYSTEM.to_string()),
+        ..Default::default()
+    };
+    let mut stream = provider
+        .complete_stream(request, Some(cancel.clone()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut summary = String::new();
+    let mut usage = None;
+    while let Some(delta) = stream.next().await {
+        match delta.map_err(|e| e.to_string())? {
+            StreamDelta::Text(text) => summary.push_str(&text),
+            StreamDelta::Done { usage: u, .. } => usage = u,
+            StreamDelta::Error(e) => return Err(e),
+            _ => {}
+        }
+    }
+    if cancel.is_cancelled() {
+        return Err("compaction was cancelled".into());
+    }
+    let summary = summary.trim();
+    if summary.is_empty() {
+        return Err("the model returned an empty summary".into());
+    }
+    Ok((summary.to_string(), usage))
+}
+
+/// Summarise `session`'s history down to roughly its last `keep_tokens`.
+/// When even the turns that would be kept reach `max_tokens`, nothing is
+/// summarised: compacting could not bring the session under it, and would
+/// only be tried again on the next request.
+pub async fn compact(
+    session: &Session,
+    provider: &dyn LlmProvider,
+    model: &str,
+    keep_tokens: usize,
+    max_tokens: usize,
+    summary_tokens: u32,
+    cancel: &CancellationToken,
+) -> Result<CompactionReport, String> {
+    let tokens_before = session.token_count().await;
+    let messages = session.get_messages().await;
+    let cut = split_point(&messages, keep_tokens)
+        .ok_or_else(|| format!("Session {} has nothing to compact", session.key))?;
+    let kept_tokens: usize = messages[cut..]
+        .iter()
+        .map(ContextManager::message_tokens)
+        .sum();
+    if kept_tokens >= max_tokens {
+        return Err(format!(
+            "Session {} can't be compacted below {} tokens: its latest turns alone take {}",
+            session.key, max_tokens, kept_tokens
+        ));
+    }
+    let (summary, usage) =
+        summarize(provider, model, &messages[..cut], summary_tokens, cancel).await?;
+    if let Some(usage) = usage {
+        session
+            .record_side_usage(model, &usage, provider.model_info(model).pricing)
+            .await;
+    }
+    let kept = session.compact(cut, &summary).await;
+    let report = CompactionReport {
+        summarized: cut,
+        kept,
+        tokens_before,
+        tokens_after: session.token_count().await,
+    };
+    info!(
+        session = %session.key,
+        summarized = report.summarized,
+        kept = report.kept,
+        tokens_before = report.tokens_before,
+        tokens_after = report.tokens_after,
+        "Compacted context"
+    );
+    Ok(report)
+}
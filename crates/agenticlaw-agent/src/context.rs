@@ -3,6 +3,8 @@
 use agenticlaw_llm::{ContentBlock, LlmContent, LlmMessage};
 
 const CHARS_PER_TOKEN: f32 = 4.0;
+/// Flat estimate for an image or document block (a full-size image is ~1600).
+const MEDIA_TOKENS: usize = 1600;
 
 pub struct ContextManager {
     max_tokens: usize,
@@ -17,6 +19,14 @@ impl ContextManager {
         }
     }
 
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    pub fn set_max_tokens(&mut self, max_tokens: usize) {
+        self.max_tokens = max_tokens;
+    }
+
     pub fn estimate_tokens(text: &str) -> usize {
         (text.len() as f32 / CHARS_PER_TOKEN).ceil() as usize
     }
@@ -31,7 +41,19 @@ impl ContextManager {
                     ContentBlock::ToolUse { name, input, .. } => {
                         Self::estimate_tokens(name) + Self::estimate_tokens(&input.to_string())
                     }
-                    ContentBlock::ToolResult { content, .. } => Self::estimate_tokens(content),
+                    ContentBlock::ToolResult { content, .. } => match content {
+                        LlmContent::Text(s) => Self::estimate_tokens(s),
+                        LlmContent::Blocks(inner) => inner
+                            .iter()
+                            .map(|b| match b {
+                                ContentBlock::Text { text } => Self::estimate_tokens(text),
+                                _ => MEDIA_TOKENS,
+                            })
+                            .sum(),
+                    },
+                    ContentBlock::Thinking { thinking, .. } => Self::estimate_tokens(thinking),
+                    ContentBlock::RedactedThinking { data } => Self::estimate_tokens(data),
+                    ContentBlock::Image { .. } | ContentBlock::Document { .. } => MEDIA_TOKENS,
                 })
                 .sum(),
         };
@@ -46,25 +68,6 @@ impl ContextManager {
         let message_tokens: usize = messages.iter().map(Self::message_tokens).sum();
         self.system_tokens + message_tokens
     }
-
-    pub fn compact(&self, messages: &mut Vec<LlmMessage>) {
-        if messages.is_empty() {
-            return;
-        }
-        let total = self.calculate_total(messages);
-        if total <= self.max_tokens {
-            return;
-        }
-        let target = (self.max_tokens as f32 * 0.75) as usize;
-        while messages.len() > 2 && self.calculate_total(messages) > target {
-            messages.remove(1);
-        }
-        tracing::info!(
-            "Compacted context: {} messages, ~{} tokens",
-            messages.len(),
-            self.calculate_total(messages)
-        );
-    }
 }
 
 #[cfg(test)]
@@ -1,35 +1,21 @@
 //! .ctx file operations — create, append, read
 //!
-//! A .ctx file is the source of truth for an agent session. This module
-//! handles all disk I/O for .ctx files. The format is plain text:
-//!
-//! ```text
-//! --- session: <id> ---
-//! started: <ISO 8601>
-//! cwd: <path>
-//!
-//! --- <timestamp> ---
-//! [concatenated SOUL.md/AGENTS.md/IDENTITY.md/... content]
-//!
-//! --- <timestamp> ---
-//! <up>
-//! User message here.
-//! </up>
-//!
-//! --- <timestamp> ---
-//! Assistant response here.
-//! [tool:read] /path/to/file
-//!
-//! --- <timestamp> ---
-//! <up>
-//! [tool result content]
-//! </up>
-//! ```
-
-use std::fs::{self, OpenOptions};
-use std::io::Write;
+//! A .ctx file is the source of truth for an agent session. The format —
+//! events, writer and parser — is defined in `agenticlaw_ctx`; this module
+//! maps it to and from LLM messages and handles the disk I/O around it:
+//! creating files, storing attachments, finding sessions and resuming them.
+
+use crate::usage::SessionUsage;
+use agenticlaw_ctx::{Attachment, Block, Event, Header, TokenUsage};
+use agenticlaw_llm::{
+    extension_for_media_type, ContentBlock, LlmContent, LlmMessage, MediaSource, Usage,
+};
+use base64::Engine;
+use std::fs;
 use std::path::{Path, PathBuf};
 
+pub use agenticlaw_ctx::append;
+
 /// Create a new .ctx file with session header and preloaded context files.
 pub fn create(
     path: &Path,
@@ -42,81 +28,239 @@ pub fn create(
         fs::create_dir_all(parent)?;
     }
 
-    let mut out = String::new();
-    out.push_str(&format!("--- session: {} ---\n", session_id));
-    out.push_str(&format!("started: {}\n", timestamp));
-    if let Some(cwd) = cwd {
-        out.push_str(&format!("cwd: {}\n", cwd));
-    }
-    out.push('\n');
-
+    let mut events = vec![Event::Header(Header::new(session_id, timestamp, cwd))];
     if !context_files.is_empty() {
-        out.push_str(&format!("--- {} ---\n", timestamp));
-        for (i, content) in context_files.iter().enumerate() {
-            out.push_str(content);
-            if !content.ends_with('\n') {
-                out.push('\n');
-            }
-            if i < context_files.len() - 1 {
-                out.push('\n');
-            }
-        }
-        out.push('\n');
+        let text: Vec<&str> = context_files
+            .iter()
+            .map(|c| c.trim_end_matches('\n'))
+            .collect();
+        events.push(Event::Context {
+            timestamp: timestamp.to_string(),
+            text: text.join("\n\n"),
+        });
     }
-
-    fs::write(path, out)
+    fs::write(path, agenticlaw_ctx::render(&events))
 }
 
 /// Append a user message wrapped in <up> tags.
 pub fn append_user_message(path: &Path, timestamp: &str, content: &str) -> std::io::Result<()> {
-    let mut f = OpenOptions::new().append(true).open(path)?;
-    write!(f, "--- {} ---\n<up>\n{}\n</up>\n\n", timestamp, content)
+    append(
+        path,
+        &Event::User {
+            timestamp: timestamp.to_string(),
+            text: content.to_string(),
+            attachments: Vec::new(),
+        },
+    )
 }
 
 /// Append assistant text (model output, no <up> tags).
 pub fn append_assistant_text(path: &Path, timestamp: &str, content: &str) -> std::io::Result<()> {
-    let mut f = OpenOptions::new().append(true).open(path)?;
-    write!(f, "--- {} ---\n{}\n\n", timestamp, content)
-}
-
-/// Append a tool call line to the current assistant block.
-pub fn append_tool_call(path: &Path, name: &str, args_summary: &str) -> std::io::Result<()> {
-    let mut f = OpenOptions::new().append(true).open(path)?;
-    writeln!(f, "[tool:{}] {}", name, args_summary)
+    append(
+        path,
+        &Event::Assistant {
+            timestamp: timestamp.to_string(),
+            blocks: vec![Block::Text(content.to_string())],
+            usage: None,
+        },
+    )
 }
 
-/// Append a tool result as <up> (input to the model from outside).
+/// Append a tool result as <up> (input to the model from outside), kept whole.
 pub fn append_tool_result(
     path: &Path,
     timestamp: &str,
-    name: &str,
+    tool_use_id: &str,
     content: &str,
     is_error: bool,
 ) -> std::io::Result<()> {
-    let mut f = OpenOptions::new().append(true).open(path)?;
-    let prefix = if is_error { "error: " } else { "" };
-    // Truncate very long results for .ctx readability
-    let display = if content.lines().count() > 30 {
-        let lines: Vec<&str> = content.lines().collect();
-        format!(
-            "{}\n  ... ({} lines omitted)\n{}",
-            lines[..15].join("\n"),
-            lines.len() - 30,
-            lines[lines.len() - 15..].join("\n")
-        )
-    } else {
-        content.to_string()
-    };
-    write!(
-        f,
-        "--- {} ---\n<up>\n[tool:{}] {}{}\n</up>\n\n",
-        timestamp,
-        name,
-        prefix,
-        display.trim()
+    append(
+        path,
+        &Event::ToolResult {
+            timestamp: timestamp.to_string(),
+            tool_use_id: tool_use_id.to_string(),
+            content: content.to_string(),
+            attachments: Vec::new(),
+            is_error,
+        },
     )
 }
 
+/// Append a note recording that the workspace was rolled back.
+pub fn append_rewind_note(path: &Path, timestamp: &str, note: &str) -> std::io::Result<()> {
+    append(
+        path,
+        &Event::Rewind {
+            timestamp: timestamp.to_string(),
+            note: note.to_string(),
+        },
+    )
+}
+
+/// The .ctx form of an assistant block: thinking, text or a tool call.
+pub fn ctx_block(block: &ContentBlock) -> Option<Block> {
+    Some(match block {
+        ContentBlock::Thinking {
+            thinking,
+            signature,
+        } => Block::Thinking {
+            text: thinking.clone(),
+            signature: Some(signature.clone()),
+        },
+        ContentBlock::RedactedThinking { data } => Block::RedactedThinking { data: data.clone() },
+        ContentBlock::Text { text } => Block::Text(text.clone()),
+        ContentBlock::ToolUse { id, name, input } => Block::ToolCall {
+            id: id.clone(),
+            name: name.clone(),
+            input: input.clone(),
+        },
+        _ => return None,
+    })
+}
+
+/// Add a tool result to the history: all results answering one assistant
+/// turn share a single user message, as the API requires.
+fn push_tool_result(messages: &mut Vec<LlmMessage>, block: ContentBlock) {
+    if let Some(LlmMessage {
+        role,
+        content: LlmContent::Blocks(blocks),
+    }) = messages.last_mut()
+    {
+        if role == "user"
+            && blocks
+                .iter()
+                .any(|b| matches!(b, ContentBlock::ToolResult { .. }))
+        {
+            blocks.push(block);
+            return;
+        }
+    }
+    messages.push(LlmMessage {
+        role: "user".to_string(),
+        content: LlmContent::Blocks(vec![block]),
+    });
+}
+
+/// How many .ctx turns a history message was written as: one per tool
+/// result for a message of them, else one.
+pub fn event_count(message: &LlmMessage) -> usize {
+    match &message.content {
+        LlmContent::Blocks(blocks) if message.role == "user" => {
+            let results = blocks
+                .iter()
+                .filter(|b| matches!(b, ContentBlock::ToolResult { .. }))
+                .count();
+            results.max(1)
+        }
+        _ => 1,
+    }
+}
+
+/// Directory holding a session's attachments, next to its .ctx file.
+pub fn attachments_dir(ctx_path: &Path) -> PathBuf {
+    ctx_path.with_extension("attachments")
+}
+
+/// Copy media into the session's attachments directory and return a source
+/// referencing the copy, so the session keeps working after the original
+/// file moves or is deleted. Sources already in the directory are returned
+/// unchanged.
+pub fn save_attachment(ctx_path: &Path, source: &MediaSource) -> std::io::Result<MediaSource> {
+    let dir = attachments_dir(ctx_path);
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+    let media_type = source.media_type();
+    let bytes = match source {
+        MediaSource::Path { path, .. } if path.starts_with(&dir) => return Ok(source.clone()),
+        MediaSource::Path { path, .. } => fs::read(path)?,
+        MediaSource::Base64 { data, .. } => base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| invalid(format!("invalid base64: {}", e)))?,
+    };
+    let ext = extension_for_media_type(media_type)
+        .ok_or_else(|| invalid(format!("unsupported media type: {}", media_type)))?;
+
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.{}", uuid::Uuid::new_v4().simple(), ext));
+    fs::write(&path, bytes)?;
+    Ok(MediaSource::Path {
+        media_type: media_type.to_string(),
+        path,
+    })
+}
+
+/// The `[attachment:...]` reference for an image/document block. `None`
+/// for other blocks and for media that isn't on disk.
+pub fn ctx_attachment(block: &ContentBlock) -> Option<Attachment> {
+    match block.media_source()? {
+        MediaSource::Path { media_type, path } => Some(Attachment {
+            media_type: media_type.clone(),
+            path: path.clone(),
+        }),
+        MediaSource::Base64 { .. } => None,
+    }
+}
+
+fn media_block(attachment: &Attachment) -> ContentBlock {
+    let source = MediaSource::Path {
+        media_type: attachment.media_type.clone(),
+        path: attachment.path.clone(),
+    };
+    if attachment.media_type.starts_with("image/") {
+        ContentBlock::Image { source }
+    } else {
+        ContentBlock::Document {
+            source,
+            title: None,
+        }
+    }
+}
+
+/// Message content for a resumed `<up>` turn: attachments become image and
+/// document blocks again when their files still exist, and stay as their
+/// reference lines otherwise.
+pub fn user_content(text: &str, attachments: &[Attachment]) -> LlmContent {
+    let (media, missing): (Vec<_>, Vec<_>) = attachments.iter().partition(|a| a.path.is_file());
+    let mut text = text.to_string();
+    for a in missing {
+        text.push_str(&format!(
+            "\n[attachment:{}] {}",
+            a.media_type,
+            a.path.display()
+        ));
+    }
+    if media.is_empty() {
+        return LlmContent::Text(text);
+    }
+    let mut blocks = Vec::new();
+    if !text.trim().is_empty() {
+        blocks.push(ContentBlock::Text { text });
+    }
+    blocks.extend(media.into_iter().map(media_block));
+    LlmContent::Blocks(blocks)
+}
+
+/// Per-turn usage as recorded in the `[tokens: ...]` line.
+pub fn ctx_usage(model: &str, usage: &Usage) -> TokenUsage {
+    TokenUsage {
+        input: usage.input_tokens.into(),
+        output: usage.output_tokens.into(),
+        cache_read: usage.cache_read_input_tokens.into(),
+        cache_write: usage.cache_creation_input_tokens.into(),
+        total: usage.total_tokens().into(),
+        model: Some(model.to_string()),
+    }
+}
+
+fn llm_usage(usage: &TokenUsage) -> Usage {
+    let tokens = |n: u64| u32::try_from(n).unwrap_or(u32::MAX);
+    Usage {
+        input_tokens: tokens(usage.input),
+        output_tokens: tokens(usage.output),
+        cache_creation_input_tokens: tokens(usage.cache_write),
+        cache_read_input_tokens: tokens(usage.cache_read),
+    }
+}
+
 /// Read the entire .ctx file contents.
 pub fn read(path: &Path) -> std::io::Result<String> {
     fs::read_to_string(path)
@@ -136,19 +280,26 @@ pub fn discover_preload_files(workspace: &Path) -> Vec<String> {
         .collect()
 }
 
+/// Directory holding a workspace's .ctx files.
+pub fn sessions_dir(workspace: &Path) -> PathBuf {
+    workspace.join(".agenticlaw").join("sessions")
+}
+
 /// Generate the .ctx file path for a session within a workspace.
 /// Format: <workspace>/.agenticlaw/sessions/<YYYYMMDD-HHMMSS>-<session_id>.ctx
 pub fn session_ctx_path(workspace: &Path, session_id: &str) -> PathBuf {
+    sessions_dir(workspace).join(ctx_file_name(session_id))
+}
+
+/// File name for a session's .ctx file created now.
+pub fn ctx_file_name(session_id: &str) -> String {
     let now = chrono::Utc::now().format("%Y%m%d-%H%M%S");
-    workspace
-        .join(".agenticlaw")
-        .join("sessions")
-        .join(format!("{}-{}.ctx", now, session_id))
+    format!("{}-{}.ctx", now, session_id)
 }
 
 /// Find the latest .ctx file in a workspace's session directory.
 pub fn find_latest(workspace: &Path) -> Option<PathBuf> {
-    let sessions_dir = workspace.join(".agenticlaw").join("sessions");
+    let sessions_dir = sessions_dir(workspace);
     if !sessions_dir.is_dir() {
         return None;
     }
@@ -166,88 +317,83 @@ pub fn find_latest(workspace: &Path) -> Option<PathBuf> {
 }
 
 /// Parse a .ctx file back into (system_prompt, messages) for resuming a session.
-/// Returns (session_id, system_prompt, Vec<(role, content)>).
+/// Tool calls and results come back as the blocks they were written from;
+/// thinking is not resumed. A compaction block replaces the turns before it
+/// but the ones it kept with its summary.
 pub fn parse_for_resume(path: &Path) -> std::io::Result<ResumedSession> {
     let content = fs::read_to_string(path)?;
     let mut session_id = String::new();
     let mut system_parts: Vec<String> = Vec::new();
-    let mut messages: Vec<(String, String)> = Vec::new(); // (role, content)
-
-    let lines: Vec<&str> = content.lines().collect();
-    let mut i = 0;
-    let mut first_turn = true;
-
-    while i < lines.len() {
-        let line = lines[i];
-
-        // Session header
-        if let Some(id) = line
-            .strip_prefix("--- session: ")
-            .and_then(|s| s.strip_suffix(" ---"))
-        {
-            session_id = id.to_string();
-            i += 1;
-            // Skip header fields
-            while i < lines.len() && !lines[i].is_empty() {
-                i += 1;
-            }
-            i += 1; // blank line
-            continue;
-        }
-
-        // Turn separator: --- <timestamp> ---
-        if line.starts_with("--- ") && line.ends_with(" ---") {
-            i += 1;
-            if i >= lines.len() {
-                break;
-            }
-
-            // Check for <up> tag
-            let is_up = lines[i] == "<up>";
-            if is_up {
-                i += 1;
+    let mut messages: Vec<LlmMessage> = Vec::new();
+    // Turn each message starts at, counting user, assistant and tool result
+    // turns; `None` for a compaction summary
+    let mut starts: Vec<Option<usize>> = Vec::new();
+    let mut turns = 0;
+    let mut user_turns = 0;
+    let mut usage = SessionUsage::default();
+
+    for event in agenticlaw_ctx::parse(&content).events {
+        match event {
+            Event::Header(header) => session_id = header.id,
+            Event::Context { text, .. } => system_parts.push(text),
+            Event::User {
+                text, attachments, ..
+            } => {
+                messages.push(LlmMessage {
+                    role: "user".to_string(),
+                    content: user_content(&text, &attachments),
+                });
+                starts.push(Some(turns));
+                turns += 1;
+                user_turns += 1;
             }
-
-            // Collect turn content
-            let mut turn_lines = Vec::new();
-            while i < lines.len() {
-                if is_up && lines[i] == "</up>" {
-                    i += 1;
-                    break;
+            Event::ToolResult {
+                tool_use_id,
+                content,
+                attachments,
+                is_error,
+                ..
+            } => {
+                push_tool_result(
+                    &mut messages,
+                    ContentBlock::ToolResult {
+                        tool_use_id,
+                        content: user_content(&content, &attachments),
+                        is_error: is_error.then_some(true),
+                    },
+                );
+                if starts.len() < messages.len() {
+                    starts.push(Some(turns));
                 }
-                if !is_up && lines[i].starts_with("--- ") && lines[i].ends_with(" ---") {
-                    break;
+                turns += 1;
+            }
+            Event::Assistant {
+                blocks,
+                usage: turn_usage,
+                ..
+            } => {
+                if let Some(turn_usage) = turn_usage {
+                    let model = turn_usage.model.clone().unwrap_or_default();
+                    usage.record(&model, &llm_usage(&turn_usage), None);
                 }
-                if !is_up
-                    && lines[i].is_empty()
-                    && (i + 1 >= lines.len() || lines[i + 1].starts_with("--- "))
-                {
-                    i += 1;
-                    break;
+                if let Some(message) = resumed_assistant(blocks) {
+                    messages.push(message);
+                    starts.push(Some(turns));
                 }
-                turn_lines.push(lines[i]);
-                i += 1;
+                turns += 1;
             }
-
-            let text = turn_lines.join("\n");
-            if text.trim().is_empty() {
-                continue;
-            }
-
-            // First non-up turn is the preloaded system context
-            if first_turn && !is_up {
-                system_parts.push(text);
-                first_turn = false;
-                continue;
+            Event::Compaction { summary, kept, .. } => {
+                let first_kept = turns.saturating_sub(kept);
+                let cut = starts
+                    .iter()
+                    .position(|s| s.is_some_and(|s| s >= first_kept))
+                    .unwrap_or(messages.len());
+                messages.splice(..cut, [crate::compaction::summary_message(&summary)]);
+                starts.splice(..cut, [None]);
             }
-            first_turn = false;
-
-            let role = if is_up { "user" } else { "assistant" };
-            messages.push((role.to_string(), text));
-            continue;
+            // Informational only
+            Event::ModelChange { .. } | Event::ThinkingLevel { .. } | Event::Rewind { .. } => {}
         }
-
-        i += 1;
     }
 
     let system_prompt = if system_parts.is_empty() {
@@ -261,6 +407,45 @@ pub fn parse_for_resume(path: &Path) -> std::io::Result<ResumedSession> {
         ctx_path: path.to_path_buf(),
         system_prompt,
         messages,
+        usage,
+        user_turns,
+    })
+}
+
+/// An assistant turn as the API saw it: its text in one block, then its
+/// tool calls. Old summary-only tool lines stay part of the text.
+fn resumed_assistant(blocks: Vec<Block>) -> Option<LlmMessage> {
+    let mut text = Vec::new();
+    let mut calls = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Text(t) => text.push(t),
+            Block::ToolCall { id, name, input } => {
+                calls.push(ContentBlock::ToolUse { id, name, input })
+            }
+            Block::LegacyTool { name, summary, .. } => {
+                text.push(format!("[tool:{}] {}", name, summary))
+            }
+            Block::Thinking { .. } | Block::RedactedThinking { .. } => {}
+        }
+    }
+    let text = text.join("\n");
+    let content = if calls.is_empty() {
+        if text.trim().is_empty() {
+            return None;
+        }
+        LlmContent::Text(text)
+    } else {
+        let mut content = Vec::new();
+        if !text.trim().is_empty() {
+            content.push(ContentBlock::Text { text });
+        }
+        content.extend(calls);
+        LlmContent::Blocks(content)
+    };
+    Some(LlmMessage {
+        role: "assistant".to_string(),
+        content,
     })
 }
 
@@ -268,7 +453,12 @@ pub struct ResumedSession {
     pub session_id: String,
     pub ctx_path: PathBuf,
     pub system_prompt: Option<String>,
-    pub messages: Vec<(String, String)>,
+    /// History as it was sent, attachments and tool blocks included.
+    pub messages: Vec<LlmMessage>,
+    /// Token usage recovered from `[tokens: ...]` lines.
+    pub usage: SessionUsage,
+    /// Number of `<up>` messages, compacted ones included.
+    pub user_turns: usize,
 }
 
 /// Get current timestamp in ISO 8601 format.
@@ -338,16 +528,72 @@ mod tests {
         append_tool_result(
             &path,
             "2026-02-16T12:00:03Z",
-            "read",
+            "toolu_1",
             "file contents here",
             false,
         )
         .unwrap();
+        append_tool_result(&path, "2026-02-16T12:00:04Z", "toolu_2", "denied", true).unwrap();
 
         let content = read(&path).unwrap();
-        assert!(content.contains("<up>"));
-        assert!(content.contains("[tool:read] file contents here"));
-        assert!(content.contains("</up>"));
+        assert!(content.contains("<up>\n[result id=toolu_1]\nfile contents here\n</up>"));
+        assert!(content.contains("<up>\n[result id=toolu_2 error]\ndenied\n</up>"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn tool_calls_and_results_round_trip_through_resume() {
+        let dir = test_path();
+        let path = dir.join("tools.ctx");
+        create(&path, "r1", "2026-02-16T12:00:00Z", None, &[]).unwrap();
+
+        let input = serde_json::json!({"command": "cat up.ctx", "timeout": 5});
+        append_user_message(&path, "2026-02-16T12:00:01Z", "Show it").unwrap();
+        append(
+            &path,
+            &Event::Assistant {
+                timestamp: "2026-02-16T12:00:02Z".into(),
+                blocks: vec![
+                    Block::Text("Running it.".into()),
+                    Block::ToolCall {
+                        id: "toolu_1".into(),
+                        name: "bash".into(),
+                        input: input.clone(),
+                    },
+                ],
+                usage: None,
+            },
+        )
+        .unwrap();
+        // Output that would otherwise close the block, and a trailing newline
+        let output = "<up>\n</up>\n\\</up>\n[tokens: 1 in]\n";
+        append_tool_result(&path, "2026-02-16T12:00:03Z", "toolu_1", output, false).unwrap();
+        append_tool_result(&path, "2026-02-16T12:00:03Z", "toolu_2", "", true).unwrap();
+        append_assistant_text(&path, "2026-02-16T12:00:04Z", "Done.").unwrap();
+
+        let resumed = parse_for_resume(&path).unwrap();
+        let roles: Vec<&str> = resumed.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "user", "assistant"]);
+        let LlmContent::Blocks(call) = &resumed.messages[1].content else {
+            panic!("Expected blocks");
+        };
+        assert!(matches!(&call[0], ContentBlock::Text { text } if text == "Running it."));
+        assert!(
+            matches!(&call[1], ContentBlock::ToolUse { id, name, input: args }
+            if id == "toolu_1" && name == "bash" && *args == input)
+        );
+        let LlmContent::Blocks(results) = &resumed.messages[2].content else {
+            panic!("Expected blocks");
+        };
+        assert!(
+            matches!(&results[0], ContentBlock::ToolResult { tool_use_id, content, is_error: None }
+            if tool_use_id == "toolu_1" && content.to_text() == output)
+        );
+        assert!(
+            matches!(&results[1], ContentBlock::ToolResult { tool_use_id, content, is_error: Some(true) }
+            if tool_use_id == "toolu_2" && content.to_text().is_empty())
+        );
+        assert_eq!(resumed.usage.totals.input_tokens, 0);
         let _ = fs::remove_dir_all(&dir);
     }
 
@@ -387,9 +633,31 @@ mod tests {
         .unwrap();
 
         append_user_message(&path, "2026-02-16T12:00:01Z", "Read /tmp/foo.txt").unwrap();
-        append_assistant_text(&path, "2026-02-16T12:00:02Z", "Let me read that file.").unwrap();
-        append_tool_call(&path, "read", "/tmp/foo.txt").unwrap();
-        append_tool_result(&path, "2026-02-16T12:00:03Z", "read", "hello world", false).unwrap();
+        let call = ContentBlock::ToolUse {
+            id: "toolu_1".into(),
+            name: "read".into(),
+            input: serde_json::json!({"file_path": "/tmp/foo.txt"}),
+        };
+        append(
+            &path,
+            &Event::Assistant {
+                timestamp: "2026-02-16T12:00:02Z".into(),
+                blocks: vec![
+                    Block::Text("Let me read that file.".into()),
+                    ctx_block(&call).unwrap(),
+                ],
+                usage: None,
+            },
+        )
+        .unwrap();
+        append_tool_result(
+            &path,
+            "2026-02-16T12:00:03Z",
+            "toolu_1",
+            "hello world",
+            false,
+        )
+        .unwrap();
         append_assistant_text(
             &path,
             "2026-02-16T12:00:04Z",
@@ -400,14 +668,162 @@ mod tests {
         let content = read(&path).unwrap();
 
         // Verify structure
-        assert!(content.contains("--- session: conv-001 ---"));
-        assert!(content.contains("You are helpful."));
+        assert!(content.contains("--- session: conv-001 ---\nformat: 1\n"));
+        assert!(content.contains("[context] ---\nYou are helpful.\n"));
         assert!(content.contains("<up>\nRead /tmp/foo.txt\n</up>"));
         assert!(content.contains("Let me read that file."));
-        assert!(content.contains("[tool:read] /tmp/foo.txt"));
-        assert!(content.contains("<up>\n[tool:read] hello world\n</up>"));
+        assert!(content.contains("[tool:read id=toolu_1] {\"file_path\":\"/tmp/foo.txt\"}"));
+        assert!(content.contains("<up>\n[result id=toolu_1]\nhello world\n</up>"));
         assert!(content.contains("The file contains: hello world"));
 
         let _ = fs::remove_dir_all(&dir);
     }
+
+    #[test]
+    fn usage_line_round_trips_through_resume() {
+        let dir = test_path();
+        let path = dir.join("usage.ctx");
+        create(&path, "u1", "2026-02-16T12:00:00Z", None, &[]).unwrap();
+
+        let usage = Usage {
+            input_tokens: 1200,
+            output_tokens: 85,
+            cache_creation_input_tokens: 0,
+            cache_read_input_tokens: 9000,
+        };
+        let turn_usage = ctx_usage("claude-opus-4-6", &usage);
+        assert_eq!(llm_usage(&turn_usage), usage);
+
+        append_user_message(&path, "2026-02-16T12:00:01Z", "Hello").unwrap();
+        append(
+            &path,
+            &Event::Assistant {
+                timestamp: "2026-02-16T12:00:02Z".into(),
+                blocks: vec![Block::Text("Hi!".into())],
+                usage: Some(turn_usage),
+            },
+        )
+        .unwrap();
+        assert!(read(&path).unwrap().contains(
+            "Hi!\n[tokens: 1200 in, 85 out, 9000 cached, 0 cache-write, 10285 total, claude-opus-4-6]\n"
+        ));
+
+        let resumed = parse_for_resume(&path).unwrap();
+        assert_eq!(resumed.messages.last().unwrap().content.to_text(), "Hi!");
+        assert_eq!(resumed.usage.totals.input_tokens, 1200);
+        assert_eq!(resumed.usage.totals.cache_read_input_tokens, 9000);
+        assert!(resumed.usage.by_model.contains_key("claude-opus-4-6"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn thinking_blocks_persist_and_skip_on_resume() {
+        let dir = test_path();
+        let path = dir.join("thinking.ctx");
+        create(&path, "t1", "2026-02-16T12:00:00Z", None, &[]).unwrap();
+
+        let blocks = [
+            ContentBlock::Thinking {
+                thinking: "Check the file.\nThen answer.".into(),
+                signature: "c2ln".into(),
+            },
+            ContentBlock::RedactedThinking {
+                data: "b3BhcXVl".into(),
+            },
+            ContentBlock::Text {
+                text: "The answer.".into(),
+            },
+        ];
+        assert!(ctx_block(&ContentBlock::Image {
+            source: MediaSource::Path {
+                media_type: "image/png".into(),
+                path: PathBuf::from("/tmp/x.png"),
+            },
+        })
+        .is_none());
+
+        append_user_message(&path, "2026-02-16T12:00:01Z", "Hello").unwrap();
+        append(
+            &path,
+            &Event::Assistant {
+                timestamp: "2026-02-16T12:00:02Z".into(),
+                blocks: blocks.iter().filter_map(ctx_block).collect(),
+                usage: None,
+            },
+        )
+        .unwrap();
+
+        let content = read(&path).unwrap();
+        assert!(content
+            .contains("<thinking signature=\"c2ln\">\nCheck the file.\nThen answer.\n</thinking>"));
+        let resumed = parse_for_resume(&path).unwrap();
+        assert_eq!(
+            resumed.messages.last().unwrap().content.to_text(),
+            "The answer."
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn attachments_saved_referenced_and_restored() {
+        let dir = test_path();
+        let path = dir.join("att.ctx");
+        create(&path, "a1", "2026-02-16T12:00:00Z", None, &[]).unwrap();
+
+        let saved = save_attachment(
+            &path,
+            &MediaSource::Base64 {
+                media_type: "image/png".into(),
+                data: "aGk=".into(),
+            },
+        )
+        .unwrap();
+        let MediaSource::Path { path: ref file, .. } = saved else {
+            panic!("Expected a path source");
+        };
+        assert!(file.starts_with(attachments_dir(&path)));
+        assert_eq!(fs::read(file).unwrap(), b"hi");
+
+        let attachment = ctx_attachment(&ContentBlock::Image {
+            source: saved.clone(),
+        })
+        .unwrap();
+        assert_eq!(attachment.path, *file);
+        append(
+            &path,
+            &Event::User {
+                timestamp: "2026-02-16T12:00:01Z".into(),
+                text: "Look".into(),
+                attachments: vec![attachment.clone()],
+            },
+        )
+        .unwrap();
+        assert!(read(&path).unwrap().contains(&format!(
+            "Look\n[attachment:image/png] {}\n</up>",
+            file.display()
+        )));
+
+        let resumed = parse_for_resume(&path).unwrap();
+        let LlmContent::Blocks(blocks) = &resumed.messages[0].content else {
+            panic!("Expected blocks");
+        };
+        assert!(matches!(&blocks[0], ContentBlock::Text { text } if text == "Look"));
+        assert_eq!(blocks[1].media_source(), Some(&saved));
+
+        // A reference to a deleted file stays plain text
+        fs::remove_file(file).unwrap();
+        assert_eq!(
+            user_content("Look", &[attachment]).to_text(),
+            format!("Look\n[attachment:image/png] {}", file.display())
+        );
+        assert!(save_attachment(
+            &path,
+            &MediaSource::Base64 {
+                media_type: "text/html".into(),
+                data: "aGk=".into(),
+            },
+        )
+        .is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,539 @@
+//! Turn engine — the stream → accumulate → execute mechanics shared by every loop
+//!
+//! `AgentRuntime::run_turn`, child agents (`spawn_child`) and the consciousness
+//! queue each drive their own control flow, but build requests, consume the
+//! LLM stream, record assistant turns and run tools through one `TurnEngine`.
+//! What differs between them is plugged in:
+//! - `EventSink` — where streaming events go (an mpsc channel, a broadcast, a collector)
+//! - `ToolScheduling` — whether steering interrupts a batch of tool calls
+//! - `Steering` — where interrupting user messages come from
+//! - a `CancellationToken` per call, honoured by both the stream and the tools
+
+use crate::runtime::AgentEvent;
+use crate::session::Session;
+use crate::spill::OutputSpill;
+use agenticlaw_llm::{
+    AccumulatedThinking, AccumulatedToolCall, ContentBlock, LlmError, LlmProvider, LlmRequest,
+    LlmTool, ModelInfo, PromptCache, StreamDelta, ToolChoice, Usage,
+};
+use agenticlaw_tools::{ToolContext, ToolRegistry, ToolResult};
+use futures::StreamExt;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+// ── Pluggable policies ──────────────────────────────────────────────────
+
+/// Receives the events of a turn as they happen.
+#[async_trait::async_trait]
+pub trait EventSink: Send + Sync {
+    async fn emit(&self, event: AgentEvent);
+}
+
+#[async_trait::async_trait]
+impl EventSink for mpsc::Sender<AgentEvent> {
+    async fn emit(&self, event: AgentEvent) {
+        let _ = mpsc::Sender::send(self, event).await;
+    }
+}
+
+/// Source of steering messages: user input that should interrupt tool execution.
+#[async_trait::async_trait]
+pub trait Steering: Send + Sync {
+    /// Take all queued steering messages.
+    async fn drain(&self) -> Vec<String>;
+}
+
+/// For loops nobody can steer (child agents).
+pub struct NoSteering;
+
+#[async_trait::async_trait]
+impl Steering for NoSteering {
+    async fn drain(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// How the tool calls from one assistant turn are run. Either way they are
+/// grouped by `ToolRegistry::batches`: calls that don't conflict (reads,
+/// writes to different files) run together, conflicting ones in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToolScheduling {
+    /// Steering is checked between batches and skips the calls not yet started.
+    Interruptible,
+    /// Every call runs; steering is checked once they all finish.
+    Uninterrupted,
+}
+
+/// Per-request additions on top of the registry's tools.
+#[derive(Clone, Debug, Default)]
+pub struct TurnOptions {
+    pub extra_tools: Vec<LlmTool>,
+    /// Dropped when thinking is on: Anthropic rejects forced tool use with thinking.
+    pub tool_choice: Option<ToolChoice>,
+}
+
+// ── Results ─────────────────────────────────────────────────────────────
+
+/// One streamed assistant response.
+#[derive(Clone, Debug, Default)]
+pub struct TurnOutput {
+    pub text: String,
+    /// Signed thinking blocks, replayed ahead of the tool calls.
+    pub thinking: Vec<ContentBlock>,
+    pub tool_calls: Vec<AccumulatedToolCall>,
+    pub stop_reason: String,
+    pub usage: Option<Usage>,
+    /// The cancellation token fired before the stream finished.
+    pub cancelled: bool,
+}
+
+impl TurnOutput {
+    /// Content blocks for the assistant message: thinking first, then tool calls.
+    pub fn assistant_blocks(&self) -> Vec<ContentBlock> {
+        self.thinking
+            .iter()
+            .cloned()
+            .chain(self.tool_calls.iter().map(|tc| {
+                ContentBlock::ToolUse {
+                    id: tc.id.clone(),
+                    name: tc.name.clone(),
+                    input: tc
+                        .parse_arguments()
+                        .unwrap_or_else(|_| serde_json::json!({})),
+                }
+            }))
+            .collect()
+    }
+
+    /// Save this response as the session's next assistant message. An empty
+    /// response is not saved: the API rejects empty assistant content.
+    pub async fn record(&self, session: &Session) {
+        if self.tool_calls.is_empty() && self.thinking.is_empty() {
+            if !self.text.is_empty() {
+                session.add_assistant_text(&self.text).await;
+            }
+        } else {
+            let text = Some(self.text.as_str()).filter(|t| !t.is_empty());
+            session
+                .add_assistant_with_tools(text, self.assistant_blocks())
+                .await;
+        }
+    }
+}
+
+/// Result of one tool call, ready for the session.
+#[derive(Clone, Debug)]
+pub struct ToolRun {
+    /// Text result; over the tool's budget, a preview of the spilled whole.
+    pub result: String,
+    /// Images/documents returned alongside the text.
+    pub media: Vec<ContentBlock>,
+    pub is_error: bool,
+}
+
+/// Run one tool call against the registry on behalf of `ctx.session`.
+/// Arguments that aren't valid JSON fail the call without running the tool.
+pub async fn run_tool(
+    tools: &ToolRegistry,
+    spill: &OutputSpill,
+    call: &AccumulatedToolCall,
+    ctx: ToolContext,
+) -> ToolRun {
+    let args = match call.parse_arguments() {
+        Ok(args) => args,
+        Err(e) => {
+            warn!(tool = %call.name, id = %call.id, error = %e, "Tool arguments are not valid JSON");
+            let result = ToolResult::error(format!(
+                "Arguments for {} are not valid JSON ({}): {}",
+                call.name,
+                e,
+                call.arguments.chars().take(200).collect::<String>()
+            ));
+            return ToolRun {
+                result: result.to_content_string(),
+                media: Vec::new(),
+                is_error: true,
+            };
+        }
+    };
+    let args_summary = args
+        .as_object()
+        .and_then(|o| o.iter().next())
+        .map(|(k, v)| {
+            format!(
+                "{}={}",
+                k,
+                v.as_str()
+                    .unwrap_or(&v.to_string())
+                    .chars()
+                    .take(80)
+                    .collect::<String>()
+            )
+        })
+        .unwrap_or_default();
+
+    let start = std::time::Instant::now();
+    info!(tool = %call.name, id = %call.id, args = %args_summary, "Tool executing");
+
+    let result = tools.execute_in(&call.name, args, &ctx).await;
+    let text = spill
+        .apply(
+            &ctx.session,
+            &call.id,
+            &call.name,
+            result.to_content_string(),
+        )
+        .await;
+
+    let duration_ms = start.elapsed().as_millis() as u64;
+    let is_error = result.is_error();
+    if is_error {
+        warn!(tool = %call.name, id = %call.id, duration_ms, "Tool failed");
+    } else {
+        info!(tool = %call.name, id = %call.id, duration_ms, "Tool completed");
+    }
+
+    ToolRun {
+        result: text,
+        media: result.media(),
+        is_error,
+    }
+}
+
+// ── Engine ──────────────────────────────────────────────────────────────
+
+#[derive(Clone)]
+pub struct TurnEngine {
+    provider: Arc<dyn LlmProvider>,
+    tools: Arc<ToolRegistry>,
+    default_model: String,
+    /// Visible-answer allowance per request, before any thinking budget.
+    answer_tokens: usize,
+    scheduling: ToolScheduling,
+    spill: Arc<OutputSpill>,
+}
+
+impl TurnEngine {
+    pub fn new(
+        provider: Arc<dyn LlmProvider>,
+        tools: Arc<ToolRegistry>,
+        default_model: impl Into<String>,
+    ) -> Self {
+        Self {
+            provider,
+            tools,
+            default_model: default_model.into(),
+            answer_tokens: 16384,
+            scheduling: ToolScheduling::Interruptible,
+            spill: Arc::new(OutputSpill::default()),
+        }
+    }
+
+    pub fn with_answer_tokens(mut self, answer_tokens: usize) -> Self {
+        self.answer_tokens = answer_tokens;
+        self
+    }
+
+    pub fn with_scheduling(mut self, scheduling: ToolScheduling) -> Self {
+        self.scheduling = scheduling;
+        self
+    }
+
+    pub fn with_spill(mut self, spill: OutputSpill) -> Self {
+        self.spill = Arc::new(spill);
+        self
+    }
+
+    pub fn provider(&self) -> &Arc<dyn LlmProvider> {
+        &self.provider
+    }
+
+    pub fn spill(&self) -> &Arc<OutputSpill> {
+        &self.spill
+    }
+
+    pub fn tools(&self) -> &Arc<ToolRegistry> {
+        &self.tools
+    }
+
+    pub fn scheduling(&self) -> ToolScheduling {
+        self.scheduling
+    }
+
+    /// The model `session` runs on: its override, else the default.
+    pub async fn model_for(&self, session: &Session) -> String {
+        session
+            .model()
+            .await
+            .unwrap_or_else(|| self.default_model.clone())
+    }
+
+    pub async fn model_info(&self, session: &Session) -> ModelInfo {
+        self.provider.model_info(&self.model_for(session).await)
+    }
+
+    /// Build the next request for `session` from its current messages.
+    pub async fn request(&self, session: &Session, options: &TurnOptions) -> LlmRequest {
+        let model = self.model_for(session).await;
+        let info = self.provider.model_info(&model);
+        let (max_tokens, thinking) =
+            info.output_limits(self.answer_tokens, session.thinking().await);
+        let messages = session.get_messages().await;
+        info!(model = %model, messages = messages.len(), "LLM request");
+        LlmRequest {
+            model,
+            messages,
+            tools: info.supports_tools.then(|| {
+                let mut defs = self.tools.get_definitions();
+                defs.extend(options.extra_tools.iter().cloned());
+                defs
+            }),
+            tool_choice: options.tool_choice.clone().filter(|_| thinking.is_none()),
+            max_tokens: Some(max_tokens),
+            system: session.system_prompt().await,
+            cache: Some(PromptCache::all()),
+            thinking,
+            ..Default::default()
+        }
+    }
+
+    /// Request and stream the next assistant response for `session`.
+    pub async fn next_turn(
+        &self,
+        session: &Session,
+        options: &TurnOptions,
+        sink: &dyn EventSink,
+        cancel: &CancellationToken,
+    ) -> Result<TurnOutput, String> {
+        let request = self.request(session, options).await;
+        self.stream(session, request, sink, cancel).await
+    }
+
+    /// Stream one response, forwarding deltas to `sink` and recording usage on
+    /// `session`. Failing to open the stream is reported to `sink` and returned
+    /// as `Err`; cancellation returns what arrived so far with `cancelled` set.
+    pub async fn stream(
+        &self,
+        session: &Session,
+        request: LlmRequest,
+        sink: &dyn EventSink,
+        cancel: &CancellationToken,
+    ) -> Result<TurnOutput, String> {
+        // Usage belongs to whichever model served the response
+        let mut model = request.model.clone();
+        let mut out = TurnOutput {
+            stop_reason: "end_turn".into(),
+            ..Default::default()
+        };
+
+        let stream = match self
+            .provider
+            .complete_stream(request, Some(cancel.clone()))
+            .await
+        {
+            Ok(s) => s,
+            Err(LlmError::Cancelled) => {
+                out.cancelled = true;
+                out.stop_reason = "aborted".into();
+                return Ok(out);
+            }
+            Err(e) => {
+                sink.emit(AgentEvent::Error(e.to_string())).await;
+                return Err(e.to_string());
+            }
+        };
+        tokio::pin!(stream);
+
+        let mut thinking = AccumulatedThinking::default();
+        let mut current_tool: Option<AccumulatedToolCall> = None;
+
+        loop {
+            let delta = tokio::select! {
+                biased;
+                _ = cancel.cancelled() => {
+                    out.cancelled = true;
+                    out.stop_reason = "aborted".into();
+                    break;
+                }
+                delta = stream.next() => match delta {
+                    Some(d) => d,
+                    None => break,
+                },
+            };
+            match delta {
+                Ok(StreamDelta::Text(text)) => {
+                    out.text.push_str(&text);
+                    sink.emit(AgentEvent::Text(text)).await;
+                }
+                Ok(StreamDelta::Thinking(t)) => {
+                    thinking.push_delta(&t);
+                    sink.emit(AgentEvent::Thinking(t)).await;
+                }
+                Ok(StreamDelta::ThinkingSignature(signature)) => {
+                    thinking.push_signature(signature);
+                }
+                Ok(StreamDelta::RedactedThinking(data)) => {
+                    thinking.push_redacted(data);
+                }
+                Ok(StreamDelta::ToolCallStart { id, name }) => {
+                    current_tool = Some(AccumulatedToolCall {
+                        id: id.clone(),
+                        name: name.clone(),
+                        arguments: String::new(),
+                    });
+                    sink.emit(AgentEvent::ToolCallStart { id, name }).await;
+                }
+                Ok(StreamDelta::ToolCallDelta { id, arguments }) => {
+                    if let Some(ref mut tool) = current_tool {
+                        tool.arguments.push_str(&arguments);
+                    }
+                    sink.emit(AgentEvent::ToolCallDelta { id, arguments }).await;
+                }
+                Ok(StreamDelta::ToolCallEnd { .. }) => {
+                    if let Some(tool) = current_tool.take() {
+                        out.tool_calls.push(tool);
+                    }
+                }
+                Ok(StreamDelta::Done { stop_reason, usage }) => {
+                    if let Some(r) = stop_reason {
+                        out.stop_reason = r;
+                    }
+                    out.usage = usage;
+                }
+                Ok(StreamDelta::Error(e)) => {
+                    sink.emit(AgentEvent::Error(e)).await;
+                }
+                Ok(StreamDelta::Failover { from, to, reason }) => {
+                    warn!(from = %from, to = %to, reason = %reason, "LLM failover");
+                    if let Some((_, served)) = to.split_once('/') {
+                        model = served.to_string();
+                    }
+                    sink.emit(AgentEvent::Failover { from, to, reason }).await;
+                }
+                Err(LlmError::Cancelled) => {
+                    out.cancelled = true;
+                    out.stop_reason = "aborted".into();
+                    break;
+                }
+                Err(e) => {
+                    sink.emit(AgentEvent::Error(e.to_string())).await;
+                }
+            }
+        }
+
+        if let Some(ref u) = out.usage {
+            let pricing = self.provider.model_info(&model).pricing;
+            session.record_usage(&model, u, pricing).await;
+        }
+        out.thinking = thinking.into_blocks();
+        Ok(out)
+    }
+
+    /// Run `calls` per the engine's scheduling, adding each result to
+    /// `session`. Returns the steering messages that arrived meanwhile, if
+    /// any; when `Interruptible` they also skip the calls not yet started.
+    pub async fn execute_tools(
+        &self,
+        session: &Session,
+        calls: &[AccumulatedToolCall],
+        sink: &dyn EventSink,
+        steering: &dyn Steering,
+        cancel: &CancellationToken,
+    ) -> Option<Vec<String>> {
+        let args: Vec<_> = calls
+            .iter()
+            .map(|call| call.parse_arguments().unwrap_or_default())
+            .collect();
+        let named: Vec<_> = calls
+            .iter()
+            .zip(&args)
+            .map(|(call, args)| (call.name.as_str(), args))
+            .collect();
+        let batches = self.tools.batches(&named);
+        let mut steering_messages: Option<Vec<String>> = None;
+
+        for (index, batch) in batches.iter().enumerate() {
+            let batch = &calls[batch.clone()];
+
+            // Once steering arrived, the remaining tools are skipped
+            if steering_messages.is_some() {
+                for call in batch {
+                    sink.emit(AgentEvent::ToolSkipped {
+                        id: call.id.clone(),
+                        name: call.name.clone(),
+                    })
+                    .await;
+                    session
+                        .add_tool_result(&call.id, "Skipped due to queued user message.", true)
+                        .await;
+                }
+                continue;
+            }
+
+            for call in batch {
+                sink.emit(AgentEvent::ToolExecuting {
+                    id: call.id.clone(),
+                    name: call.name.clone(),
+                })
+                .await;
+            }
+            let runs = futures::future::join_all(batch.iter().map(|call| {
+                run_tool(
+                    &self.tools,
+                    &self.spill,
+                    call,
+                    Self::tool_context(session, cancel),
+                )
+            }))
+            .await;
+            for (call, run) in batch.iter().zip(runs) {
+                Self::finish_tool(session, call, run, sink).await;
+            }
+
+            // The caller checks steering after the last batch
+            if self.scheduling == ToolScheduling::Interruptible && index < batches.len() - 1 {
+                let queued = steering.drain().await;
+                if !queued.is_empty() {
+                    info!(
+                        steering_count = queued.len(),
+                        remaining_batches = batches.len() - index - 1,
+                        "Steering interrupt — skipping remaining tools"
+                    );
+                    steering_messages = Some(queued);
+                }
+            }
+        }
+
+        match self.scheduling {
+            ToolScheduling::Interruptible => steering_messages,
+            ToolScheduling::Uninterrupted => Some(steering.drain().await).filter(|s| !s.is_empty()),
+        }
+    }
+
+    pub(crate) fn tool_context(session: &Session, cancel: &CancellationToken) -> ToolContext {
+        ToolContext::new(session.key.as_str())
+            .with_lineage(session.lineage())
+            .with_turn(session.turn())
+            .with_cancel(cancel.child_token())
+    }
+
+    async fn finish_tool(
+        session: &Session,
+        call: &AccumulatedToolCall,
+        run: ToolRun,
+        sink: &dyn EventSink,
+    ) {
+        sink.emit(AgentEvent::ToolResult {
+            id: call.id.clone(),
+            name: call.name.clone(),
+            result: run.result.clone(),
+            is_error: run.is_error,
+        })
+        .await;
+        session
+            .add_tool_result_with_media(&call.id, &run.result, run.media, run.is_error)
+            .await;
+    }
+}
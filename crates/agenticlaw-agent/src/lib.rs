@@ -1,17 +1,29 @@
 //! Agenticlaw Agent - Runtime for tool-using AI agents with .ctx persistence
 
+pub mod branch;
+pub mod compaction;
 pub mod context;
 pub mod ctx_file;
+pub mod engine;
 pub mod queue;
 pub mod runtime;
+pub mod search;
 pub mod session;
+pub mod spill;
+pub mod structured;
 pub mod subagent;
+pub mod usage;
 
+pub use compaction::{CompactionPolicy, CompactionReport};
 pub use context::ContextManager;
+pub use engine::{EventSink, Steering, ToolScheduling, TurnEngine, TurnOptions, TurnOutput};
 pub use queue::{
     ConsciousnessLoop, ConsciousnessLoopConfig, OutputEvent, Priority, QueueEvent, ToolHandle,
     ToolState,
 };
 pub use runtime::{AgentConfig, AgentEvent, AgentRuntime};
 pub use session::{Session, SessionKey, SessionRegistry};
+pub use spill::OutputSpill;
+pub use structured::StructuredOutput;
 pub use subagent::{SubagentInfo, SubagentRegistry, SubagentStatus};
+pub use usage::{ModelUsage, SessionUsage, UsageTotals};
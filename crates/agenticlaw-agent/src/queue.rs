@@ -7,13 +7,17 @@
 //!
 //! Human messages ALWAYS preempt tool calls (park tools, cancel LLM stream).
 
+use crate::engine::{
+    run_tool, EventSink, ToolRun, ToolScheduling, TurnEngine, TurnOptions, TurnOutput,
+};
+use crate::runtime::AgentEvent;
 use crate::session::{Session, SessionKey, SessionRegistry};
-use agenticlaw_llm::{AccumulatedToolCall, ContentBlock, LlmProvider, LlmRequest, StreamDelta};
-use agenticlaw_tools::{ToolRegistry, ToolResult};
-use futures::StreamExt;
+use crate::spill::OutputSpill;
+use agenticlaw_llm::{AccumulatedToolCall, ContentBlock, LlmProvider};
+use agenticlaw_tools::ToolRegistry;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, Semaphore};
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info};
@@ -39,6 +43,8 @@ pub enum QueueEvent {
         tool_use_id: String,
         name: String,
         result: String,
+        /// Images/documents returned alongside the text.
+        media: Vec<ContentBlock>,
         is_error: bool,
     },
 
@@ -59,6 +65,8 @@ pub enum QueueEvent {
     LlmComplete {
         session: SessionKey,
         text: Option<String>,
+        /// Signed thinking blocks, replayed ahead of the tool calls
+        thinking: Vec<ContentBlock>,
         tool_calls: Vec<AccumulatedToolCall>,
         stop_reason: String,
         /// Unique ID for this LLM call — stale responses are ignored
@@ -151,6 +159,53 @@ pub enum OutputEvent {
     Error { session: String, message: String },
     /// Session sleeping
     Sleep { session: String, token_count: usize },
+    /// Older turns summarised to free up context
+    Compacted {
+        session: String,
+        summarized: usize,
+        kept: usize,
+        tokens_before: usize,
+        tokens_after: usize,
+    },
+    /// LLM request failed over to a fallback provider/model
+    Failover {
+        session: String,
+        from: String,
+        to: String,
+        reason: String,
+    },
+}
+
+/// Forwards engine events to the broadcast channel, tagged with the session.
+struct OutputSink {
+    output_tx: broadcast::Sender<OutputEvent>,
+    session: String,
+}
+
+#[async_trait::async_trait]
+impl EventSink for OutputSink {
+    async fn emit(&self, event: AgentEvent) {
+        let session = self.session.clone();
+        let output = match event {
+            AgentEvent::Text(content) => OutputEvent::Delta { session, content },
+            AgentEvent::Thinking(content) => OutputEvent::Thinking { session, content },
+            AgentEvent::ToolCallStart { id, name } => OutputEvent::ToolCall { session, id, name },
+            AgentEvent::ToolCallDelta { id, arguments } => OutputEvent::ToolCallDelta {
+                session,
+                id,
+                arguments,
+            },
+            AgentEvent::Error(message) => OutputEvent::Error { session, message },
+            AgentEvent::Failover { from, to, reason } => OutputEvent::Failover {
+                session,
+                from,
+                to,
+                reason,
+            },
+            _ => return,
+        };
+        let _ = self.output_tx.send(output);
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -164,7 +219,7 @@ pub struct ToolHandle {
     /// Cancel the tool's execution
     pub cancel: CancellationToken,
     /// Join handle for the spawned task
-    pub join: JoinHandle<ToolResult>,
+    pub join: JoinHandle<ToolRun>,
     /// Current state
     pub state: ToolState,
 }
@@ -177,6 +232,31 @@ pub enum ToolState {
     Cancelled,
 }
 
+/// Opens once every call of a batch of tool calls has finished.
+#[derive(Clone)]
+struct BatchGate {
+    finished: Arc<Semaphore>,
+    calls: u32,
+}
+
+impl BatchGate {
+    fn new(calls: usize) -> Self {
+        Self {
+            finished: Arc::new(Semaphore::new(0)),
+            calls: calls as u32,
+        }
+    }
+
+    fn finish(&self) {
+        self.finished.add_permits(1);
+    }
+
+    async fn wait(&self) {
+        // Permits go back on drop, so every waiter sees the gate open
+        let _ = self.finished.acquire_many(self.calls).await;
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Consciousness Loop Configuration
 // ---------------------------------------------------------------------------
@@ -185,7 +265,10 @@ pub struct ConsciousnessLoopConfig {
     pub default_model: String,
     pub max_tool_iterations: usize,
     pub sleep_threshold_pct: f64,
-    pub max_context_tokens: usize,
+    /// Overrides the model's context window. `None` asks the provider.
+    pub max_context_tokens: Option<usize>,
+    /// Byte budgets for tool results, and where oversized ones spill.
+    pub tool_output: OutputSpill,
 }
 
 impl Default for ConsciousnessLoopConfig {
@@ -194,7 +277,8 @@ impl Default for ConsciousnessLoopConfig {
             default_model: "claude-opus-4-6-20250929".to_string(),
             max_tool_iterations: 25,
             sleep_threshold_pct: 0.55,
-            max_context_tokens: 200_000,
+            max_context_tokens: None,
+            tool_output: OutputSpill::default(),
         }
     }
 }
@@ -215,10 +299,8 @@ pub struct ConsciousnessLoop {
     queue_tx: mpsc::Sender<QueueEvent>,
     /// Output events (to WebSocket clients, TUI, etc.)
     output_tx: broadcast::Sender<OutputEvent>,
-    /// The LLM provider
-    provider: Arc<dyn LlmProvider>,
-    /// Tool registry
-    tools: Arc<ToolRegistry>,
+    /// Request building, streaming and tool execution
+    engine: TurnEngine,
     /// Session registry
     sessions: Arc<SessionRegistry>,
     /// Configuration
@@ -259,8 +341,10 @@ impl ConsciousnessLoop {
             queue_rx,
             queue_tx: queue_tx.clone(),
             output_tx: output_tx.clone(),
-            provider,
-            tools,
+            engine: TurnEngine::new(provider, tools, config.default_model.clone())
+                .with_answer_tokens(8192)
+                .with_scheduling(ToolScheduling::Uninterrupted)
+                .with_spill(config.tool_output.clone()),
             sessions,
             config,
             active_tools: HashMap::new(),
@@ -298,21 +382,30 @@ impl ConsciousnessLoop {
                     tool_use_id,
                     name,
                     result,
+                    media,
                     is_error,
                 } => {
-                    self.handle_tool_result(session, tool_use_id, name, result, is_error)
+                    self.handle_tool_result(session, tool_use_id, name, result, media, is_error)
                         .await;
                 }
 
                 QueueEvent::LlmComplete {
                     session,
                     text,
+                    thinking,
                     tool_calls,
                     stop_reason,
                     request_id,
                 } => {
-                    self.handle_llm_complete(session, text, tool_calls, stop_reason, request_id)
-                        .await;
+                    self.handle_llm_complete(
+                        session,
+                        text,
+                        thinking,
+                        tool_calls,
+                        stop_reason,
+                        request_id,
+                    )
+                    .await;
                 }
 
                 QueueEvent::CascadeDelta { session, delta, .. } => {
@@ -398,12 +491,13 @@ impl ConsciousnessLoop {
 
         // 4. Add message to session
         let sess = self.get_session(&session);
+        let max_context = match self.config.max_context_tokens {
+            Some(tokens) => tokens,
+            None => self.engine.model_info(&sess).await.context_window,
+        };
+        sess.set_context_window(max_context).await;
         let should_sleep = sess
-            .add_user_message(
-                &content,
-                self.config.sleep_threshold_pct,
-                self.config.max_context_tokens,
-            )
+            .add_user_message(&content, self.config.sleep_threshold_pct, max_context)
             .await;
 
         if should_sleep {
@@ -429,6 +523,7 @@ impl ConsciousnessLoop {
         tool_use_id: String,
         name: String,
         result: String,
+        media: Vec<ContentBlock>,
         is_error: bool,
     ) {
         // Remove from active tools (if it's there — may have been parked/drained)
@@ -436,7 +531,8 @@ impl ConsciousnessLoop {
 
         // Add result to session
         let sess = self.get_session(&session);
-        sess.add_tool_result(&tool_use_id, &result, is_error).await;
+        sess.add_tool_result_with_media(&tool_use_id, &result, media, is_error)
+            .await;
 
         let session_str = session.as_str().to_string();
         let _ = self.output_tx.send(OutputEvent::ToolResult {
@@ -468,6 +564,7 @@ impl ConsciousnessLoop {
         &mut self,
         session: SessionKey,
         text: Option<String>,
+        thinking: Vec<ContentBlock>,
         tool_calls: Vec<AccumulatedToolCall>,
         _stop_reason: String,
         request_id: String,
@@ -483,32 +580,41 @@ impl ConsciousnessLoop {
         self.current_request_id = None;
 
         let sess = self.get_session(&session);
-        let session_str = session.as_str().to_string();
+        let turn = TurnOutput {
+            text: text.unwrap_or_default(),
+            thinking,
+            tool_calls,
+            ..Default::default()
+        };
+        turn.record(&sess).await;
 
-        if tool_calls.is_empty() {
-            // No tool calls — save text and emit Done
-            if let Some(ref t) = text {
-                sess.add_assistant_text(t).await;
-            }
+        if turn.tool_calls.is_empty() {
             let _ = self.output_tx.send(OutputEvent::Done {
-                session: session_str,
+                session: session.as_str().to_string(),
             });
         } else {
-            // Save assistant message with tool calls
-            let blocks: Vec<ContentBlock> = tool_calls
+            let args: Vec<_> = turn
+                .tool_calls
                 .iter()
-                .map(|tc| ContentBlock::ToolUse {
-                    id: tc.id.clone(),
-                    name: tc.name.clone(),
-                    input: tc.parse_arguments().unwrap_or_default(),
-                })
+                .map(|tc| tc.parse_arguments().unwrap_or_default())
                 .collect();
-            sess.add_assistant_with_tools(text.as_deref().filter(|t| !t.is_empty()), blocks)
-                .await;
-
-            // Launch tools
-            for tc in tool_calls {
-                self.launch_tool(&session, tc).await;
+            let named: Vec<_> = turn
+                .tool_calls
+                .iter()
+                .zip(&args)
+                .map(|(tc, args)| (tc.name.as_str(), args))
+                .collect();
+            let batches = self.engine.tools().batches(&named);
+
+            // Each batch starts once the one before it has finished
+            let mut after = None;
+            for batch in batches {
+                let gate = BatchGate::new(batch.len());
+                for tc in &turn.tool_calls[batch] {
+                    self.launch_tool(&session, tc.clone(), after.clone(), gate.clone())
+                        .await;
+                }
+                after = Some(gate);
             }
         }
     }
@@ -516,20 +622,7 @@ impl ConsciousnessLoop {
     /// Start a new LLM call for the given session.
     async fn start_llm_call(&mut self, session_key: &SessionKey) {
         let sess = self.get_session(session_key);
-        let messages = sess.get_messages().await;
-        let model = sess
-            .model()
-            .await
-            .unwrap_or_else(|| self.config.default_model.clone());
-
-        let request = LlmRequest {
-            model,
-            messages,
-            tools: Some(self.tools.get_definitions()),
-            max_tokens: Some(8192),
-            system: sess.system_prompt().await,
-            ..Default::default()
-        };
+        let request = self.engine.request(&sess, &TurnOptions::default()).await;
 
         let cancel = CancellationToken::new();
         self.llm_cancel = Some(cancel.clone());
@@ -537,133 +630,58 @@ impl ConsciousnessLoop {
         let request_id = uuid::Uuid::new_v4().to_string();
         self.current_request_id = Some(request_id.clone());
 
-        let provider = self.provider.clone();
+        let engine = self.engine.clone();
         let queue_tx = self.queue_tx.clone();
-        let output_tx = self.output_tx.clone();
-        let session_str = session_key.as_str().to_string();
+        let sink = OutputSink {
+            output_tx: self.output_tx.clone(),
+            session: session_key.as_str().to_string(),
+        };
         let sk = session_key.clone();
 
         tokio::spawn(async move {
-            let stream = match provider.complete_stream(request, None).await {
-                Ok(s) => s,
-                Err(e) => {
-                    let _ = output_tx.send(OutputEvent::Error {
-                        session: session_str,
-                        message: e.to_string(),
-                    });
-                    return;
-                }
+            // Open failures are already reported through the sink
+            let Ok(output) = engine.stream(&sess, request, &sink, &cancel).await else {
+                return;
             };
-
-            tokio::pin!(stream);
-
-            let mut text_content = String::new();
-            let mut tool_calls: Vec<AccumulatedToolCall> = Vec::new();
-            let mut current_tool: Option<AccumulatedToolCall> = None;
-            let mut stop_reason = "end_turn".to_string();
-
-            loop {
-                tokio::select! {
-                    delta = stream.next() => {
-                        match delta {
-                            Some(Ok(d)) => match d {
-                                StreamDelta::Text(text) => {
-                                    text_content.push_str(&text);
-                                    let _ = output_tx.send(OutputEvent::Delta {
-                                        session: session_str.clone(),
-                                        content: text,
-                                    });
-                                }
-                                StreamDelta::Thinking(thinking) => {
-                                    let _ = output_tx.send(OutputEvent::Thinking {
-                                        session: session_str.clone(),
-                                        content: thinking,
-                                    });
-                                }
-                                StreamDelta::ToolCallStart { id, name } => {
-                                    current_tool = Some(AccumulatedToolCall {
-                                        id: id.clone(),
-                                        name: name.clone(),
-                                        arguments: String::new(),
-                                    });
-                                    let _ = output_tx.send(OutputEvent::ToolCall {
-                                        session: session_str.clone(),
-                                        id,
-                                        name,
-                                    });
-                                }
-                                StreamDelta::ToolCallDelta { id, arguments } => {
-                                    if let Some(ref mut tool) = current_tool {
-                                        tool.arguments.push_str(&arguments);
-                                    }
-                                    let _ = output_tx.send(OutputEvent::ToolCallDelta {
-                                        session: session_str.clone(),
-                                        id,
-                                        arguments,
-                                    });
-                                }
-                                StreamDelta::ToolCallEnd { .. } => {
-                                    if let Some(tool) = current_tool.take() {
-                                        tool_calls.push(tool);
-                                    }
-                                }
-                                StreamDelta::Done { stop_reason: sr, .. } => {
-                                    if let Some(r) = sr {
-                                        stop_reason = r;
-                                    }
-                                }
-                                StreamDelta::Error(e) => {
-                                    let _ = output_tx.send(OutputEvent::Error {
-                                        session: session_str.clone(),
-                                        message: e,
-                                    });
-                                }
-                            },
-                            Some(Err(e)) => {
-                                let _ = output_tx.send(OutputEvent::Error {
-                                    session: session_str.clone(),
-                                    message: e.to_string(),
-                                });
-                            }
-                            None => break,
-                        }
-                    }
-                    _ = cancel.cancelled() => {
-                        // LLM stream cancelled — do NOT submit LlmComplete
-                        debug!("LLM stream cancelled for session {}", session_str);
-                        return;
-                    }
-                }
+            if output.cancelled {
+                // LLM stream cancelled — do NOT submit LlmComplete
+                debug!("LLM stream cancelled for session {}", sink.session);
+                return;
             }
 
             // Stream finished naturally — submit LlmComplete
             let _ = queue_tx
                 .send(QueueEvent::LlmComplete {
                     session: sk,
-                    text: if text_content.is_empty() {
-                        None
-                    } else {
-                        Some(text_content)
-                    },
-                    tool_calls,
-                    stop_reason,
+                    text: Some(output.text).filter(|t| !t.is_empty()),
+                    thinking: output.thinking,
+                    tool_calls: output.tool_calls,
+                    stop_reason: output.stop_reason,
                     request_id,
                 })
                 .await;
         });
     }
 
-    /// Launch a tool execution in a background task.
-    async fn launch_tool(&mut self, session: &SessionKey, tc: AccumulatedToolCall) {
+    /// Launch a tool execution in a background task. It waits for `after`
+    /// to open first, and counts towards opening `gate` when done.
+    async fn launch_tool(
+        &mut self,
+        session: &SessionKey,
+        tc: AccumulatedToolCall,
+        after: Option<BatchGate>,
+        gate: BatchGate,
+    ) {
         let cancel = CancellationToken::new();
-        let tools = self.tools.clone();
+        let tools = self.engine.tools().clone();
+        let spill = self.engine.spill().clone();
         let queue_tx = self.queue_tx.clone();
         let output_tx = self.output_tx.clone();
         let session_str = session.as_str().to_string();
         let session_key = session.clone();
         let tc_id = tc.id.clone();
         let tc_name = tc.name.clone();
-        let cancel_clone = cancel.clone();
+        let ctx = TurnEngine::tool_context(&self.get_session(session), &cancel);
 
         let _ = output_tx.send(OutputEvent::ToolExecuting {
             session: session_str.clone(),
@@ -674,37 +692,35 @@ impl ConsciousnessLoop {
         let spawn_id = tc_id.clone();
         let spawn_name = tc_name.clone();
         let join = tokio::spawn(async move {
-            let args = tc.parse_arguments().unwrap_or_default();
-            let tool_name = tc.name.clone();
-
-            // Execute with cancellation
-            let result = tools
-                .execute_cancellable(&tool_name, args, cancel_clone)
-                .await;
-
-            let is_error = result.is_error();
-            let result_str = result.to_content_string();
-            let result_str = if result_str.len() > 50000 {
-                format!(
-                    "{}...\n[truncated, {} total chars]",
-                    &result_str[..50000],
-                    result_str.len()
-                )
+            if let Some(after) = after {
+                tokio::select! {
+                    _ = after.wait() => {}
+                    _ = ctx.cancel.cancelled() => {}
+                }
+            }
+            // Parked before its turn: the calls it waited on may still be
+            // running, and not every tool stops when cancelled
+            let run = if ctx.cancel.is_cancelled() {
+                ToolRun {
+                    result: "[cancelled]".to_string(),
+                    media: Vec::new(),
+                    is_error: false,
+                }
             } else {
-                result_str
+                run_tool(&tools, &spill, &tc, ctx).await
             };
-
+            gate.finish();
             let _ = queue_tx
                 .send(QueueEvent::ToolResult {
                     session: session_key,
                     tool_use_id: spawn_id,
                     name: spawn_name,
-                    result: result_str,
-                    is_error,
+                    result: run.result.clone(),
+                    media: run.media.clone(),
+                    is_error: run.is_error,
                 })
                 .await;
-
-            result
+            run
         });
 
         self.active_tools.insert(
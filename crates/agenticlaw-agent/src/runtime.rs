@@ -7,15 +7,20 @@
 //! - Concurrent tool execution with per-tool cancellation
 //! - .ctx persistence built into the loop
 //! - Sleep/wake architecture for context management
+//! - Optional compaction: old turns summarised as the context window fills
 
+use crate::compaction::{self, CompactionPolicy, CompactionReport};
+use crate::ctx_file;
+use crate::engine::{EventSink, NoSteering, Steering, ToolScheduling, TurnEngine, TurnOptions};
 use crate::session::{Session, SessionKey, SessionRegistry};
+use crate::spill::OutputSpill;
+use crate::structured::{StructuredOutput, FINAL_ANSWER_TOOL};
 use agenticlaw_llm::{
-    AccumulatedToolCall, AnthropicProvider, ContentBlock, LlmProvider, LlmRequest, LlmTool,
-    StreamDelta,
+    AnthropicProvider, ContentBlock, LlmProvider, LlmTool, ModelInfo, Route, RoutingProvider,
+    ToolChoice,
 };
 use agenticlaw_tools::SpawnableRuntime;
-use agenticlaw_tools::ToolRegistry;
-use futures::StreamExt;
+use agenticlaw_tools::{CheckpointSummary, RestoreReport, ToolContext, ToolRegistry};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
@@ -55,6 +60,19 @@ pub enum AgentEvent {
     FollowUpInjected { message_count: usize },
     /// Layer hit context limit — should sleep
     Sleep { token_count: usize },
+    /// Older turns were replaced by a summary to free up context
+    Compacted {
+        summarized: usize,
+        kept: usize,
+        tokens_before: usize,
+        tokens_after: usize,
+    },
+    /// Primary provider exhausted; response is coming from a fallback route
+    Failover {
+        from: String,
+        to: String,
+        reason: String,
+    },
     /// Turn completed
     TurnEnd {
         turn: usize,
@@ -125,29 +143,38 @@ impl MessageQueues {
     }
 }
 
+#[async_trait::async_trait]
+impl Steering for Mutex<MessageQueues> {
+    async fn drain(&self) -> Vec<String> {
+        self.lock().await.drain_steering()
+    }
+}
+
 // ── Runtime ─────────────────────────────────────────────────────────────
 
 pub struct AgentRuntime {
-    provider: Arc<dyn LlmProvider>,
-    tools: Arc<ToolRegistry>,
+    /// Interruptible tool scheduling; children derive an uninterrupted one.
+    engine: TurnEngine,
     sessions: Arc<SessionRegistry>,
     config: AgentConfig,
     /// Shared message queues for HITL injection
     queues: Arc<Mutex<MessageQueues>>,
     /// Cancellation token for aborting the current run
     cancel: CancellationToken,
+    /// When set, sessions are compacted before requests that near the window
+    compaction: Option<CompactionPolicy>,
+    /// When set, replaces `config.sleep_threshold_pct` with a token count
+    sleep_threshold: Option<usize>,
 }
 
 impl AgentRuntime {
     pub fn new(api_key: &str, tools: ToolRegistry, config: AgentConfig) -> Self {
-        Self {
-            provider: Arc::new(AnthropicProvider::new(api_key)),
-            tools: Arc::new(tools),
-            sessions: Arc::new(SessionRegistry::new()),
+        let anthropic: Arc<dyn LlmProvider> = Arc::new(AnthropicProvider::new(api_key));
+        Self::with_provider(
+            Arc::new(RoutingProvider::new(Route::new(anthropic))),
+            tools,
             config,
-            queues: Arc::new(Mutex::new(MessageQueues::default())),
-            cancel: CancellationToken::new(),
-        }
+        )
     }
 
     pub fn with_provider(
@@ -156,26 +183,52 @@ impl AgentRuntime {
         config: AgentConfig,
     ) -> Self {
         Self {
-            provider,
-            tools: Arc::new(tools),
+            engine: TurnEngine::new(provider, Arc::new(tools), config.default_model.clone())
+                .with_spill(OutputSpill::new(&config.workspace_root)),
             sessions: Arc::new(SessionRegistry::new()),
             config,
             queues: Arc::new(Mutex::new(MessageQueues::default())),
             cancel: CancellationToken::new(),
+            compaction: None,
+            sleep_threshold: None,
         }
     }
 
+    /// Replace the tool output budgets; the default spills anything over
+    /// 50,000 bytes into the workspace.
+    pub fn with_spill(mut self, spill: OutputSpill) -> Self {
+        self.engine = self.engine.with_spill(spill);
+        self
+    }
+
+    /// Summarise a session's oldest turns whenever it passes the policy's
+    /// threshold. Off by default.
+    pub fn with_compaction(mut self, policy: CompactionPolicy) -> Self {
+        self.compaction = Some(policy);
+        self
+    }
+
+    /// Signal sleep once a session passes `tokens`, whatever the model's
+    /// context window. Overrides `AgentConfig::sleep_threshold_pct`.
+    pub fn with_sleep_threshold(mut self, tokens: usize) -> Self {
+        self.sleep_threshold = Some(tokens);
+        self
+    }
+
     pub fn sessions(&self) -> &Arc<SessionRegistry> {
         &self.sessions
     }
     pub fn provider(&self) -> &Arc<dyn LlmProvider> {
-        &self.provider
+        self.engine.provider()
     }
     pub fn tools(&self) -> &Arc<ToolRegistry> {
-        &self.tools
+        self.engine.tools()
+    }
+    pub fn engine(&self) -> &TurnEngine {
+        &self.engine
     }
     pub fn tool_definitions(&self) -> Vec<LlmTool> {
-        self.tools.get_definitions()
+        self.engine.tools().get_definitions()
     }
     pub fn workspace(&self) -> &Path {
         &self.config.workspace_root
@@ -200,7 +253,68 @@ impl AgentRuntime {
         self.cancel.cancel();
     }
 
-    fn get_session(&self, session_key: &SessionKey) -> Arc<Session> {
+    /// Release what tools hold for a session (background shells and the like).
+    /// Called when the session is deleted or aborted.
+    pub async fn end_session(&self, session_key: &SessionKey) {
+        self.engine.tools().end_session(session_key.as_str()).await;
+    }
+
+    /// File checkpoints recorded for a session, oldest turn first.
+    pub fn checkpoints(&self, session_key: &SessionKey) -> Vec<CheckpointSummary> {
+        self.engine.tools().checkpoints().list(session_key.as_str())
+    }
+
+    /// Roll the workspace back to how it was before `turn` and note the
+    /// rewind in the session's .ctx file.
+    pub async fn restore_checkpoint(
+        &self,
+        session_key: &SessionKey,
+        turn: usize,
+    ) -> Result<RestoreReport, String> {
+        let report = self
+            .engine
+            .tools()
+            .checkpoints()
+            .restore(session_key.as_str(), turn)
+            .await?;
+        let ctx_path = self
+            .sessions
+            .get(session_key)
+            .and_then(|s| s.ctx_path().map(Path::to_path_buf));
+        if let Some(path) = ctx_path {
+            let note = rewind_note(&report, &self.config.workspace_root);
+            if let Err(e) = ctx_file::append_rewind_note(&path, &ctx_file::now_timestamp(), &note) {
+                warn!("Failed to note rewind in {}: {}", path.display(), e);
+            }
+        }
+        info!(session = %session_key, turn, "Workspace restored from checkpoint");
+        Ok(report)
+    }
+
+    /// Drop a session's checkpoints. Called when the session is deleted.
+    pub fn forget_checkpoints(&self, session_key: &SessionKey) {
+        self.engine
+            .tools()
+            .checkpoints()
+            .forget(session_key.as_str());
+    }
+
+    /// Drop the file tools' read records for a deleted session. Children
+    /// share their root's records, so only a root's deletion drops them.
+    pub async fn forget_file_reads(&self, session: &Session) {
+        let lineage = session.lineage();
+        if lineage == session.key.as_str() {
+            self.engine.tools().end_lineage(&lineage).await;
+        }
+    }
+
+    /// Delete a session's spilled tool output. Called when the session is deleted.
+    pub fn forget_spilled_output(&self, session_key: &SessionKey) {
+        self.engine.spill().forget(session_key.as_str());
+    }
+
+    /// Get or create a session, persisted to .ctx under the workspace.
+    pub fn get_session(&self, session_key: &SessionKey) -> Arc<Session> {
         self.sessions.create_with_ctx(
             session_key,
             self.config.system_prompt.as_deref(),
@@ -208,6 +322,90 @@ impl AgentRuntime {
         )
     }
 
+    /// Capabilities of `model` as reported by the provider.
+    pub fn model_info(&self, model: &str) -> ModelInfo {
+        self.engine.provider().model_info(model)
+    }
+
+    /// Size the session's context to the model it will run on. Returns the
+    /// context window in tokens.
+    async fn sync_context_window(&self, session: &Session) -> usize {
+        let context_window = self.engine.model_info(session).await.context_window;
+        session.set_context_window(context_window).await;
+        context_window
+    }
+
+    /// Fraction of `max_context` at which sessions signal sleep.
+    fn sleep_threshold_pct(&self, max_context: usize) -> f64 {
+        match self.sleep_threshold {
+            Some(tokens) if max_context > 0 => tokens as f64 / max_context as f64,
+            _ => self.config.sleep_threshold_pct,
+        }
+    }
+
+    /// Compact a session now, whatever its size. Uses the configured policy,
+    /// or the default one when automatic compaction is off.
+    pub async fn compact_session(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<CompactionReport, String> {
+        let session = self
+            .sessions
+            .get(session_key)
+            .ok_or_else(|| format!("Session not found: {}", session_key))?;
+        let policy = self.compaction.clone().unwrap_or_default();
+        let context_window = self.sync_context_window(&session).await;
+        self.compact(&session, &policy, context_window).await
+    }
+
+    async fn compact(
+        &self,
+        session: &Session,
+        policy: &CompactionPolicy,
+        context_window: usize,
+    ) -> Result<CompactionReport, String> {
+        let model = self.engine.model_for(session).await;
+        compaction::compact(
+            session,
+            self.engine.provider().as_ref(),
+            &model,
+            policy.keep_tokens(context_window),
+            policy.threshold_tokens(context_window),
+            policy.summary_tokens,
+            &self.cancel,
+        )
+        .await
+    }
+
+    /// Compact ahead of a request if the session is past the threshold. A
+    /// failed summary is logged and the request goes ahead uncompacted.
+    async fn compact_if_needed(
+        &self,
+        session: &Session,
+        context_window: usize,
+        event_tx: &mpsc::Sender<AgentEvent>,
+    ) {
+        let Some(policy) = &self.compaction else {
+            return;
+        };
+        if !policy.should_compact(session.token_count().await, context_window) {
+            return;
+        }
+        match self.compact(session, policy, context_window).await {
+            Ok(report) => {
+                let _ = event_tx
+                    .send(AgentEvent::Compacted {
+                        summarized: report.summarized,
+                        kept: report.kept,
+                        tokens_before: report.tokens_before,
+                        tokens_after: report.tokens_after,
+                    })
+                    .await;
+            }
+            Err(e) => warn!(session = %session.key, "Compaction failed: {}", e),
+        }
+    }
+
     /// Run the full agentic loop.
     ///
     /// Architecture (mirrors OpenClaw agent-loop but better):
@@ -216,13 +414,14 @@ impl AgentRuntime {
     /// OUTER LOOP (follow-up continuation):
     ///   INNER LOOP (tool calls + steering):
     ///     1. Check steering queue → inject as user messages
-    ///     2. Stream LLM response
-    ///     3. If tool calls:
-    ///        a. Execute tools concurrently
-    ///        b. Between each tool, check steering → skip remaining on interrupt
+    ///     2. Compact if near the context window (when enabled)
+    ///     3. Stream LLM response
+    ///     4. If tool calls:
+    ///        a. Execute tools in batches; independent calls run in parallel
+    ///        b. Between batches, check steering → skip remaining on interrupt
     ///        c. Add results to session
     ///        d. Continue inner loop
-    ///     4. If no tool calls:
+    ///     5. If no tool calls:
     ///        a. Check for pending HITL input → continue if found
     ///        b. Otherwise exit inner loop
     ///   Check follow-up queue → continue outer loop if found
@@ -233,13 +432,30 @@ impl AgentRuntime {
         session_key: &SessionKey,
         user_message: &str,
         event_tx: mpsc::Sender<AgentEvent>,
+    ) -> Result<(), String> {
+        self.run_turn_with_attachments(session_key, user_message, Vec::new(), event_tx)
+            .await
+    }
+
+    /// `run_turn` with image/document blocks attached to the user message.
+    pub async fn run_turn_with_attachments(
+        &self,
+        session_key: &SessionKey,
+        user_message: &str,
+        attachments: Vec<ContentBlock>,
+        event_tx: mpsc::Sender<AgentEvent>,
     ) -> Result<(), String> {
         let session = self.get_session(session_key);
-        let max_context = 200_000; // TODO: get from provider/model
+        let max_context = self.sync_context_window(&session).await;
 
         // Add the initial user message
         let should_sleep = session
-            .add_user_message(user_message, self.config.sleep_threshold_pct, max_context)
+            .add_user_message_with_attachments(
+                user_message,
+                attachments,
+                self.sleep_threshold_pct(max_context),
+                max_context,
+            )
             .await;
 
         if should_sleep {
@@ -276,7 +492,11 @@ impl AgentRuntime {
                     let count = pending_steering.len();
                     for msg in pending_steering.drain(..) {
                         session
-                            .add_user_message(&msg, self.config.sleep_threshold_pct, max_context)
+                            .add_user_message(
+                                &msg,
+                                self.sleep_threshold_pct(max_context),
+                                max_context,
+                            )
                             .await;
                     }
                     let _ = event_tx
@@ -289,52 +509,37 @@ impl AgentRuntime {
                 // Drain pending input counter
                 session.drain_pending_input();
 
+                self.compact_if_needed(&session, max_context, &event_tx)
+                    .await;
+
                 // Stream LLM response
-                let (text_content, tool_calls, stop_reason) =
-                    match self.stream_llm_response(&session, &event_tx).await {
-                        Ok(result) => result,
-                        Err(e) => {
-                            let _ = event_tx.send(AgentEvent::Error(e.clone())).await;
-                            return Err(e);
-                        }
-                    };
+                let output = self
+                    .engine
+                    .next_turn(&session, &TurnOptions::default(), &event_tx, &self.cancel)
+                    .await?;
 
                 // Check for abort
-                if self.cancel.is_cancelled() {
+                if output.cancelled {
                     let _ = event_tx.send(AgentEvent::Aborted).await;
                     return Ok(());
                 }
 
                 // Save assistant response to session
-                if tool_calls.is_empty() {
-                    session.add_assistant_text(&text_content).await;
-                } else {
-                    let blocks: Vec<ContentBlock> = tool_calls
-                        .iter()
-                        .map(|tc| ContentBlock::ToolUse {
-                            id: tc.id.clone(),
-                            name: tc.name.clone(),
-                            input: tc.parse_arguments().unwrap_or_default(),
-                        })
-                        .collect();
-                    session
-                        .add_assistant_with_tools(
-                            if text_content.is_empty() {
-                                None
-                            } else {
-                                Some(&text_content)
-                            },
-                            blocks,
-                        )
-                        .await;
-                }
+                output.record(&session).await;
 
-                has_more_tool_calls = !tool_calls.is_empty();
+                has_more_tool_calls = !output.tool_calls.is_empty();
 
                 if has_more_tool_calls {
                     // Execute tools with steering-aware interruption
                     let steering_after = self
-                        .execute_tools_with_steering(&session, &tool_calls, &event_tx)
+                        .engine
+                        .execute_tools(
+                            &session,
+                            &output.tool_calls,
+                            &event_tx,
+                            &*self.queues,
+                            &self.cancel,
+                        )
                         .await;
 
                     if let Some(steering) = steering_after {
@@ -360,7 +565,7 @@ impl AgentRuntime {
                 let _ = event_tx
                     .send(AgentEvent::TurnEnd {
                         turn,
-                        stop_reason: stop_reason.clone(),
+                        stop_reason: output.stop_reason.clone(),
                         has_tool_calls: has_more_tool_calls,
                     })
                     .await;
@@ -372,7 +577,7 @@ impl AgentRuntime {
                 let count = follow_ups.len();
                 for msg in follow_ups {
                     session
-                        .add_user_message(&msg, self.config.sleep_threshold_pct, max_context)
+                        .add_user_message(&msg, self.sleep_threshold_pct(max_context), max_context)
                         .await;
                 }
                 let _ = event_tx
@@ -417,410 +622,281 @@ impl AgentRuntime {
         );
         Ok(())
     }
+}
 
-    /// Stream a single LLM response. Returns (text, tool_calls, stop_reason).
-    async fn stream_llm_response(
-        &self,
-        session: &Session,
-        event_tx: &mpsc::Sender<AgentEvent>,
-    ) -> Result<(String, Vec<AccumulatedToolCall>, String), String> {
-        let messages = session.get_messages().await;
-        let model = session
-            .model()
-            .await
-            .unwrap_or_else(|| self.config.default_model.clone());
-
-        let msg_count = messages.len();
-        info!(model = %model, messages = msg_count, "LLM request");
-
-        let request = LlmRequest {
-            model,
-            messages,
-            tools: Some(self.tools.get_definitions()),
-            max_tokens: Some(16384),
-            system: session.system_prompt().await,
-            ..Default::default()
-        };
-
-        let stream = self
-            .provider
-            .complete_stream(request, None)
-            .await
-            .map_err(|e| e.to_string())?;
-
-        let mut text_content = String::new();
-        let mut tool_calls: Vec<AccumulatedToolCall> = Vec::new();
-        let mut current_tool: Option<AccumulatedToolCall> = None;
-        let mut stop_reason = "end_turn".to_string();
-
-        tokio::pin!(stream);
-
-        while let Some(delta_result) = stream.next().await {
-            // Check abort between chunks
-            if self.cancel.is_cancelled() {
-                return Ok((text_content, tool_calls, "aborted".into()));
-            }
-
-            match delta_result {
-                Ok(delta) => match delta {
-                    StreamDelta::Text(text) => {
-                        text_content.push_str(&text);
-                        let _ = event_tx.send(AgentEvent::Text(text)).await;
-                    }
-                    StreamDelta::Thinking(thinking) => {
-                        let _ = event_tx.send(AgentEvent::Thinking(thinking)).await;
-                    }
-                    StreamDelta::ToolCallStart { id, name } => {
-                        current_tool = Some(AccumulatedToolCall {
-                            id: id.clone(),
-                            name: name.clone(),
-                            arguments: String::new(),
-                        });
-                        let _ = event_tx.send(AgentEvent::ToolCallStart { id, name }).await;
-                    }
-                    StreamDelta::ToolCallDelta { id, arguments } => {
-                        if let Some(ref mut tool) = current_tool {
-                            tool.arguments.push_str(&arguments);
-                        }
-                        let _ = event_tx
-                            .send(AgentEvent::ToolCallDelta { id, arguments })
-                            .await;
-                    }
-                    StreamDelta::ToolCallEnd { id: _ } => {
-                        if let Some(tool) = current_tool.take() {
-                            tool_calls.push(tool);
-                        }
-                    }
-                    StreamDelta::Done {
-                        stop_reason: sr, ..
-                    } => {
-                        if let Some(r) = sr {
-                            stop_reason = r;
-                        }
-                    }
-                    StreamDelta::Error(e) => {
-                        let _ = event_tx.send(AgentEvent::Error(e)).await;
-                    }
-                },
-                Err(e) => {
-                    let _ = event_tx.send(AgentEvent::Error(e.to_string())).await;
-                }
-            }
-        }
-
-        Ok((text_content, tool_calls, stop_reason))
-    }
+// ── SpawnableRuntime for subagent/KG child execution ────────────────────
 
-    /// Execute tool calls with steering-aware interruption.
-    ///
-    /// Between each tool execution, checks the steering queue.
-    /// If steering messages arrive, remaining tools are SKIPPED
-    /// (returned as "Skipped due to queued user message").
-    ///
-    /// Returns Some(steering_messages) if interrupted, None otherwise.
-    async fn execute_tools_with_steering(
+#[async_trait::async_trait]
+impl SpawnableRuntime for AgentRuntime {
+    async fn spawn_child(
         &self,
-        session: &Session,
-        tool_calls: &[AccumulatedToolCall],
-        event_tx: &mpsc::Sender<AgentEvent>,
-    ) -> Option<Vec<String>> {
-        let mut steering_messages: Option<Vec<String>> = None;
-
-        for (index, tc) in tool_calls.iter().enumerate() {
-            // If we already got steering, skip remaining tools
-            if steering_messages.is_some() {
-                self.skip_tool(session, tc, event_tx).await;
-                continue;
-            }
-
-            let _ = event_tx
-                .send(AgentEvent::ToolExecuting {
-                    id: tc.id.clone(),
-                    name: tc.name.clone(),
-                })
-                .await;
-
-            let args = tc.parse_arguments().unwrap_or_default();
-            let args_summary = args
-                .as_object()
-                .and_then(|o| o.iter().next())
-                .map(|(k, v)| {
-                    format!(
-                        "{}={}",
-                        k,
-                        v.as_str()
-                            .unwrap_or(&v.to_string())
-                            .chars()
-                            .take(80)
-                            .collect::<String>()
-                    )
-                })
-                .unwrap_or_default();
-
-            let start = std::time::Instant::now();
-            info!(tool = %tc.name, id = %tc.id, args = %args_summary, "Tool executing");
-
-            let result = self.tools.execute(&tc.name, args).await;
-
-            let duration = start.elapsed();
-            let is_error = result.is_error();
-            let result_str = result.to_content_string();
-            let result_str = if result_str.len() > 50000 {
-                format!(
-                    "{}...\n[truncated, {} total chars]",
-                    &result_str[..50000],
-                    result_str.len()
-                )
-            } else {
-                result_str
-            };
-
-            if is_error {
-                warn!(tool = %tc.name, id = %tc.id, duration_ms = duration.as_millis() as u64, "Tool failed");
-            } else {
-                info!(tool = %tc.name, id = %tc.id, duration_ms = duration.as_millis() as u64, "Tool completed");
-            }
-
-            let _ = event_tx
-                .send(AgentEvent::ToolResult {
-                    id: tc.id.clone(),
-                    name: tc.name.clone(),
-                    result: result_str.clone(),
-                    is_error,
-                })
-                .await;
-            session.add_tool_result(&tc.id, &result_str, is_error).await;
-
-            // Check steering queue after each tool (not after the last one — that's checked outside)
-            if index < tool_calls.len() - 1 {
-                let queued = self.queues.lock().await.drain_steering();
-                if !queued.is_empty() {
-                    info!(
-                        steering_count = queued.len(),
-                        remaining_tools = tool_calls.len() - index - 1,
-                        "Steering interrupt — skipping remaining tools"
-                    );
-                    steering_messages = Some(queued);
-                    // Don't break — loop continues but skips remaining via the guard above
-                }
-            }
-        }
-
-        steering_messages
+        parent: &ToolContext,
+        session_id: &str,
+        system_prompt: &str,
+        user_message: &str,
+        max_iterations: usize,
+    ) -> Result<(String, usize), String> {
+        let (output, _, tokens) = self
+            .run_child(
+                parent,
+                session_id,
+                system_prompt,
+                user_message,
+                max_iterations,
+                None,
+            )
+            .await?;
+        Ok((output, tokens))
     }
 
-    /// Skip a tool call due to steering interrupt.
-    async fn skip_tool(
+    async fn spawn_child_structured(
         &self,
-        session: &Session,
-        tc: &AccumulatedToolCall,
-        event_tx: &mpsc::Sender<AgentEvent>,
-    ) {
-        let _ = event_tx
-            .send(AgentEvent::ToolSkipped {
-                id: tc.id.clone(),
-                name: tc.name.clone(),
-            })
-            .await;
-        session
-            .add_tool_result(&tc.id, "Skipped due to queued user message.", true)
-            .await;
+        parent: &ToolContext,
+        session_id: &str,
+        system_prompt: &str,
+        user_message: &str,
+        max_iterations: usize,
+        schema: &serde_json::Value,
+        max_retries: usize,
+    ) -> Result<(serde_json::Value, usize), String> {
+        let structured = StructuredOutput::new(schema.clone()).with_max_retries(max_retries);
+        let (_, answer, tokens) = self
+            .run_child(
+                parent,
+                session_id,
+                system_prompt,
+                user_message,
+                max_iterations,
+                Some(structured),
+            )
+            .await?;
+        let answer = answer
+            .ok_or_else(|| format!("child finished without calling {}", FINAL_ANSWER_TOOL))?;
+        Ok((answer, tokens))
     }
 }
 
-// ── SpawnableRuntime for subagent/KG child execution ────────────────────
-
-#[async_trait::async_trait]
-impl SpawnableRuntime for AgentRuntime {
-    async fn spawn_child(
+impl AgentRuntime {
+    /// Run a child agent to completion. With `structured`, the child must
+    /// finish through `final_answer`; the validated answer is returned
+    /// alongside the streamed text.
+    async fn run_child(
         &self,
+        parent: &ToolContext,
         session_id: &str,
         system_prompt: &str,
         user_message: &str,
         max_iterations: usize,
-    ) -> Result<(String, usize), String> {
+        structured: Option<StructuredOutput>,
+    ) -> Result<(String, Option<serde_json::Value>, usize), String> {
         let session_key = SessionKey::from(format!("kg-child:{}", session_id));
         let session = self
             .sessions
             .get_or_create(&session_key, Some(system_prompt));
         session.set_system_prompt(system_prompt).await;
+        if !parent.lineage.is_empty() {
+            session.set_lineage(&parent.lineage, parent.turn);
+        }
+        let max_context = self.sync_context_window(&session).await;
+
+        let engine = self
+            .engine
+            .clone()
+            .with_answer_tokens(8192)
+            .with_scheduling(ToolScheduling::Uninterrupted);
+        let sink = ChildOutput::new(session_id);
+        // Aborting the parent aborts its children
+        let cancel = self.cancel.child_token();
+        session
+            .add_user_message(
+                user_message,
+                self.sleep_threshold_pct(max_context),
+                max_context,
+            )
+            .await;
+        let answer = self
+            .drive_child(
+                &engine,
+                &session,
+                max_iterations,
+                max_context,
+                structured.as_ref(),
+                &sink,
+                &cancel,
+            )
+            .await;
+        self.sessions.remove(&session_key);
+        self.end_session(&session_key).await;
 
-        let (tx, mut rx) = mpsc::channel::<AgentEvent>(256);
-
-        let tools = self.tools.clone();
-        let provider = self.provider.clone();
-        let sessions = self.sessions.clone();
-        let default_model = self.config.default_model.clone();
-        let sk = session_key.clone();
-        let msg = user_message.to_string();
-
-        let handle = tokio::spawn(async move {
-            let session = sessions.get(&sk).unwrap();
-            let max_context = 200_000;
-            session.add_user_message(&msg, 0.55, max_context).await;
-
-            let mut iterations = 0;
-            loop {
-                iterations += 1;
-                if iterations > max_iterations {
-                    let _ = tx
-                        .send(AgentEvent::Error("Max tool iterations exceeded".into()))
-                        .await;
-                    break;
-                }
+        let output = sink.into_text();
+        let token_estimate = output.len() / 4;
+        Ok((output, answer?, token_estimate))
+    }
 
-                let messages = session.get_messages().await;
-                let model = session
-                    .model()
-                    .await
-                    .unwrap_or_else(|| default_model.clone());
-
-                let request = LlmRequest {
-                    model,
-                    messages,
-                    tools: Some(tools.get_definitions()),
-                    max_tokens: Some(8192),
-                    system: session.system_prompt().await,
-                    ..Default::default()
-                };
+    /// The child loop: stream, record, run tools uninterrupted until the model
+    /// stops calling tools (or, when structured, gives an accepted answer).
+    #[allow(clippy::too_many_arguments)]
+    async fn drive_child(
+        &self,
+        engine: &TurnEngine,
+        session: &Session,
+        max_iterations: usize,
+        max_context: usize,
+        structured: Option<&StructuredOutput>,
+        sink: &ChildOutput,
+        cancel: &CancellationToken,
+    ) -> Result<Option<serde_json::Value>, String> {
+        let mut options = TurnOptions::default();
+        if let Some(s) = structured {
+            let info = engine.model_info(session).await;
+            if !info.supports_tools {
+                return Err(format!(
+                    "structured output needs tool support, which {} lacks",
+                    engine.model_for(session).await
+                ));
+            }
+            options.extra_tools.push(s.tool());
+            // Any tool call is allowed along the way, but the turn can't end in
+            // plain text. Without forcing (thinking on) the re-prompt below does it.
+            options.tool_choice = Some(ToolChoice::Any);
+        }
 
-                let stream = match provider.complete_stream(request, None).await {
-                    Ok(s) => s,
-                    Err(e) => {
-                        let _ = tx.send(AgentEvent::Error(e.to_string())).await;
-                        return Err(e.to_string());
-                    }
+        let mut retries = 0;
+        for _ in 0..max_iterations {
+            let output = engine.next_turn(session, &options, sink, cancel).await?;
+            if output.cancelled {
+                return Err("child aborted".into());
+            }
+            output.record(session).await;
+
+            if output.tool_calls.is_empty() {
+                let Some(s) = structured else {
+                    return Ok(None);
                 };
+                retries += 1;
+                if retries > s.max_retries {
+                    return Err(format!(
+                        "child did not call {} after {} reminders",
+                        FINAL_ANSWER_TOOL,
+                        retries - 1
+                    ));
+                }
+                session
+                    .add_user_message(
+                        &format!(
+                            "Finish by calling the {} tool with your answer.",
+                            FINAL_ANSWER_TOOL
+                        ),
+                        self.sleep_threshold_pct(max_context),
+                        max_context,
+                    )
+                    .await;
+                continue;
+            }
 
-                let mut text_content = String::new();
-                let mut tool_calls: Vec<AccumulatedToolCall> = Vec::new();
-                let mut current_tool: Option<AccumulatedToolCall> = None;
-
-                tokio::pin!(stream);
-                while let Some(delta_result) = stream.next().await {
-                    match delta_result {
-                        Ok(delta) => match delta {
-                            StreamDelta::Text(text) => {
-                                text_content.push_str(&text);
-                                let _ = tx.send(AgentEvent::Text(text)).await;
-                            }
-                            StreamDelta::Thinking(t) => {
-                                let _ = tx.send(AgentEvent::Thinking(t)).await;
-                            }
-                            StreamDelta::ToolCallStart { id, name } => {
-                                current_tool = Some(AccumulatedToolCall {
-                                    id: id.clone(),
-                                    name: name.clone(),
-                                    arguments: String::new(),
-                                });
-                                let _ = tx.send(AgentEvent::ToolCallStart { id, name }).await;
-                            }
-                            StreamDelta::ToolCallDelta { id, arguments } => {
-                                if let Some(ref mut tool) = current_tool {
-                                    tool.arguments.push_str(&arguments);
-                                }
-                                let _ = tx.send(AgentEvent::ToolCallDelta { id, arguments }).await;
-                            }
-                            StreamDelta::ToolCallEnd { id: _ } => {
-                                if let Some(tool) = current_tool.take() {
-                                    tool_calls.push(tool);
-                                }
-                            }
-                            StreamDelta::Done { .. } => {}
-                            StreamDelta::Error(e) => {
-                                let _ = tx.send(AgentEvent::Error(e)).await;
-                            }
-                        },
-                        Err(e) => {
-                            let _ = tx.send(AgentEvent::Error(e.to_string())).await;
+            // Check a final answer before running anything else
+            let final_call = structured.and_then(|s| {
+                output
+                    .tool_calls
+                    .iter()
+                    .find(|tc| tc.name == FINAL_ANSWER_TOOL)
+                    .map(|tc| (s, tc))
+            });
+            if let Some((s, call)) = final_call {
+                match s.accept(&call.arguments) {
+                    Ok(answer) => {
+                        for tc in &output.tool_calls {
+                            let (result, is_error) = if tc.id == call.id {
+                                ("Answer accepted.", false)
+                            } else {
+                                ("Skipped: final answer already given.", true)
+                            };
+                            session.add_tool_result(&tc.id, result, is_error).await;
                         }
+                        return Ok(Some(answer));
+                    }
+                    Err(violations) => {
+                        retries += 1;
+                        if retries > s.max_retries {
+                            return Err(format!(
+                                "{} rejected after {} attempts: {}",
+                                FINAL_ANSWER_TOOL, retries, violations
+                            ));
+                        }
+                        warn!(attempt = retries, "{}", violations);
+                        session
+                            .add_tool_result(
+                                &call.id,
+                                &format!("{}\nCall {} again.", violations, FINAL_ANSWER_TOOL),
+                                true,
+                            )
+                            .await;
                     }
                 }
+            }
 
-                if tool_calls.is_empty() {
-                    session.add_assistant_text(&text_content).await;
-                    let _ = tx
-                        .send(AgentEvent::Done {
-                            stop_reason: "end_turn".into(),
-                        })
-                        .await;
-                    break;
-                } else {
-                    let blocks: Vec<ContentBlock> = tool_calls
-                        .iter()
-                        .map(|tc| ContentBlock::ToolUse {
-                            id: tc.id.clone(),
-                            name: tc.name.clone(),
-                            input: tc.parse_arguments().unwrap_or_default(),
-                        })
-                        .collect();
-                    session
-                        .add_assistant_with_tools(
-                            if text_content.is_empty() {
-                                None
-                            } else {
-                                Some(&text_content)
-                            },
-                            blocks,
-                        )
-                        .await;
-                }
+            let calls: Vec<_> = output
+                .tool_calls
+                .iter()
+                .filter(|tc| structured.is_none() || tc.name != FINAL_ANSWER_TOOL)
+                .cloned()
+                .collect();
+            engine
+                .execute_tools(session, &calls, sink, &NoSteering, cancel)
+                .await;
+        }
 
-                // Execute tools concurrently
-                let tool_futures: Vec<_> = tool_calls
-                    .iter()
-                    .map(|tc| {
-                        let tools = tools.clone();
-                        let name = tc.name.clone();
-                        let args = tc.parse_arguments().unwrap_or_default();
-                        async move { tools.execute(&name, args).await }
-                    })
-                    .collect();
+        warn!(child = %sink.child, "Max tool iterations exceeded");
+        Ok(None)
+    }
+}
 
-                let results = futures::future::join_all(tool_futures).await;
+/// Collects a child's streamed text. Errors are logged; the parent only sees
+/// the outcome.
+struct ChildOutput {
+    child: String,
+    text: std::sync::Mutex<String>,
+}
 
-                for (tc, result) in tool_calls.iter().zip(results) {
-                    let is_error = result.is_error();
-                    let result_str = result.to_content_string();
-                    let result_str = if result_str.len() > 50000 {
-                        format!("{}...\n[truncated]", &result_str[..50000])
-                    } else {
-                        result_str
-                    };
-                    let _ = tx
-                        .send(AgentEvent::ToolResult {
-                            id: tc.id.clone(),
-                            name: tc.name.clone(),
-                            result: result_str.clone(),
-                            is_error,
-                        })
-                        .await;
-                    session.add_tool_result(&tc.id, &result_str, is_error).await;
-                }
-            }
-            Ok(())
-        });
-
-        // Collect output
-        let mut output = String::new();
-        let mut token_estimate = 0usize;
-        while let Some(event) = rx.recv().await {
-            match event {
-                AgentEvent::Text(t) => {
-                    output.push_str(&t);
-                    token_estimate += t.len() / 4;
-                }
-                AgentEvent::Error(e) => {
-                    if e != "Max tool iterations exceeded" {
-                        warn!(child = session_id, "child error: {}", e);
-                    }
-                }
-                _ => {}
-            }
+impl ChildOutput {
+    fn new(child: &str) -> Self {
+        Self {
+            child: child.to_string(),
+            text: std::sync::Mutex::new(String::new()),
         }
+    }
 
-        handle.await.map_err(|e| e.to_string())??;
-        self.sessions.remove(&session_key);
-        Ok((output, token_estimate))
+    fn into_text(self) -> String {
+        self.text.into_inner().unwrap_or_default()
+    }
+}
+
+#[async_trait::async_trait]
+impl EventSink for ChildOutput {
+    async fn emit(&self, event: AgentEvent) {
+        match event {
+            AgentEvent::Text(t) => self.text.lock().unwrap().push_str(&t),
+            AgentEvent::Error(e) => warn!(child = %self.child, "child error: {}", e),
+            _ => {}
+        }
+    }
+}
+
+/// `before turn 3: restored src/lib.rs; removed notes.md`, workspace-relative.
+fn rewind_note(report: &RestoreReport, workspace: &Path) -> String {
+    let list = |paths: &[PathBuf]| {
+        paths
+            .iter()
+            .map(|p| p.strip_prefix(workspace).unwrap_or(p).display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut parts = Vec::new();
+    if !report.restored.is_empty() {
+        parts.push(format!("restored {}", list(&report.restored)));
+    }
+    if !report.removed.is_empty() {
+        parts.push(format!("removed {}", list(&report.removed)));
     }
+    format!("before turn {}: {}", report.turn, parts.join("; "))
 }
@@ -0,0 +1,419 @@
+//! Session search — word search over a workspace's .ctx files
+//!
+//! Covers the workspace's own `.agenticlaw/sessions` and those of the
+//! directories directly under it (consciousness layers `L0`–`L3`, cores).
+//! Every user, assistant and tool result turn becomes one entry. The parsed
+//! turns are cached in `.agenticlaw/search-index.json` and refreshed
+//! incrementally: only files whose size or modification time changed are
+//! parsed again. A query scans every cached turn; there is no term index.
+
+use crate::ctx_file;
+use agenticlaw_ctx::{Block, Event};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::UNIX_EPOCH;
+
+/// Bumped when the stored layout changes; older indexes are rebuilt.
+const INDEX_VERSION: u32 = 1;
+
+/// Characters of context either side of a match in a snippet.
+const SNIPPET_CONTEXT: usize = 60;
+
+/// Numbers temp files, so concurrent saves in one process don't share one.
+static SAVES: AtomicU64 = AtomicU64::new(0);
+
+/// Which side of the conversation a turn came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// `<up>` blocks: user messages and tool results.
+    Up,
+    /// Model output: assistant turns and compaction summaries.
+    Model,
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "up" | "user" => Ok(Role::Up),
+            "model" | "assistant" => Ok(Role::Model),
+            other => Err(format!("Invalid role: {} (expected up or model)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Role::Up => "up",
+            Role::Model => "model",
+        })
+    }
+}
+
+/// What to look for. Every word of `text` must appear in a turn, in any
+/// case; an empty `text` matches every turn the filters let through.
+#[derive(Clone, Debug)]
+pub struct SearchQuery {
+    pub text: String,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub role: Option<Role>,
+    /// Turns calling this tool, or answering a call to it.
+    pub tool: Option<String>,
+    pub limit: usize,
+}
+
+impl SearchQuery {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            since: None,
+            until: None,
+            role: None,
+            tool: None,
+            limit: 20,
+        }
+    }
+}
+
+/// Parse a date filter: an RFC 3339 timestamp, or a `YYYY-MM-DD` date
+/// standing for its start, or with `end_of_day` its last moment.
+pub fn parse_time(text: &str, end_of_day: bool) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {} (expected YYYY-MM-DD or RFC 3339)", text))?;
+    let time = if end_of_day {
+        date.and_hms_milli_opt(23, 59, 59, 999)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time.expect("valid time of day").and_utc())
+}
+
+/// One matching turn.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub session: String,
+    pub path: PathBuf,
+    /// When the turn was written.
+    pub timestamp: String,
+    pub role: Role,
+    pub snippet: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct IndexedTurn {
+    timestamp: String,
+    role: Role,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<String>,
+    text: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct IndexedFile {
+    path: PathBuf,
+    session: String,
+    size: u64,
+    /// Modification time, milliseconds since the epoch.
+    modified: u64,
+    turns: Vec<IndexedTurn>,
+}
+
+/// The turns of every .ctx file in a workspace, as last parsed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SessionIndex {
+    version: u32,
+    files: Vec<IndexedFile>,
+}
+
+/// Files parsed and dropped by a refresh.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RefreshStats {
+    pub indexed: usize,
+    pub removed: usize,
+}
+
+impl SessionIndex {
+    /// Where a workspace's index is stored.
+    pub fn path(workspace: &Path) -> PathBuf {
+        workspace.join(".agenticlaw").join("search-index.json")
+    }
+
+    /// Load a workspace's index. A missing, unreadable or outdated one
+    /// comes back empty, to be filled by `refresh`.
+    pub fn open(workspace: &Path) -> Self {
+        fs::read_to_string(Self::path(workspace))
+            .ok()
+            .and_then(|text| serde_json::from_str::<SessionIndex>(&text).ok())
+            .filter(|index| index.version == INDEX_VERSION)
+            .unwrap_or_else(|| SessionIndex {
+                version: INDEX_VERSION,
+                files: Vec::new(),
+            })
+    }
+
+    /// Write the index back, replacing the stored one in a single rename.
+    pub fn save(&self, workspace: &Path) -> std::io::Result<()> {
+        let path = Self::path(workspace);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension(format!(
+            "json.{}.{}",
+            std::process::id(),
+            SAVES.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp, serde_json::to_vec(self)?)?;
+        fs::rename(&tmp, &path)
+    }
+
+    /// Bring the index up to date with the .ctx files on disk.
+    pub fn refresh(&mut self, workspace: &Path) -> std::io::Result<RefreshStats> {
+        let mut stats = RefreshStats::default();
+        let mut known: HashMap<PathBuf, IndexedFile> = std::mem::take(&mut self.files)
+            .into_iter()
+            .map(|file| (file.path.clone(), file))
+            .collect();
+
+        for path in ctx_files(workspace) {
+            let Ok(meta) = fs::metadata(&path) else {
+                continue;
+            };
+            let size = meta.len();
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as u64);
+            match known.remove(&path) {
+                Some(file) if file.size == size && file.modified == modified => {
+                    self.files.push(file)
+                }
+                _ => {
+                    // Deleted between listing and reading: drop it
+                    let Ok(text) = fs::read_to_string(&path) else {
+                        continue;
+                    };
+                    let (session, turns) = index_turns(&text);
+                    self.files.push(IndexedFile {
+                        path,
+                        session,
+                        size,
+                        modified,
+                        turns,
+                    });
+                    stats.indexed += 1;
+                }
+            }
+        }
+        stats.removed = known.len();
+        Ok(stats)
+    }
+
+    /// Matching turns, newest first.
+    pub fn search(&self, query: &SearchQuery) -> Vec<SearchHit> {
+        let terms: Vec<String> = query
+            .text
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+        let mut hits: Vec<(Option<DateTime<Utc>>, SearchHit)> = Vec::new();
+        for file in &self.files {
+            for turn in &file.turns {
+                if query.role.is_some_and(|role| role != turn.role) {
+                    continue;
+                }
+                if let Some(tool) = &query.tool {
+                    if !turn.tools.iter().any(|t| t == tool) {
+                        continue;
+                    }
+                }
+                let time = DateTime::parse_from_rfc3339(&turn.timestamp)
+                    .ok()
+                    .map(|t| t.with_timezone(&Utc));
+                let in_range = match time {
+                    Some(time) => {
+                        query.since.is_none_or(|since| time >= since)
+                            && query.until.is_none_or(|until| time <= until)
+                    }
+                    None => query.since.is_none() && query.until.is_none(),
+                };
+                if !in_range {
+                    continue;
+                }
+                let lower = turn.text.to_lowercase();
+                if !terms.iter().all(|term| lower.contains(term.as_str())) {
+                    continue;
+                }
+                hits.push((
+                    time,
+                    SearchHit {
+                        session: file.session.clone(),
+                        path: file.path.clone(),
+                        timestamp: turn.timestamp.clone(),
+                        role: turn.role,
+                        snippet: snippet(&turn.text, terms.first().map(String::as_str)),
+                    },
+                ));
+            }
+        }
+        hits.sort_by_key(|(time, _)| std::cmp::Reverse(*time));
+        hits.into_iter()
+            .take(query.limit)
+            .map(|(_, hit)| hit)
+            .collect()
+    }
+}
+
+/// Refresh the workspace's index, save it and search it.
+pub fn search(workspace: &Path, query: &SearchQuery) -> std::io::Result<Vec<SearchHit>> {
+    let mut index = SessionIndex::open(workspace);
+    let stats = index.refresh(workspace)?;
+    if stats != RefreshStats::default() {
+        if let Err(e) = index.save(workspace) {
+            tracing::warn!("Failed to save search index: {}", e);
+        }
+    }
+    Ok(index.search(query))
+}
+
+/// The .ctx files of a workspace and of the directories directly under it.
+fn ctx_files(workspace: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![ctx_file::sessions_dir(workspace)];
+    if let Ok(entries) = fs::read_dir(workspace) {
+        dirs.extend(
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.is_dir())
+                .map(|p| ctx_file::sessions_dir(&p)),
+        );
+    }
+    let mut files: Vec<PathBuf> = dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "ctx"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// The session id and searchable turns of a .ctx file.
+fn index_turns(text: &str) -> (String, Vec<IndexedTurn>) {
+    let mut session = String::new();
+    let mut turns = Vec::new();
+    // Tool results only carry the call's id
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+    for event in agenticlaw_ctx::parse(text).events {
+        let (timestamp, role, tools, text) = match event {
+            Event::Header(header) => {
+                session = header.id;
+                continue;
+            }
+            Event::User {
+                timestamp, text, ..
+            } => (timestamp, Role::Up, Vec::new(), text),
+            Event::ToolResult {
+                timestamp,
+                tool_use_id,
+                content,
+                ..
+            } => {
+                let tools = tool_names.get(&tool_use_id).cloned().into_iter().collect();
+                (timestamp, Role::Up, tools, content)
+            }
+            Event::Assistant {
+                timestamp, blocks, ..
+            } => {
+                let mut tools = Vec::new();
+                let mut lines = Vec::new();
+                for block in blocks {
+                    match block {
+                        Block::Text(text) => lines.push(text),
+                        Block::ToolCall { id, name, input } => {
+                            lines.push(format!("[tool:{}] {}", name, input));
+                            tool_names.insert(id, name.clone());
+                            tools.push(name);
+                        }
+                        Block::LegacyTool {
+                            name,
+                            summary,
+                            result,
+                            ..
+                        } => {
+                            lines.push(format!("[tool:{}] {}", name, summary));
+                            lines.extend(result);
+                            tools.push(name);
+                        }
+                        Block::Thinking { .. } | Block::RedactedThinking { .. } => {}
+                    }
+                }
+                (timestamp, Role::Model, tools, lines.join("\n"))
+            }
+            Event::Compaction {
+                timestamp, summary, ..
+            } => (timestamp, Role::Model, Vec::new(), summary),
+            Event::Context { .. }
+            | Event::ModelChange { .. }
+            | Event::ThinkingLevel { .. }
+            | Event::Rewind { .. } => continue,
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        turns.push(IndexedTurn {
+            timestamp,
+            role,
+            tools,
+            text,
+        });
+    }
+    (session, turns)
+}
+
+/// A line of `text` around the first match of `term`, or its start.
+fn snippet(text: &str, term: Option<&str>) -> String {
+    let at = term.and_then(|t| find_ignore_case(text, t)).unwrap_or(0);
+    let start = text[..at]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map_or(0, |(i, _)| i);
+    let end = text[at..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT + term.map_or(0, |t| t.chars().count()))
+        .map_or(text.len(), |(i, _)| at + i);
+    let mut out = text[start..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if start > 0 {
+        out.insert(0, '…');
+    }
+    if end < text.len() {
+        out.push('…');
+    }
+    out
+}
+
+/// Byte offset of the first case-insensitive match of a lowercase `term`.
+fn find_ignore_case(text: &str, term: &str) -> Option<usize> {
+    text.char_indices().map(|(i, _)| i).find(|&i| {
+        let mut rest = text[i..].chars().flat_map(char::to_lowercase);
+        term.chars().all(|c| rest.next() == Some(c))
+    })
+}
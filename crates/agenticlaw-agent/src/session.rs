@@ -1,13 +1,19 @@
 //! Session management with .ctx file persistence
 
+use crate::branch;
+use crate::compaction;
 use crate::context::ContextManager;
 use crate::ctx_file;
-use agenticlaw_llm::{ContentBlock, LlmContent, LlmMessage};
+use crate::usage::SessionUsage;
+use agenticlaw_ctx::{Block, Event, TokenUsage};
+use agenticlaw_llm::{
+    ContentBlock, LlmContent, LlmMessage, ModelInfo, ModelPricing, ThinkingConfig, Usage,
+};
 use dashmap::DashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
-use tracing::info;
+use tracing::{info, warn};
 
 // Sleep threshold is configured via consciousness.toml [sleep] section
 
@@ -103,14 +109,7 @@ impl SessionRegistry {
                 );
 
                 // Hydrate messages from the parsed .ctx
-                let messages = &resumed.messages;
-                let mut msg_vec = Vec::new();
-                for (role, content) in messages {
-                    msg_vec.push(agenticlaw_llm::LlmMessage {
-                        role: role.clone(),
-                        content: agenticlaw_llm::LlmContent::Text(content.clone()),
-                    });
-                }
+                let msg_vec = resumed.messages.clone();
 
                 // Set messages synchronously during construction.
                 // block_in_place allows blocking inside async runtime without panic.
@@ -121,8 +120,10 @@ impl SessionRegistry {
                     tokio::runtime::Handle::current().block_on(async {
                         let mut lock = s.messages_mut().await;
                         *lock = msg_vec;
+                        *s.usage.write().await = resumed.usage.clone();
                     });
                 });
+                session.set_turn(resumed.user_turns);
 
                 info!(
                     "Resumed session {} from {} ({} messages)",
@@ -135,6 +136,40 @@ impl SessionRegistry {
             .clone()
     }
 
+    /// Fork `parent` after user turn `turn` into a new session `key` with
+    /// its own .ctx file. The fork starts from the parent's system prompt,
+    /// model and thinking settings; spend so far stays with the parent.
+    pub async fn fork(
+        &self,
+        parent: &Session,
+        turn: usize,
+        key: &SessionKey,
+    ) -> Result<Arc<Session>, String> {
+        if self.sessions.contains_key(key) {
+            return Err(format!("Session already exists: {}", key));
+        }
+        let parent_path = parent
+            .ctx_path()
+            .ok_or_else(|| format!("Session {} has no .ctx file", parent.key))?;
+        let path = branch::fork_ctx(parent_path, turn, key.as_str()).map_err(|e| e.to_string())?;
+        let resumed = ctx_file::parse_for_resume(&path).map_err(|e| e.to_string())?;
+
+        let system_prompt = parent.system_prompt().await.or(resumed.system_prompt);
+        let session = Session::new_with_ctx(key.clone(), system_prompt.as_deref(), Some(path));
+        *session.messages.write().await = resumed.messages;
+        *session.model.write().await = parent.model().await;
+        session.set_thinking(parent.thinking().await).await;
+        session.set_turn(turn);
+
+        let session = Arc::new(session);
+        self.sessions.insert(key.clone(), session.clone());
+        info!(
+            "Forked session {} at turn {} into {}",
+            parent.key, turn, key
+        );
+        Ok(session)
+    }
+
     pub fn get_or_create(&self, key: &SessionKey, system_prompt: Option<&str>) -> Arc<Session> {
         self.sessions
             .entry(key.clone())
@@ -161,11 +196,34 @@ pub struct Session {
     messages: RwLock<Vec<LlmMessage>>,
     context: RwLock<ContextManager>,
     model: RwLock<Option<String>>,
+    thinking: RwLock<Option<ThinkingConfig>>,
     ctx_path: Option<PathBuf>,
     abort_tx: mpsc::Sender<()>,
     abort_rx: RwLock<Option<mpsc::Receiver<()>>>,
     /// Count of user messages added since last LLM call — for detecting injected HITL input
     pending_user_messages: std::sync::atomic::AtomicUsize,
+    usage: RwLock<SessionUsage>,
+    /// Real prompt size of the last request, pinned to the estimate at that point
+    usage_anchor: RwLock<Option<UsageAnchor>>,
+    /// Usage waiting to close the next assistant turn in .ctx
+    pending_usage: std::sync::Mutex<Option<TokenUsage>>,
+    /// Shared file-tracking scope; children inherit their parent's
+    lineage: std::sync::Mutex<Option<String>>,
+    /// User turns started in this process; checkpoints are numbered by it
+    turn: std::sync::atomic::AtomicUsize,
+}
+
+fn with_source(block: ContentBlock, source: agenticlaw_llm::MediaSource) -> ContentBlock {
+    match block {
+        ContentBlock::Document { title, .. } => ContentBlock::Document { source, title },
+        _ => ContentBlock::Image { source },
+    }
+}
+
+#[derive(Clone, Copy)]
+struct UsageAnchor {
+    real_tokens: usize,
+    estimate: usize,
 }
 
 impl Session {
@@ -179,7 +237,7 @@ impl Session {
         ctx_path: Option<PathBuf>,
     ) -> Self {
         let (abort_tx, abort_rx) = mpsc::channel(1);
-        let mut context = ContextManager::new(128_000);
+        let mut context = ContextManager::new(ModelInfo::DEFAULT.context_window);
         if let Some(sys) = system_prompt {
             context.set_system(sys);
         }
@@ -189,10 +247,16 @@ impl Session {
             messages: RwLock::new(Vec::new()),
             context: RwLock::new(context),
             model: RwLock::new(None),
+            thinking: RwLock::new(None),
             ctx_path,
             abort_tx,
             abort_rx: RwLock::new(Some(abort_rx)),
             pending_user_messages: std::sync::atomic::AtomicUsize::new(0),
+            usage: RwLock::new(SessionUsage::default()),
+            usage_anchor: RwLock::new(None),
+            pending_usage: std::sync::Mutex::new(None),
+            lineage: std::sync::Mutex::new(None),
+            turn: std::sync::atomic::AtomicUsize::new(0),
         }
     }
 
@@ -215,19 +279,51 @@ impl Session {
         self.context.write().await.set_system(prompt);
     }
 
-    /// Add a user message. Returns `true` if the session should sleep
-    /// (context limit reached), `false` otherwise.
-    /// Add a user message. Returns true if estimated tokens exceed the sleep threshold
-    /// (pct * max_context_tokens), signaling the layer should sleep.
+    /// Add a user message. Returns true if the context size (see `token_count`)
+    /// exceeds the sleep threshold (pct * max_context_tokens), signaling the layer should sleep.
     pub async fn add_user_message(
         &self,
         content: &str,
         sleep_threshold_pct: f64,
         max_context_tokens: usize,
     ) -> bool {
+        self.add_user_message_with_attachments(
+            content,
+            Vec::new(),
+            sleep_threshold_pct,
+            max_context_tokens,
+        )
+        .await
+    }
+
+    /// `add_user_message` with image/document blocks after the text.
+    pub async fn add_user_message_with_attachments(
+        &self,
+        content: &str,
+        attachments: Vec<ContentBlock>,
+        sleep_threshold_pct: f64,
+        max_context_tokens: usize,
+    ) -> bool {
+        let attachments = self.store_media(attachments);
+        let event = Event::User {
+            timestamp: ctx_file::now_timestamp(),
+            text: content.to_string(),
+            attachments: attachments
+                .iter()
+                .filter_map(ctx_file::ctx_attachment)
+                .collect(),
+        };
         let message = LlmMessage {
             role: "user".to_string(),
-            content: LlmContent::Text(content.to_string()),
+            content: if attachments.is_empty() {
+                LlmContent::Text(content.to_string())
+            } else {
+                let mut blocks = vec![ContentBlock::Text {
+                    text: content.to_string(),
+                }];
+                blocks.extend(attachments);
+                LlmContent::Blocks(blocks)
+            },
         };
         let mut messages = self.messages.write().await;
         messages.push(message);
@@ -236,11 +332,11 @@ impl Session {
 
         // Persist to .ctx
         if let Some(ref path) = self.ctx_path {
-            let _ = ctx_file::append_user_message(path, &ctx_file::now_timestamp(), content);
+            let _ = ctx_file::append(path, &event);
         }
+        self.begin_turn();
 
-        let context = self.context.read().await;
-        let total = context.calculate_total(&messages);
+        let total = self.context_tokens(&messages).await;
         let sleep_threshold = (sleep_threshold_pct * max_context_tokens as f64) as usize;
         if total > sleep_threshold {
             info!(
@@ -264,16 +360,25 @@ impl Session {
         self.messages.write().await.push(message);
 
         if let Some(ref path) = self.ctx_path {
-            let _ = ctx_file::append_assistant_text(path, &ctx_file::now_timestamp(), content);
+            let event = Event::Assistant {
+                timestamp: ctx_file::now_timestamp(),
+                blocks: vec![Block::Text(content.to_string())],
+                usage: self.take_usage(),
+            };
+            let _ = ctx_file::append(path, &event);
         }
     }
 
+    /// Add an assistant turn made of optional text plus content blocks
+    /// (tool calls, thinking). Thinking blocks are moved ahead of the text,
+    /// where the API expects them when the turn is replayed.
     pub async fn add_assistant_with_tools(
         &self,
         text: Option<&str>,
         tool_calls: Vec<ContentBlock>,
     ) {
-        let mut blocks = Vec::new();
+        let (mut blocks, tool_calls): (Vec<_>, Vec<_>) =
+            tool_calls.into_iter().partition(ContentBlock::is_thinking);
         if let Some(t) = text {
             if !t.is_empty() {
                 blocks.push(ContentBlock::Text {
@@ -281,41 +386,57 @@ impl Session {
                 });
             }
         }
-        blocks.extend(tool_calls.clone());
+        blocks.extend(tool_calls);
+
+        // Persist: thinking, text and tool calls in one .ctx turn
+        if let Some(ref path) = self.ctx_path {
+            let event = Event::Assistant {
+                timestamp: ctx_file::now_timestamp(),
+                blocks: blocks.iter().filter_map(ctx_file::ctx_block).collect(),
+                usage: self.take_usage(),
+            };
+            let _ = ctx_file::append(path, &event);
+        }
+
         let message = LlmMessage {
             role: "assistant".to_string(),
             content: LlmContent::Blocks(blocks),
         };
         self.messages.write().await.push(message);
-
-        // Persist: write text + tool calls to .ctx
-        if let Some(ref path) = self.ctx_path {
-            let ts = ctx_file::now_timestamp();
-            let mut ctx_content = String::new();
-            if let Some(t) = text {
-                if !t.is_empty() {
-                    ctx_content.push_str(t);
-                    ctx_content.push('\n');
-                }
-            }
-            for tc in &tool_calls {
-                if let ContentBlock::ToolUse { name, input, .. } = tc {
-                    let summary = input
-                        .as_object()
-                        .and_then(|o| o.iter().next())
-                        .map(|(k, v)| format!("{}={}", k, v.as_str().unwrap_or(&v.to_string())))
-                        .unwrap_or_default();
-                    ctx_content.push_str(&format!("[tool:{}] {}\n", name, summary));
-                }
-            }
-            let _ = ctx_file::append_assistant_text(path, &ts, ctx_content.trim());
-        }
     }
 
     pub async fn add_tool_result(&self, tool_use_id: &str, content: &str, is_error: bool) {
-        let block = ContentBlock::ToolResult {
+        self.add_tool_result_with_media(tool_use_id, content, Vec::new(), is_error)
+            .await
+    }
+
+    /// Add a tool result whose content carries images/documents after the text.
+    pub async fn add_tool_result_with_media(
+        &self,
+        tool_use_id: &str,
+        content: &str,
+        media: Vec<ContentBlock>,
+        is_error: bool,
+    ) {
+        let media = self.store_media(media);
+        let event = Event::ToolResult {
+            timestamp: ctx_file::now_timestamp(),
             tool_use_id: tool_use_id.to_string(),
             content: content.to_string(),
+            attachments: media.iter().filter_map(ctx_file::ctx_attachment).collect(),
+            is_error,
+        };
+        let block = ContentBlock::ToolResult {
+            tool_use_id: tool_use_id.to_string(),
+            content: if media.is_empty() {
+                LlmContent::Text(content.to_string())
+            } else {
+                let mut blocks = vec![ContentBlock::Text {
+                    text: content.to_string(),
+                }];
+                blocks.extend(media);
+                LlmContent::Blocks(blocks)
+            },
             is_error: if is_error { Some(true) } else { None },
         };
 
@@ -356,16 +477,35 @@ impl Session {
 
         // Tool results are <up> in .ctx — they're input to the model from outside
         if let Some(ref path) = self.ctx_path {
-            let _ = ctx_file::append_tool_result(
-                path,
-                &ctx_file::now_timestamp(),
-                "result",
-                content,
-                is_error,
-            );
+            let _ = ctx_file::append(path, &event);
         }
     }
 
+    /// Copy media into the session's attachments directory so the history
+    /// holds references to files the session owns rather than bytes or paths
+    /// that may disappear. Without a .ctx file
+    /// (or if the write fails) blocks are kept as they are.
+    fn store_media(&self, blocks: Vec<ContentBlock>) -> Vec<ContentBlock> {
+        let Some(ref ctx_path) = self.ctx_path else {
+            return blocks;
+        };
+        blocks
+            .into_iter()
+            .map(|block| {
+                let Some(source) = block.media_source() else {
+                    return block;
+                };
+                match ctx_file::save_attachment(ctx_path, source) {
+                    Ok(saved) => with_source(block, saved),
+                    Err(e) => {
+                        warn!("Session {}: could not store attachment: {}", self.key, e);
+                        block
+                    }
+                }
+            })
+            .collect()
+    }
+
     pub async fn get_messages(&self) -> Vec<LlmMessage> {
         self.messages.read().await.clone()
     }
@@ -389,9 +529,70 @@ impl Session {
             .swap(0, std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// Current context size in tokens. Anchored to the provider-reported prompt
+    /// size of the last request, plus a chars/4 estimate for anything added since;
+    /// falls back to the pure estimate before the first response.
     pub async fn token_count(&self) -> usize {
         let messages = self.messages.read().await;
-        self.context.read().await.calculate_total(&messages)
+        self.context_tokens(&messages).await
+    }
+
+    async fn context_tokens(&self, messages: &[LlmMessage]) -> usize {
+        let estimate = self.context.read().await.calculate_total(messages);
+        match *self.usage_anchor.read().await {
+            // History shrank (compaction, clear) — the anchor no longer applies
+            Some(anchor) if estimate >= anchor.estimate => {
+                anchor.real_tokens + (estimate - anchor.estimate)
+            }
+            _ => estimate,
+        }
+    }
+
+    /// Context window of the model serving this session (see `LlmProvider::model_info`).
+    pub async fn context_window(&self) -> usize {
+        self.context.read().await.max_tokens()
+    }
+
+    pub async fn set_context_window(&self, tokens: usize) {
+        self.context.write().await.set_max_tokens(tokens);
+    }
+
+    /// Record provider-reported usage for the request just completed. Call
+    /// before appending the assistant response so the .ctx line lands in its block.
+    /// `pricing` is the provider's for `model`; see `SessionUsage::record`.
+    pub async fn record_usage(&self, model: &str, usage: &Usage, pricing: Option<ModelPricing>) {
+        self.usage.write().await.record(model, usage, pricing);
+        let estimate = {
+            let messages = self.messages.read().await;
+            self.context.read().await.calculate_total(&messages)
+        };
+        *self.usage_anchor.write().await = Some(UsageAnchor {
+            real_tokens: usage.prompt_tokens() as usize,
+            estimate,
+        });
+        if self.ctx_path.is_some() {
+            *self.pending_usage.lock().unwrap() = Some(ctx_file::ctx_usage(model, usage));
+        }
+    }
+
+    /// Record usage of a request made on the session's behalf outside its
+    /// history, such as a compaction summary. Counts toward spend only.
+    pub async fn record_side_usage(
+        &self,
+        model: &str,
+        usage: &Usage,
+        pricing: Option<ModelPricing>,
+    ) {
+        self.usage.write().await.record(model, usage, pricing);
+    }
+
+    /// Cumulative usage and cost estimate for this session.
+    pub async fn usage(&self) -> SessionUsage {
+        self.usage.read().await.clone()
+    }
+
+    fn take_usage(&self) -> Option<TokenUsage> {
+        self.pending_usage.lock().unwrap().take()
     }
 
     pub async fn model(&self) -> Option<String> {
@@ -400,6 +601,44 @@ impl Session {
     pub async fn set_model(&self, model: &str) {
         *self.model.write().await = Some(model.to_string());
     }
+    /// Key the file tools track reads under. Defaults to the session key.
+    pub fn lineage(&self) -> String {
+        self.lineage
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| self.key.as_str().to_string())
+    }
+    /// Work under another session's lineage during its user turn `turn`:
+    /// reads and checkpoints go to that lineage, and this session's own
+    /// messages (a child's prompt and reminders) don't start turns of their own.
+    pub fn set_lineage(&self, lineage: &str, turn: usize) {
+        *self.lineage.lock().unwrap() = Some(lineage.to_string());
+        self.set_turn(turn);
+    }
+    /// Current user turn; 0 before the first. Every user message starts
+    /// one, steering and follow-ups included, so turn N is the Nth `<up>`
+    /// in the .ctx file.
+    pub fn turn(&self) -> usize {
+        self.turn.load(std::sync::atomic::Ordering::SeqCst)
+    }
+    /// Start the next user turn and return its number.
+    fn begin_turn(&self) -> usize {
+        if self.lineage.lock().unwrap().is_some() {
+            return self.turn();
+        }
+        self.turn.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1
+    }
+    pub fn set_turn(&self, turn: usize) {
+        self.turn.store(turn, std::sync::atomic::Ordering::SeqCst);
+    }
+    /// Extended thinking for this session's requests. `None` disables it.
+    pub async fn thinking(&self) -> Option<ThinkingConfig> {
+        *self.thinking.read().await
+    }
+    pub async fn set_thinking(&self, thinking: Option<ThinkingConfig>) {
+        *self.thinking.write().await = thinking;
+    }
     pub async fn abort(&self) {
         let _ = self.abort_tx.send(()).await;
     }
@@ -408,7 +647,30 @@ impl Session {
         self.abort_rx.write().await.take()
     }
 
+    /// Replace the first `cut` messages with `summary` and write a compaction
+    /// block to .ctx. Returns the number of messages kept after the summary.
+    pub async fn compact(&self, cut: usize, summary: &str) -> usize {
+        let mut messages = self.messages.write().await;
+        let cut = cut.min(messages.len());
+        let kept_events = messages[cut..].iter().map(ctx_file::event_count).sum();
+        messages.splice(..cut, [compaction::summary_message(summary)]);
+        let kept = messages.len() - 1;
+        drop(messages);
+        *self.usage_anchor.write().await = None;
+
+        if let Some(ref path) = self.ctx_path {
+            let event = Event::Compaction {
+                timestamp: ctx_file::now_timestamp(),
+                summary: summary.to_string(),
+                kept: kept_events,
+            };
+            let _ = ctx_file::append(path, &event);
+        }
+        kept
+    }
+
     pub async fn clear(&self) {
         self.messages.write().await.clear();
+        *self.usage_anchor.write().await = None;
     }
 }
@@ -0,0 +1,215 @@
+//! Tool output spill — oversized results go to disk, the model gets a preview
+//!
+//! A result over its tool's byte budget is written whole to
+//! `<workspace>/.agenticlaw/spill/<session>/<call id>.txt`. The context gets
+//! its first and last lines plus that path, so the model can page through the
+//! rest with `read` and `offset`/`limit` instead of losing it. `read` pages by
+//! line, so lines longer than `WRAP_BYTES` are broken up in the spilled copy.
+//! Without a workspace (or if the write fails) the middle is dropped instead.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Budget for tools without one of their own.
+pub const DEFAULT_BUDGET_BYTES: usize = 50_000;
+
+/// A spilled result's preview is at most this big, whatever the budget.
+pub const PREVIEW_BYTES: usize = 8_000;
+
+/// Longest line in a spilled file; longer ones continue on the next line.
+pub const WRAP_BYTES: usize = 2_000;
+
+#[derive(Clone, Debug)]
+pub struct OutputSpill {
+    dir: Option<PathBuf>,
+    default_budget: usize,
+    budgets: HashMap<String, usize>,
+}
+
+impl Default for OutputSpill {
+    /// No spill directory: oversized results lose their middle.
+    fn default() -> Self {
+        Self {
+            dir: None,
+            default_budget: DEFAULT_BUDGET_BYTES,
+            budgets: HashMap::new(),
+        }
+    }
+}
+
+impl OutputSpill {
+    /// Spill into `<workspace>/.agenticlaw/spill`, where `read` can reach it.
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            dir: Some(workspace.join(".agenticlaw").join("spill")),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_default_budget(mut self, bytes: usize) -> Self {
+        self.default_budget = bytes;
+        self
+    }
+
+    /// Give `tool` its own budget, e.g. more for `read`, less for `bash`.
+    pub fn with_budget(mut self, tool: impl Into<String>, bytes: usize) -> Self {
+        self.budgets.insert(tool.into(), bytes);
+        self
+    }
+
+    pub fn budget(&self, tool: &str) -> usize {
+        self.budgets
+            .get(tool)
+            .copied()
+            .unwrap_or(self.default_budget)
+    }
+
+    /// Where `session`'s spilled results live, if spilling is on.
+    pub fn session_dir(&self, session: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|d| d.join(file_safe(session)))
+    }
+
+    /// `text` if it fits `tool`'s budget, else a preview pointing at the
+    /// spilled whole.
+    pub async fn apply(&self, session: &str, call_id: &str, tool: &str, text: String) -> String {
+        let budget = self.budget(tool);
+        if text.len() <= budget {
+            return text;
+        }
+        let size = |text: &str| match text.lines().count() {
+            1 => format!("{} bytes", text.len()),
+            lines => format!("{} bytes, {} lines", text.len(), lines),
+        };
+
+        if let Some(dir) = self.session_dir(session) {
+            let path = dir.join(format!("{}.txt", file_safe(call_id)));
+            // Line numbers in the preview are the spilled file's
+            let wrapped = hard_wrap(&text, WRAP_BYTES);
+            match write_spill(&dir, &path, &wrapped).await {
+                Ok(()) => {
+                    let (preview, omitted) = preview(&wrapped, budget.min(PREVIEW_BYTES));
+                    return format!(
+                        "{}\n[Output too large: {}{}. Full output saved to {}; \
+                         use read with offset/limit to see the rest.]",
+                        preview,
+                        size(&wrapped),
+                        omitted,
+                        path.display()
+                    );
+                }
+                Err(e) => warn!(
+                    "Failed to spill {} output to {}: {}",
+                    tool,
+                    path.display(),
+                    e
+                ),
+            }
+        }
+
+        let (preview, omitted) = preview(&text, budget);
+        format!(
+            "{}\n[Output too large: {}{}.]",
+            preview,
+            size(&text),
+            omitted
+        )
+    }
+
+    /// Delete a session's spilled results. Called when the session is deleted.
+    pub fn forget(&self, session: &str) {
+        if let Some(dir) = self.session_dir(session) {
+            if let Err(e) = std::fs::remove_dir_all(&dir) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove {}: {}", dir.display(), e);
+                }
+            }
+        }
+    }
+}
+
+async fn write_spill(dir: &Path, path: &Path, text: &str) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    tokio::fs::write(path, text).await
+}
+
+/// `text` with every line over `width` bytes broken at char boundaries.
+fn hard_wrap(text: &str, width: usize) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / width);
+    for line in text.split_inclusive('\n') {
+        let mut rest = line;
+        while rest.trim_end_matches('\n').len() > width {
+            let mut at = width;
+            while !rest.is_char_boundary(at) {
+                at -= 1;
+            }
+            out.push_str(&rest[..at]);
+            out.push('\n');
+            rest = &rest[at..];
+        }
+        out.push_str(rest);
+    }
+    out
+}
+
+/// Head (three quarters of `bytes`) and tail (the rest) of `text`, cut at
+/// line ends where possible, with the omitted line range described.
+fn preview(text: &str, bytes: usize) -> (String, String) {
+    let head_end = cut_back(text, bytes * 3 / 4);
+    let tail_start = cut_forward(text, text.len().saturating_sub(bytes / 4)).max(head_end);
+    let head = &text[..head_end];
+    let tail = &text[tail_start..];
+
+    let first_omitted = head.lines().count() + 1;
+    let last_omitted = text[..tail_start].lines().count();
+    let omitted = if last_omitted >= first_omitted {
+        format!(", lines {}-{} not shown", first_omitted, last_omitted)
+    } else {
+        String::new()
+    };
+    (
+        format!("{}\n...\n{}", head.trim_end_matches('\n'), tail),
+        omitted,
+    )
+}
+
+/// The last line end at or before `at`, or the nearest char boundary below
+/// it when `at` is inside the first line.
+fn cut_back(text: &str, at: usize) -> usize {
+    let mut at = at.min(text.len());
+    while !text.is_char_boundary(at) {
+        at -= 1;
+    }
+    match text[..at].rfind('\n') {
+        Some(i) => i + 1,
+        None => at,
+    }
+}
+
+/// The first line start at or after `at`, or the nearest char boundary above
+/// it when `at` is inside the last line.
+fn cut_forward(text: &str, at: usize) -> usize {
+    let mut at = at.min(text.len());
+    while !text.is_char_boundary(at) {
+        at += 1;
+    }
+    if at > 0 && text.as_bytes()[at - 1] == b'\n' {
+        return at;
+    }
+    match text[at..].find('\n') {
+        Some(i) if at + i + 1 < text.len() => at + i + 1,
+        _ => at,
+    }
+}
+
+fn file_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,77 @@
+//! Structured output — typed final answers from child agents
+//!
+//! The child gets a synthetic `final_answer` tool whose input schema is the
+//! caller's JSON Schema, and must finish by calling it. Answers are checked
+//! against the schema; violations go back to the model as the tool result so
+//! it can correct itself, up to `max_retries` times.
+
+use agenticlaw_core::schema;
+use agenticlaw_llm::LlmTool;
+use serde_json::{json, Value};
+
+pub const FINAL_ANSWER_TOOL: &str = "final_answer";
+
+#[derive(Clone, Debug)]
+pub struct StructuredOutput {
+    pub schema: Value,
+    /// Re-prompts allowed after an invalid or missing final answer.
+    pub max_retries: usize,
+}
+
+impl StructuredOutput {
+    pub fn new(schema: Value) -> Self {
+        Self {
+            schema,
+            max_retries: 2,
+        }
+    }
+
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Tool inputs must be objects, so other schemas are wrapped as `{"answer": ...}`.
+    fn is_wrapped(&self) -> bool {
+        self.schema.get("type").and_then(Value::as_str) != Some("object")
+    }
+
+    /// The `final_answer` tool definition offered to the model.
+    pub fn tool(&self) -> LlmTool {
+        let input_schema = if self.is_wrapped() {
+            json!({
+                "type": "object",
+                "properties": { "answer": self.schema },
+                "required": ["answer"],
+            })
+        } else {
+            self.schema.clone()
+        };
+        LlmTool {
+            name: FINAL_ANSWER_TOOL.to_string(),
+            description: "Submit your final answer. Call this exactly once, when the task is \
+                          done; the input must match the schema."
+                .to_string(),
+            input_schema,
+        }
+    }
+
+    /// Validate the raw `final_answer` arguments and unwrap the answer.
+    /// On failure returns the message to send back to the model.
+    pub fn accept(&self, arguments: &str) -> Result<Value, String> {
+        let input: Value = serde_json::from_str(arguments)
+            .map_err(|e| format!("final_answer arguments are not valid JSON: {}", e))?;
+        let answer = if self.is_wrapped() {
+            input.get("answer").cloned().unwrap_or(Value::Null)
+        } else {
+            input
+        };
+        schema::validate(&self.schema, &answer).map_err(|errors| {
+            format!(
+                "final_answer does not match the schema:\n- {}",
+                errors.join("\n- ")
+            )
+        })?;
+        Ok(answer)
+    }
+}
@@ -0,0 +1,81 @@
+//! Session token accounting — real provider-reported usage, not estimates.
+
+use agenticlaw_llm::{ModelPricing, Usage};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Cumulative token counts. u64 so long-lived sessions can't overflow.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct UsageTotals {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    pub turns: usize,
+}
+
+impl UsageTotals {
+    fn add(&mut self, usage: &Usage) {
+        self.input_tokens += usage.input_tokens as u64;
+        self.output_tokens += usage.output_tokens as u64;
+        self.cache_creation_input_tokens += usage.cache_creation_input_tokens as u64;
+        self.cache_read_input_tokens += usage.cache_read_input_tokens as u64;
+        self.turns += 1;
+    }
+
+    /// Share of prompt tokens served from the prompt cache. `None` before
+    /// the first request.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let prompt =
+            self.input_tokens + self.cache_creation_input_tokens + self.cache_read_input_tokens;
+        (prompt > 0).then(|| self.cache_read_input_tokens as f64 / prompt as f64)
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens
+            + self.output_tokens
+            + self.cache_creation_input_tokens
+            + self.cache_read_input_tokens
+    }
+}
+
+/// Per-model slice of a session's usage with its cost estimate.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ModelUsage {
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+    /// `None` when the model has no known pricing (e.g. self-hosted).
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SessionUsage {
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+    pub by_model: BTreeMap<String, ModelUsage>,
+    /// Prompt + output tokens of the most recent request — the real context size.
+    pub last_context_tokens: Option<u64>,
+}
+
+impl SessionUsage {
+    /// Record one request's usage against `model`, priced at `pricing` (the
+    /// provider's `model_info`, which carries openclaw.json costs) or else
+    /// the built-in list price.
+    pub fn record(&mut self, model: &str, usage: &Usage, pricing: Option<ModelPricing>) {
+        self.totals.add(usage);
+        let entry = self.by_model.entry(model.to_string()).or_default();
+        entry.totals.add(usage);
+        if let Some(pricing) = pricing.or_else(|| ModelPricing::for_model(model)) {
+            *entry.cost_usd.get_or_insert(0.0) += pricing.cost(usage);
+        }
+        self.last_context_tokens = Some(usage.total_tokens() as u64);
+    }
+
+    /// Sum of per-model cost estimates; `None` if no model had pricing.
+    pub fn cost_usd(&self) -> Option<f64> {
+        self.by_model
+            .values()
+            .filter_map(|m| m.cost_usd)
+            .fold(None, |acc, c| Some(acc.unwrap_or(0.0) + c))
+    }
+}
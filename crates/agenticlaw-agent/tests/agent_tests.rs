@@ -1,5 +1,6 @@
-//! Tests for agenticlaw-agent: Session, SessionRegistry, ContextManager, and real AgentRuntime
+//! Tests for agenticlaw-agent: Session, SessionRegistry, ContextManager, compaction, search, and real AgentRuntime
 
+use agenticlaw_agent::search::{self, Role, SearchQuery, SessionIndex};
 use agenticlaw_agent::*;
 use agenticlaw_llm::{ContentBlock, LlmContent, LlmMessage};
 
@@ -82,51 +83,6 @@ fn context_manager_set_system_adds_tokens() {
     assert!(with_system > 0, "System prompt should add tokens");
 }
 
-#[test]
-fn context_manager_compact_removes_old_messages() {
-    let cm = ContextManager::new(100); // Very small limit
-
-    let mut messages: Vec<LlmMessage> = (0..50)
-        .map(|i| LlmMessage {
-            role: "user".into(),
-            content: LlmContent::Text(format!(
-                "This is message number {} with some padding text to use tokens",
-                i
-            )),
-        })
-        .collect();
-
-    let before = messages.len();
-    cm.compact(&mut messages);
-    assert!(
-        messages.len() < before,
-        "Compaction should remove messages: {} -> {}",
-        before,
-        messages.len()
-    );
-    assert!(messages.len() >= 2, "Should keep at least 2 messages");
-}
-
-#[test]
-fn context_manager_no_compact_under_limit() {
-    let cm = ContextManager::new(1_000_000);
-    let mut messages = vec![LlmMessage {
-        role: "user".into(),
-        content: LlmContent::Text("hello".into()),
-    }];
-    let before = messages.len();
-    cm.compact(&mut messages);
-    assert_eq!(messages.len(), before, "Should not compact under limit");
-}
-
-#[test]
-fn context_manager_compact_empty() {
-    let cm = ContextManager::new(100);
-    let mut messages: Vec<LlmMessage> = Vec::new();
-    cm.compact(&mut messages); // should not panic
-    assert!(messages.is_empty());
-}
-
 // ===========================================================================
 // Session
 // ===========================================================================
@@ -176,6 +132,85 @@ async fn session_add_assistant_with_tools() {
     }
 }
 
+#[tokio::test]
+async fn session_keeps_thinking_blocks_first() {
+    let session = Session::new(SessionKey::new("s1"), None);
+    assert!(session.thinking().await.is_none());
+    session
+        .set_thinking(Some(agenticlaw_llm::ThinkingConfig::new(2048)))
+        .await;
+    assert_eq!(session.thinking().await.unwrap().budget_tokens, 2048);
+
+    session
+        .add_assistant_with_tools(
+            Some("Reading."),
+            vec![
+                ContentBlock::ToolUse {
+                    id: "tc-1".into(),
+                    name: "read".into(),
+                    input: serde_json::json!({"path": "/tmp/foo"}),
+                },
+                ContentBlock::Thinking {
+                    thinking: "Need the file.".into(),
+                    signature: "sig".into(),
+                },
+            ],
+        )
+        .await;
+    let messages = session.get_messages().await;
+    let LlmContent::Blocks(blocks) = &messages[0].content else {
+        panic!("Expected Blocks");
+    };
+    assert!(blocks[0].is_thinking());
+    assert!(matches!(&blocks[1], ContentBlock::Text { .. }));
+    assert!(matches!(&blocks[2], ContentBlock::ToolUse { .. }));
+}
+
+#[tokio::test]
+async fn session_stores_attachments_on_disk() {
+    use agenticlaw_llm::MediaSource;
+
+    let dir = std::env::temp_dir().join(format!("agenticlaw-attach-{}", std::process::id()));
+    let ctx = dir.join("s.ctx");
+    agenticlaw_agent::ctx_file::create(&ctx, "s1", "2026-01-01T00:00:00Z", None, &[]).unwrap();
+    let session = Session::new_with_ctx(SessionKey::new("s1"), None, Some(ctx.clone()));
+
+    let image = ContentBlock::Image {
+        source: MediaSource::Base64 {
+            media_type: "image/png".into(),
+            data: "aGk=".into(),
+        },
+    };
+    session
+        .add_user_message_with_attachments("What is this?", vec![image.clone()], 1.0, usize::MAX)
+        .await;
+    session
+        .add_tool_result_with_media("tc-1", "Image: b.png", vec![image], false)
+        .await;
+
+    // History holds file references, not bytes
+    let messages = session.get_messages().await;
+    let LlmContent::Blocks(blocks) = &messages[0].content else {
+        panic!("Expected Blocks");
+    };
+    assert!(matches!(
+        blocks[1].media_source(),
+        Some(MediaSource::Path { path, .. }) if std::fs::read(path).unwrap() == b"hi"
+    ));
+    let LlmContent::Blocks(results) = &messages[1].content else {
+        panic!("Expected Blocks");
+    };
+    assert!(matches!(
+        &results[0],
+        ContentBlock::ToolResult { content: LlmContent::Blocks(inner), .. } if inner.len() == 2
+    ));
+
+    let text = session.read_ctx().unwrap();
+    assert_eq!(text.matches("[attachment:image/png] ").count(), 2);
+    assert!(!text.contains("aGk="));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
 #[tokio::test]
 async fn session_add_tool_result() {
     let session = Session::new(SessionKey::new("s1"), None);
@@ -222,6 +257,75 @@ async fn session_token_count() {
     assert!(session.token_count().await > 0);
 }
 
+#[tokio::test]
+async fn session_record_usage_accumulates_per_model() {
+    let session = Session::new(SessionKey::new("s1"), None);
+    let turn = agenticlaw_llm::Usage {
+        input_tokens: 1000,
+        output_tokens: 200,
+        ..Default::default()
+    };
+    session.record_usage("claude-opus-4-6", &turn, None).await;
+    session.record_usage("claude-opus-4-6", &turn, None).await;
+    session.record_usage("local-llama", &turn, None).await;
+
+    let usage = session.usage().await;
+    assert_eq!(usage.totals.turns, 3);
+    assert_eq!(usage.totals.input_tokens, 3000);
+    assert_eq!(usage.by_model["claude-opus-4-6"].totals.turns, 2);
+    assert!(usage.by_model["local-llama"].cost_usd.is_none());
+    // 2 * (1000 * $5 + 200 * $25) / 1M
+    let cost = usage.cost_usd().unwrap();
+    assert!((cost - 0.02).abs() < 1e-9, "cost {}", cost);
+}
+
+#[test]
+fn usage_totals_cache_hit_rate() {
+    let mut usage = SessionUsage::default();
+    assert_eq!(usage.totals.cache_hit_rate(), None);
+    usage.record(
+        "claude-opus-4-6",
+        &agenticlaw_llm::Usage {
+            input_tokens: 100,
+            cache_creation_input_tokens: 900,
+            ..Default::default()
+        },
+        None,
+    );
+    usage.record(
+        "claude-opus-4-6",
+        &agenticlaw_llm::Usage {
+            input_tokens: 100,
+            cache_read_input_tokens: 900,
+            ..Default::default()
+        },
+        None,
+    );
+    assert_eq!(usage.totals.cache_hit_rate(), Some(0.45));
+}
+
+#[tokio::test]
+async fn session_sleep_uses_reported_usage() {
+    let session = Session::new(SessionKey::new("s1"), None);
+    assert!(!session.add_user_message("hi", 0.5, 200_000).await);
+
+    // Provider says the prompt was far bigger than the chars/4 estimate
+    let turn = agenticlaw_llm::Usage {
+        input_tokens: 500,
+        cache_read_input_tokens: 120_000,
+        output_tokens: 10,
+        ..Default::default()
+    };
+    session.record_usage("claude-opus-4-6", &turn, None).await;
+    assert!(session.token_count().await >= 120_500);
+
+    assert!(session.add_user_message("next", 0.5, 200_000).await);
+
+    // Clearing history drops the anchor
+    session.clear().await;
+    assert_eq!(session.token_count().await, 0);
+}
+
 #[tokio::test]
 async fn session_clear() {
     let session = Session::new(SessionKey::new("s1"), None);
@@ -287,6 +391,554 @@ async fn registry_remove() {
     assert!(registry.get(&key).is_none());
 }
 
+/// Replay a recorded OpenClaw session (`tests/fixtures/*.jsonl` at the repo
+/// root) into `session`.
+async fn replay_fixture(session: &Session, name: &str) {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../tests/fixtures")
+        .join(name);
+    for line in std::fs::read_to_string(path).unwrap().lines() {
+        let record: serde_json::Value = serde_json::from_str(line).unwrap();
+        let message = &record["message"];
+        let parts = message["content"].as_array().cloned().unwrap_or_default();
+        let text = |kind: &str| -> String {
+            parts
+                .iter()
+                .filter(|p| p["type"] == kind)
+                .filter_map(|p| p["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        match message["role"].as_str() {
+            Some("user") => {
+                session
+                    .add_user_message(&text("text"), 1.0, usize::MAX)
+                    .await;
+            }
+            Some("assistant") => {
+                let blocks: Vec<ContentBlock> = parts
+                    .iter()
+                    .filter_map(|p| match p["type"].as_str()? {
+                        "thinking" => Some(ContentBlock::Thinking {
+                            thinking: p["thinking"].as_str()?.into(),
+                            signature: "c2ln".into(),
+                        }),
+                        "toolCall" => Some(ContentBlock::ToolUse {
+                            id: p["id"].as_str()?.into(),
+                            name: p["name"].as_str()?.into(),
+                            input: p["arguments"].clone(),
+                        }),
+                        _ => None,
+                    })
+                    .collect();
+                let text = text("text");
+                if blocks.is_empty() {
+                    session.add_assistant_text(&text).await;
+                } else {
+                    session.add_assistant_with_tools(Some(&text), blocks).await;
+                }
+            }
+            Some("toolResult") => {
+                let is_error = message["isError"].as_bool().unwrap_or(false);
+                session
+                    .add_tool_result(
+                        message["toolCallId"].as_str().unwrap(),
+                        &text("text"),
+                        is_error,
+                    )
+                    .await;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn registry_resume_restores_tool_calls_from_ctx() {
+    let ws = std::env::temp_dir().join(format!("agenticlaw-resume-{}", std::process::id()));
+    let key = SessionKey::new("real-session");
+    let original = SessionRegistry::new().create_with_ctx(&key, None, &ws);
+    replay_fixture(&original, "real-session.jsonl").await;
+
+    let resumed = ctx_file::parse_for_resume(original.ctx_path().unwrap()).unwrap();
+    assert_eq!(resumed.session_id, "real-session");
+    let session = SessionRegistry::new().resume_from_ctx(&resumed, None);
+
+    // Everything but thinking, which is not resumed, comes back block for block
+    let expected: Vec<serde_json::Value> = original
+        .get_messages()
+        .await
+        .into_iter()
+        .map(|mut m| {
+            if let LlmContent::Blocks(blocks) = &mut m.content {
+                blocks.retain(|b| !b.is_thinking());
+            }
+            serde_json::to_value(m).unwrap()
+        })
+        .collect();
+    let restored: Vec<serde_json::Value> = session
+        .get_messages()
+        .await
+        .into_iter()
+        .map(|m| serde_json::to_value(m).unwrap())
+        .collect();
+    assert_eq!(restored.len(), 14);
+    assert_eq!(restored, expected);
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+// ===========================================================================
+// Session forks
+// ===========================================================================
+
+/// A persisted session with two user turns, the first using a tool.
+async fn two_turn_session(
+    ws: &std::path::Path,
+    name: &str,
+) -> (SessionRegistry, std::sync::Arc<Session>) {
+    let registry = SessionRegistry::new();
+    let session = registry.create_with_ctx(&SessionKey::new(name), None, ws);
+    session.add_user_message("Read foo.", 1.0, usize::MAX).await;
+    session
+        .add_assistant_with_tools(
+            Some("Reading."),
+            vec![ContentBlock::ToolUse {
+                id: "tc-1".into(),
+                name: "read".into(),
+                input: serde_json::json!({"file_path": "/tmp/foo"}),
+            }],
+        )
+        .await;
+    session.add_tool_result("tc-1", "foo contents", false).await;
+    session.add_assistant_text("It says foo.").await;
+    session.add_user_message("Now bar.", 1.0, usize::MAX).await;
+    session.add_assistant_text("Bar it is.").await;
+    (registry, session)
+}
+
+#[tokio::test]
+async fn registry_fork_keeps_turns_up_to_the_fork_point() {
+    let ws = std::env::temp_dir().join(format!("agenticlaw-fork-{}", std::process::id()));
+    let (registry, parent) = two_turn_session(&ws, "fork-parent").await;
+    parent.set_model("claude-sonnet-4-5").await;
+
+    let key = SessionKey::new("fork-child");
+    let fork = registry.fork(&parent, 1, &key).await.unwrap();
+    assert!(std::sync::Arc::ptr_eq(&registry.get(&key).unwrap(), &fork));
+    assert_eq!(fork.model().await.as_deref(), Some("claude-sonnet-4-5"));
+
+    // The first turn, tool round trip included, and nothing after it
+    let expected = parent.get_messages().await[..4].to_vec();
+    assert_eq!(
+        serde_json::to_value(fork.get_messages().await).unwrap(),
+        serde_json::to_value(expected).unwrap()
+    );
+    let ctx = fork.read_ctx().unwrap();
+    assert!(ctx.contains("--- session: fork-child ---\n"));
+    assert!(ctx.contains("\nforked-from: fork-parent turn 1\n"));
+    assert!(!ctx.contains("Now bar."));
+    assert_eq!(parent.message_count().await, 6);
+
+    let err = registry.fork(&parent, 3, &SessionKey::new("too-far")).await;
+    assert!(err.err().unwrap().contains("2 turns"));
+    let err = registry.fork(&parent, 0, &key).await;
+    assert!(err.err().unwrap().contains("already exists"));
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test]
+async fn session_tree_nests_forks_under_their_parents() {
+    let ws = std::env::temp_dir().join(format!("agenticlaw-tree-{}", std::process::id()));
+    let (registry, root) = two_turn_session(&ws, "tree-root").await;
+    let child = registry
+        .fork(&root, 2, &SessionKey::new("tree-child"))
+        .await
+        .unwrap();
+    registry
+        .fork(&child, 0, &SessionKey::new("tree-grandchild"))
+        .await
+        .unwrap();
+    registry.create_with_ctx(&SessionKey::new("tree-other"), None, &ws);
+
+    let tree = branch::session_tree(&ws).unwrap();
+    assert_eq!(tree.len(), 2);
+    let root = tree.iter().find(|n| n.session == "tree-root").unwrap();
+    assert_eq!(root.children.len(), 1);
+    let child = &root.children[0];
+    assert_eq!(
+        (
+            child.session.as_str(),
+            child.parent.as_deref(),
+            child.fork_turn
+        ),
+        ("tree-child", Some("tree-root"), Some(2))
+    );
+    assert_eq!(child.children[0].session, "tree-grandchild");
+    assert_eq!(child.children[0].fork_turn, Some(0));
+    assert!(tree
+        .iter()
+        .any(|n| n.session == "tree-other" && n.children.is_empty()));
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+// ===========================================================================
+// Compaction
+// ===========================================================================
+
+fn text_message(role: &str, text: &str) -> LlmMessage {
+    LlmMessage {
+        role: role.into(),
+        content: LlmContent::Text(text.into()),
+    }
+}
+
+#[test]
+fn split_point_keeps_tool_pairs_together() {
+    let call = LlmMessage {
+        role: "assistant".into(),
+        content: LlmContent::Blocks(vec![ContentBlock::ToolUse {
+            id: "tc-1".into(),
+            name: "read".into(),
+            input: serde_json::json!({"file_path": "/tmp/foo"}),
+        }]),
+    };
+    let result = LlmMessage {
+        role: "user".into(),
+        content: LlmContent::Blocks(vec![ContentBlock::ToolResult {
+            tool_use_id: "tc-1".into(),
+            content: LlmContent::Text("x".repeat(400)),
+            is_error: None,
+        }]),
+    };
+    let messages = vec![
+        text_message("user", "Read foo."),
+        call,
+        result,
+        text_message("assistant", "It says x."),
+        text_message("user", "Thanks."),
+    ];
+
+    // Only assistant turns start the kept history, so a result never loses its call
+    assert_eq!(compaction::split_point(&messages, 50), Some(3));
+    assert_eq!(compaction::split_point(&messages, 10_000), Some(1));
+    // Nothing fits: keep from the last assistant turn anyway
+    assert_eq!(compaction::split_point(&messages, 0), Some(3));
+    assert_eq!(compaction::split_point(&messages[..1], 0), None);
+}
+
+#[test]
+fn compaction_transcript_shows_tool_calls_and_results() {
+    let messages = vec![
+        text_message("user", "Read foo."),
+        LlmMessage {
+            role: "assistant".into(),
+            content: LlmContent::Blocks(vec![
+                ContentBlock::Text {
+                    text: "Reading.".into(),
+                },
+                ContentBlock::ToolUse {
+                    id: "tc-1".into(),
+                    name: "read".into(),
+                    input: serde_json::json!({"file_path": "/tmp/foo"}),
+                },
+            ]),
+        },
+        LlmMessage {
+            role: "user".into(),
+            content: LlmContent::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: "tc-1".into(),
+                content: LlmContent::Text("no such file".into()),
+                is_error: Some(true),
+            }]),
+        },
+    ];
+    assert_eq!(
+        compaction::transcript(&messages),
+        "User:\nRead foo.\n\nAssistant:\nReading.\n[tool:read] {\"file_path\":\"/tmp/foo\"}\n\n\
+         User:\n[result error] no such file\n\n"
+    );
+}
+
+/// Answers every request with `reply`, reporting 100 input tokens.
+struct FixedReply(&'static str);
+
+#[async_trait::async_trait]
+impl agenticlaw_llm::LlmProvider for FixedReply {
+    fn name(&self) -> &str {
+        "fixed"
+    }
+    fn models(&self) -> &[&str] {
+        &["fixed"]
+    }
+    fn model_info(&self, _model: &str) -> agenticlaw_llm::ModelInfo {
+        agenticlaw_llm::ModelInfo {
+            context_window: 1000,
+            ..agenticlaw_llm::ModelInfo::DEFAULT
+        }
+    }
+    async fn complete_stream(
+        &self,
+        _request: agenticlaw_llm::LlmRequest,
+        _cancel: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<agenticlaw_llm::provider::LlmStream, agenticlaw_llm::provider::LlmError> {
+        Ok(Box::pin(futures::stream::iter(vec![
+            Ok(agenticlaw_llm::StreamDelta::Text(self.0.into())),
+            Ok(agenticlaw_llm::StreamDelta::Done {
+                stop_reason: Some("end_turn".into()),
+                usage: Some(agenticlaw_llm::Usage {
+                    input_tokens: 100,
+                    output_tokens: 10,
+                    ..Default::default()
+                }),
+            }),
+        ])))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compacted_session_resumes_from_the_summary() {
+    let ws = std::env::temp_dir().join(format!("agenticlaw-compact-{}", std::process::id()));
+    let (_registry, session) = two_turn_session(&ws, "compact-me").await;
+
+    // Keep only the last exchange: the cut lands on "Bar it is."
+    let report = compaction::compact(
+        &session,
+        &FixedReply("They read foo."),
+        "fixed",
+        5,
+        usize::MAX,
+        512,
+        &tokio_util::sync::CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!((report.summarized, report.kept), (5, 1));
+    let messages = session.get_messages().await;
+    assert_eq!(messages.len(), 2);
+    assert!(
+        matches!(&messages[0].content, LlmContent::Text(t) if t.ends_with("\n\nThey read foo."))
+    );
+    assert_eq!(session.usage().await.totals.input_tokens, 100);
+
+    let ctx = session.read_ctx().unwrap();
+    assert!(ctx.contains("[compaction: kept 1] ---\nThey read foo.\n"));
+    let resumed = ctx_file::parse_for_resume(session.ctx_path().unwrap()).unwrap();
+    assert_eq!(
+        serde_json::to_value(&resumed.messages).unwrap(),
+        serde_json::to_value(&messages).unwrap()
+    );
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancelled_compaction_leaves_the_session_alone() {
+    let ws = std::env::temp_dir().join(format!("agenticlaw-compact-cancel-{}", std::process::id()));
+    let (_registry, session) = two_turn_session(&ws, "compact-cancel").await;
+    let before = serde_json::to_value(session.get_messages().await).unwrap();
+
+    let cancel = tokio_util::sync::CancellationToken::new();
+    cancel.cancel();
+    let err = compaction::compact(
+        &session,
+        &FixedReply("They read foo."),
+        "fixed",
+        5,
+        usize::MAX,
+        512,
+        &cancel,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.contains("cancelled"), "{}", err);
+    assert_eq!(
+        serde_json::to_value(session.get_messages().await).unwrap(),
+        before
+    );
+    assert!(!session.read_ctx().unwrap().contains("[compaction"));
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn oversized_latest_turn_is_not_summarised_again_and_again() {
+    let ws = std::env::temp_dir().join(format!("agenticlaw-compact-tail-{}", std::process::id()));
+    let registry = SessionRegistry::new();
+    let session = registry.create_with_ctx(&SessionKey::new("compact-tail"), None, &ws);
+    session.add_user_message("Dump it.", 1.0, usize::MAX).await;
+    session.add_assistant_text(&"z".repeat(4000)).await;
+    let before = serde_json::to_value(session.get_messages().await).unwrap();
+
+    // The only cut keeps the ~1000-token reply, over a 300-token limit
+    let err = compaction::compact(
+        &session,
+        &FixedReply("A big reply."),
+        "fixed",
+        5,
+        300,
+        512,
+        &tokio_util::sync::CancellationToken::new(),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.contains("can't be compacted below 300 tokens"),
+        "{}",
+        err
+    );
+    // No summary was requested, and the session is as it was
+    assert_eq!(session.usage().await.totals.turns, 0);
+    assert_eq!(
+        serde_json::to_value(session.get_messages().await).unwrap(),
+        before
+    );
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test]
+async fn agent_runtime_compacts_near_the_context_window() {
+    let config = AgentConfig {
+        default_model: "fixed".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        std::sync::Arc::new(FixedReply("ok")),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    )
+    .with_compaction(CompactionPolicy {
+        threshold_pct: 0.3,
+        keep_recent_pct: 0.1,
+        ..Default::default()
+    });
+    let key = SessionKey::new(format!("test-compact-{}", std::process::id()));
+
+    // ~260 tokens: under 30% of the 1k window
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+    runtime
+        .run_turn(&key, &"x".repeat(1000), event_tx)
+        .await
+        .unwrap();
+    while let Some(event) = event_rx.recv().await {
+        assert!(!matches!(event, AgentEvent::Compacted { .. }));
+    }
+
+    // Another ~260 crosses it, even counted from the 100 the provider reported:
+    // the first exchange is summarised before the request
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+    runtime
+        .run_turn(&key, &"y".repeat(1000), event_tx)
+        .await
+        .unwrap();
+    let mut compacted = None;
+    while let Some(event) = event_rx.recv().await {
+        if let AgentEvent::Compacted {
+            summarized,
+            tokens_before,
+            tokens_after,
+            ..
+        } = event
+        {
+            compacted = Some((summarized, tokens_before > tokens_after));
+        }
+    }
+    assert_eq!(compacted, Some((1, true)));
+    let messages = runtime.sessions().get(&key).unwrap().get_messages().await;
+    assert_eq!(messages.len(), 4);
+    assert!(matches!(&messages[0].content, LlmContent::Text(t) if t.ends_with("\n\nok")));
+}
+
+// ===========================================================================
+// Session search
+// ===========================================================================
+
+#[tokio::test]
+async fn session_search_filters_by_role_tool_and_date() {
+    let ws = std::env::temp_dir().join(format!("agenticlaw-search-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&ws);
+    let (registry, _) = two_turn_session(&ws, "search-main").await;
+    // Layer sessions live one directory down
+    let layer = registry.create_with_ctx(&SessionKey::new("search-l0"), None, &ws.join("L0"));
+    layer
+        .add_user_message("Remember FOO.", 1.0, usize::MAX)
+        .await;
+
+    let hits = search::search(&ws, &SearchQuery::new("foo")).unwrap();
+    assert_eq!(hits.len(), 5);
+    assert!(hits.iter().any(|h| h.session == "search-l0"));
+    // Every word must match, in any case
+    let hits = search::search(&ws, &SearchQuery::new("says FOO")).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].snippet, "It says foo.");
+    assert_eq!(hits[0].role, Role::Model);
+
+    let mut query = SearchQuery::new("foo");
+    query.role = Some(Role::Up);
+    let hits = search::search(&ws, &query).unwrap();
+    assert_eq!(hits.len(), 3);
+
+    // Tool filters match the call and its result
+    let mut query = SearchQuery::new("");
+    query.tool = Some("read".into());
+    let hits = search::search(&ws, &query).unwrap();
+    assert_eq!(hits.len(), 2);
+    assert!(hits.iter().any(|h| h.snippet == "foo contents"));
+
+    let mut query = SearchQuery::new("foo");
+    query.until = Some(search::parse_time("2000-01-01", true).unwrap());
+    assert!(search::search(&ws, &query).unwrap().is_empty());
+    query.until = None;
+    query.since = Some(search::parse_time("2000-01-01", false).unwrap());
+    assert_eq!(search::search(&ws, &query).unwrap().len(), 5);
+
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test]
+async fn session_index_refreshes_only_changed_files() {
+    let ws = std::env::temp_dir().join(format!("agenticlaw-index-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&ws);
+    let (registry, main) = two_turn_session(&ws, "index-main").await;
+    let layer = registry.create_with_ctx(&SessionKey::new("index-l1"), None, &ws.join("L1"));
+    layer.add_user_message("Layer note.", 1.0, usize::MAX).await;
+
+    let mut index = SessionIndex::open(&ws);
+    let stats = index.refresh(&ws).unwrap();
+    assert_eq!((stats.indexed, stats.removed), (2, 0));
+    index.save(&ws).unwrap();
+
+    // A saved index needs no work until a file changes
+    let mut index = SessionIndex::open(&ws);
+    let stats = index.refresh(&ws).unwrap();
+    assert_eq!((stats.indexed, stats.removed), (0, 0));
+
+    main.add_user_message("Find the quokka.", 1.0, usize::MAX)
+        .await;
+    std::fs::remove_file(layer.ctx_path().unwrap()).unwrap();
+    let stats = index.refresh(&ws).unwrap();
+    assert_eq!((stats.indexed, stats.removed), (1, 1));
+    assert_eq!(index.search(&SearchQuery::new("quokka")).len(), 1);
+    assert!(index.search(&SearchQuery::new("layer")).is_empty());
+
+    // Concurrent saves in one process each use their own temp file
+    let index = std::sync::Arc::new(index);
+    let saves: Vec<_> = (0..8)
+        .map(|_| {
+            let (index, ws) = (index.clone(), ws.clone());
+            std::thread::spawn(move || index.save(&ws))
+        })
+        .collect();
+    for save in saves {
+        save.join().unwrap().unwrap();
+    }
+    let mut index = SessionIndex::open(&ws);
+    let stats = index.refresh(&ws).unwrap();
+    assert_eq!((stats.indexed, stats.removed), (0, 0));
+
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
 // ===========================================================================
 // AgentRuntime — real API integration
 // ===========================================================================
@@ -307,25 +959,49 @@ fn load_api_key() -> Option<String> {
     }
 }
 
+/// Provider for the real-API tests. Replays `tests/fixtures/<name>.jsonl` when
+/// it exists; otherwise runs live (if a key is available) and records that
+/// fixture, so later runs need no network. Delete a fixture to re-record it.
+fn live_provider(name: &str) -> Option<std::sync::Arc<dyn agenticlaw_llm::LlmProvider>> {
+    use agenticlaw_llm::{AnthropicProvider, RecordingProvider, ReplayProvider};
+
+    let fixture = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.jsonl", name));
+    if fixture.exists() {
+        return Some(std::sync::Arc::new(ReplayProvider::load(&fixture).unwrap()));
+    }
+    let api_key = load_api_key()?;
+    Some(std::sync::Arc::new(RecordingProvider::new(
+        std::sync::Arc::new(AnthropicProvider::new(&api_key)),
+        fixture,
+    )))
+}
+
 #[tokio::test]
 async fn agent_runtime_simple_text_turn() {
-    let api_key = match load_api_key() {
-        Some(k) => k,
+    let provider = match live_provider("simple_text_turn") {
+        Some(p) => p,
         None => {
-            eprintln!("SKIP: no ANTHROPIC_API_KEY");
+            eprintln!("SKIP: no ANTHROPIC_API_KEY or fixture");
             return;
         }
     };
 
+    // A dedicated workspace keeps stray bootstrap files in the temp dir out of
+    // the prompt, so the fixture key is the same on every machine.
+    let ws = std::env::temp_dir().join("agenticlaw-agent-simple");
+    std::fs::create_dir_all(&ws).unwrap();
+
     let tools = agenticlaw_tools::ToolRegistry::new(); // no tools
     let config = AgentConfig {
         default_model: "claude-haiku-4-5-20251001".into(),
         max_tool_iterations: 5,
         system_prompt: Some("Reply with exactly the word 'pong' and nothing else.".into()),
-        workspace_root: std::env::temp_dir(),
+        workspace_root: ws,
         sleep_threshold_pct: 1.0,
     };
-    let runtime = AgentRuntime::new(&api_key, tools, config);
+    let runtime = AgentRuntime::with_provider(provider, tools, config);
 
     let session_key = SessionKey::new("test-simple");
     let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
@@ -361,10 +1037,10 @@ async fn agent_runtime_simple_text_turn() {
 
 #[tokio::test]
 async fn agent_runtime_with_tool_call() {
-    let api_key = match load_api_key() {
-        Some(k) => k,
+    let provider = match live_provider("with_tool_call") {
+        Some(p) => p,
         None => {
-            eprintln!("SKIP: no ANTHROPIC_API_KEY");
+            eprintln!("SKIP: no ANTHROPIC_API_KEY or fixture");
             return;
         }
     };
@@ -384,7 +1060,7 @@ async fn agent_runtime_with_tool_call() {
         workspace_root: ws.clone(),
         sleep_threshold_pct: 1.0,
     };
-    let runtime = AgentRuntime::new(&api_key, tools, config);
+    let runtime = AgentRuntime::with_provider(provider, tools, config);
 
     let session_key = SessionKey::new("test-tools");
     let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
@@ -444,10 +1120,10 @@ async fn agent_runtime_with_tool_call() {
 
 #[tokio::test]
 async fn agent_runtime_max_iterations_enforced() {
-    let api_key = match load_api_key() {
-        Some(k) => k,
+    let provider = match live_provider("max_iterations_enforced") {
+        Some(p) => p,
         None => {
-            eprintln!("SKIP: no ANTHROPIC_API_KEY");
+            eprintln!("SKIP: no ANTHROPIC_API_KEY or fixture");
             return;
         }
     };
@@ -464,7 +1140,7 @@ async fn agent_runtime_max_iterations_enforced() {
         workspace_root: ws.clone(),
         sleep_threshold_pct: 1.0,
     };
-    let runtime = AgentRuntime::new(&api_key, tools, config);
+    let runtime = AgentRuntime::with_provider(provider, tools, config);
 
     let session_key = SessionKey::new("test-maxiter");
     let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
@@ -547,21 +1223,21 @@ async fn tool_results_collected_in_single_message() {
 }
 
 // ===========================================================================
-// ConsciousnessLoop / Event Queue (Issue #28)
+// AgentRuntime — provider failover
 // ===========================================================================
 
 #[tokio::test]
-async fn consciousness_loop_processes_human_message() {
+async fn agent_runtime_emits_failover_event() {
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
     use agenticlaw_llm::*;
     use std::sync::Arc;
 
-    // Mock provider that returns a simple text response
-    struct MockProvider;
+    struct DownProvider;
 
     #[async_trait::async_trait]
-    impl LlmProvider for MockProvider {
+    impl LlmProvider for DownProvider {
         fn name(&self) -> &str {
-            "mock"
+            "down"
         }
         fn models(&self) -> &[&str] {
             &["mock"]
@@ -570,23 +1246,1279 @@ async fn consciousness_loop_processes_human_message() {
             &self,
             _request: LlmRequest,
             _cancel: Option<tokio_util::sync::CancellationToken>,
-        ) -> Result<
-            std::pin::Pin<
-                Box<
-                    dyn futures::Stream<
-                            Item = Result<StreamDelta, agenticlaw_llm::provider::LlmError>,
-                        > + Send,
-                >,
-            >,
-            agenticlaw_llm::provider::LlmError,
-        > {
-            let deltas = vec![
-                Ok(StreamDelta::Text("Hello from event queue!".to_string())),
-                Ok(StreamDelta::Done {
-                    stop_reason: Some("end_turn".to_string()),
-                    usage: None,
-                }),
-            ];
+        ) -> Result<LlmStream, LlmError> {
+            Err(LlmError::ServerError {
+                status: 503,
+                message: "unavailable".into(),
+            })
+        }
+    }
+
+    struct UpProvider;
+
+    #[async_trait::async_trait]
+    impl LlmProvider for UpProvider {
+        fn name(&self) -> &str {
+            "up"
+        }
+        fn models(&self) -> &[&str] {
+            &["mock"]
+        }
+        async fn complete_stream(
+            &self,
+            _request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            Ok(Box::pin(futures::stream::iter(vec![
+                Ok(StreamDelta::Text("from fallback".into())),
+                Ok(StreamDelta::Done {
+                    stop_reason: Some("end_turn".into()),
+                    usage: None,
+                }),
+            ])))
+        }
+    }
+
+    let router = RoutingProvider::new(Route::new(Arc::new(DownProvider)))
+        .with_fallback(Route::new(Arc::new(UpProvider)).with_model("mock-small"))
+        .with_policy(RetryPolicy {
+            max_retries: 1,
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            ..RetryPolicy::default()
+        });
+    let config = AgentConfig {
+        default_model: "mock".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        Arc::new(router),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    );
+
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+    runtime
+        .run_turn(&SessionKey::new("test-failover"), "hello", event_tx)
+        .await
+        .unwrap();
+
+    let mut failover = None;
+    let mut text = String::new();
+    while let Some(event) = event_rx.recv().await {
+        match event {
+            AgentEvent::Failover { from, to, .. } => failover = Some((from, to)),
+            AgentEvent::Text(t) => text.push_str(&t),
+            _ => {}
+        }
+    }
+    assert_eq!(
+        failover,
+        Some(("down/mock".to_string(), "up/mock-small".to_string()))
+    );
+    assert_eq!(text, "from fallback");
+}
+
+#[tokio::test]
+async fn agent_runtime_charges_usage_to_the_fallback_model() {
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::*;
+    use std::sync::Arc;
+
+    struct DownProvider;
+
+    #[async_trait::async_trait]
+    impl LlmProvider for DownProvider {
+        fn name(&self) -> &str {
+            "down"
+        }
+        fn models(&self) -> &[&str] {
+            &["claude-opus-4-6"]
+        }
+        async fn complete_stream(
+            &self,
+            _request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            Err(LlmError::ServerError {
+                status: 503,
+                message: "unavailable".into(),
+            })
+        }
+    }
+
+    let router = RoutingProvider::new(Route::new(Arc::new(DownProvider)))
+        .with_fallback(Route::new(Arc::new(FixedReply("cheap"))).with_model("claude-haiku-4-5"))
+        .with_policy(RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        });
+    let config = AgentConfig {
+        default_model: "claude-opus-4-6".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        Arc::new(router),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    );
+    let key = SessionKey::new("test-failover-usage");
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+    runtime.run_turn(&key, "hello", event_tx).await.unwrap();
+    while event_rx.recv().await.is_some() {}
+
+    let usage = runtime.sessions().get(&key).unwrap().usage().await;
+    assert_eq!(
+        usage.by_model.keys().collect::<Vec<_>>(),
+        ["claude-haiku-4-5"]
+    );
+    // (100 * $1 + 10 * $5) / 1M at haiku's price, not opus's
+    let cost = usage.cost_usd().unwrap();
+    assert!((cost - 0.00015).abs() < 1e-12, "cost {}", cost);
+}
+
+// ===========================================================================
+// AgentRuntime — model capabilities
+// ===========================================================================
+
+#[tokio::test]
+async fn agent_runtime_prices_usage_at_the_configured_cost() {
+    use agenticlaw_core::openclaw_config::{OcModelCost, OcModelEntry};
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::*;
+    use std::sync::Arc;
+
+    /// FixedReply's usage, with openclaw.json overriding the built-in price.
+    struct ConfiguredProvider;
+
+    #[async_trait::async_trait]
+    impl LlmProvider for ConfiguredProvider {
+        fn name(&self) -> &str {
+            "configured"
+        }
+        fn models(&self) -> &[&str] {
+            &["claude-opus-4-6"]
+        }
+        fn model_info(&self, model: &str) -> ModelInfo {
+            ModelInfo::for_model(model).with_entry(&OcModelEntry {
+                id: model.into(),
+                cost: Some(OcModelCost {
+                    input: 1.0,
+                    output: 2.0,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        }
+        async fn complete_stream(
+            &self,
+            request: LlmRequest,
+            cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            FixedReply("priced").complete_stream(request, cancel).await
+        }
+    }
+
+    let config = AgentConfig {
+        default_model: "claude-opus-4-6".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        Arc::new(ConfiguredProvider),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    );
+    let key = SessionKey::new("test-configured-cost");
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+    runtime.run_turn(&key, "hello", event_tx).await.unwrap();
+    while event_rx.recv().await.is_some() {}
+
+    // (100 * $1 + 10 * $2) / 1M, not the built-in $5/$25
+    let cost = runtime
+        .sessions()
+        .get(&key)
+        .unwrap()
+        .usage()
+        .await
+        .cost_usd();
+    assert!((cost.unwrap() - 0.00012).abs() < 1e-12, "cost {:?}", cost);
+}
+
+#[tokio::test]
+async fn agent_runtime_sleeps_at_model_context_window() {
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::*;
+    use std::sync::Arc;
+
+    /// Tiny local model: 1k context, no tool support.
+    struct TinyProvider;
+
+    #[async_trait::async_trait]
+    impl LlmProvider for TinyProvider {
+        fn name(&self) -> &str {
+            "tiny"
+        }
+        fn models(&self) -> &[&str] {
+            &["tiny"]
+        }
+        fn model_info(&self, _model: &str) -> ModelInfo {
+            ModelInfo {
+                context_window: 1000,
+                max_output_tokens: 256,
+                supports_tools: false,
+                ..ModelInfo::DEFAULT
+            }
+        }
+        async fn complete_stream(
+            &self,
+            request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            assert!(request.tools.is_none());
+            assert_eq!(request.max_tokens, Some(256));
+            Ok(Box::pin(futures::stream::iter(vec![
+                Ok(StreamDelta::Text("ok".into())),
+                Ok(StreamDelta::Done {
+                    stop_reason: Some("end_turn".into()),
+                    usage: None,
+                }),
+            ])))
+        }
+    }
+
+    let config = AgentConfig {
+        default_model: "tiny".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 0.5,
+    };
+    let runtime = AgentRuntime::with_provider(
+        Arc::new(TinyProvider),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    );
+    let key = SessionKey::new(format!("test-tiny-{}", std::process::id()));
+
+    // Small message fits and is answered
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+    runtime.run_turn(&key, "hello", event_tx).await.unwrap();
+    let mut answered = false;
+    while let Some(event) = event_rx.recv().await {
+        answered |= matches!(event, AgentEvent::Done { .. });
+    }
+    assert!(answered);
+    let session = runtime.sessions().get(&key).unwrap();
+    assert_eq!(session.context_window().await, 1000);
+
+    // ~750 tokens crosses 50% of the 1k window — a 200k default would not
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+    runtime
+        .run_turn(&key, &"x".repeat(3000), event_tx)
+        .await
+        .unwrap();
+    let mut slept = false;
+    while let Some(event) = event_rx.recv().await {
+        slept |= matches!(event, AgentEvent::Sleep { .. });
+    }
+    assert!(slept);
+
+    // A token threshold overrides the percentage: ~150 tokens is well under
+    // 100% of the window but over 100 tokens
+    let config = AgentConfig {
+        default_model: "tiny".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        Arc::new(TinyProvider),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    )
+    .with_sleep_threshold(100);
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+    runtime
+        .run_turn(
+            &SessionKey::new("test-tiny-tokens"),
+            &"x".repeat(600),
+            event_tx,
+        )
+        .await
+        .unwrap();
+    let mut slept = false;
+    while let Some(event) = event_rx.recv().await {
+        slept |= matches!(event, AgentEvent::Sleep { .. });
+    }
+    assert!(slept);
+}
+
+#[tokio::test]
+async fn agent_runtime_replays_signed_thinking() {
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Thinks, then answers; records every request it receives.
+    struct ThinkingProvider {
+        requests: Mutex<Vec<LlmRequest>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for ThinkingProvider {
+        fn name(&self) -> &str {
+            "thinking"
+        }
+        fn models(&self) -> &[&str] {
+            &["claude-sonnet-4"]
+        }
+        async fn complete_stream(
+            &self,
+            request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            self.requests.lock().unwrap().push(request);
+            Ok(Box::pin(futures::stream::iter(vec![
+                Ok(StreamDelta::Thinking("Simple ".into())),
+                Ok(StreamDelta::Thinking("greeting.".into())),
+                Ok(StreamDelta::ThinkingSignature("c2ln".into())),
+                Ok(StreamDelta::Text("hi".into())),
+                Ok(StreamDelta::Done {
+                    stop_reason: Some("end_turn".into()),
+                    usage: None,
+                }),
+            ])))
+        }
+    }
+
+    let provider = Arc::new(ThinkingProvider {
+        requests: Mutex::new(Vec::new()),
+    });
+    let config = AgentConfig {
+        default_model: "claude-sonnet-4".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        provider.clone(),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    );
+    let key = SessionKey::new("test-thinking");
+    runtime
+        .get_session(&key)
+        .set_thinking(Some(ThinkingConfig::new(4096)))
+        .await;
+
+    for msg in ["hello", "again"] {
+        let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+        runtime.run_turn(&key, msg, event_tx).await.unwrap();
+        while event_rx.recv().await.is_some() {}
+    }
+
+    let requests = provider.requests.lock().unwrap();
+    assert_eq!(requests[0].thinking, Some(ThinkingConfig::new(4096)));
+    assert_eq!(requests[0].max_tokens, Some(16384 + 4096));
+
+    // The second request replays the first answer with its signed thinking
+    let LlmContent::Blocks(blocks) = &requests[1].messages[1].content else {
+        panic!("Expected Blocks");
+    };
+    assert!(
+        matches!(&blocks[0], ContentBlock::Thinking { thinking, signature }
+        if thinking == "Simple greeting." && signature == "c2ln")
+    );
+    assert!(matches!(&blocks[1], ContentBlock::Text { text } if text == "hi"));
+}
+
+#[tokio::test]
+async fn agent_runtime_keeps_attachments_after_the_original_is_deleted() {
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Inlines media like a real provider and records what it would send.
+    struct InliningProvider {
+        sent: Mutex<Vec<Vec<LlmMessage>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for InliningProvider {
+        fn name(&self) -> &str {
+            "inlining"
+        }
+        fn models(&self) -> &[&str] {
+            &["claude-sonnet-4"]
+        }
+        async fn complete_stream(
+            &self,
+            request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            let mut messages = request.messages;
+            inline_media(&mut messages).await;
+            self.sent.lock().unwrap().push(messages);
+            Ok(Box::pin(futures::stream::iter(vec![
+                Ok(StreamDelta::Text("ok".into())),
+                Ok(StreamDelta::Done {
+                    stop_reason: Some("end_turn".into()),
+                    usage: None,
+                }),
+            ])))
+        }
+    }
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-attach-rt-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&ws);
+    std::fs::create_dir_all(&ws).unwrap();
+    let png = ws.join("photo.png");
+    std::fs::write(&png, b"hi").unwrap();
+
+    let provider = Arc::new(InliningProvider {
+        sent: Mutex::new(Vec::new()),
+    });
+    let config = AgentConfig {
+        default_model: "claude-sonnet-4".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: ws.clone(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        provider.clone(),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    );
+    let key = SessionKey::new("test-attach");
+    let turn = |msg: &'static str, attachments: Vec<ContentBlock>| {
+        let runtime = &runtime;
+        let key = &key;
+        async move {
+            let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(256);
+            runtime
+                .run_turn_with_attachments(key, msg, attachments, event_tx)
+                .await
+                .unwrap();
+            while event_rx.recv().await.is_some() {}
+        }
+    };
+
+    turn(
+        "What is this?",
+        vec![ContentBlock::from_path(&png).unwrap()],
+    )
+    .await;
+    std::fs::remove_file(&png).unwrap();
+    turn("And now?", Vec::new()).await;
+
+    // The session sends its own copy, not the deleted original
+    let first_user = |sent: &[LlmMessage]| match &sent[0].content {
+        LlmContent::Blocks(blocks) => blocks[1].clone(),
+        other => panic!("Expected Blocks, got {:?}", other),
+    };
+    let image = first_user(&provider.sent.lock().unwrap()[1]);
+    assert!(matches!(
+        image.media_source(),
+        Some(MediaSource::Base64 { data, .. }) if data == "aGk="
+    ));
+
+    // Losing the copy too degrades to a placeholder instead of failing
+    let session = runtime.sessions().get(&key).unwrap();
+    let ctx_path = session.ctx_path().unwrap().to_path_buf();
+    std::fs::remove_dir_all(agenticlaw_agent::ctx_file::attachments_dir(&ctx_path)).unwrap();
+    turn("Still there?", Vec::new()).await;
+    let placeholder = first_user(&provider.sent.lock().unwrap()[2]);
+    assert!(
+        matches!(&placeholder, ContentBlock::Text { text } if text.starts_with("[attachment unavailable: "))
+    );
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+// ===========================================================================
+// TurnEngine
+// ===========================================================================
+
+#[tokio::test]
+async fn spill_saves_oversized_output_with_preview() {
+    use agenticlaw_agent::OutputSpill;
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-spill-{}", std::process::id()));
+    let spill = OutputSpill::new(&ws).with_budget("bash", 1000);
+    let text: String = (1..=500).map(|i| format!("line {}\n", i)).collect();
+
+    // Within budget, or another tool with the default budget: untouched
+    assert_eq!(
+        spill.apply("s1", "t0", "bash", "short".into()).await,
+        "short"
+    );
+    assert_eq!(spill.apply("s1", "t0", "read", text.clone()).await, text);
+
+    let out = spill.apply("s1", "toolu_1", "bash", text.clone()).await;
+    assert!(out.len() < 1200, "{}", out.len());
+    assert!(out.starts_with("line 1\n"), "{}", out);
+    assert!(out.contains("line 500\n"), "{}", out);
+    assert!(out.contains("500 lines, lines"), "{}", out);
+    let path = spill.session_dir("s1").unwrap().join("toolu_1.txt");
+    assert!(out.contains(&path.display().to_string()), "{}", out);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+
+    spill.forget("s1");
+    assert!(!path.exists());
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test]
+async fn spill_wraps_a_single_huge_line_so_read_can_page_through_it() {
+    use agenticlaw_agent::spill::{OutputSpill, WRAP_BYTES};
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-spill-line-{}", std::process::id()));
+    let spill = OutputSpill::new(&ws);
+    // Minified JSON: 72,000 bytes on one line
+    let text = serde_json::to_string(&(10_000..22_000).collect::<Vec<_>>()).unwrap();
+    assert!(text.len() > 50_000 && !text.contains('\n'));
+
+    let out = spill.apply("s1", "toolu_1", "bash", text.clone()).await;
+    assert!(out.contains("lines, lines"), "{}", out);
+    let path = spill.session_dir("s1").unwrap().join("toolu_1.txt");
+    let spilled = std::fs::read_to_string(&path).unwrap();
+    assert!(spilled.lines().all(|l| l.len() <= WRAP_BYTES));
+    assert_eq!(spilled.replace('\n', ""), text);
+
+    // The middle is reachable a page at a time
+    let tools = agenticlaw_tools::create_default_registry(&ws);
+    let page = tools
+        .execute(
+            "read",
+            serde_json::json!({"path": path.display().to_string(), "offset": 15, "limit": 2}),
+        )
+        .await
+        .to_content_string();
+    assert!(page.len() < 2 * WRAP_BYTES + 100, "{}", page.len());
+    let middle = spilled.lines().nth(14).unwrap();
+    assert!(page.contains(middle));
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test]
+async fn spill_without_workspace_cuts_on_char_boundaries() {
+    use agenticlaw_agent::spill::{OutputSpill, DEFAULT_BUDGET_BYTES};
+
+    // 'é' is two bytes, so the cut points land mid-character
+    let long = format!("a{}", "é".repeat(DEFAULT_BUDGET_BYTES));
+    let out = OutputSpill::default()
+        .apply("s1", "t1", "bash", long.clone())
+        .await;
+    assert!(out.starts_with("aé"));
+    assert!(out.ends_with(&format!("[Output too large: {} bytes.]", long.len())));
+    assert!(out.len() < DEFAULT_BUDGET_BYTES + 100);
+}
+
+#[tokio::test]
+async fn turn_engine_steering_skips_remaining_batches() {
+    use agenticlaw_agent::engine::Steering;
+    use agenticlaw_llm::AccumulatedToolCall;
+
+    /// Always has a message waiting.
+    struct Interrupting;
+
+    #[async_trait::async_trait]
+    impl Steering for Interrupting {
+        async fn drain(&self) -> Vec<String> {
+            vec!["stop".into()]
+        }
+    }
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-engine-{}", std::process::id()));
+    std::fs::create_dir_all(&ws).unwrap();
+    std::fs::write(ws.join("a.txt"), "alpha").unwrap();
+
+    let engine = TurnEngine::new(
+        std::sync::Arc::new(agenticlaw_llm::ReplayProvider::new(Vec::new())),
+        std::sync::Arc::new(agenticlaw_tools::create_default_registry(&ws)),
+        "claude-sonnet-4",
+    );
+    let session = Session::new(SessionKey::new("engine-steer"), None);
+    // The write to the file just read waits for a batch of its own
+    let calls: Vec<_> = [
+        ("r1", "read", r#"{"path": "a.txt"}"#),
+        ("w1", "write", r#"{"path": "a.txt", "content": "beta"}"#),
+    ]
+    .iter()
+    .map(|(id, name, args)| AccumulatedToolCall {
+        id: id.to_string(),
+        name: name.to_string(),
+        arguments: args.to_string(),
+    })
+    .collect();
+    let blocks = agenticlaw_agent::TurnOutput {
+        tool_calls: calls.clone(),
+        ..Default::default()
+    }
+    .assistant_blocks();
+    session.add_assistant_with_tools(None, blocks).await;
+
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(64);
+    let steering = engine
+        .execute_tools(
+            &session,
+            &calls,
+            &event_tx,
+            &Interrupting,
+            &tokio_util::sync::CancellationToken::new(),
+        )
+        .await;
+    drop(event_tx);
+    assert_eq!(steering, Some(vec!["stop".to_string()]));
+
+    let mut skipped = Vec::new();
+    while let Some(event) = event_rx.recv().await {
+        if let AgentEvent::ToolSkipped { id, .. } = event {
+            skipped.push(id);
+        }
+    }
+    assert_eq!(skipped, vec!["w1"]);
+    assert_eq!(std::fs::read_to_string(ws.join("a.txt")).unwrap(), "alpha");
+
+    let messages = session.get_messages().await;
+    let LlmContent::Blocks(results) = &messages.last().unwrap().content else {
+        panic!("Expected Blocks");
+    };
+    assert!(
+        matches!(&results[0], ContentBlock::ToolResult { content, is_error: None | Some(false), .. }
+        if content.to_text().contains("alpha"))
+    );
+    assert!(matches!(
+        &results[1],
+        ContentBlock::ToolResult {
+            is_error: Some(true),
+            ..
+        }
+    ));
+
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test]
+async fn turn_engine_orders_edits_to_the_same_file() {
+    use agenticlaw_agent::engine::NoSteering;
+    use agenticlaw_agent::ToolScheduling;
+    use agenticlaw_llm::AccumulatedToolCall;
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-engine-order-{}", std::process::id()));
+    std::fs::create_dir_all(&ws).unwrap();
+    std::fs::write(ws.join("a.txt"), "alpha").unwrap();
+
+    let engine = TurnEngine::new(
+        std::sync::Arc::new(agenticlaw_llm::ReplayProvider::new(Vec::new())),
+        std::sync::Arc::new(agenticlaw_tools::create_default_registry(&ws)),
+        "claude-sonnet-4",
+    )
+    .with_scheduling(ToolScheduling::Uninterrupted);
+    let session = Session::new(SessionKey::new("engine-order"), None);
+    // Each edit only applies on top of the one before it
+    let calls: Vec<_> = [("alpha", "beta"), ("beta", "gamma"), ("gamma", "delta")]
+        .iter()
+        .enumerate()
+        .map(|(i, (old, new))| AccumulatedToolCall {
+            id: format!("e{}", i),
+            name: "edit".into(),
+            arguments: serde_json::json!({"path": "a.txt", "old_string": old, "new_string": new})
+                .to_string(),
+        })
+        .collect();
+    let blocks = agenticlaw_agent::TurnOutput {
+        tool_calls: calls.clone(),
+        ..Default::default()
+    }
+    .assistant_blocks();
+    session.add_assistant_with_tools(None, blocks).await;
+
+    let (event_tx, _event_rx) = tokio::sync::mpsc::channel(64);
+    let steering = engine
+        .execute_tools(
+            &session,
+            &calls,
+            &event_tx,
+            &NoSteering,
+            &tokio_util::sync::CancellationToken::new(),
+        )
+        .await;
+    assert_eq!(steering, None);
+    assert_eq!(std::fs::read_to_string(ws.join("a.txt")).unwrap(), "delta");
+
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test]
+async fn run_tool_rejects_malformed_arguments_without_running() {
+    use agenticlaw_agent::engine::run_tool;
+    use agenticlaw_llm::AccumulatedToolCall;
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-engine-args-{}", std::process::id()));
+    std::fs::create_dir_all(&ws).unwrap();
+    let tools = agenticlaw_tools::create_default_registry(&ws);
+    let spill = OutputSpill::default();
+    let call = |args: &str| AccumulatedToolCall {
+        id: "w1".into(),
+        name: "write".into(),
+        arguments: args.into(),
+    };
+    let ctx = || agenticlaw_tools::ToolContext::new("engine-args");
+
+    // Cut off mid-stream: reported as such, the tool never sees null
+    let run = run_tool(&tools, &spill, &call(r#"{"path": "a.txt", "con"#), ctx()).await;
+    assert!(run.is_error);
+    assert!(run.result.contains("not valid JSON"), "{}", run.result);
+    assert!(!ws.join("a.txt").exists());
+
+    // Parses, but breaks the schema: every violation is listed
+    let run = run_tool(&tools, &spill, &call(r#"{"content": 7}"#), ctx()).await;
+    assert!(run.is_error);
+    assert!(
+        run.result.contains("Invalid arguments for write"),
+        "{}",
+        run.result
+    );
+    assert!(
+        run.result.contains("$.content: expected string"),
+        "{}",
+        run.result
+    );
+    assert!(run.result.contains("file_path"), "{}", run.result);
+
+    // A tool call turned into history still gets an object input
+    let blocks = TurnOutput {
+        tool_calls: vec![call("{")],
+        ..Default::default()
+    }
+    .assistant_blocks();
+    assert!(matches!(&blocks[0], ContentBlock::ToolUse { input, .. } if input.is_object()));
+
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test]
+async fn spawn_child_stops_when_parent_aborts() {
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::*;
+    use agenticlaw_tools::SpawnableRuntime;
+    use std::sync::Arc;
+
+    /// Never finishes its stream.
+    struct HangingProvider;
+
+    #[async_trait::async_trait]
+    impl LlmProvider for HangingProvider {
+        fn name(&self) -> &str {
+            "hanging"
+        }
+        fn models(&self) -> &[&str] {
+            &["claude-sonnet-4"]
+        }
+        async fn complete_stream(
+            &self,
+            _request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            Ok(Box::pin(futures::stream::pending()))
+        }
+    }
+
+    let config = AgentConfig {
+        default_model: "claude-sonnet-4".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = Arc::new(AgentRuntime::with_provider(
+        Arc::new(HangingProvider),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    ));
+    let child = {
+        let runtime = runtime.clone();
+        tokio::spawn(async move {
+            runtime
+                .spawn_child(
+                    &agenticlaw_tools::ToolContext::new("parent"),
+                    "hang",
+                    "Wait.",
+                    "Go",
+                    5,
+                )
+                .await
+        })
+    };
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    runtime.abort();
+
+    let result = tokio::time::timeout(std::time::Duration::from_secs(5), child)
+        .await
+        .expect("child ignored abort")
+        .unwrap();
+    assert_eq!(result.unwrap_err(), "child aborted");
+}
+
+// ===========================================================================
+// Structured output
+// ===========================================================================
+
+#[test]
+fn structured_output_wraps_non_object_schemas() {
+    let output = StructuredOutput::new(serde_json::json!({
+        "type": "array",
+        "items": { "type": "string" }
+    }));
+    let tool = output.tool();
+    assert_eq!(tool.name, "final_answer");
+    assert_eq!(tool.input_schema["required"], serde_json::json!(["answer"]));
+
+    assert_eq!(
+        output.accept(r#"{"answer": ["a", "b"]}"#).unwrap(),
+        serde_json::json!(["a", "b"])
+    );
+    let err = output.accept(r#"{"answer": [1]}"#).unwrap_err();
+    assert!(err.contains("$[0]: expected string, got number"), "{}", err);
+    assert!(output.accept("not json").is_err());
+}
+
+#[tokio::test]
+async fn spawn_child_structured_retries_until_valid() {
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::*;
+    use agenticlaw_tools::SpawnableRuntime;
+    use std::sync::{Arc, Mutex};
+
+    /// Answers through final_answer: first with a bad value, then a good one.
+    struct AnsweringProvider {
+        requests: Mutex<Vec<LlmRequest>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for AnsweringProvider {
+        fn name(&self) -> &str {
+            "answering"
+        }
+        fn models(&self) -> &[&str] {
+            &["claude-sonnet-4"]
+        }
+        async fn complete_stream(
+            &self,
+            request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(request);
+            let (id, arguments) = if requests.len() == 1 {
+                ("fa1", r#"{"count": "three"}"#)
+            } else {
+                ("fa2", r#"{"count": 3}"#)
+            };
+            Ok(Box::pin(futures::stream::iter(vec![
+                Ok(StreamDelta::ToolCallStart {
+                    id: id.into(),
+                    name: "final_answer".into(),
+                }),
+                Ok(StreamDelta::ToolCallDelta {
+                    id: id.into(),
+                    arguments: arguments.into(),
+                }),
+                Ok(StreamDelta::ToolCallEnd { id: id.into() }),
+                Ok(StreamDelta::Done {
+                    stop_reason: Some("tool_use".into()),
+                    usage: None,
+                }),
+            ])))
+        }
+    }
+
+    let provider = Arc::new(AnsweringProvider {
+        requests: Mutex::new(Vec::new()),
+    });
+    let config = AgentConfig {
+        default_model: "claude-sonnet-4".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        provider.clone(),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    );
+    let schema = serde_json::json!({
+        "type": "object",
+        "required": ["count"],
+        "properties": { "count": { "type": "integer" } }
+    });
+
+    let (answer, _) = runtime
+        .spawn_child_structured(
+            &agenticlaw_tools::ToolContext::new("parent"),
+            "structured",
+            "Count things.",
+            "How many?",
+            5,
+            &schema,
+            2,
+        )
+        .await
+        .unwrap();
+    assert_eq!(answer, serde_json::json!({"count": 3}));
+
+    let requests = provider.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(matches!(requests[0].tool_choice, Some(ToolChoice::Any)));
+    let tools = requests[0].tools.as_ref().unwrap();
+    assert_eq!(tools.last().unwrap().input_schema, schema);
+
+    // The violation went back to the model as an error tool result
+    let LlmContent::Blocks(blocks) = &requests[1].messages.last().unwrap().content else {
+        panic!("Expected Blocks");
+    };
+    assert!(matches!(
+        &blocks[0],
+        ContentBlock::ToolResult { tool_use_id, content, is_error: Some(true) }
+        if tool_use_id == "fa1" && content.to_text().contains("$.count: expected integer")
+    ));
+}
+
+#[tokio::test]
+async fn spawn_child_structured_gives_up_after_max_retries() {
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::*;
+    use agenticlaw_tools::SpawnableRuntime;
+    use std::sync::Arc;
+
+    /// Never calls final_answer.
+    struct ChattyProvider;
+
+    #[async_trait::async_trait]
+    impl LlmProvider for ChattyProvider {
+        fn name(&self) -> &str {
+            "chatty"
+        }
+        fn models(&self) -> &[&str] {
+            &["claude-sonnet-4"]
+        }
+        async fn complete_stream(
+            &self,
+            _request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            Ok(Box::pin(futures::stream::iter(vec![
+                Ok(StreamDelta::Text("It's three.".into())),
+                Ok(StreamDelta::Done {
+                    stop_reason: Some("end_turn".into()),
+                    usage: None,
+                }),
+            ])))
+        }
+    }
+
+    let config = AgentConfig {
+        default_model: "claude-sonnet-4".into(),
+        max_tool_iterations: 10,
+        system_prompt: None,
+        workspace_root: std::env::temp_dir(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        Arc::new(ChattyProvider),
+        agenticlaw_tools::ToolRegistry::new(),
+        config,
+    );
+    let schema = serde_json::json!({ "type": "integer" });
+    let err = runtime
+        .spawn_child_structured(
+            &agenticlaw_tools::ToolContext::new("parent"),
+            "chatty",
+            "Count things.",
+            "How many?",
+            10,
+            &schema,
+            1,
+        )
+        .await
+        .unwrap_err();
+    assert!(err.contains("final_answer"), "{}", err);
+}
+
+// ===========================================================================
+// File checkpoints
+// ===========================================================================
+
+#[tokio::test]
+async fn aborting_keeps_stale_write_protection_until_the_session_is_deleted() {
+    use serde_json::json;
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-abort-stale-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&ws);
+    std::fs::create_dir_all(&ws).unwrap();
+    std::fs::write(ws.join("notes.txt"), "v1\n").unwrap();
+    let runtime = AgentRuntime::with_provider(
+        std::sync::Arc::new(FixedReply("unused")),
+        agenticlaw_tools::create_default_registry(&ws),
+        AgentConfig {
+            workspace_root: ws.clone(),
+            ..AgentConfig::default()
+        },
+    );
+    let sk = SessionKey::new("abort-stale");
+    let session = runtime.sessions().get_or_create(&sk, None);
+    let ctx = agenticlaw_tools::ToolContext::new(sk.as_str()).with_lineage(session.lineage());
+    let tools = runtime.tools();
+    tools
+        .execute_in("read", json!({"path": "notes.txt"}), &ctx)
+        .await;
+
+    // What chat.abort does
+    session.abort().await;
+    runtime.end_session(&sk).await;
+
+    std::fs::write(ws.join("notes.txt"), "changed outside\n").unwrap();
+    let write = json!({"path": "notes.txt", "content": "mine\n"});
+    let result = tools.execute_in("write", write.clone(), &ctx).await;
+    assert!(result.is_error());
+    assert!(result.to_content_string().contains("changed on disk"));
+
+    // What sessions.delete does
+    let removed = runtime.sessions().remove(&sk).unwrap();
+    runtime.forget_file_reads(&removed).await;
+    assert!(!tools.execute_in("write", write, &ctx).await.is_error());
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+/// Writes `out.txt` with the turn's prompt, then says done.
+struct WritingProvider {
+    calls: std::sync::Mutex<usize>,
+}
+
+#[async_trait::async_trait]
+impl agenticlaw_llm::LlmProvider for WritingProvider {
+    fn name(&self) -> &str {
+        "writing"
+    }
+    fn models(&self) -> &[&str] {
+        &["claude-sonnet-4"]
+    }
+    async fn complete_stream(
+        &self,
+        request: agenticlaw_llm::LlmRequest,
+        _cancel: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<agenticlaw_llm::provider::LlmStream, agenticlaw_llm::provider::LlmError> {
+        let last = request.messages.last().unwrap();
+        let deltas = match &last.content {
+            agenticlaw_llm::LlmContent::Text(prompt) => {
+                let mut calls = self.calls.lock().unwrap();
+                *calls += 1;
+                let id = format!("w{}", calls);
+                let arguments = serde_json::json!({"path": "out.txt", "content": prompt});
+                vec![
+                    agenticlaw_llm::StreamDelta::ToolCallStart {
+                        id: id.clone(),
+                        name: "write".into(),
+                    },
+                    agenticlaw_llm::StreamDelta::ToolCallDelta {
+                        id: id.clone(),
+                        arguments: arguments.to_string(),
+                    },
+                    agenticlaw_llm::StreamDelta::ToolCallEnd { id },
+                    agenticlaw_llm::StreamDelta::Done {
+                        stop_reason: Some("tool_use".into()),
+                        usage: None,
+                    },
+                ]
+            }
+            agenticlaw_llm::LlmContent::Blocks(_) => vec![
+                agenticlaw_llm::StreamDelta::Text("done".into()),
+                agenticlaw_llm::StreamDelta::Done {
+                    stop_reason: Some("end_turn".into()),
+                    usage: None,
+                },
+            ],
+        };
+        Ok(Box::pin(futures::stream::iter(deltas.into_iter().map(Ok))))
+    }
+}
+
+#[tokio::test]
+async fn restore_checkpoint_rewinds_turn_and_notes_ctx() {
+    use std::sync::{Arc, Mutex};
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-rewind-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&ws);
+    std::fs::create_dir_all(&ws).unwrap();
+    let config = AgentConfig {
+        default_model: "claude-sonnet-4".into(),
+        max_tool_iterations: 5,
+        system_prompt: None,
+        workspace_root: ws.clone(),
+        sleep_threshold_pct: 1.0,
+    };
+    let runtime = AgentRuntime::with_provider(
+        Arc::new(WritingProvider {
+            calls: Mutex::new(0),
+        }),
+        agenticlaw_tools::create_default_registry(&ws),
+        config,
+    );
+    let sk = SessionKey::new("rewind");
+    for prompt in ["first", "second"] {
+        let (event_tx, _event_rx) = tokio::sync::mpsc::channel(256);
+        runtime.run_turn(&sk, prompt, event_tx).await.unwrap();
+    }
+    assert_eq!(
+        std::fs::read_to_string(ws.join("out.txt")).unwrap(),
+        "second"
+    );
+    let turns: Vec<usize> = runtime.checkpoints(&sk).iter().map(|c| c.turn).collect();
+    assert_eq!(turns, vec![1, 2]);
+
+    let report = runtime.restore_checkpoint(&sk, 2).await.unwrap();
+    assert_eq!(report.restored, vec![ws.join("out.txt")]);
+    assert_eq!(
+        std::fs::read_to_string(ws.join("out.txt")).unwrap(),
+        "first"
+    );
+    runtime.restore_checkpoint(&sk, 1).await.unwrap();
+    assert!(!ws.join("out.txt").exists());
+
+    let ctx_path = ctx_file::session_ctx_path(&ws, "rewind");
+    let ctx = std::fs::read_to_string(&ctx_path).unwrap();
+    assert!(
+        ctx.contains("[rewind] before turn 2: restored out.txt"),
+        "{}",
+        ctx
+    );
+    assert!(ctx.contains("[rewind] before turn 1: removed out.txt"));
+    let resumed = ctx_file::parse_for_resume(&ctx_path).unwrap();
+    assert!(resumed
+        .messages
+        .iter()
+        .all(|m| !m.content.to_text().contains("[rewind]")));
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn steered_turns_fork_and_rewind_at_the_same_turn() {
+    use std::sync::{Arc, Mutex};
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-steer-turns-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&ws);
+    std::fs::create_dir_all(&ws).unwrap();
+    let runtime = AgentRuntime::with_provider(
+        Arc::new(WritingProvider {
+            calls: Mutex::new(0),
+        }),
+        agenticlaw_tools::create_default_registry(&ws),
+        AgentConfig {
+            default_model: "claude-sonnet-4".into(),
+            workspace_root: ws.clone(),
+            ..AgentConfig::default()
+        },
+    );
+    let sk = SessionKey::new("steer-turns");
+    let (event_tx, _event_rx) = tokio::sync::mpsc::channel(256);
+    runtime.run_turn(&sk, "first", event_tx).await.unwrap();
+    // Steering that lands with the second prompt is a user turn of its own
+    runtime.steer("steer".into()).await;
+    let (event_tx, _event_rx) = tokio::sync::mpsc::channel(256);
+    runtime.run_turn(&sk, "second", event_tx).await.unwrap();
+
+    let session = runtime.sessions().get(&sk).unwrap();
+    assert_eq!(session.turn(), 3);
+    let turns: Vec<usize> = runtime.checkpoints(&sk).iter().map(|c| c.turn).collect();
+    assert_eq!(turns, vec![1, 3]);
+
+    // Fork and rewind agree on what turn 2 is
+    let fork = runtime
+        .sessions()
+        .fork(&session, 2, &SessionKey::new("steer-fork"))
+        .await
+        .unwrap();
+    assert_eq!(fork.turn(), 2);
+    let user_texts: Vec<String> = fork
+        .get_messages()
+        .await
+        .iter()
+        .filter_map(|m| match &m.content {
+            agenticlaw_llm::LlmContent::Text(t) if m.role == "user" => Some(t.clone()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(user_texts, vec!["first", "second"]);
+    runtime.restore_checkpoint(&sk, 3).await.unwrap();
+    assert_eq!(
+        std::fs::read_to_string(ws.join("out.txt")).unwrap(),
+        "first"
+    );
+
+    // Resuming picks the count back up from the .ctx
+    let resumed =
+        ctx_file::parse_for_resume(&ctx_file::session_ctx_path(&ws, "steer-turns")).unwrap();
+    assert_eq!(resumed.user_turns, 3);
+    let registry = SessionRegistry::new();
+    let again = registry.resume_from_ctx(&resumed, Some("steer-resumed"));
+    assert_eq!(again.turn(), 3);
+    let _ = std::fs::remove_dir_all(&ws);
+}
+
+// ===========================================================================
+// ConsciousnessLoop / Event Queue (Issue #28)
+// ===========================================================================
+
+#[tokio::test]
+async fn consciousness_loop_processes_human_message() {
+    use agenticlaw_llm::*;
+    use std::sync::Arc;
+
+    // Mock provider that returns a simple text response
+    struct MockProvider;
+
+    #[async_trait::async_trait]
+    impl LlmProvider for MockProvider {
+        fn name(&self) -> &str {
+            "mock"
+        }
+        fn models(&self) -> &[&str] {
+            &["mock"]
+        }
+        async fn complete_stream(
+            &self,
+            _request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<
+            std::pin::Pin<
+                Box<
+                    dyn futures::Stream<
+                            Item = Result<StreamDelta, agenticlaw_llm::provider::LlmError>,
+                        > + Send,
+                >,
+            >,
+            agenticlaw_llm::provider::LlmError,
+        > {
+            let deltas = vec![
+                Ok(StreamDelta::Text("Hello from event queue!".to_string())),
+                Ok(StreamDelta::Done {
+                    stop_reason: Some("end_turn".to_string()),
+                    usage: None,
+                }),
+            ];
             Ok(Box::pin(futures::stream::iter(deltas)))
         }
     }
@@ -814,3 +2746,157 @@ async fn consciousness_loop_hitl_preempts_tools() {
     queue_tx.send(QueueEvent::Shutdown).await.unwrap();
     loop_handle.await.unwrap();
 }
+
+#[tokio::test]
+async fn consciousness_loop_parked_batch_never_runs() {
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::*;
+    use agenticlaw_tools::{Concurrency, Tool, ToolContext, ToolResult};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Holds a file for a while and ignores cancellation, like `write`.
+    struct Hold(std::path::PathBuf);
+
+    #[async_trait::async_trait]
+    impl Tool for Hold {
+        fn name(&self) -> &str {
+            "hold"
+        }
+        fn description(&self) -> &str {
+            "Holds a file"
+        }
+        fn input_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+        fn concurrency(&self) -> Concurrency {
+            Concurrency::Write
+        }
+        fn resources(&self, _args: &serde_json::Value) -> Vec<std::path::PathBuf> {
+            vec![self.0.clone()]
+        }
+        async fn execute(&self, _args: serde_json::Value) -> ToolResult {
+            tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+            ToolResult::text("held")
+        }
+        async fn execute_in(&self, args: serde_json::Value, _ctx: &ToolContext) -> ToolResult {
+            self.execute(args).await
+        }
+    }
+
+    struct HoldThenWrite {
+        calls: AtomicUsize,
+        path: String,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for HoldThenWrite {
+        fn name(&self) -> &str {
+            "mock"
+        }
+        fn models(&self) -> &[&str] {
+            &["mock"]
+        }
+        async fn complete_stream(
+            &self,
+            _request: LlmRequest,
+            _cancel: Option<tokio_util::sync::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            let deltas = if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                // Two calls on the same file: two batches
+                let write = serde_json::json!({"path": self.path, "content": "second"});
+                vec![
+                    Ok(StreamDelta::ToolCallStart {
+                        id: "h1".into(),
+                        name: "hold".into(),
+                    }),
+                    Ok(StreamDelta::ToolCallDelta {
+                        id: "h1".into(),
+                        arguments: "{}".into(),
+                    }),
+                    Ok(StreamDelta::ToolCallEnd { id: "h1".into() }),
+                    Ok(StreamDelta::ToolCallStart {
+                        id: "w2".into(),
+                        name: "write".into(),
+                    }),
+                    Ok(StreamDelta::ToolCallDelta {
+                        id: "w2".into(),
+                        arguments: write.to_string(),
+                    }),
+                    Ok(StreamDelta::ToolCallEnd { id: "w2".into() }),
+                    Ok(StreamDelta::Done {
+                        stop_reason: Some("tool_use".into()),
+                        usage: None,
+                    }),
+                ]
+            } else {
+                vec![
+                    Ok(StreamDelta::Text("Stopped.".into())),
+                    Ok(StreamDelta::Done {
+                        stop_reason: Some("end_turn".into()),
+                        usage: None,
+                    }),
+                ]
+            };
+            Ok(Box::pin(futures::stream::iter(deltas)))
+        }
+    }
+
+    let ws = std::env::temp_dir().join(format!("agenticlaw-park-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&ws);
+    std::fs::create_dir_all(&ws).unwrap();
+    let target = ws.join("x.txt");
+    let mut registry = agenticlaw_tools::create_default_registry(&ws);
+    registry.register(Hold(target.clone()));
+    let provider = Arc::new(HoldThenWrite {
+        calls: AtomicUsize::new(0),
+        path: target.display().to_string(),
+    });
+    let sessions = Arc::new(SessionRegistry::new());
+    let (mut cl, queue_tx, output_tx) = ConsciousnessLoop::new(
+        provider,
+        Arc::new(registry),
+        sessions.clone(),
+        ConsciousnessLoopConfig::default(),
+    );
+    let mut output_rx = output_tx.subscribe();
+    let loop_handle = tokio::spawn(async move {
+        cl.run().await;
+    });
+
+    let sk = SessionKey::new("park-batch");
+    for (content, pause) in [("Hold, then write", 100), ("Stop", 0)] {
+        queue_tx
+            .send(QueueEvent::HumanMessage {
+                session: sk.clone(),
+                content: content.into(),
+                priority: Priority::Human,
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(pause)).await;
+    }
+
+    let timeout = tokio::time::sleep(std::time::Duration::from_secs(5));
+    tokio::pin!(timeout);
+    loop {
+        tokio::select! {
+            event = output_rx.recv() => match event {
+                Ok(OutputEvent::Delta { content, .. }) if content == "Stopped." => break,
+                Ok(_) => {}
+                Err(_) => break,
+            },
+            _ = &mut timeout => panic!("Timed out waiting for the second reply"),
+        }
+    }
+
+    // Long after the held call finished, the parked write still hasn't run
+    tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+    assert!(!target.exists(), "the parked write ran");
+
+    queue_tx.send(QueueEvent::Shutdown).await.unwrap();
+    loop_handle.await.unwrap();
+    let _ = std::fs::remove_dir_all(&ws);
+}
@@ -3,6 +3,8 @@
 //! All tunable parameters in one place. Loaded from TOML at startup,
 //! falls back to defaults if no config file exists.
 
+use agenticlaw_llm::ModelInfo;
+use agenticlaw_tools::SandboxConfig;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
@@ -24,6 +26,8 @@ pub struct ConsciousnessConfig {
     pub injection: InjectionConfig,
     /// Sleep/wake thresholds.
     pub sleep: SleepConfig,
+    /// File access for the L1-L3 and core tools.
+    pub sandbox: SandboxConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -133,6 +137,14 @@ pub struct SleepConfig {
     pub context_threshold_pct: f64,
 }
 
+impl SleepConfig {
+    /// Token count at which a layer running on a model with these
+    /// capabilities goes to sleep.
+    pub fn threshold_tokens(&self, model: &ModelInfo) -> usize {
+        (self.context_threshold_pct * model.context_window as f64) as usize
+    }
+}
+
 // ============================================================
 // Defaults
 // ============================================================
@@ -5,7 +5,11 @@
 
 use crate::injection;
 use agenticlaw_agent::{AgentConfig, AgentEvent, AgentRuntime, SessionKey};
-use agenticlaw_tools::create_default_registry;
+use agenticlaw_llm::LlmProvider;
+use agenticlaw_tools::{
+    create_runtime_handle, create_sandboxed_registry, NetworkPolicy, SandboxConfig,
+    WorkspaceSandbox,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
@@ -138,32 +142,53 @@ pub struct DualCore {
 }
 
 impl DualCore {
-    pub fn new(workspace: PathBuf, api_key: &str, soul: &str, models: [String; 2]) -> Self {
-        let runtimes = std::array::from_fn(|i| {
+    pub fn new(
+        workspace: PathBuf,
+        provider: Arc<dyn LlmProvider>,
+        soul: &str,
+        models: [String; 2],
+        sandbox: &SandboxConfig,
+    ) -> anyhow::Result<Self> {
+        let network = NetworkPolicy::from_sandbox_config(sandbox)
+            .map_err(|e| anyhow::anyhow!("network: {}", e))?;
+        let network = Arc::new(network);
+        let mut runtimes = Vec::with_capacity(2);
+        for (i, model) in models.iter().enumerate() {
             let core_ws = workspace.join(CoreId::from_index(i).dir_name());
             let _ = std::fs::create_dir_all(&core_ws);
-            let tools = create_default_registry(&core_ws);
+            let sandbox = WorkspaceSandbox::from_config(&core_ws, sandbox)
+                .map_err(|e| anyhow::anyhow!("sandbox: {}", e))?;
+            let tools =
+                create_sandboxed_registry(sandbox, network.clone(), create_runtime_handle());
             let config = AgentConfig {
-                default_model: models[i].clone(),
+                default_model: model.clone(),
                 max_tool_iterations: 3,
                 system_prompt: Some(soul.to_string()),
                 workspace_root: core_ws,
                 sleep_threshold_pct: 1.0,
             };
-            Arc::new(AgentRuntime::new(api_key, tools, config))
-        });
+            runtimes.push(Arc::new(AgentRuntime::with_provider(
+                provider.clone(),
+                tools,
+                config,
+            )));
+        }
+        let runtimes: [Arc<AgentRuntime>; 2] = runtimes
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("one runtime per core"));
 
         let state_path = workspace.join("core-state.json");
-        let state = Self::hydrate_or_create(&state_path, 200_000);
+        let budget = runtimes[0].model_info(&models[0]).context_window;
+        let state = Self::hydrate_or_create(&state_path, budget);
 
-        Self {
+        Ok(Self {
             runtimes,
             state: Arc::new(Mutex::new(state)),
             workspace,
             state_path,
             semaphores: [Arc::new(Semaphore::new(1)), Arc::new(Semaphore::new(1))],
             ready_since: Arc::new(Mutex::new([None, None])),
-        }
+        })
     }
 
     fn hydrate_or_create(state_path: &Path, budget: usize) -> CoreState {
@@ -11,9 +11,7 @@
 
 use crate::config::ConsciousnessConfig;
 use crate::stack::{extract_tail_paragraphs, find_latest_ctx, safe_byte_boundary};
-use agenticlaw_llm::{
-    AnthropicProvider, LlmContent, LlmMessage, LlmProvider, LlmRequest, StreamDelta,
-};
+use agenticlaw_llm::{LlmContent, LlmMessage, LlmProvider, LlmRequest, StreamDelta};
 use futures::StreamExt;
 use std::path::Path;
 use tracing::{error, info, warn};
@@ -26,7 +24,7 @@ use tracing::{error, info, warn};
 /// `context_budget` — max chars of watcher .ctx to include as context
 /// `max_tokens` — max output tokens for the LLM call
 pub async fn distill_ego(
-    api_key: &str,
+    provider: &dyn LlmProvider,
     model: &str,
     watcher_sessions: &Path,
     target_name: &str,
@@ -49,7 +47,6 @@ pub async fn distill_ego(
         &content
     };
 
-    let provider = AnthropicProvider::new(api_key);
     let request = LlmRequest {
         model: model.to_string(),
         messages: vec![LlmMessage {
@@ -132,7 +129,7 @@ pub fn read_ego(workspace: &Path, layer_dir: &str) -> Option<String> {
 /// Returns: [L0_ego, L1_ego, L2_ego, L3_ego, core_ego]
 pub async fn distill_all_egos(
     workspace: &Path,
-    api_key: &str,
+    provider: &dyn LlmProvider,
     config: &ConsciousnessConfig,
 ) -> [Option<String>; 5] {
     let mut egos: [Option<String>; 5] = Default::default();
@@ -140,7 +137,7 @@ pub async fn distill_all_egos(
     // L1 distills L0's ego (L1 watches L0, L1 knows L0 best)
     let l1_sessions = workspace.join("L1").join(".agenticlaw").join("sessions");
     if let Some(ego) = distill_ego(
-        api_key,
+        provider,
         &config.models.l1,
         &l1_sessions,
         "L0",
@@ -157,7 +154,7 @@ pub async fn distill_all_egos(
     // L2 distills L1's ego (L2 watches L1)
     let l2_sessions = workspace.join("L2").join(".agenticlaw").join("sessions");
     if let Some(ego) = distill_ego(
-        api_key,
+        provider,
         &config.models.l2,
         &l2_sessions,
         "L1",
@@ -174,7 +171,7 @@ pub async fn distill_all_egos(
     // L3 distills L2's ego (L3 watches L2)
     let l3_sessions = workspace.join("L3").join(".agenticlaw").join("sessions");
     if let Some(ego) = distill_ego(
-        api_key,
+        provider,
         &config.models.l3,
         &l3_sessions,
         "L2",
@@ -197,7 +194,7 @@ pub async fn distill_all_egos(
         .join("sessions");
 
     if let Some(ego) = distill_ego(
-        api_key,
+        provider,
         &config.models.core,
         &core_sessions,
         "L3",
@@ -213,7 +210,7 @@ pub async fn distill_all_egos(
 
     // Warm core self-distills (for its own wake)
     if let Some(ego) = distill_ego(
-        api_key,
+        provider,
         &config.models.core,
         &core_sessions,
         "Core (self)",
@@ -237,7 +234,7 @@ pub async fn distill_all_egos(
 pub async fn distill_layer_ego_on_sleep(
     workspace: &Path,
     layer: usize,
-    api_key: &str,
+    provider: &dyn LlmProvider,
     config: &ConsciousnessConfig,
 ) -> Option<String> {
     let layer_dirs = ["L0", "L1", "L2", "L3"];
@@ -301,7 +298,7 @@ pub async fn distill_layer_ego_on_sleep(
 
     // 1. Distill the ego summary (first person) from the watcher
     let ego_summary = distill_ego(
-        api_key,
+        provider,
         model,
         &watcher_sessions,
         layer_dirs[layer],
@@ -345,7 +342,7 @@ pub async fn distill_layer_ego_on_sleep(
 /// Distill core's ego on sleep/wake. Core self-distills + staples its own .ctx tail.
 pub async fn distill_core_ego_on_sleep(
     workspace: &Path,
-    api_key: &str,
+    provider: &dyn LlmProvider,
     config: &ConsciousnessConfig,
 ) -> Option<String> {
     let warm_dir = warm_core_name(&workspace.join("core-state.json")).unwrap_or("core-a");
@@ -355,7 +352,7 @@ pub async fn distill_core_ego_on_sleep(
         .join("sessions");
 
     let ego_summary = distill_ego(
-        api_key,
+        provider,
         &config.models.core,
         &core_sessions,
         warm_dir,
@@ -94,9 +94,12 @@ async fn main() -> anyhow::Result<()> {
         .unwrap_or_else(|| workspace.join("consciousness.toml"));
     let config = ConsciousnessConfig::load(&config_path);
 
+    // A replayed run (RUSTCLAW_LLM_REPLAY) makes no API calls and needs no key
+    let replaying = std::env::var_os("RUSTCLAW_LLM_REPLAY").is_some();
     let api_key = cli
         .api_key
         .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
+        .or_else(|| replaying.then(String::new))
         .ok_or_else(|| {
             anyhow::anyhow!("ANTHROPIC_API_KEY not set. Pass --api-key or set the env var.")
         })?;
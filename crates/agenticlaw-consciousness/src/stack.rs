@@ -11,9 +11,11 @@ use crate::injection;
 use crate::version::VersionController;
 use crate::watcher::{CtxChange, CtxWatcher};
 use agenticlaw_agent::{AgentConfig, AgentEvent, AgentRuntime, SessionKey};
-use agenticlaw_core::{AuthConfig, AuthMode, BindMode, GatewayConfig};
+use agenticlaw_core::{AuthConfig, AuthMode, BindMode, GatewayConfig, OpenclawConfig};
 use agenticlaw_gateway::ExtendedConfig;
-use agenticlaw_tools::create_default_registry;
+use agenticlaw_tools::{
+    create_runtime_handle, create_sandboxed_registry, NetworkPolicy, WorkspaceSandbox,
+};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -362,8 +364,21 @@ impl ConsciousnessStack {
             version_ctrl.current_version()
         );
 
-        // Auto-detect models
-        let layer_models = Self::detect_models(&self.api_key).await;
+        // Same provider stack as L0: openclaw.json routes, fallbacks and catalog
+        let provider = agenticlaw_gateway::provider_from_config(
+            &OpenclawConfig::discover(),
+            Some(self.api_key.clone()),
+        )?;
+
+        // Auto-detect models. Recorded and replayed runs keep the defaults so
+        // a replay sends the same requests its recording did.
+        let pinned = std::env::var_os("RUSTCLAW_LLM_RECORD").is_some()
+            || std::env::var_os("RUSTCLAW_LLM_REPLAY").is_some();
+        let layer_models = if pinned {
+            std::array::from_fn(|i| LAYER_MODEL_TIERS[i].1.to_string())
+        } else {
+            Self::detect_models(&self.api_key).await
+        };
         let core_model = Self::resolve_core_model(&layer_models);
 
         // Determine system prompts for each layer: ego (wake) or soul (birth)
@@ -384,7 +399,7 @@ impl ConsciousnessStack {
                 let ego = ego::distill_layer_ego_on_sleep(
                     &self.workspace,
                     i,
-                    &self.api_key,
+                    provider.as_ref(),
                     &self.config,
                 )
                 .await;
@@ -434,7 +449,8 @@ impl ConsciousnessStack {
             // Core self-distills fresh
             let _warm_dir = self.warm_core_dir();
             let core_ego =
-                ego::distill_core_ego_on_sleep(&self.workspace, &self.api_key, &self.config).await;
+                ego::distill_core_ego_on_sleep(&self.workspace, provider.as_ref(), &self.config)
+                    .await;
             if let Some(ref ego) = core_ego {
                 info!("Core ego distilled ({} chars)", ego.len());
                 self.wake_core_prompt(ego)
@@ -447,10 +463,11 @@ impl ConsciousnessStack {
         // Create DualCore with the resolved prompt
         let dual_core = Arc::new(DualCore::new(
             self.workspace.clone(),
-            &self.api_key,
+            provider.clone(),
             &core_prompt,
             [core_model.clone(), core_model.clone()],
-        ));
+            &self.config.sandbox,
+        )?);
 
         // Core workspace setup
         for dir_name in ["core-a", "core-b"] {
@@ -483,10 +500,16 @@ impl ConsciousnessStack {
 
         // 3. Create inner layer runtimes (L1-L3) with resolved prompts
         let max_tool_iter = self.config.cascade.max_tool_iterations;
+        let network = NetworkPolicy::from_sandbox_config(&self.config.sandbox)
+            .map_err(|e| anyhow::anyhow!("network: {}", e))?;
+        let network = Arc::new(network);
         let inner_runtimes: Vec<Arc<AgentRuntime>> = (1..4)
             .map(|i| {
                 let ws = self.layer_workspace(i);
-                let tools = create_default_registry(&ws);
+                let sandbox = WorkspaceSandbox::from_config(&ws, &self.config.sandbox)
+                    .map_err(|e| anyhow::anyhow!("sandbox: {}", e))?;
+                let tools =
+                    create_sandboxed_registry(sandbox, network.clone(), create_runtime_handle());
                 let config = AgentConfig {
                     default_model: layer_models[i].clone(),
                     max_tool_iterations: max_tool_iter,
@@ -494,9 +517,18 @@ impl ConsciousnessStack {
                     workspace_root: ws,
                     sleep_threshold_pct: self.config.sleep.context_threshold_pct,
                 };
-                Arc::new(AgentRuntime::new(&self.api_key, tools, config))
+                let runtime = AgentRuntime::with_provider(provider.clone(), tools, config);
+                let model_info = runtime.model_info(&layer_models[i]);
+                let threshold = self.config.sleep.threshold_tokens(&model_info);
+                info!(
+                    "L{} sleeps at {}k of {}k tokens",
+                    i,
+                    threshold / 1000,
+                    model_info.context_window / 1000
+                );
+                Ok(Arc::new(runtime.with_sleep_threshold(threshold)))
             })
-            .collect();
+            .collect::<anyhow::Result<_>>()?;
 
         // Per-layer semaphores (1 concurrent task per layer)
         let layer_semaphores: Vec<Arc<Semaphore>> =
@@ -533,6 +565,7 @@ impl ConsciousnessStack {
                                 .send(CtxChange {
                                     layer,
                                     path,
+                                    events: agenticlaw_ctx::parse(&content).events,
                                     delta: content,
                                     total_size: 0,
                                 })
@@ -635,6 +668,7 @@ impl ConsciousnessStack {
             anthropic_api_key: Some(self.api_key.clone()),
             workspace_root: self.layer_workspace(0),
             system_prompt: Some(prompt.to_string()),
+            consciousness: true,
         };
 
         let handle = tokio::spawn(async move {
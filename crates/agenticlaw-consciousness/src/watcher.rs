@@ -1,8 +1,10 @@
 //! File-change watcher for .ctx files
 //!
 //! Polls .ctx file sizes to detect changes. Fires callbacks on size increase.
-//! Uses byte offset tracking to extract only new content (the delta).
+//! Uses byte offset tracking to extract only new content (the delta), and a
+//! streaming parser per file to turn it into typed .ctx events.
 
+use agenticlaw_ctx::{Event, StreamParser};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
@@ -15,6 +17,9 @@ pub struct CtxChange {
     pub layer: usize,
     pub path: PathBuf,
     pub delta: String,
+    /// Events completed by this delta. A turn split across writes arrives
+    /// with the change that finishes it.
+    pub events: Vec<Event>,
     pub total_size: u64,
 }
 
@@ -27,6 +32,8 @@ pub struct CtxWatcher {
     scan_dirs: Vec<(usize, PathBuf)>,
     /// Last known size per path
     sizes: HashMap<PathBuf, u64>,
+    /// Parser per path, fed everything up to the last known size
+    parsers: HashMap<PathBuf, StreamParser>,
     /// Minimum poll interval
     poll_interval: Duration,
 }
@@ -37,6 +44,7 @@ impl CtxWatcher {
             targets: Vec::new(),
             scan_dirs: Vec::new(),
             sizes: HashMap::new(),
+            parsers: HashMap::new(),
             poll_interval,
         }
     }
@@ -44,7 +52,7 @@ impl CtxWatcher {
     pub fn watch(&mut self, layer: usize, path: PathBuf) {
         // Initialize with current size (don't fire on startup for existing content)
         let current_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
-        self.sizes.insert(path.clone(), current_size);
+        self.track(&path, current_size);
         self.targets.push((layer, path));
     }
 
@@ -55,6 +63,7 @@ impl CtxWatcher {
 
     /// Scan for new .ctx files in registered directories and add them to targets.
     fn scan_for_new_files(&mut self) {
+        let mut new_files = Vec::new();
         for (layer, dir) in &self.scan_dirs {
             let entries = match std::fs::read_dir(dir) {
                 Ok(e) => e,
@@ -73,11 +82,31 @@ impl CtxWatcher {
                     // Start watching from current size (don't replay history) unless it's tiny
                     // For new files under 8KB, watch from 0 to catch the initial content
                     let start_from = if current_size < 8192 { 0 } else { current_size };
-                    self.sizes.insert(path.clone(), start_from);
-                    self.targets.push((*layer, path));
+                    new_files.push((*layer, path, start_from));
                 }
             }
         }
+        for (layer, path, start_from) in new_files {
+            self.track(&path, start_from);
+            self.targets.push((layer, path));
+        }
+    }
+
+    /// Start tracking `path` from byte `from`. The parser still reads what
+    /// came before, so the first delta lands in the right turn.
+    fn track(&mut self, path: &Path, from: u64) {
+        let mut parser = StreamParser::new();
+        if from > 0 {
+            match read_delta(path, 0, from) {
+                Ok(existing) => {
+                    parser.feed(&existing);
+                }
+                Err(e) => debug!("Failed to read {}: {}", path.display(), e),
+            }
+            parser.take_errors();
+        }
+        self.sizes.insert(path.to_path_buf(), from);
+        self.parsers.insert(path.to_path_buf(), parser);
     }
 
     /// Run the poll loop, sending change events to the channel.
@@ -113,6 +142,17 @@ impl CtxWatcher {
                     }
                 };
 
+                let parser = self.parsers.entry(path.clone()).or_default();
+                let mut events = parser.feed(&delta);
+                // Writers append whole events, so a delta ending in a blank
+                // line ends its last turn
+                if delta.ends_with("\n\n") {
+                    events.extend(parser.flush());
+                }
+                for e in parser.take_errors() {
+                    debug!("{} line {}: {}", path.display(), e.line, e.message);
+                }
+
                 if delta.trim().is_empty() {
                     self.sizes.insert(path.clone(), current_size);
                     continue;
@@ -130,6 +170,7 @@ impl CtxWatcher {
                     layer: *layer,
                     path: path.clone(),
                     delta,
+                    events,
                     total_size: current_size,
                 };
 
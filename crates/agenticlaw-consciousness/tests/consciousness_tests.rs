@@ -18,6 +18,8 @@ use agenticlaw_consciousness::stack::{
     extract_tail_paragraphs, find_latest_ctx, ConsciousnessStack, LAYER_NAMES, LAYER_PORTS,
 };
 use agenticlaw_consciousness::version::VersionController;
+use agenticlaw_consciousness::watcher::CtxWatcher;
+use agenticlaw_ctx::{Block, Event};
 use std::fs;
 use std::path::{Path, PathBuf};
 use tempfile::TempDir;
@@ -460,6 +462,36 @@ fn find_latest_ctx_ignores_non_ctx_files() {
     assert!(find_latest_ctx(tmp.path()).is_none());
 }
 
+#[tokio::test]
+async fn watcher_parses_appended_turns_into_events() {
+    let tmp = TempDir::new().unwrap();
+    let ctx_path = tmp.path().join("session-001.ctx");
+    fs::write(
+        &ctx_path,
+        "--- session: s ---\nformat: 1\nstarted: t0\n\n--- t1 ---\n<up>\nHi\n</up>\n\n",
+    )
+    .unwrap();
+
+    let mut watcher = CtxWatcher::new(std::time::Duration::from_millis(10));
+    watcher.watch(0, ctx_path.clone());
+    let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+    tokio::spawn(watcher.run(tx));
+
+    tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+    let mut f = fs::OpenOptions::new().append(true).open(&ctx_path).unwrap();
+    std::io::Write::write_all(&mut f, b"--- t2 ---\nHello.\n\n").unwrap();
+
+    let change = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(change.delta, "--- t2 ---\nHello.\n\n");
+    assert!(
+        matches!(&change.events[..], [Event::Assistant { blocks, .. }]
+        if blocks == &[Block::Text("Hello.".into())])
+    );
+}
+
 // ============================================================
 // VersionController — workspace schema management
 // ============================================================
@@ -1216,6 +1248,61 @@ fn ego_md_preferred_over_ctx_extraction() {
     assert!(ego.unwrap().contains("Distilled"));
 }
 
+/// Stands in for the API: every distillation gets the same first-person ego.
+struct ScriptedEgo;
+
+#[async_trait::async_trait]
+impl agenticlaw_llm::LlmProvider for ScriptedEgo {
+    fn name(&self) -> &str {
+        "scripted"
+    }
+    fn models(&self) -> &[&str] {
+        &[]
+    }
+    async fn complete_stream(
+        &self,
+        _request: agenticlaw_llm::LlmRequest,
+        _cancel: Option<agenticlaw_llm::CancellationToken>,
+    ) -> Result<agenticlaw_llm::provider::LlmStream, agenticlaw_llm::LlmError> {
+        use agenticlaw_llm::StreamDelta;
+        Ok(Box::pin(futures::stream::iter([
+            Ok(StreamDelta::Text("I am the gateway.".into())),
+            Ok(StreamDelta::Done {
+                stop_reason: Some("end_turn".into()),
+                usage: None,
+            }),
+        ])))
+    }
+}
+
+#[tokio::test]
+async fn ego_distillation_replays_from_a_recording() {
+    use agenticlaw_llm::{RecordingProvider, ReplayProvider};
+
+    let tmp = TempDir::new().unwrap();
+    let watcher = tmp.path().join("L1").join(".agenticlaw").join("sessions");
+    fs::create_dir_all(&watcher).unwrap();
+    fs::create_dir_all(tmp.path().join("L0")).unwrap();
+    fs::write(
+        watcher.join("20260219-000000-l1.ctx"),
+        "--- session: l1 ---\n\nL0 greeted the user.\n",
+    )
+    .unwrap();
+    let config = ConsciousnessConfig::default();
+    let fixture = tmp.path().join("ego.jsonl");
+
+    let recorder = RecordingProvider::new(std::sync::Arc::new(ScriptedEgo), &fixture);
+    let live = ego::distill_layer_ego_on_sleep(tmp.path(), 0, &recorder, &config).await;
+    assert_eq!(live.as_deref(), Some("I am the gateway."));
+
+    // Offline: the same distillation served from the recording
+    fs::remove_file(tmp.path().join("L0").join("ego.md")).unwrap();
+    let replay = ReplayProvider::load(&fixture).unwrap();
+    let replayed = ego::distill_layer_ego_on_sleep(tmp.path(), 0, &replay, &config).await;
+    assert_eq!(replayed, live);
+    assert_eq!(ego::read_ego(tmp.path(), "L0"), live);
+}
+
 // ============================================================
 // Parent tail extraction tests
 // ============================================================
@@ -1291,6 +1378,19 @@ fn sleep_threshold_is_configurable() {
     );
 }
 
+#[test]
+fn sleep_threshold_scales_with_model_context_window() {
+    use agenticlaw_llm::ModelInfo;
+    let sleep = ConsciousnessConfig::default().sleep;
+    let opus = ModelInfo::for_model("claude-opus-4-6");
+    assert_eq!(sleep.threshold_tokens(&opus), 110_000);
+    let local = ModelInfo {
+        context_window: 32_768,
+        ..ModelInfo::DEFAULT
+    };
+    assert_eq!(sleep.threshold_tokens(&local), 18_022);
+}
+
 #[test]
 fn agent_event_sleep_variant_exists() {
     use agenticlaw_agent::AgentEvent;
@@ -3,6 +3,7 @@
 pub mod error;
 pub mod openclaw_config;
 pub mod protocol;
+pub mod schema;
 pub mod types;
 
 pub use error::{Error, Result};
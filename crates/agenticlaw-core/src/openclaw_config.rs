@@ -3,6 +3,7 @@
 //! Pure types and parsing only. Watching/hot-reload lives in agenticlaw-agent.
 
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -45,12 +46,29 @@ pub struct OcAgentDefaults {
     pub context_tokens: Option<usize>,
     #[serde(rename = "maxConcurrent")]
     pub max_concurrent: Option<usize>,
+    pub compaction: OcCompaction,
+}
+
+/// Summarising old turns as a session nears its model's context window.
+/// Applies to sessions without the consciousness stack (`--no-consciousness`,
+/// `gateway`, `chat`); the stack's own gateway never compacts. Omitted fields
+/// keep the defaults: on, at 80% of the window, keeping 25%.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OcCompaction {
+    pub enabled: Option<bool>,
+    #[serde(rename = "thresholdPct")]
+    pub threshold_pct: Option<f64>,
+    #[serde(rename = "keepRecentPct")]
+    pub keep_recent_pct: Option<f64>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
 #[serde(default)]
 pub struct OcModelRef {
     pub primary: Option<String>,
+    /// Tried in order when the primary is rate limited or down, e.g. `anthropic/claude-haiku-4-5`.
+    pub fallbacks: Vec<String>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -63,6 +81,7 @@ pub struct OcModels {
 #[serde(default)]
 pub struct OcProviders {
     pub anthropic: Option<OcAnthropicProvider>,
+    pub openai: Option<OcOpenAiProvider>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -73,6 +92,18 @@ pub struct OcAnthropicProvider {
     pub models: Vec<OcModelEntry>,
 }
 
+/// Any server speaking the OpenAI chat completions API (OpenAI, vLLM,
+/// llama.cpp, Ollama). `baseUrl` is the API root, e.g. `http://localhost:8000/v1`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OcOpenAiProvider {
+    #[serde(rename = "baseUrl")]
+    pub base_url: Option<String>,
+    #[serde(rename = "apiKey")]
+    pub api_key: Option<String>,
+    pub models: Vec<OcModelEntry>,
+}
+
 #[derive(Debug, Clone, Default, Deserialize)]
 #[serde(default)]
 pub struct OcModelEntry {
@@ -82,18 +113,80 @@ pub struct OcModelEntry {
     pub context_window: Option<usize>,
     #[serde(rename = "maxTokens")]
     pub max_tokens: Option<usize>,
+    /// Supports extended thinking / reasoning output.
+    pub reasoning: Option<bool>,
+    /// Supports tool calls. Some small local models don't.
+    pub tools: Option<bool>,
+    pub cost: Option<OcModelCost>,
+}
+
+/// USD per million tokens.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OcModelCost {
+    pub input: f64,
+    pub output: f64,
+    #[serde(rename = "cacheRead")]
+    pub cache_read: f64,
+    #[serde(rename = "cacheWrite")]
+    pub cache_write: f64,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
 #[serde(default)]
 pub struct OcTools {
     pub web: OcWebTools,
+    pub sandbox: OcSandbox,
+    pub output: OcToolOutput,
+}
+
+/// Byte budgets for tool results. Larger results are saved under
+/// `.agenticlaw/spill` and the model gets a preview plus the path.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OcToolOutput {
+    #[serde(rename = "maxBytes")]
+    pub max_bytes: Option<usize>,
+    /// Per-tool overrides, keyed by tool name.
+    #[serde(rename = "perTool")]
+    pub per_tool: HashMap<String, usize>,
+}
+
+/// Where the file tools may read and write. Omitted fields keep the defaults:
+/// the workspace as the only root, and common secret files denied.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OcSandbox {
+    pub roots: Vec<String>,
+    #[serde(rename = "readOnlyRoots")]
+    pub read_only_roots: Vec<String>,
+    /// Replaces the default deny list when set.
+    pub deny: Option<Vec<String>>,
+    #[serde(rename = "denyWrite")]
+    pub deny_write: Vec<String>,
+    /// Operator policy JSON; its `filesystem` rules are compiled in.
+    pub policy: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
 #[serde(default)]
 pub struct OcWebTools {
     pub search: OcSearchConfig,
+    pub fetch: OcFetchConfig,
+}
+
+/// Which URLs `web_fetch` may reach, as `connect:<url glob>` rules. Omitted
+/// lists keep the defaults: any HTTP(S) URL except cloud metadata endpoints.
+/// A sandbox `policy` file's `network` tier takes precedence.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OcFetchConfig {
+    pub allow: Option<Vec<String>>,
+    pub deny: Option<Vec<String>>,
+    /// Let loopback, private and link-local addresses through; refused
+    /// otherwise, whatever the rules allow.
+    #[serde(rename = "allowPrivateNetwork")]
+    pub allow_private_network: bool,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -157,6 +250,17 @@ impl OpenclawConfig {
             .map(|m| m.split('/').next_back().unwrap_or(m).to_string())
     }
 
+    /// Provider prefix of the primary model (`"openai"` for `openai/qwen2.5`).
+    pub fn default_provider(&self) -> Option<&str> {
+        self.agents
+            .defaults
+            .model
+            .primary
+            .as_deref()
+            .and_then(|m| m.split_once('/'))
+            .map(|(provider, _)| provider)
+    }
+
     pub fn gateway_port(&self) -> Option<u16> {
         self.gateway.port
     }
@@ -178,6 +282,30 @@ impl OpenclawConfig {
             .and_then(|p| p.base_url.as_deref())
     }
 
+    /// Model metadata declared under `models.providers.anthropic.models`.
+    pub fn anthropic_models(&self) -> &[OcModelEntry] {
+        self.models
+            .providers
+            .anthropic
+            .as_ref()
+            .map(|p| p.models.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Fallback model refs (`provider/model`), in failover order.
+    pub fn model_fallbacks(&self) -> &[String] {
+        &self.agents.defaults.model.fallbacks
+    }
+
+    pub fn openai_provider(&self) -> Option<&OcOpenAiProvider> {
+        self.models.providers.openai.as_ref()
+    }
+
+    /// True when the primary model is routed to the OpenAI-compatible provider.
+    pub fn uses_openai(&self) -> bool {
+        self.default_provider() == Some("openai") && self.openai_provider().is_some()
+    }
+
     pub fn context_tokens(&self) -> Option<usize> {
         self.agents.defaults.context_tokens
     }
@@ -0,0 +1,189 @@
+//! JSON Schema validation — the subset used by tool and output schemas
+//!
+//! Supports `type` (single or list), `enum`, `const`, `properties`,
+//! `required`, `additionalProperties`, `items`, `minItems`/`maxItems`,
+//! `minLength`/`maxLength`, `minimum`/`maximum` (and their exclusive forms),
+//! and `anyOf`/`oneOf`/`allOf`. Other keywords are ignored, so richer schemas
+//! validate leniently instead of failing.
+
+use serde_json::Value;
+
+/// Validate `value` against `schema`. On failure returns every violation,
+/// each prefixed with its location (`$`, `$.field`, `$.list[2]`).
+pub fn validate(schema: &Value, value: &Value) -> Result<(), Vec<String>> {
+    validate_at(schema, value, "$")
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true`/`{}` accept anything; `false` accepts nothing
+        if schema == &Value::Bool(false) {
+            errors.push(format!("{}: not allowed", path));
+        }
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+            errors.push(format!(
+                "{}: expected {}, got {}",
+                path,
+                types.join(" or "),
+                type_name(value)
+            ));
+            return; // further checks would only repeat the mismatch
+        }
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            errors.push(format!(
+                "{}: must be one of {}",
+                path,
+                Value::Array(allowed.clone())
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{}: must be {}", path, expected));
+        }
+    }
+
+    match value {
+        Value::Object(obj) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !obj.contains_key(name) {
+                        errors.push(format!("{}: missing required property \"{}\"", path, name));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, v) in obj {
+                let child = format!("{}.{}", path, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(prop) => check(prop, v, &child, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{}: unexpected property", child))
+                        }
+                        Some(extra @ Value::Object(_)) => check(extra, v, &child, errors),
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(n) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < n {
+                    errors.push(format!("{}: expected at least {} items", path, n));
+                }
+            }
+            if let Some(n) = schema.get("maxItems").and_then(Value::as_u64) {
+                if items.len() as u64 > n {
+                    errors.push(format!("{}: expected at most {} items", path, n));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}[{}]", path, i), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(n) = schema.get("minLength").and_then(Value::as_u64) {
+                if len < n {
+                    errors.push(format!("{}: shorter than {} characters", path, n));
+                }
+            }
+            if let Some(n) = schema.get("maxLength").and_then(Value::as_u64) {
+                if len > n {
+                    errors.push(format!("{}: longer than {} characters", path, n));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(f64::NAN);
+            let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+            if let Some(min) = bound("minimum").filter(|min| n < *min) {
+                errors.push(format!("{}: must be >= {}", path, min));
+            }
+            if let Some(max) = bound("maximum").filter(|max| n > *max) {
+                errors.push(format!("{}: must be <= {}", path, max));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|min| n <= *min) {
+                errors.push(format!("{}: must be > {}", path, min));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|max| n >= *max) {
+                errors.push(format!("{}: must be < {}", path, max));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(Value::Array(all)) = schema.get("allOf") {
+        for sub in all {
+            check(sub, value, path, errors);
+        }
+    }
+    if let Some(Value::Array(any)) = schema.get("anyOf") {
+        if !any.iter().any(|sub| validate_at(sub, value, path).is_ok()) {
+            errors.push(format!("{}: does not match any allowed schema", path));
+        }
+    }
+    if let Some(Value::Array(one)) = schema.get("oneOf") {
+        let matches = one
+            .iter()
+            .filter(|sub| validate_at(sub, value, path).is_ok())
+            .count();
+        if matches != 1 {
+            errors.push(format!(
+                "{}: must match exactly one schema (matched {})",
+                path, matches
+            ));
+        }
+    }
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    check(schema, value, path, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
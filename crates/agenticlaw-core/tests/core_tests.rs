@@ -556,3 +556,139 @@ fn error_display_all_variants() {
         let _ = format!("{}", e);
     }
 }
+
+// ===========================================================================
+// OpenclawConfig — providers
+// ===========================================================================
+
+#[test]
+fn openclaw_config_openai_provider_selected() {
+    let json = r#"{
+        "agents": { "defaults": { "model": { "primary": "openai/qwen2.5-coder" } } },
+        "models": { "providers": { "openai": {
+            "baseUrl": "http://localhost:8000/v1",
+            "apiKey": "sk-local",
+            "models": [{ "id": "qwen2.5-coder", "contextWindow": 32768 }]
+        } } }
+    }"#;
+    let oc: OpenclawConfig = serde_json::from_str(json).unwrap();
+    assert_eq!(oc.default_provider(), Some("openai"));
+    assert_eq!(oc.default_model().as_deref(), Some("qwen2.5-coder"));
+    assert!(oc.uses_openai());
+    let openai = oc.openai_provider().unwrap();
+    assert_eq!(openai.base_url.as_deref(), Some("http://localhost:8000/v1"));
+    assert_eq!(openai.api_key.as_deref(), Some("sk-local"));
+    assert_eq!(openai.models[0].context_window, Some(32768));
+}
+
+#[test]
+fn openclaw_config_anthropic_primary_ignores_openai_section() {
+    let json = r#"{
+        "agents": { "defaults": { "model": { "primary": "anthropic/claude-opus-4-6" } } },
+        "models": { "providers": { "openai": { "baseUrl": "http://localhost:8000/v1" } } }
+    }"#;
+    let oc: OpenclawConfig = serde_json::from_str(json).unwrap();
+    assert_eq!(oc.default_provider(), Some("anthropic"));
+    assert!(!oc.uses_openai());
+}
+
+#[test]
+fn openclaw_config_unprefixed_model_has_no_provider() {
+    let json = r#"{ "agents": { "defaults": { "model": { "primary": "claude-opus-4-6" } } } }"#;
+    let oc: OpenclawConfig = serde_json::from_str(json).unwrap();
+    assert_eq!(oc.default_provider(), None);
+    assert!(oc.openai_provider().is_none());
+}
+
+#[test]
+fn openclaw_config_model_fallbacks() {
+    let json = r#"{ "agents": { "defaults": { "model": {
+        "primary": "anthropic/claude-opus-4-6",
+        "fallbacks": ["anthropic/claude-haiku-4-5", "openai/qwen2.5-coder"]
+    } } } }"#;
+    let oc: OpenclawConfig = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        oc.model_fallbacks(),
+        ["anthropic/claude-haiku-4-5", "openai/qwen2.5-coder"]
+    );
+    assert!(OpenclawConfig::default().model_fallbacks().is_empty());
+}
+
+#[test]
+fn openclaw_config_model_capabilities() {
+    let json = r#"{ "models": { "providers": { "anthropic": { "models": [{
+        "id": "claude-opus-4-6",
+        "contextWindow": 1000000,
+        "maxTokens": 64000,
+        "reasoning": true,
+        "cost": { "input": 10, "output": 37.5, "cacheRead": 1, "cacheWrite": 12.5 }
+    }] } } } }"#;
+    let oc: OpenclawConfig = serde_json::from_str(json).unwrap();
+    let entry = &oc.anthropic_models()[0];
+    assert_eq!(entry.context_window, Some(1_000_000));
+    assert_eq!(entry.reasoning, Some(true));
+    assert_eq!(entry.tools, None);
+    let cost = entry.cost.as_ref().unwrap();
+    assert_eq!(cost.output, 37.5);
+    assert_eq!(cost.cache_write, 12.5);
+    assert!(OpenclawConfig::default().anthropic_models().is_empty());
+}
+
+// ===========================================================================
+// JSON Schema validation
+// ===========================================================================
+
+#[test]
+fn schema_accepts_matching_value() {
+    let schema = serde_json::json!({
+        "type": "object",
+        "required": ["name", "tags"],
+        "properties": {
+            "name": { "type": "string", "minLength": 1 },
+            "tags": { "type": "array", "items": { "type": "string" } },
+            "score": { "type": "number", "minimum": 0, "maximum": 1 }
+        }
+    });
+    let value = serde_json::json!({ "name": "a", "tags": ["x"], "score": 0.5 });
+    assert!(schema::validate(&schema, &value).is_ok());
+}
+
+#[test]
+fn schema_reports_every_violation_with_path() {
+    let schema = serde_json::json!({
+        "type": "object",
+        "required": ["name", "kind"],
+        "additionalProperties": false,
+        "properties": {
+            "name": { "type": "string" },
+            "kind": { "enum": ["bug", "feature"] },
+            "items": { "type": "array", "items": { "type": "integer", "minimum": 1 } }
+        }
+    });
+    let value = serde_json::json!({ "kind": "chore", "items": [1, 0, "x"], "extra": true });
+    let errors = schema::validate(&schema, &value).unwrap_err();
+    assert!(errors.contains(&"$: missing required property \"name\"".to_string()));
+    assert!(errors.contains(&"$.kind: must be one of [\"bug\",\"feature\"]".to_string()));
+    assert!(errors.contains(&"$.items[1]: must be >= 1".to_string()));
+    assert!(errors.contains(&"$.items[2]: expected integer, got string".to_string()));
+    assert!(errors.contains(&"$.extra: unexpected property".to_string()));
+    assert_eq!(errors.len(), 5);
+}
+
+#[test]
+fn schema_any_of_and_type_lists() {
+    let schema = serde_json::json!({
+        "anyOf": [{ "type": "string" }, { "type": ["integer", "null"] }]
+    });
+    assert!(schema::validate(&schema, &serde_json::json!("x")).is_ok());
+    assert!(schema::validate(&schema, &serde_json::json!(null)).is_ok());
+    assert!(schema::validate(&schema, &serde_json::json!(3)).is_ok());
+    let errors = schema::validate(&schema, &serde_json::json!(1.5)).unwrap_err();
+    assert_eq!(errors, vec!["$: does not match any allowed schema"]);
+}
+
+#[test]
+fn schema_ignores_unknown_keywords() {
+    let schema = serde_json::json!({ "type": "string", "format": "email", "pattern": "^x" });
+    assert!(schema::validate(&schema, &serde_json::json!("anything")).is_ok());
+}
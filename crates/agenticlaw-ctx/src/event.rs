@@ -0,0 +1,147 @@
+//! Typed .ctx events — one per header, turn or marker in the file
+
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// The `--- session: <id> ---` block that opens every file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub id: String,
+    /// `format:` field; 0 for files written before it existed.
+    pub version: u32,
+    pub started: String,
+    pub cwd: Option<String>,
+    /// Set when the session was forked from another one.
+    pub forked_from: Option<ForkPoint>,
+}
+
+/// Where a forked session branched off: its parent and the last user turn
+/// it kept (0 for none).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForkPoint {
+    pub session: String,
+    pub turn: usize,
+}
+
+impl Header {
+    /// Header for a new file in the current format.
+    pub fn new(id: &str, started: &str, cwd: Option<&str>) -> Self {
+        Self {
+            id: id.to_string(),
+            version: crate::FORMAT_VERSION,
+            started: started.to_string(),
+            cwd: cwd.map(String::from),
+            forked_from: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Header(Header),
+    /// Preloaded context files, the source of the system prompt.
+    Context {
+        timestamp: String,
+        text: String,
+    },
+    User {
+        timestamp: String,
+        text: String,
+        attachments: Vec<Attachment>,
+    },
+    Assistant {
+        timestamp: String,
+        blocks: Vec<Block>,
+        usage: Option<TokenUsage>,
+    },
+    ToolResult {
+        timestamp: String,
+        tool_use_id: String,
+        content: String,
+        attachments: Vec<Attachment>,
+        is_error: bool,
+    },
+    ModelChange {
+        timestamp: String,
+        model: String,
+        provider: String,
+    },
+    ThinkingLevel {
+        timestamp: String,
+        level: String,
+    },
+    /// Summary standing in for the turns before it, except the last `kept`
+    /// user, assistant and tool result turns, which stay as they are.
+    Compaction {
+        timestamp: String,
+        summary: String,
+        kept: usize,
+    },
+    /// The workspace was rolled back; informational only.
+    Rewind {
+        timestamp: String,
+        note: String,
+    },
+}
+
+impl Event {
+    /// When the event was written; a header's start time.
+    pub fn timestamp(&self) -> &str {
+        match self {
+            Event::Header(header) => &header.started,
+            Event::Context { timestamp, .. }
+            | Event::User { timestamp, .. }
+            | Event::Assistant { timestamp, .. }
+            | Event::ToolResult { timestamp, .. }
+            | Event::ModelChange { timestamp, .. }
+            | Event::ThinkingLevel { timestamp, .. }
+            | Event::Compaction { timestamp, .. }
+            | Event::Rewind { timestamp, .. } => timestamp,
+        }
+    }
+}
+
+/// One piece of an assistant turn, in the order the model produced it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Block {
+    Thinking {
+        text: String,
+        signature: Option<String>,
+    },
+    RedactedThinking {
+        data: String,
+    },
+    Text(String),
+    ToolCall {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    /// Version 0 `[tool:name] summary` line: no id, arguments only as a
+    /// summary, and the result (possibly truncated) inline.
+    LegacyTool {
+        name: String,
+        summary: String,
+        result: Option<String>,
+        is_error: bool,
+    },
+}
+
+/// An `[attachment:<type>] <path>` line: media stored beside the .ctx file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attachment {
+    pub media_type: String,
+    pub path: PathBuf,
+}
+
+/// A `[tokens: ...]` line closing an assistant turn.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TokenUsage {
+    pub input: u64,
+    pub output: u64,
+    pub cache_read: u64,
+    pub cache_write: u64,
+    pub total: u64,
+    /// Model that served the request; absent in version 0 files.
+    pub model: Option<String>,
+}
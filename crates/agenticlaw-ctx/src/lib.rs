@@ -0,0 +1,80 @@
+//! Agenticlaw .ctx — the session file format
+//!
+//! A .ctx file is the source of truth for an agent session: plain text that
+//! people, agents and tools can all read. This crate is the one definition of
+//! it — a typed event model (`Event`), a writer (`render`, `append`) and a
+//! streaming parser (`StreamParser`, `parse`) — shared by the runtime, the
+//! consciousness watcher and `agenticlaw-fmt`.
+//!
+//! # Format
+//!
+//! ```text
+//! --- session: <id> ---
+//! format: 1
+//! started: <ISO 8601>
+//! cwd: <path>
+//! forked-from: <parent session id> turn <n>
+//!
+//! --- <timestamp> [context] ---
+//! [concatenated SOUL.md/AGENTS.md/IDENTITY.md/... content]
+//!
+//! --- <timestamp> [model: claude-opus-4-6 (anthropic)] ---
+//!
+//! --- <timestamp> ---
+//! <up>
+//! User message here.
+//! [attachment:image/png] /path/to/<session>.attachments/<id>.png
+//! </up>
+//!
+//! --- <timestamp> ---
+//! <thinking signature="...">
+//! Model reasoning, when extended thinking is on.
+//! </thinking>
+//! Assistant response here.
+//! [tool:read id=toolu_01] {"file_path":"/path/to/file"}
+//! [tokens: 1200 in, 85 out, 9000 cached, 0 cache-write, 10285 total, claude-opus-4-6]
+//!
+//! --- <timestamp> ---
+//! <up>
+//! [result id=toolu_01]
+//! Tool result content, verbatim; `[result id=... error]` for failures.
+//! </up>
+//!
+//! --- <timestamp> [compaction: kept 4] ---
+//! Summary of the turns it replaces: every turn before it but the last 4.
+//!
+//! --- <timestamp> ---
+//! [rewind] before turn 3: restored src/lib.rs; removed notes.md
+//! ```
+//!
+//! `--- ... ---` lines start every turn; an optional `[annotation]` before
+//! the closing dashes marks the non-conversational ones. `<up>` wraps input
+//! to the model from outside (user messages, tool results); everything else
+//! is model output. Inside `<up>`, a line reading `</up>` (after any leading
+//! backslashes) is written with one more backslash. Outside it, so is any
+//! line of text that would otherwise read as a separator, an opening `<up>`
+//! or `<thinking>` tag, a tool call, a usage line or a rewind note; inside
+//! a thinking block, also a line reading `</thinking>`. Thinking text is
+//! otherwise kept verbatim, since its signature covers every byte.
+//!
+//! `forked-from:` appears only in forks: the session's turns up to user turn
+//! `n` of the parent are copied, then it carries on independently.
+//!
+//! # Versions
+//!
+//! `format: 1` in the header is the current version. Files without it are
+//! version 0: the parser also reads what older writers produced there — a
+//! first turn stamped with the session's start time is the preloaded
+//! context, `[thinking] ...` lines are reasoning, and `[tool:name] summary`
+//! lines with `  → ` result lines are tool calls.
+
+mod event;
+mod parse;
+mod write;
+
+pub use event::{Attachment, Block, Event, ForkPoint, Header, TokenUsage};
+pub use parse::{parse, ParseError, Parsed, StreamParser};
+pub use write::{append, render, render_event};
+
+/// Version written in the `format:` header field.
+pub const FORMAT_VERSION: u32 = 1;
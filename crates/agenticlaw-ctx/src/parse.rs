@@ -0,0 +1,603 @@
+//! Parser — .ctx text to events, incrementally
+//!
+//! `StreamParser` takes the file in chunks of any size (a watcher's deltas)
+//! and hands back each event once it is complete. `<up>` blocks complete at
+//! `</up>`; other turns only at the next `---` line or the end of input,
+//! since model output may contain blank lines. Feeding a file in pieces
+//! yields exactly the events of parsing it whole.
+
+use crate::event::{Attachment, Block, Event, ForkPoint, Header, TokenUsage};
+use crate::write::{needs_escape, needs_text_escape, needs_thinking_escape, REWIND_TAG};
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub events: Vec<Event>,
+    pub errors: Vec<ParseError>,
+}
+
+/// Parse a whole file.
+pub fn parse(input: &str) -> Parsed {
+    let mut parser = StreamParser::new();
+    let mut events = parser.feed(input);
+    events.extend(parser.finish());
+    Parsed {
+        events,
+        errors: parser.take_errors(),
+    }
+}
+
+enum State {
+    Idle,
+    Header(Header),
+    /// Just past a separator; the next line says whether it opens `<up>`.
+    Turn {
+        timestamp: String,
+        annotation: Option<String>,
+    },
+    Up {
+        timestamp: String,
+        lines: Vec<String>,
+    },
+    Bare {
+        timestamp: String,
+        annotation: Option<String>,
+        lines: Vec<String>,
+    },
+}
+
+pub struct StreamParser {
+    state: State,
+    /// Input after the last newline.
+    partial: String,
+    line_no: usize,
+    version: u32,
+    started: String,
+    seen_turn: bool,
+    errors: Vec<ParseError>,
+}
+
+impl Default for StreamParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        Self {
+            state: State::Idle,
+            partial: String::new(),
+            line_no: 0,
+            version: 0,
+            started: String::new(),
+            seen_turn: false,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Format version from the header read so far (0 until then).
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Parse the next chunk, returning the events it completed.
+    pub fn feed(&mut self, chunk: &str) -> Vec<Event> {
+        let mut out = Vec::new();
+        self.partial.push_str(chunk);
+        while let Some(end) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=end).collect();
+            let line = line.trim_end_matches('\n');
+            self.line(line.strip_suffix('\r').unwrap_or(line), &mut out);
+        }
+        out
+    }
+
+    /// End the open turn here, for a caller that knows a write is whole
+    /// (the runtime appends one complete event at a time). Anything
+    /// written into the same turn afterwards is reported as an error.
+    pub fn flush(&mut self) -> Vec<Event> {
+        let mut out = Vec::new();
+        self.close(&mut out);
+        out
+    }
+
+    /// End of input: parse an unterminated last line and close the open turn.
+    pub fn finish(&mut self) -> Vec<Event> {
+        let mut out = Vec::new();
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.line(line.strip_suffix('\r').unwrap_or(&line), &mut out);
+        }
+        if let State::Up { .. } = self.state {
+            self.error("unterminated <up> block".to_string());
+        }
+        self.close(&mut out);
+        out
+    }
+
+    /// Problems found so far; parsing always continues past them.
+    pub fn take_errors(&mut self) -> Vec<ParseError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn line(&mut self, line: &str, out: &mut Vec<Event>) {
+        self.line_no += 1;
+        match &mut self.state {
+            State::Up { lines, .. } => {
+                if line == "</up>" {
+                    self.close(out);
+                } else {
+                    lines.push(line.to_string());
+                }
+                return;
+            }
+            State::Header(header) => {
+                if line.is_empty() {
+                    self.close(out);
+                    return;
+                }
+                if !is_separator(line) {
+                    if let Some(started) = line.strip_prefix("started: ") {
+                        header.started = started.to_string();
+                    } else if let Some(cwd) = line.strip_prefix("cwd: ") {
+                        header.cwd = Some(cwd.to_string());
+                    } else if let Some(fork) = line.strip_prefix("forked-from: ") {
+                        match parse_fork_point(fork) {
+                            Some(fork) => header.forked_from = Some(fork),
+                            None => {
+                                let message = format!("Invalid fork point: {}", fork);
+                                self.error(message);
+                            }
+                        }
+                    } else if let Some(version) = line.strip_prefix("format: ") {
+                        match version.parse() {
+                            Ok(v) => header.version = v,
+                            Err(_) => {
+                                let message = format!("Invalid format version: {}", version);
+                                self.error(message);
+                            }
+                        }
+                    }
+                    // Unknown fields are for newer readers
+                    return;
+                }
+            }
+            State::Turn {
+                timestamp,
+                annotation,
+            } => {
+                let timestamp = std::mem::take(timestamp);
+                if line == "<up>" && annotation.is_none() {
+                    self.state = State::Up {
+                        timestamp,
+                        lines: Vec::new(),
+                    };
+                    return;
+                }
+                let separator = is_separator(line);
+                self.state = State::Bare {
+                    timestamp,
+                    annotation: annotation.take(),
+                    lines: if separator {
+                        Vec::new()
+                    } else {
+                        vec![line.to_string()]
+                    },
+                };
+                if !separator {
+                    return;
+                }
+            }
+            State::Bare { lines, .. } => {
+                if !is_separator(line) {
+                    lines.push(line.to_string());
+                    return;
+                }
+            }
+            State::Idle => {}
+        }
+
+        // A separator, or a line outside any turn
+        if let State::Bare { .. } | State::Header(_) = self.state {
+            self.close(out);
+        }
+        if let Some(id) = line
+            .strip_prefix("--- session: ")
+            .and_then(|s| s.strip_suffix(" ---"))
+        {
+            self.state = State::Header(Header {
+                id: id.to_string(),
+                version: 0,
+                started: String::new(),
+                cwd: None,
+                forked_from: None,
+            });
+        } else if is_separator(line) {
+            let (timestamp, annotation) = split_separator(line);
+            self.state = State::Turn {
+                timestamp: timestamp.to_string(),
+                annotation: annotation.map(String::from),
+            };
+        } else if !line.is_empty() {
+            self.error(format!("Unexpected content outside turn: {}", line));
+        }
+    }
+
+    /// Complete whatever is open and return to idle.
+    fn close(&mut self, out: &mut Vec<Event>) {
+        match std::mem::replace(&mut self.state, State::Idle) {
+            State::Idle => {}
+            State::Header(header) => {
+                if header.version > crate::FORMAT_VERSION {
+                    self.error(format!(
+                        "format {} is newer than this reader (up to {}); reading it as {}",
+                        header.version,
+                        crate::FORMAT_VERSION,
+                        crate::FORMAT_VERSION
+                    ));
+                }
+                self.version = header.version;
+                self.started = header.started.clone();
+                out.push(Event::Header(header));
+            }
+            State::Up { timestamp, lines } => {
+                self.seen_turn = true;
+                out.push(up_event(timestamp, lines));
+            }
+            State::Turn {
+                timestamp,
+                annotation,
+            } => self.bare(timestamp, annotation, Vec::new(), out),
+            State::Bare {
+                timestamp,
+                annotation,
+                lines,
+            } => self.bare(timestamp, annotation, lines, out),
+        }
+    }
+
+    fn bare(
+        &mut self,
+        timestamp: String,
+        annotation: Option<String>,
+        mut lines: Vec<String>,
+        out: &mut Vec<Event>,
+    ) {
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        if let Some(annotation) = annotation {
+            let text = if self.version >= 1 {
+                lines
+                    .iter()
+                    .map(|l| unescape_text(l))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            } else {
+                lines.join("\n")
+            };
+            let event = match annotation.as_str() {
+                "context" => Event::Context { timestamp, text },
+                "compaction" => Event::Compaction {
+                    timestamp,
+                    summary: text,
+                    kept: 0,
+                },
+                a => {
+                    if let Some(kept) = a
+                        .strip_prefix("compaction: kept ")
+                        .and_then(|n| n.parse().ok())
+                    {
+                        Event::Compaction {
+                            timestamp,
+                            summary: text,
+                            kept,
+                        }
+                    } else if let Some((model, provider)) =
+                        a.strip_prefix("model: ").and_then(parse_model)
+                    {
+                        Event::ModelChange {
+                            timestamp,
+                            model,
+                            provider,
+                        }
+                    } else if let Some(level) = a.strip_prefix("thinking-level: ") {
+                        Event::ThinkingLevel {
+                            timestamp,
+                            level: level.to_string(),
+                        }
+                    } else {
+                        self.error(format!("Unknown annotation: [{}]", a));
+                        return;
+                    }
+                }
+            };
+            out.push(event);
+            return;
+        }
+
+        let first_turn = !std::mem::replace(&mut self.seen_turn, true);
+        if lines.is_empty() {
+            return;
+        }
+        let text = lines.join("\n");
+        if let Some(note) = text.strip_prefix(REWIND_TAG) {
+            out.push(Event::Rewind {
+                timestamp,
+                note: note.trim_start().to_string(),
+            });
+        } else if self.version == 0 && first_turn && timestamp == self.started {
+            out.push(Event::Context { timestamp, text });
+        } else {
+            let (blocks, usage) = parse_blocks(&lines, self.version);
+            if !blocks.is_empty() || usage.is_some() {
+                out.push(Event::Assistant {
+                    timestamp,
+                    blocks,
+                    usage,
+                });
+            }
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        self.errors.push(ParseError {
+            line: self.line_no,
+            message,
+        });
+    }
+}
+
+pub(crate) fn is_separator(line: &str) -> bool {
+    line.len() > 8 && line.starts_with("--- ") && line.ends_with(" ---")
+}
+
+/// `--- <ts> [annotation] ---` into its timestamp and annotation.
+fn split_separator(line: &str) -> (&str, Option<&str>) {
+    let inner = &line[4..line.len() - 4];
+    match inner.strip_suffix(']').and_then(|s| s.split_once(" [")) {
+        Some((timestamp, annotation)) => (timestamp, Some(annotation)),
+        None => (inner, None),
+    }
+}
+
+/// `claude-opus-4-6 (anthropic)` into model and provider.
+fn parse_model(s: &str) -> Option<(String, String)> {
+    let (model, provider) = s.strip_suffix(')')?.rsplit_once(" (")?;
+    Some((model.to_string(), provider.to_string()))
+}
+
+/// `<session id> turn <n>` into a fork point.
+fn parse_fork_point(s: &str) -> Option<ForkPoint> {
+    let (session, turn) = s.rsplit_once(" turn ")?;
+    Some(ForkPoint {
+        session: session.to_string(),
+        turn: turn.parse().ok()?,
+    })
+}
+
+/// A user message or, when headed by `[result id=...]`, a tool result.
+fn up_event(timestamp: String, lines: Vec<String>) -> Event {
+    let result = lines.first().and_then(|l| parse_result_header(l));
+    let body = if result.is_some() {
+        &lines[1..]
+    } else {
+        &lines[..]
+    };
+    let mut body: Vec<&str> = body.iter().map(String::as_str).collect();
+    let mut attachments = Vec::new();
+    while let Some(a) = body.last().and_then(|l| parse_attachment(l)) {
+        attachments.insert(0, a);
+        body.pop();
+    }
+    let text = body
+        .iter()
+        .enumerate()
+        .map(|(i, l)| unescape(l, result.is_none() && i == 0))
+        .collect::<Vec<_>>()
+        .join("\n");
+    match result {
+        Some((tool_use_id, is_error)) => Event::ToolResult {
+            timestamp,
+            tool_use_id,
+            content: text,
+            attachments,
+            is_error,
+        },
+        None => Event::User {
+            timestamp,
+            text,
+            attachments,
+        },
+    }
+}
+
+fn unescape(line: &str, first_user_line: bool) -> &str {
+    match line.strip_prefix('\\') {
+        Some(rest) if needs_escape(rest.trim_start_matches('\\'), first_user_line) => rest,
+        _ => line,
+    }
+}
+
+/// A line of text outside `<up>` as written, before escaping.
+fn unescape_text(line: &str) -> &str {
+    match line.strip_prefix('\\') {
+        Some(rest) if needs_text_escape(rest.trim_start_matches('\\')) => rest,
+        _ => line,
+    }
+}
+
+/// A thinking line as the model produced it, before escaping.
+fn unescape_thinking(line: &str) -> &str {
+    match line.strip_prefix('\\') {
+        Some(rest) if needs_thinking_escape(rest.trim_start_matches('\\')) => rest,
+        _ => line,
+    }
+}
+
+/// `[result id=<id>]` or `[result id=<id> error]`.
+pub(crate) fn parse_result_header(line: &str) -> Option<(String, bool)> {
+    let body = line.strip_prefix("[result id=")?.strip_suffix(']')?;
+    Some(match body.strip_suffix(" error") {
+        Some(id) => (id.to_string(), true),
+        None => (body.to_string(), false),
+    })
+}
+
+/// `[attachment:<type>] <path>`.
+pub(crate) fn parse_attachment(line: &str) -> Option<Attachment> {
+    let (media_type, path) = line.strip_prefix("[attachment:")?.split_once("] ")?;
+    Some(Attachment {
+        media_type: media_type.to_string(),
+        path: PathBuf::from(path),
+    })
+}
+
+/// The blocks of an assistant turn, plus its usage line.
+fn parse_blocks(lines: &[String], version: u32) -> (Vec<Block>, Option<TokenUsage>) {
+    let mut blocks = Vec::new();
+    let mut usage = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].as_str();
+        i += 1;
+        if is_thinking_tag(line) {
+            let start = i;
+            while i < lines.len() && lines[i] != "</thinking>" {
+                i += 1;
+            }
+            let text = lines[start..i]
+                .iter()
+                .map(|l| {
+                    if version >= 1 {
+                        unescape_thinking(l)
+                    } else {
+                        l.as_str()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            i += 1;
+            blocks.push(match attribute(line, "redacted") {
+                Some(data) => Block::RedactedThinking { data },
+                None => Block::Thinking {
+                    text,
+                    signature: attribute(line, "signature"),
+                },
+            });
+        } else if let Some(u) = parse_usage(line) {
+            usage = Some(u);
+        } else if let Some(call) = parse_tool_call(line) {
+            blocks.push(call);
+        } else if let Some(text) = line.strip_prefix("[thinking] ").filter(|_| version == 0) {
+            blocks.push(Block::Thinking {
+                text: text.to_string(),
+                signature: None,
+            });
+        } else if let Some((name, summary)) = parse_legacy_tool(line).filter(|_| version == 0) {
+            let mut result: Vec<&str> = Vec::new();
+            let mut is_error = false;
+            while i < lines.len() {
+                if let Some(l) = lines[i].strip_prefix("  → ") {
+                    match l.strip_prefix("error: ").filter(|_| result.is_empty()) {
+                        Some(l) => {
+                            is_error = true;
+                            result.push(l);
+                        }
+                        None => result.push(l),
+                    }
+                } else if !(lines[i].starts_with("  ... (")
+                    && lines[i].ends_with(" lines omitted)"))
+                {
+                    break;
+                }
+                i += 1;
+            }
+            blocks.push(Block::LegacyTool {
+                name,
+                summary,
+                result: (!result.is_empty()).then(|| result.join("\n")),
+                is_error,
+            });
+        } else {
+            let line = if version >= 1 {
+                unescape_text(line)
+            } else {
+                line
+            };
+            match blocks.last_mut() {
+                Some(Block::Text(text)) => {
+                    text.push('\n');
+                    text.push_str(line);
+                }
+                _ => blocks.push(Block::Text(line.to_string())),
+            }
+        }
+    }
+    blocks.retain_mut(|b| match b {
+        Block::Text(text) => {
+            *text = text.trim_matches('\n').to_string();
+            !text.is_empty()
+        }
+        _ => true,
+    });
+    (blocks, usage)
+}
+
+/// `<thinking>` or `<thinking ...>`, opening a thinking block.
+pub(crate) fn is_thinking_tag(line: &str) -> bool {
+    (line == "<thinking>" || line.starts_with("<thinking ")) && line.ends_with('>')
+}
+
+/// `name="value"` from a `<thinking ...>` tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(tag[start..start + len].to_string())
+}
+
+/// `[tool:<name> id=<id>] <json>`.
+pub(crate) fn parse_tool_call(line: &str) -> Option<Block> {
+    let (head, input) = line.strip_prefix("[tool:")?.split_once("] ")?;
+    let (name, id) = head.split_once(" id=")?;
+    Some(Block::ToolCall {
+        id: id.to_string(),
+        name: name.to_string(),
+        input: serde_json::from_str(input).ok()?,
+    })
+}
+
+/// Version 0 `[tool:<name>] <summary>`.
+fn parse_legacy_tool(line: &str) -> Option<(String, String)> {
+    let (name, summary) = line.strip_prefix("[tool:")?.split_once(']')?;
+    if name.is_empty() || name.contains(' ') {
+        return None;
+    }
+    Some((name.to_string(), summary.trim().to_string()))
+}
+
+/// `[tokens: 1200 in, 85 out, 9000 cached, 0 cache-write, 10285 total, model]`;
+/// version 0 lines may lack the cache-write count and the model.
+pub(crate) fn parse_usage(line: &str) -> Option<TokenUsage> {
+    let body = line.strip_prefix("[tokens: ")?.strip_suffix(']')?;
+    let mut usage = TokenUsage::default();
+    for field in body.split(", ") {
+        match field.split_once(' ') {
+            Some((n, "in")) => usage.input = n.parse().ok()?,
+            Some((n, "out")) => usage.output = n.parse().ok()?,
+            Some((n, "cached")) => usage.cache_read = n.parse().ok()?,
+            Some((n, "cache-write")) => usage.cache_write = n.parse().ok()?,
+            Some((n, "total")) => usage.total = n.parse().ok()?,
+            _ => usage.model = Some(field.to_string()),
+        }
+    }
+    Some(usage)
+}
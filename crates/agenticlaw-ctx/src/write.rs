@@ -0,0 +1,246 @@
+//! Writer — events to .ctx text
+
+use crate::event::{Attachment, Block, Event, TokenUsage};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// Render a whole file.
+pub fn render(events: &[Event]) -> String {
+    events.iter().map(render_event).collect()
+}
+
+/// Render one event, blank line included, ready to append to a file.
+pub fn render_event(event: &Event) -> String {
+    let mut out = String::new();
+    match event {
+        Event::Header(header) => {
+            out.push_str(&format!("--- session: {} ---\n", header.id));
+            if header.version > 0 {
+                out.push_str(&format!("format: {}\n", header.version));
+            }
+            out.push_str(&format!("started: {}\n", header.started));
+            if let Some(cwd) = &header.cwd {
+                out.push_str(&format!("cwd: {}\n", cwd));
+            }
+            if let Some(fork) = &header.forked_from {
+                out.push_str(&format!(
+                    "forked-from: {} turn {}\n",
+                    fork.session, fork.turn
+                ));
+            }
+            out.push('\n');
+        }
+        Event::Context { timestamp, text } => {
+            out.push_str(&format!("--- {} [context] ---\n", timestamp));
+            push_text(&mut out, text);
+            out.push('\n');
+        }
+        Event::User {
+            timestamp,
+            text,
+            attachments,
+        } => {
+            out.push_str(&format!("--- {} ---\n<up>\n", timestamp));
+            push_up_body(&mut out, text, true);
+            push_attachments(&mut out, attachments);
+            out.push_str("</up>\n\n");
+        }
+        Event::Assistant {
+            timestamp,
+            blocks,
+            usage,
+        } => {
+            out.push_str(&format!("--- {} ---\n", timestamp));
+            for block in blocks {
+                push_block(&mut out, block);
+            }
+            if let Some(usage) = usage {
+                out.push_str(&format_usage(usage));
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+        Event::ToolResult {
+            timestamp,
+            tool_use_id,
+            content,
+            attachments,
+            is_error,
+        } => {
+            let flag = if *is_error { " error" } else { "" };
+            out.push_str(&format!(
+                "--- {} ---\n<up>\n[result id={}{}]\n",
+                timestamp, tool_use_id, flag
+            ));
+            push_up_body(&mut out, content, false);
+            push_attachments(&mut out, attachments);
+            out.push_str("</up>\n\n");
+        }
+        Event::ModelChange {
+            timestamp,
+            model,
+            provider,
+        } => {
+            out.push_str(&format!(
+                "--- {} [model: {} ({})] ---\n\n",
+                timestamp, model, provider
+            ));
+        }
+        Event::ThinkingLevel { timestamp, level } => {
+            out.push_str(&format!(
+                "--- {} [thinking-level: {}] ---\n\n",
+                timestamp, level
+            ));
+        }
+        Event::Compaction {
+            timestamp,
+            summary,
+            kept,
+        } => {
+            let annotation = match kept {
+                0 => "compaction".to_string(),
+                n => format!("compaction: kept {}", n),
+            };
+            out.push_str(&format!("--- {} [{}] ---\n", timestamp, annotation));
+            push_text(&mut out, summary);
+            out.push('\n');
+        }
+        Event::Rewind { timestamp, note } => {
+            out.push_str(&format!(
+                "--- {} ---\n{} {}\n\n",
+                timestamp, REWIND_TAG, note
+            ));
+        }
+    }
+    out
+}
+
+/// Append one event to the file at `path`, which must exist.
+pub fn append(path: &Path, event: &Event) -> std::io::Result<()> {
+    let mut f = OpenOptions::new().append(true).open(path)?;
+    f.write_all(render_event(event).as_bytes())
+}
+
+pub(crate) const REWIND_TAG: &str = "[rewind]";
+
+fn push_block(out: &mut String, block: &Block) {
+    match block {
+        Block::Thinking { text, signature } => {
+            match signature {
+                Some(signature) => {
+                    out.push_str(&format!("<thinking signature=\"{}\">\n", signature))
+                }
+                None => out.push_str("<thinking>\n"),
+            }
+            push_thinking(out, text);
+            out.push_str("</thinking>\n");
+        }
+        Block::RedactedThinking { data } => {
+            out.push_str(&format!("<thinking redacted=\"{}\">\n</thinking>\n", data));
+        }
+        Block::Text(text) => push_text(out, text),
+        Block::ToolCall { id, name, input } => {
+            out.push_str(&format!("[tool:{} id={}] {}\n", name, id, input));
+        }
+        Block::LegacyTool {
+            name,
+            summary,
+            result,
+            is_error,
+        } => {
+            out.push_str(&format!("[tool:{}] {}\n", name, summary));
+            if let Some(result) = result {
+                let prefix = if *is_error { "error: " } else { "" };
+                for (i, line) in result.split('\n').enumerate() {
+                    let prefix = if i == 0 { prefix } else { "" };
+                    out.push_str(&format!("  → {}{}\n", prefix, line));
+                }
+            }
+        }
+    }
+}
+
+fn format_usage(usage: &TokenUsage) -> String {
+    let mut line = format!(
+        "[tokens: {} in, {} out, {} cached, {} cache-write, {} total",
+        usage.input, usage.output, usage.cache_read, usage.cache_write, usage.total
+    );
+    if let Some(model) = &usage.model {
+        line.push_str(", ");
+        line.push_str(model);
+    }
+    line.push(']');
+    line
+}
+
+/// Lines of model output or context, escaped so none reads as structure.
+fn push_text(out: &mut String, text: &str) {
+    for line in text.split('\n') {
+        if needs_text_escape(line.trim_start_matches('\\')) {
+            out.push('\\');
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// Lines of a thinking block, verbatim since its signature covers them,
+/// escaped so none reads as structure or ends the block early.
+fn push_thinking(out: &mut String, text: &str) {
+    for line in text.split('\n') {
+        if needs_thinking_escape(line.trim_start_matches('\\')) {
+            out.push('\\');
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// Body lines of an `<up>` block, escaped so none can end it early.
+fn push_up_body(out: &mut String, text: &str, is_user: bool) {
+    for (i, line) in text.split('\n').enumerate() {
+        if needs_escape(line.trim_start_matches('\\'), is_user && i == 0) {
+            out.push('\\');
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+fn push_attachments(out: &mut String, attachments: &[Attachment]) {
+    for a in attachments {
+        out.push_str(&format!(
+            "[attachment:{}] {}\n",
+            a.media_type,
+            a.path.display()
+        ));
+    }
+}
+
+/// Whether a line of text outside `<up>`, stripped of leading backslashes,
+/// would be read as structure: a turn separator, an opening `<up>` or
+/// `<thinking>` tag, a tool call, a usage line or a rewind note.
+pub(crate) fn needs_text_escape(unescaped: &str) -> bool {
+    crate::parse::is_separator(unescaped)
+        || unescaped == "<up>"
+        || crate::parse::is_thinking_tag(unescaped)
+        || crate::parse::parse_tool_call(unescaped).is_some()
+        || crate::parse::parse_usage(unescaped).is_some()
+        || unescaped.starts_with(REWIND_TAG)
+}
+
+/// Whether a thinking line, stripped of leading backslashes, would be read
+/// as structure: anything `needs_text_escape` covers, or the closing tag.
+pub(crate) fn needs_thinking_escape(unescaped: &str) -> bool {
+    unescaped == "</thinking>" || needs_text_escape(unescaped)
+}
+
+/// Whether an `<up>` body line, stripped of leading backslashes, would be
+/// read as structure: the closing tag, an attachment reference, or a tool
+/// result header as the first line of a user message.
+pub(crate) fn needs_escape(unescaped: &str, first_user_line: bool) -> bool {
+    unescaped == "</up>"
+        || crate::parse::parse_attachment(unescaped).is_some()
+        || (first_user_line && crate::parse::parse_result_header(unescaped).is_some())
+}
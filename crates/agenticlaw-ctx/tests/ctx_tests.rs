@@ -0,0 +1,486 @@
+//! Conformance tests for the .ctx format: the files under `conformance/`
+//! pin what every reader and writer must agree on.
+
+use agenticlaw_ctx::*;
+use serde_json::json;
+use std::path::PathBuf;
+
+const SESSION_V1: &str = include_str!("conformance/session-v1.ctx");
+const RUNTIME_V0: &str = include_str!("conformance/runtime-v0.ctx");
+const FMT_V0: &str = include_str!("conformance/fmt-v0.ctx");
+const ESCAPES_V1: &str = include_str!("conformance/escapes-v1.ctx");
+
+fn parse_clean(input: &str) -> Vec<Event> {
+    let parsed = parse(input);
+    assert!(parsed.errors.is_empty(), "errors: {:?}", parsed.errors);
+    parsed.events
+}
+
+fn assistant(event: &Event) -> (&[Block], Option<&TokenUsage>) {
+    match event {
+        Event::Assistant { blocks, usage, .. } => (blocks, usage.as_ref()),
+        other => panic!("Expected an assistant turn, got {:?}", other),
+    }
+}
+
+// ===========================================================================
+// Version 1
+// ===========================================================================
+
+#[test]
+fn v1_fixture_parses_to_typed_events() {
+    let events = parse_clean(SESSION_V1);
+    assert_eq!(events.len(), 12);
+    assert_eq!(
+        events[0],
+        Event::Header(Header::new(
+            "conf-001",
+            "2026-03-01T09:00:00.000Z",
+            Some("/workspace")
+        ))
+    );
+    assert!(matches!(&events[1], Event::Context { text, .. }
+        if text == "You are an agent.\n\nAvailable agents:\n- researcher"));
+    assert!(
+        matches!(&events[2], Event::ModelChange { model, provider, .. }
+        if model == "claude-opus-4-6" && provider == "anthropic")
+    );
+    assert!(matches!(&events[3], Event::ThinkingLevel { level, .. } if level == "high"));
+    assert_eq!(
+        events[4],
+        Event::User {
+            timestamp: "2026-03-01T09:00:01.000Z".into(),
+            text: "Read the config, then tell me the port.\n\nThanks.".into(),
+            attachments: vec![Attachment {
+                media_type: "image/png".into(),
+                path: PathBuf::from("/workspace/.agenticlaw/sessions/conf-001.attachments/a1.png"),
+            }],
+        }
+    );
+
+    let (blocks, usage) = assistant(&events[5]);
+    assert_eq!(
+        blocks,
+        [
+            Block::Thinking {
+                text: "The user wants the port.\nRead the file first.".into(),
+                signature: Some("c2ln".into()),
+            },
+            Block::RedactedThinking {
+                data: "b3BhcXVl".into()
+            },
+            Block::Text("Let me look.".into()),
+            Block::ToolCall {
+                id: "toolu_01".into(),
+                name: "read".into(),
+                input: json!({"file_path": "/etc/app/config.toml"}),
+            },
+            Block::ToolCall {
+                id: "toolu_02".into(),
+                name: "bash".into(),
+                input: json!({"command": "grep -n port /etc/app/config.toml", "timeout": 5}),
+            },
+        ]
+    );
+    assert_eq!(
+        usage,
+        Some(&TokenUsage {
+            input: 1200,
+            output: 85,
+            cache_read: 9000,
+            cache_write: 0,
+            total: 10285,
+            model: Some("claude-opus-4-6".into()),
+        })
+    );
+
+    assert!(
+        matches!(&events[6], Event::ToolResult { tool_use_id, content, is_error: false, .. }
+        if tool_use_id == "toolu_01" && content == "[server]\nport = 8080\n")
+    );
+    assert!(
+        matches!(&events[10], Event::Compaction { summary, kept: 2, .. }
+        if summary == "The user asked for the port; it is 8080.")
+    );
+    assert!(matches!(&events[11], Event::Rewind { note, .. }
+        if note == "before turn 3: restored src/lib.rs; removed notes.md"));
+}
+
+#[test]
+fn v1_fixture_round_trips_byte_for_byte() {
+    assert_eq!(render(&parse_clean(SESSION_V1)), SESSION_V1);
+}
+
+#[test]
+fn escaped_lines_come_back_verbatim() {
+    let events = parse_clean(SESSION_V1);
+    let Event::ToolResult {
+        content,
+        attachments,
+        is_error,
+        ..
+    } = &events[7]
+    else {
+        panic!("Expected a tool result");
+    };
+    assert!(is_error);
+    assert_eq!(
+        content,
+        "grep: exit status 2\n</up>\n\\</up>\n[attachment:text/plain] not really an attachment"
+    );
+    assert_eq!(attachments.len(), 1);
+    assert!(matches!(&events[9], Event::User { text, .. }
+        if text.starts_with("[result id=toolu_99]\n")));
+}
+
+#[test]
+fn written_events_parse_back_equal() {
+    let events = vec![
+        Event::Header(Header::new("w1", "2026-03-01T09:00:00Z", None)),
+        Event::User {
+            timestamp: "2026-03-01T09:00:01Z".into(),
+            text: String::new(),
+            attachments: vec![Attachment {
+                media_type: "application/pdf".into(),
+                path: PathBuf::from("/tmp/w1.attachments/doc.pdf"),
+            }],
+        },
+        Event::Assistant {
+            timestamp: "2026-03-01T09:00:02Z".into(),
+            blocks: vec![Block::ToolCall {
+                id: "t1".into(),
+                name: "write".into(),
+                input: json!({"file_path": "/tmp/x", "content": "a\n</up>\n--- 1 ---"}),
+            }],
+            usage: None,
+        },
+        Event::Assistant {
+            timestamp: "2026-03-01T09:00:02Z".into(),
+            blocks: vec![Block::Text(
+                "<up>\n[tool:bash id=t9] {}\n<thinking>\n--- 2026-03-01T09:00:09Z ---\n\\[tokens: 1 in, 1 out, 0 cached, 0 cache-write, 2 total]".into(),
+            )],
+            usage: None,
+        },
+        Event::ToolResult {
+            timestamp: "2026-03-01T09:00:03Z".into(),
+            tool_use_id: "t1".into(),
+            content: String::new(),
+            attachments: Vec::new(),
+            is_error: false,
+        },
+        Event::ToolResult {
+            timestamp: "2026-03-01T09:00:03Z".into(),
+            tool_use_id: "t2".into(),
+            content: "\n\nblank lines around\n\n".into(),
+            attachments: Vec::new(),
+            is_error: true,
+        },
+    ];
+    assert_eq!(parse_clean(&render(&events)), events);
+}
+
+#[test]
+fn hostile_thinking_text_round_trips_verbatim() {
+    let thinking = [
+        "Plan:\n--- Step 1 ---\n</thinking>\nmore",
+        "\\</thinking>\n\\--- 2026-03-01T09:00:09Z ---\n<up>\n[tokens: 1 in, 1 out, 0 cached, 0 cache-write, 2 total]",
+        "trailing whitespace the signature covers  \n\n",
+        "",
+    ];
+    let mut events = vec![Event::Header(Header::new(
+        "w2",
+        "2026-03-01T09:00:00Z",
+        None,
+    ))];
+    events.extend(thinking.iter().map(|text| Event::Assistant {
+        timestamp: "2026-03-01T09:00:01Z".into(),
+        blocks: vec![
+            Block::Thinking {
+                text: text.to_string(),
+                signature: Some("c2ln".into()),
+            },
+            Block::Text("after".into()),
+        ],
+        usage: None,
+    }));
+    assert_eq!(parse_clean(&render(&events)), events);
+}
+
+#[test]
+fn fork_point_round_trips_in_the_header() {
+    let mut header = Header::new("child", "2026-03-01T10:00:00Z", Some("/workspace"));
+    header.forked_from = Some(ForkPoint {
+        session: "conf-001".into(),
+        turn: 2,
+    });
+    let text = render(&[Event::Header(header.clone())]);
+    assert!(text.contains("\nforked-from: conf-001 turn 2\n"));
+    assert_eq!(parse_clean(&text), [Event::Header(header)]);
+}
+
+// ===========================================================================
+// Escaping outside <up>
+// ===========================================================================
+
+/// The text of a turn that is a single text block.
+fn text_of(event: &Event) -> &str {
+    match event {
+        Event::Assistant { blocks, usage, .. } => {
+            assert_eq!(*usage, None);
+            match blocks.as_slice() {
+                [Block::Text(text)] => text,
+                other => panic!("Expected one text block, got {:?}", other),
+            }
+        }
+        Event::Compaction { summary, .. } => summary,
+        Event::Context { text, .. } => text,
+        other => panic!("Expected a text turn, got {:?}", other),
+    }
+}
+
+#[test]
+fn escaped_up_tag_opening_a_reply_stays_model_output() {
+    let events = parse_clean(ESCAPES_V1);
+    assert_eq!(
+        text_of(&events[1]),
+        "<up>\nopens the reply, not a user message."
+    );
+}
+
+#[test]
+fn escaped_rewind_tag_is_not_a_rewind() {
+    let events = parse_clean(ESCAPES_V1);
+    assert_eq!(text_of(&events[2]), "[rewind] is how a rewind note starts.");
+}
+
+#[test]
+fn escaped_tool_call_line_is_not_a_call() {
+    let events = parse_clean(ESCAPES_V1);
+    assert_eq!(
+        text_of(&events[3]),
+        "A tool call, quoted:\n[tool:bash id=x] {\"command\":\"ls\"}"
+    );
+}
+
+#[test]
+fn escaped_thinking_tags_do_not_open_blocks() {
+    let events = parse_clean(ESCAPES_V1);
+    assert_eq!(
+        text_of(&events[4]),
+        "Thinking tags, quoted:\n<thinking>\n<thinking signature=\"c2ln\">\n</thinking>"
+    );
+}
+
+#[test]
+fn escaped_separators_do_not_start_turns() {
+    let events = parse_clean(ESCAPES_V1);
+    assert_eq!(
+        text_of(&events[5]),
+        "A turn separator, quoted:\n--- 2026-03-02T09:00:00.000Z ---\n--- session: other ---\nstill the same turn."
+    );
+    assert_eq!(
+        text_of(&events[8]),
+        "Summary quoting a separator:\n--- 2026-03-02T09:00:00.000Z ---"
+    );
+}
+
+#[test]
+fn escaped_usage_line_is_not_usage() {
+    let events = parse_clean(ESCAPES_V1);
+    assert_eq!(
+        text_of(&events[6]),
+        "A usage line, quoted:\n[tokens: 1 in, 2 out, 3 cached, 0 cache-write, 6 total]"
+    );
+    assert_eq!(
+        text_of(&events[9]),
+        "Context quoting a usage line:\n[tokens: 1 in, 2 out, 3 cached, 0 cache-write, 6 total]"
+    );
+}
+
+#[test]
+fn backslashes_in_model_output_come_back_verbatim() {
+    let events = parse_clean(ESCAPES_V1);
+    assert_eq!(events.len(), 10);
+    assert_eq!(
+        text_of(&events[7]),
+        "Backslashes the model wrote itself:\n\\[tool:read id=y] {}\n\\plain text keeps its backslash."
+    );
+}
+
+#[test]
+fn escapes_v1_fixture_round_trips_byte_for_byte() {
+    assert_eq!(render(&parse_clean(ESCAPES_V1)), ESCAPES_V1);
+}
+
+// ===========================================================================
+// Streaming
+// ===========================================================================
+
+#[test]
+fn any_two_chunks_parse_like_the_whole_file() {
+    for fixture in [SESSION_V1, ESCAPES_V1, RUNTIME_V0, FMT_V0] {
+        let whole = parse(fixture).events;
+        for split in (0..=fixture.len()).filter(|&i| fixture.is_char_boundary(i)) {
+            let mut parser = StreamParser::new();
+            let mut events = parser.feed(&fixture[..split]);
+            events.extend(parser.feed(&fixture[split..]));
+            events.extend(parser.finish());
+            assert_eq!(events, whole, "split at byte {}", split);
+        }
+    }
+}
+
+#[test]
+fn char_at_a_time_parses_like_the_whole_file() {
+    let mut parser = StreamParser::new();
+    let mut events = Vec::new();
+    for c in SESSION_V1.chars() {
+        events.extend(parser.feed(c.encode_utf8(&mut [0; 4])));
+    }
+    events.extend(parser.finish());
+    assert_eq!(events, parse_clean(SESSION_V1));
+    assert!(parser.take_errors().is_empty());
+}
+
+#[test]
+fn events_arrive_as_soon_as_they_are_complete() {
+    let mut parser = StreamParser::new();
+    let header = parser.feed("--- session: s ---\nformat: 1\nstarted: t0\n\n");
+    assert!(matches!(&header[..], [Event::Header(h)] if h.version == 1));
+    assert_eq!(parser.version(), 1);
+
+    // An <up> block is done at </up>
+    let user = parser.feed("--- t1 ---\n<up>\nhi\n</up>\n\n");
+    assert_eq!(user.len(), 1);
+
+    // A bare turn may continue until the next separator...
+    assert!(parser.feed("--- t2 ---\nHello\n\n").is_empty());
+    assert!(parser.feed("still the same turn\n\n").is_empty());
+    let closed = parser.feed("--- t3 ---\n");
+    let (blocks, _) = assistant(&closed[0]);
+    assert_eq!(blocks, [Block::Text("Hello\n\nstill the same turn".into())]);
+
+    // ...unless the caller knows the write was whole
+    assert!(parser.feed("Done.\n\n").is_empty());
+    assert_eq!(parser.flush().len(), 1);
+    assert!(parser.finish().is_empty());
+    assert!(parser.take_errors().is_empty());
+}
+
+// ===========================================================================
+// Version 0
+// ===========================================================================
+
+#[test]
+fn runtime_v0_preload_and_tool_lines() {
+    let events = parse_clean(RUNTIME_V0);
+    let Event::Header(header) = &events[0] else {
+        panic!("Expected a header");
+    };
+    assert_eq!(header.version, 0);
+    assert!(matches!(&events[1], Event::Context { text, .. } if text == "You are helpful."));
+
+    let (blocks, usage) = assistant(&events[3]);
+    assert_eq!(
+        blocks,
+        [
+            Block::Text("Let me read that file.".into()),
+            Block::LegacyTool {
+                name: "read".into(),
+                summary: "file_path=/tmp/foo.txt".into(),
+                result: None,
+                is_error: false,
+            },
+        ]
+    );
+    assert_eq!(usage.unwrap().model.as_deref(), Some("claude-sonnet-4-5"));
+    assert!(matches!(&events[4], Event::User { text, .. } if text == "[tool:result] hello world"));
+    let (blocks, _) = assistant(&events[5]);
+    assert!(matches!(&blocks[1], Block::ToolCall { id, .. } if id == "toolu_9"));
+    assert!(matches!(&events[6], Event::ToolResult { content, .. } if content == "foo.txt"));
+    assert!(matches!(&events[7], Event::Rewind { .. }));
+}
+
+#[test]
+fn fmt_v0_thinking_lines_inline_results_and_short_usage() {
+    let events = parse_clean(FMT_V0);
+    assert!(matches!(&events[1], Event::ModelChange { .. }));
+    // A first bare turn only counts as the preload at the start time
+    assert!(matches!(&events[2], Event::User { .. }));
+    let (blocks, usage) = assistant(&events[3]);
+    assert_eq!(
+        blocks,
+        [
+            Block::Thinking {
+                text: "The user wants the config.".into(),
+                signature: None,
+            },
+            Block::Text("I'll read it.".into()),
+            Block::LegacyTool {
+                name: "read".into(),
+                summary: "/etc/app/config.toml".into(),
+                result: Some("[server]\nport = 8080".into()),
+                is_error: false,
+            },
+            Block::LegacyTool {
+                name: "bash".into(),
+                summary: "cat /missing".into(),
+                result: Some("No such file\nlast line".into()),
+                is_error: true,
+            },
+        ]
+    );
+    let usage = usage.unwrap();
+    assert_eq!((usage.cache_read, usage.total), (200, 350));
+    assert_eq!(usage.model, None);
+    assert!(matches!(&events[4], Event::Compaction { .. }));
+}
+
+#[test]
+fn v0_forms_are_plain_text_in_v1() {
+    let ctx = "--- session: s ---\nformat: 1\nstarted: t0\n\n--- t0 ---\n[thinking] musing\n[tool:read] /tmp/a\n\n";
+    let events = parse_clean(ctx);
+    let (blocks, _) = assistant(&events[1]);
+    assert_eq!(
+        blocks,
+        [Block::Text("[thinking] musing\n[tool:read] /tmp/a".into())]
+    );
+}
+
+// ===========================================================================
+// Errors
+// ===========================================================================
+
+#[test]
+fn newer_format_is_reported_and_read_best_effort() {
+    let parsed = parse("--- session: s ---\nformat: 2\nstarted: t0\n\n--- t1 ---\nHi\n\n");
+    assert_eq!(parsed.errors.len(), 1);
+    assert!(parsed.errors[0].message.contains("format 2"));
+    assert_eq!(parsed.events.len(), 2);
+}
+
+#[test]
+fn stray_lines_and_unterminated_blocks_are_reported() {
+    let parsed = parse("stray\n--- t1 ---\n<up>\nnever closed\n");
+    assert_eq!(
+        parsed.errors,
+        [
+            ParseError {
+                line: 1,
+                message: "Unexpected content outside turn: stray".into(),
+            },
+            ParseError {
+                line: 4,
+                message: "unterminated <up> block".into(),
+            },
+        ]
+    );
+    assert!(matches!(&parsed.events[..], [Event::User { text, .. }] if text == "never closed"));
+}
+
+#[test]
+fn empty_input_has_no_events() {
+    let parsed = parse("");
+    assert!(parsed.events.is_empty());
+    assert!(parsed.errors.is_empty());
+}
@@ -8,4 +8,4 @@ pub mod tui;
 pub mod tui_client;
 pub mod ws;
 
-pub use server::{start_gateway, ExtendedConfig};
+pub use server::{provider_from_config, start_gateway, ExtendedConfig};
@@ -3,7 +3,9 @@
 //! `agenticlaw` is a bee. The CLI is a frontend to the systemd service.
 //! `agenticlaw chat` connects to the running service via WebSocket.
 //! `agenticlaw gateway` starts the daemon directly (used by systemd).
+//! `agenticlaw sessions search` searches the workspace's .ctx files.
 
+use agenticlaw_agent::search::{self, SearchQuery};
 use agenticlaw_core::{AuthConfig, AuthMode, BindMode, GatewayConfig};
 use agenticlaw_gateway::{start_gateway, ExtendedConfig};
 use clap::{Parser, Subcommand};
@@ -53,6 +55,11 @@ enum Commands {
         #[arg(short, long, default_value_t = DEFAULT_PORT)]
         port: u16,
     },
+    /// Work with the workspace's recorded sessions
+    Sessions {
+        #[command(subcommand)]
+        command: SessionsCommand,
+    },
     /// Check health of running gateway
     Status {
         #[arg(short, long, default_value_t = DEFAULT_PORT)]
@@ -71,6 +78,31 @@ enum Commands {
     Version,
 }
 
+#[derive(Subcommand)]
+enum SessionsCommand {
+    /// Full-text search over every .ctx file in the workspace
+    Search {
+        /// Words that must all appear in a turn
+        query: Vec<String>,
+        #[arg(short, long)]
+        workspace: Option<PathBuf>,
+        /// Only turns from this date (YYYY-MM-DD or RFC 3339) on
+        #[arg(long)]
+        since: Option<String>,
+        /// Only turns up to this date (YYYY-MM-DD or RFC 3339)
+        #[arg(long)]
+        until: Option<String>,
+        /// `up` (user messages and tool results) or `model`
+        #[arg(long)]
+        role: Option<String>,
+        /// Only turns calling this tool, or its results
+        #[arg(long)]
+        tool: Option<String>,
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
@@ -120,6 +152,7 @@ async fn main() -> anyhow::Result<()> {
                 anthropic_api_key: std::env::var("ANTHROPIC_API_KEY").ok(),
                 workspace_root,
                 system_prompt,
+                consciousness: false,
             };
             start_gateway(config).await?;
         }
@@ -170,6 +203,49 @@ async fn main() -> anyhow::Result<()> {
             agenticlaw_gateway::tui::run_tui(workspace, Some(session_name), model, resume).await?;
         }
 
+        Some(Commands::Sessions {
+            command:
+                SessionsCommand::Search {
+                    query,
+                    workspace,
+                    since,
+                    until,
+                    role,
+                    tool,
+                    limit,
+                },
+        }) => {
+            let workspace = workspace
+                .or_else(|| std::env::var("RUSTCLAW_WORKSPACE").ok().map(PathBuf::from))
+                .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+            let mut search = SearchQuery::new(&query.join(" "));
+            search.since = since
+                .map(|s| search::parse_time(&s, false))
+                .transpose()
+                .map_err(anyhow::Error::msg)?;
+            search.until = until
+                .map(|s| search::parse_time(&s, true))
+                .transpose()
+                .map_err(anyhow::Error::msg)?;
+            search.role = role
+                .map(|r| r.parse())
+                .transpose()
+                .map_err(anyhow::Error::msg)?;
+            search.tool = tool;
+            search.limit = limit;
+
+            let hits = search::search(&workspace, &search)?;
+            if hits.is_empty() {
+                eprintln!("No matches");
+            }
+            for hit in hits {
+                println!(
+                    "{}  {}  [{}]  {}",
+                    hit.timestamp, hit.session, hit.role, hit.snippet
+                );
+            }
+        }
+
         Some(Commands::Status { port }) => {
             match agenticlaw_gateway::service::check_health(port).await {
                 Ok(health) => {
@@ -3,9 +3,12 @@
 //! Each RPC method (chat.send, chat.history, sessions.list, etc.) is handled
 //! by a dedicated async function. The router maps method names to handlers.
 
+use agenticlaw_agent::search::{self, SearchQuery};
 use agenticlaw_agent::{AgentEvent, AgentRuntime, OutputEvent, SessionKey};
 use agenticlaw_core::{EventMessage, RpcResponse};
+use agenticlaw_llm::{extension_for_media_type, ContentBlock, MediaSource, ThinkingConfig};
 use serde_json::Value;
+use std::path::{Component, Path};
 use std::sync::Arc;
 use tokio::sync::{broadcast, mpsc};
 use tracing::info;
@@ -31,9 +34,15 @@ pub async fn route_rpc(method: &str, params: Value, ctx: &ConnectionContext) ->
         "chat.send" => handle_chat_send(params, ctx).await,
         "chat.history" => handle_chat_history(params, ctx).await,
         "chat.abort" => handle_chat_abort(params, ctx).await,
+        "chat.compact" => handle_chat_compact(params, ctx).await,
         "sessions.list" => handle_sessions_list(ctx).await,
         "sessions.usage" => handle_sessions_usage(params, ctx).await,
         "sessions.delete" => handle_sessions_delete(params, ctx).await,
+        "sessions.fork" => handle_sessions_fork(params, ctx).await,
+        "sessions.tree" => handle_sessions_tree(ctx).await,
+        "sessions.search" => handle_sessions_search(params, ctx).await,
+        "checkpoints.list" => handle_checkpoints_list(params, ctx).await,
+        "checkpoints.restore" => handle_checkpoints_restore(params, ctx).await,
         "health" => handle_health(ctx).await,
         "tools.list" => handle_tools_list(ctx).await,
         "echo" => Ok(params),
@@ -63,6 +72,8 @@ async fn handle_chat_send(params: Value, ctx: &ConnectionContext) -> RpcResult {
         .ok_or_else(|| (-32602, "Missing required param: message".to_string()))?
         .to_string();
     let model = params["model"].as_str().map(String::from);
+    let thinking = parse_thinking_param(&params["thinking"])?;
+    let attachments = parse_attachments_param(&params["attachments"], ctx.agent.workspace())?;
 
     let session_key = SessionKey::new(&session);
 
@@ -73,6 +84,14 @@ async fn handle_chat_send(params: Value, ctx: &ConnectionContext) -> RpcResult {
         }
     }
 
+    // Thinking sticks to the session until changed, including for its first message
+    if let Some(thinking) = thinking {
+        ctx.agent
+            .get_session(&session_key)
+            .set_thinking(thinking)
+            .await;
+    }
+
     info!(
         "chat.send: session={} message={}",
         session,
@@ -133,6 +152,24 @@ async fn handle_chat_send(params: Value, ctx: &ConnectionContext) -> RpcResult {
                         session: fwd_session.clone(),
                         token_count,
                     },
+                    AgentEvent::Compacted {
+                        summarized,
+                        kept,
+                        tokens_before,
+                        tokens_after,
+                    } => OutputEvent::Compacted {
+                        session: fwd_session.clone(),
+                        summarized,
+                        kept,
+                        tokens_before,
+                        tokens_after,
+                    },
+                    AgentEvent::Failover { from, to, reason } => OutputEvent::Failover {
+                        session: fwd_session.clone(),
+                        from,
+                        to,
+                        reason,
+                    },
                     AgentEvent::Done { .. } => OutputEvent::Done {
                         session: fwd_session.clone(),
                     },
@@ -153,7 +190,9 @@ async fn handle_chat_send(params: Value, ctx: &ConnectionContext) -> RpcResult {
             }
         });
 
-        let result = agent.run_turn(&sk, &message, event_tx).await;
+        let result = agent
+            .run_turn_with_attachments(&sk, &message, attachments, event_tx)
+            .await;
         let _ = forward_task.await;
 
         if let Err(e) = result {
@@ -168,6 +207,99 @@ async fn handle_chat_send(params: Value, ctx: &ConnectionContext) -> RpcResult {
     Ok(serde_json::json!({ "ok": true }))
 }
 
+/// `thinking` param of chat.send: a budget in tokens, `{"budget_tokens": n}`,
+/// or `false`/`0` to turn thinking off. `None` when absent (leave unchanged).
+fn parse_thinking_param(value: &Value) -> Result<Option<Option<ThinkingConfig>>, (i32, String)> {
+    let budget = match value {
+        Value::Null => return Ok(None),
+        Value::Bool(false) => 0,
+        Value::Number(n) => n.as_u64().unwrap_or(u64::MAX),
+        Value::Object(o) => o
+            .get("budget_tokens")
+            .and_then(Value::as_u64)
+            .unwrap_or(u64::MAX),
+        _ => u64::MAX,
+    };
+    match budget {
+        0 => Ok(Some(None)),
+        b if b >= ThinkingConfig::MIN_BUDGET_TOKENS as u64 && b <= u32::MAX as u64 => {
+            Ok(Some(Some(ThinkingConfig::new(b as u32))))
+        }
+        _ => Err((
+            -32602,
+            format!(
+                "Invalid param: thinking (budget of at least {} tokens, or false)",
+                ThinkingConfig::MIN_BUDGET_TOKENS
+            ),
+        )),
+    }
+}
+
+/// `attachments` param of chat.send: a list of `{"path": ...}` (relative to
+/// the workspace, which it may not leave) or `{"media_type": ...,
+/// "data": <base64>}` objects, each an image or PDF.
+fn parse_attachments_param(
+    value: &Value,
+    workspace: &Path,
+) -> Result<Vec<ContentBlock>, (i32, String)> {
+    let items = match value {
+        Value::Null => return Ok(Vec::new()),
+        Value::Array(items) => items,
+        _ => return Err((-32602, "Invalid param: attachments (expected array)".into())),
+    };
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let invalid = |why: &str| (-32602, format!("Invalid attachment {}: {}", i, why));
+            if let Some(path) = item["path"].as_str() {
+                let relative = Path::new(path);
+                let escapes = relative.is_absolute()
+                    || relative
+                        .components()
+                        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+                if escapes {
+                    return Err(invalid("path must be inside the workspace"));
+                }
+                let path = workspace.join(relative);
+                if !path.is_file() {
+                    return Err(invalid("file not found"));
+                }
+                // A symlink inside the workspace may still point out of it
+                let inside = match (path.canonicalize(), workspace.canonicalize()) {
+                    (Ok(real), Ok(root)) => real.starts_with(root),
+                    _ => false,
+                };
+                if !inside {
+                    return Err(invalid("path must be inside the workspace"));
+                }
+                return ContentBlock::from_path(path)
+                    .ok_or_else(|| invalid("unsupported file type"));
+            }
+            let (Some(media_type), Some(data)) =
+                (item["media_type"].as_str(), item["data"].as_str())
+            else {
+                return Err(invalid("expected path, or media_type and data"));
+            };
+            let source = MediaSource::Base64 {
+                media_type: media_type.to_string(),
+                data: data.to_string(),
+            };
+            if extension_for_media_type(media_type).is_none() {
+                return Err(invalid("unsupported media_type"));
+            }
+            Ok(if media_type.starts_with("image/") {
+                ContentBlock::Image { source }
+            } else {
+                ContentBlock::Document {
+                    source,
+                    title: item["title"].as_str().map(String::from),
+                }
+            })
+        })
+        .collect()
+}
+
 // ---------------------------------------------------------------------------
 // chat.history — get conversation history
 // ---------------------------------------------------------------------------
@@ -219,6 +351,7 @@ async fn handle_chat_abort(params: Value, ctx: &ConnectionContext) -> RpcResult
     let session_key = SessionKey::new(session);
     if let Some(sess) = ctx.agent.sessions().get(&session_key) {
         sess.abort().await;
+        ctx.agent.end_session(&session_key).await;
         info!("Aborted session: {}", session);
         Ok(serde_json::json!({ "ok": true }))
     } else {
@@ -226,6 +359,33 @@ async fn handle_chat_abort(params: Value, ctx: &ConnectionContext) -> RpcResult
     }
 }
 
+// ---------------------------------------------------------------------------
+// chat.compact — summarise the session's older turns
+// ---------------------------------------------------------------------------
+
+async fn handle_chat_compact(params: Value, ctx: &ConnectionContext) -> RpcResult {
+    let session = params["session"]
+        .as_str()
+        .ok_or_else(|| (-32602, "Missing required param: session".to_string()))?;
+
+    let session_key = SessionKey::new(session);
+    if ctx.agent.sessions().get(&session_key).is_none() {
+        return Err((-32001, format!("Session not found: {}", session)));
+    }
+    let report = ctx
+        .agent
+        .compact_session(&session_key)
+        .await
+        .map_err(|e| (-32002, e))?;
+    Ok(serde_json::json!({
+        "session": session,
+        "summarized": report.summarized,
+        "kept": report.kept,
+        "tokens_before": report.tokens_before,
+        "tokens_after": report.tokens_after,
+    }))
+}
+
 // ---------------------------------------------------------------------------
 // sessions.list — list all sessions
 // ---------------------------------------------------------------------------
@@ -260,12 +420,16 @@ async fn handle_sessions_usage(params: Value, ctx: &ConnectionContext) -> RpcRes
     let token_count = sess.token_count().await;
     let message_count = sess.message_count().await;
     let model = sess.model().await;
+    let usage = sess.usage().await;
 
     Ok(serde_json::json!({
         "session": session,
         "token_count": token_count,
         "message_count": message_count,
         "model": model,
+        "usage": usage,
+        "cost_usd": usage.cost_usd(),
+        "cache_hit_rate": usage.totals.cache_hit_rate(),
     }))
 }
 
@@ -280,7 +444,11 @@ async fn handle_sessions_delete(params: Value, ctx: &ConnectionContext) -> RpcRe
 
     let session_key = SessionKey::new(session);
     match ctx.agent.sessions().remove(&session_key) {
-        Some(_) => {
+        Some(removed) => {
+            ctx.agent.end_session(&session_key).await;
+            ctx.agent.forget_file_reads(&removed).await;
+            ctx.agent.forget_checkpoints(&session_key);
+            ctx.agent.forget_spilled_output(&session_key);
             info!("Deleted session: {}", session);
             Ok(serde_json::json!({ "ok": true }))
         }
@@ -288,6 +456,133 @@ async fn handle_sessions_delete(params: Value, ctx: &ConnectionContext) -> RpcRe
     }
 }
 
+// ---------------------------------------------------------------------------
+// sessions.fork — branch a session off after one of its user turns
+// ---------------------------------------------------------------------------
+
+async fn handle_sessions_fork(params: Value, ctx: &ConnectionContext) -> RpcResult {
+    let session = params["session"]
+        .as_str()
+        .ok_or_else(|| (-32602, "Missing required param: session".to_string()))?;
+    let turn = params["turn"]
+        .as_u64()
+        .ok_or_else(|| (-32602, "Missing required param: turn".to_string()))?;
+    let name = match params["name"].as_str() {
+        Some(name) => name.to_string(),
+        None => format!(
+            "{}-fork-{}",
+            session,
+            &uuid::Uuid::new_v4().to_string()[..8]
+        ),
+    };
+
+    let sessions = ctx.agent.sessions();
+    let parent = sessions
+        .get(&SessionKey::new(session))
+        .ok_or_else(|| (-32001, format!("Session not found: {}", session)))?;
+    let fork = sessions
+        .fork(&parent, turn as usize, &SessionKey::new(&name))
+        .await
+        .map_err(|e| (-32002, e))?;
+    Ok(serde_json::json!({
+        "session": name,
+        "parent": session,
+        "turn": turn,
+        "ctx_path": fork.ctx_path(),
+        "message_count": fork.message_count().await,
+    }))
+}
+
+// ---------------------------------------------------------------------------
+// sessions.tree — fork lineage of the workspace's .ctx files
+// ---------------------------------------------------------------------------
+
+async fn handle_sessions_tree(ctx: &ConnectionContext) -> RpcResult {
+    let tree = agenticlaw_agent::branch::session_tree(ctx.agent.workspace())
+        .map_err(|e| (-32002, format!("Failed to read sessions: {}", e)))?;
+    Ok(serde_json::json!({ "sessions": tree }))
+}
+
+// ---------------------------------------------------------------------------
+// sessions.search — word search over the workspace's .ctx files
+// ---------------------------------------------------------------------------
+
+async fn handle_sessions_search(params: Value, ctx: &ConnectionContext) -> RpcResult {
+    let query = params["query"]
+        .as_str()
+        .ok_or_else(|| (-32602, "Missing required param: query".to_string()))?;
+    let query = parse_search_params(query, &params).map_err(|e| (-32602, e))?;
+
+    let workspace = ctx.agent.workspace().to_path_buf();
+    let hits = tokio::task::spawn_blocking(move || search::search(&workspace, &query))
+        .await
+        .map_err(|e| (-32002, e.to_string()))?
+        .map_err(|e| (-32002, format!("Failed to search sessions: {}", e)))?;
+    Ok(serde_json::json!({ "results": hits }))
+}
+
+/// Optional filters of sessions.search: `since`/`until` (dates or RFC 3339
+/// timestamps), `role` (`up` or `model`), `tool` and `limit`.
+fn parse_search_params(text: &str, params: &Value) -> Result<SearchQuery, String> {
+    let mut query = SearchQuery::new(text);
+    if let Some(since) = params["since"].as_str() {
+        query.since = Some(search::parse_time(since, false)?);
+    }
+    if let Some(until) = params["until"].as_str() {
+        query.until = Some(search::parse_time(until, true)?);
+    }
+    if let Some(role) = params["role"].as_str() {
+        query.role = Some(role.parse()?);
+    }
+    query.tool = params["tool"].as_str().map(String::from);
+    if let Some(limit) = params["limit"].as_u64() {
+        query.limit = limit as usize;
+    }
+    Ok(query)
+}
+
+// ---------------------------------------------------------------------------
+// checkpoints.list — file snapshots taken before each turn's edits
+// ---------------------------------------------------------------------------
+
+async fn handle_checkpoints_list(params: Value, ctx: &ConnectionContext) -> RpcResult {
+    let session = params["session"]
+        .as_str()
+        .ok_or_else(|| (-32602, "Missing required param: session".to_string()))?;
+
+    let checkpoints = ctx.agent.checkpoints(&SessionKey::new(session));
+    Ok(serde_json::json!({
+        "session": session,
+        "checkpoints": checkpoints,
+    }))
+}
+
+// ---------------------------------------------------------------------------
+// checkpoints.restore — roll the workspace back to before a turn
+// ---------------------------------------------------------------------------
+
+async fn handle_checkpoints_restore(params: Value, ctx: &ConnectionContext) -> RpcResult {
+    let session = params["session"]
+        .as_str()
+        .ok_or_else(|| (-32602, "Missing required param: session".to_string()))?;
+    let turn = params["turn"]
+        .as_u64()
+        .ok_or_else(|| (-32602, "Missing required param: turn".to_string()))?;
+
+    let report = ctx
+        .agent
+        .restore_checkpoint(&SessionKey::new(session), turn as usize)
+        .await
+        .map_err(|e| (-32002, e))?;
+    info!("Restored session {} to before turn {}", session, turn);
+    Ok(serde_json::json!({
+        "session": session,
+        "turn": report.turn,
+        "restored": report.restored,
+        "removed": report.removed,
+    }))
+}
+
 // ---------------------------------------------------------------------------
 // health — health check
 // ---------------------------------------------------------------------------
@@ -358,5 +653,66 @@ pub fn output_event_to_message(event: &OutputEvent) -> EventMessage {
             "sleep",
             serde_json::json!({ "token_count": token_count }),
         ),
+        OutputEvent::Compacted {
+            session,
+            summarized,
+            kept,
+            tokens_before,
+            tokens_after,
+        } => EventMessage::chat(
+            session,
+            "compacted",
+            serde_json::json!({
+                "summarized": summarized,
+                "kept": kept,
+                "tokens_before": tokens_before,
+                "tokens_after": tokens_after,
+            }),
+        ),
+        OutputEvent::Failover {
+            session,
+            from,
+            to,
+            reason,
+        } => EventMessage::chat(
+            session,
+            "failover",
+            serde_json::json!({ "from": from, "to": to, "reason": reason }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_paths_stay_in_the_workspace() {
+        let base = std::env::temp_dir().join(format!("agenticlaw-rpc-{}", std::process::id()));
+        let workspace = base.join("ws");
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::write(workspace.join("photo.png"), b"png").unwrap();
+        std::fs::write(base.join("secret.png"), b"png").unwrap();
+
+        let parse =
+            |path: &str| parse_attachments_param(&serde_json::json!([{"path": path}]), &workspace);
+        assert_eq!(parse("photo.png").unwrap().len(), 1);
+        for path in ["/etc/hosts", "../secret.png", "sub/../../secret.png"] {
+            let (code, msg) = parse(path).unwrap_err();
+            assert_eq!(code, -32602);
+            assert!(msg.contains("inside the workspace"), "{}: {}", path, msg);
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(base.join("secret.png"), workspace.join("link.png"))
+                .unwrap();
+            assert!(parse("link.png")
+                .unwrap_err()
+                .1
+                .contains("inside the workspace"));
+        }
+        let _ = std::fs::remove_dir_all(&base);
     }
 }
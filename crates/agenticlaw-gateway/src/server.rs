@@ -2,9 +2,18 @@
 
 use crate::auth::ResolvedAuth;
 use crate::ws::{handle_connection, WsState};
-use agenticlaw_agent::{AgentConfig, AgentRuntime, OutputEvent, SessionKey};
-use agenticlaw_core::GatewayConfig;
-use agenticlaw_tools::create_default_registry;
+use agenticlaw_agent::{
+    AgentConfig, AgentRuntime, CompactionPolicy, OutputEvent, OutputSpill, SessionKey,
+};
+use agenticlaw_core::{GatewayConfig, OpenclawConfig};
+use agenticlaw_llm::{
+    replay_or_record, AnthropicProvider, LlmProvider, ModelCatalog, OpenAiCompatProvider, Route,
+    RoutingProvider,
+};
+use agenticlaw_tools::{
+    create_runtime_handle, create_sandboxed_registry, NetworkPolicy, SandboxConfig, ToolRegistry,
+    WorkspaceSandbox,
+};
 use axum::{
     extract::{Path as AxumPath, State, WebSocketUpgrade},
     response::{Html, IntoResponse},
@@ -12,17 +21,20 @@ use axum::{
     Json, Router,
 };
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use tracing::{info, warn};
 
 pub struct ExtendedConfig {
     pub gateway: GatewayConfig,
     pub anthropic_api_key: Option<String>,
     pub workspace_root: PathBuf,
     pub system_prompt: Option<String>,
+    /// Running as the consciousness stack's L0 gateway. Those sessions are
+    /// managed by the stack, so openclaw.json compaction is not applied.
+    pub consciousness: bool,
 }
 
 impl Default for ExtendedConfig {
@@ -32,8 +44,144 @@ impl Default for ExtendedConfig {
             anthropic_api_key: None,
             workspace_root: std::env::current_dir().unwrap_or_default(),
             system_prompt: None,
+            consciousness: false,
+        }
+    }
+}
+
+/// Build the LLM provider for openclaw.json's primary model and fallbacks.
+///
+/// The result is always a `RoutingProvider`, so transient failures are retried
+/// even with no fallbacks configured. Each `agents.defaults.model.fallbacks`
+/// entry (`provider/model`) becomes a failover route; entries whose provider
+/// can't be built are skipped with a warning.
+///
+/// `RUSTCLAW_LLM_REPLAY=<fixture>` replaces all of this with recorded
+/// responses (no network, no key needed); `RUSTCLAW_LLM_RECORD=<fixture>`
+/// appends every live exchange to that file.
+pub fn provider_from_config(
+    oc: &OpenclawConfig,
+    anthropic_api_key: Option<String>,
+) -> anyhow::Result<Arc<dyn LlmProvider>> {
+    replay_or_record(|| routed_provider(oc, anthropic_api_key))
+}
+
+fn routed_provider(
+    oc: &OpenclawConfig,
+    anthropic_api_key: Option<String>,
+) -> anyhow::Result<Arc<dyn LlmProvider>> {
+    let primary_kind = if oc.uses_openai() {
+        Some("openai")
+    } else {
+        None
+    };
+    let primary = build_provider(oc, primary_kind, anthropic_api_key.clone())?;
+    let mut router = RoutingProvider::new(Route::new(primary));
+
+    for fallback in oc.model_fallbacks() {
+        let (kind, model) = match fallback.split_once('/') {
+            Some((kind, model)) => (Some(kind), model),
+            None => (None, fallback.as_str()),
+        };
+        match build_provider(oc, kind, anthropic_api_key.clone()) {
+            Ok(provider) => {
+                info!("Fallback route: {}", fallback);
+                router = router.with_fallback(Route::new(provider).with_model(model));
+            }
+            Err(e) => warn!("Skipping fallback {}: {}", fallback, e),
         }
     }
+
+    Ok(Arc::new(router))
+}
+
+/// `openai` selects the OpenAI-compatible provider from `models.providers.openai`;
+/// anything else is Anthropic, which needs an API key (explicit or
+/// `ANTHROPIC_API_KEY`) and honours `ANTHROPIC_API_URL`.
+fn build_provider(
+    oc: &OpenclawConfig,
+    kind: Option<&str>,
+    anthropic_api_key: Option<String>,
+) -> anyhow::Result<Arc<dyn LlmProvider>> {
+    if kind == Some("openai") {
+        let openai = oc
+            .openai_provider()
+            .ok_or_else(|| anyhow::anyhow!("models.providers.openai not configured"))?;
+        let provider = OpenAiCompatProvider::from_config(openai);
+        info!("Using OpenAI-compatible API: {}", provider.base_url());
+        return Ok(Arc::new(provider));
+    }
+
+    let api_key = anthropic_api_key
+        .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
+        .ok_or_else(|| anyhow::anyhow!("ANTHROPIC_API_KEY not set"))?;
+
+    let provider = AnthropicProvider::new(&api_key)
+        .with_catalog(ModelCatalog::from_entries(oc.anthropic_models()));
+
+    // If ANTHROPIC_API_URL is set, use it as the base URL (for protectgateway proxy)
+    if let Ok(api_url) = std::env::var("ANTHROPIC_API_URL") {
+        info!("Using custom API URL: {}/v1/messages", api_url);
+        return Ok(Arc::new(
+            provider.with_base_url(format!("{}/v1/messages", api_url)),
+        ));
+    }
+    Ok(Arc::new(provider))
+}
+
+/// The default tools, with file access confined per openclaw.json's
+/// `tools.sandbox`. `RUSTCLAW_SANDBOX_POLICY` names an operator policy file
+/// to compile in when the config doesn't.
+pub fn tools_from_config(oc: &OpenclawConfig, workspace: &Path) -> anyhow::Result<ToolRegistry> {
+    let mut sandbox_config = SandboxConfig::from(&oc.tools.sandbox);
+    if sandbox_config.policy.is_none() {
+        sandbox_config.policy = std::env::var("RUSTCLAW_SANDBOX_POLICY")
+            .ok()
+            .map(PathBuf::from);
+    }
+    let network = match &sandbox_config.policy {
+        Some(policy) => NetworkPolicy::from_policy_file(policy)
+            .map_err(|e| anyhow::anyhow!("network: {}", e))?
+            .with_private_network(oc.tools.web.fetch.allow_private_network),
+        None => NetworkPolicy::from(&oc.tools.web.fetch),
+    };
+    let sandbox = WorkspaceSandbox::from_config(workspace, &sandbox_config)
+        .map_err(|e| anyhow::anyhow!("sandbox: {}", e))?;
+    Ok(create_sandboxed_registry(
+        sandbox,
+        Arc::new(network),
+        create_runtime_handle(),
+    ))
+}
+
+/// Tool output budgets from openclaw.json `tools.output`.
+pub fn spill_from_config(oc: &OpenclawConfig, workspace: &Path) -> OutputSpill {
+    let output = &oc.tools.output;
+    let mut spill = OutputSpill::new(workspace);
+    if let Some(bytes) = output.max_bytes {
+        spill = spill.with_default_budget(bytes);
+    }
+    for (tool, &bytes) in &output.per_tool {
+        spill = spill.with_budget(tool.clone(), bytes);
+    }
+    spill
+}
+
+/// Compaction policy from openclaw.json `agents.defaults.compaction`; on
+/// unless `enabled` is false. Only used outside the consciousness stack.
+pub fn compaction_from_config(oc: &OpenclawConfig) -> Option<CompactionPolicy> {
+    let config = &oc.agents.defaults.compaction;
+    if config.enabled == Some(false) {
+        return None;
+    }
+    let mut policy = CompactionPolicy::default();
+    if let Some(pct) = config.threshold_pct {
+        policy.threshold_pct = pct;
+    }
+    if let Some(pct) = config.keep_recent_pct {
+        policy.keep_recent_pct = pct;
+    }
+    Some(policy)
 }
 
 pub async fn start_gateway(config: ExtendedConfig) -> anyhow::Result<()> {
@@ -42,22 +190,21 @@ pub async fn start_gateway(config: ExtendedConfig) -> anyhow::Result<()> {
         .ok();
     let auth = ResolvedAuth::from_config(&config.gateway.auth, env_token);
 
-    let api_key = config
-        .anthropic_api_key
-        .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
-        .ok_or_else(|| anyhow::anyhow!("ANTHROPIC_API_KEY not set"))?;
+    let oc = OpenclawConfig::discover();
 
     let layer = std::env::var("RUSTCLAW_LAYER")
         .or_else(|_| std::env::var("OPENCLAW_LAYER"))
         .ok();
 
-    let tools = create_default_registry(&config.workspace_root);
+    let tools = tools_from_config(&oc, &config.workspace_root)?;
     info!("Registered tools: {:?}", tools.list());
 
     let agent_config = AgentConfig {
         default_model: std::env::var("RUSTCLAW_MODEL")
             .or_else(|_| std::env::var("OPENCLAW_MODEL"))
-            .unwrap_or_else(|_| "claude-opus-4-6-20250929".to_string()),
+            .ok()
+            .or_else(|| oc.default_model().filter(|_| oc.uses_openai()))
+            .unwrap_or_else(|| "claude-opus-4-6-20250929".to_string()),
         max_tool_iterations: 25,
         system_prompt: config
             .system_prompt
@@ -66,19 +213,15 @@ pub async fn start_gateway(config: ExtendedConfig) -> anyhow::Result<()> {
         sleep_threshold_pct: 1.0,
     };
 
-    // If ANTHROPIC_API_URL is set, use it as the base URL (for protectgateway proxy)
-    let agent = if let Ok(api_url) = std::env::var("ANTHROPIC_API_URL") {
-        let provider = agenticlaw_llm::AnthropicProvider::new(&api_key)
-            .with_base_url(format!("{}/v1/messages", api_url));
-        info!("Using custom API URL: {}/v1/messages", api_url);
-        Arc::new(AgentRuntime::with_provider(
-            Arc::new(provider),
-            tools,
-            agent_config,
-        ))
-    } else {
-        Arc::new(AgentRuntime::new(&api_key, tools, agent_config))
-    };
+    let provider = provider_from_config(&oc, config.anthropic_api_key)?;
+    let spill = spill_from_config(&oc, &config.workspace_root);
+    let mut agent = AgentRuntime::with_provider(provider, tools, agent_config).with_spill(spill);
+    if !config.consciousness {
+        if let Some(policy) = compaction_from_config(&oc) {
+            agent = agent.with_compaction(policy);
+        }
+    }
+    let agent = Arc::new(agent);
 
     // Create broadcast channel for OutputEvents — fan-out to all WS clients
     let (output_tx, _) = broadcast::channel::<OutputEvent>(1024);
@@ -89,7 +232,7 @@ pub async fn start_gateway(config: ExtendedConfig) -> anyhow::Result<()> {
         layer: layer.clone(),
         port: config.gateway.port,
         output_tx,
-        consciousness_enabled: false,
+        consciousness_enabled: config.consciousness,
         started_at: std::time::Instant::now(),
     });
 
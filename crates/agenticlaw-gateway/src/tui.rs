@@ -1,8 +1,9 @@
 //! Terminal UI with vim-style editor, streaming output, and context bar
 
+use agenticlaw_agent::search::{self, SearchHit, SearchQuery};
 use agenticlaw_agent::{AgentConfig, AgentEvent, AgentRuntime, SessionKey};
-use agenticlaw_core::openclaw_config;
-use agenticlaw_tools::create_default_registry;
+use agenticlaw_core::{openclaw_config, OpenclawConfig};
+use agenticlaw_llm::ModelInfo;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
@@ -13,7 +14,7 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame, Terminal,
 };
 use std::io::{self, Stdout};
@@ -70,10 +71,19 @@ pub struct App {
     pub session_id: String,
     pub ctx_path: String,
 
+    /// Search results being picked from; keys go to the picker while open
+    pub picker: Option<Picker>,
+
     // Control
     pub should_quit: bool,
 }
 
+/// Session search results with one selected.
+pub struct Picker {
+    pub hits: Vec<SearchHit>,
+    pub selected: usize,
+}
+
 impl App {
     pub fn new(model: &str, session_id: &str, ctx_path: &str) -> Self {
         Self {
@@ -86,9 +96,10 @@ impl App {
             agent_running: false,
             model: model.to_string(),
             context_used: 0,
-            context_max: 128_000,
+            context_max: ModelInfo::DEFAULT.context_window,
             session_id: session_id.to_string(),
             ctx_path: ctx_path.to_string(),
+            picker: None,
             should_quit: false,
         }
     }
@@ -135,7 +146,7 @@ impl App {
         }
         let char_len = self.current_line_char_len();
         if self.mode == VimMode::Normal {
-            self.cursor_col = self.cursor_col.min(char_len.saturating_sub(1).max(0));
+            self.cursor_col = self.cursor_col.min(char_len.saturating_sub(1));
         } else {
             self.cursor_col = self.cursor_col.min(char_len);
         }
@@ -154,6 +165,80 @@ fn char_to_byte(s: &str, char_idx: usize) -> usize {
         .unwrap_or(s.len())
 }
 
+// ---------------------------------------------------------------------------
+// Slash commands — handled locally instead of being sent to the agent
+// ---------------------------------------------------------------------------
+
+pub enum Command {
+    /// `/checkpoints` — list the session's file checkpoints
+    Checkpoints,
+    /// `/rewind <turn>` — restore the workspace to before `turn`
+    Rewind(usize),
+    /// `/fork <turn> [name]` — branch the session after `turn` and switch to it
+    Fork {
+        turn: usize,
+        name: Option<String>,
+    },
+    /// `/search <words> [role:up|model] [tool:<name>] [since:<date>] [until:<date>]`
+    /// — search every session and pick a result
+    Search(SearchQuery),
+    /// `/open <path>` — resume the session in a .ctx file and switch to it
+    Open(PathBuf),
+    /// `/compact` — summarise the session's older turns now
+    Compact,
+    Invalid(String),
+}
+
+/// Parse an editor submission as a slash command. `None` for plain messages.
+pub fn parse_command(text: &str) -> Option<Command> {
+    let mut words = text.split_whitespace();
+    let command = match words.next()? {
+        "/checkpoints" => Command::Checkpoints,
+        "/compact" => Command::Compact,
+        "/rewind" => match words.next().map(str::parse) {
+            Some(Ok(turn)) => Command::Rewind(turn),
+            _ => Command::Invalid("usage: /rewind <turn>".into()),
+        },
+        "/fork" => match words.next().map(str::parse) {
+            Some(Ok(turn)) => Command::Fork {
+                turn,
+                name: words.next().map(String::from),
+            },
+            _ => Command::Invalid("usage: /fork <turn> [name]".into()),
+        },
+        "/search" => match parse_search(words) {
+            Ok(query) => Command::Search(query),
+            Err(e) => Command::Invalid(format!(
+                "{}\nusage: /search <words> [role:up|model] [tool:<name>] [since:<date>] [until:<date>]",
+                e
+            )),
+        },
+        "/open" => match text.trim().split_once(char::is_whitespace) {
+            Some((_, path)) => Command::Open(PathBuf::from(path.trim())),
+            None => Command::Invalid("usage: /open <path>".into()),
+        },
+        _ => return None,
+    };
+    Some(command)
+}
+
+/// Search words, with `key:value` words as filters.
+fn parse_search<'a>(words: impl Iterator<Item = &'a str>) -> Result<SearchQuery, String> {
+    let mut text = Vec::new();
+    let mut query = SearchQuery::new("");
+    for word in words {
+        match word.split_once(':') {
+            Some(("role", role)) => query.role = Some(role.parse()?),
+            Some(("tool", tool)) => query.tool = Some(tool.to_string()),
+            Some(("since", date)) => query.since = Some(search::parse_time(date, false)?),
+            Some(("until", date)) => query.until = Some(search::parse_time(date, true)?),
+            _ => text.push(word),
+        }
+    }
+    query.text = text.join(" ");
+    Ok(query)
+}
+
 // ---------------------------------------------------------------------------
 // Key handling
 // ---------------------------------------------------------------------------
@@ -166,12 +251,44 @@ pub fn handle_key(app: &mut App, key: KeyEvent) -> Option<String> {
         return None;
     }
 
+    if app.picker.is_some() {
+        return handle_picker_key(app, key);
+    }
     match app.mode {
         VimMode::Normal => handle_normal_key(app, key),
         VimMode::Insert => handle_insert_key(app, key),
     }
 }
 
+/// j/k move, Enter opens the selected session, Esc or q closes the picker.
+fn handle_picker_key(app: &mut App, key: KeyEvent) -> Option<String> {
+    let picker = app.picker.as_mut()?;
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => {
+            picker.selected = (picker.selected + 1).min(picker.hits.len().saturating_sub(1));
+            None
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            picker.selected = picker.selected.saturating_sub(1);
+            None
+        }
+        KeyCode::Enter => {
+            let selected = picker.selected;
+            let hit = app.picker.take()?.hits.into_iter().nth(selected)?;
+            app.push_output(&format!(
+                "\n[{} at {}] {}\n",
+                hit.session, hit.timestamp, hit.snippet
+            ));
+            Some(format!("/open {}", hit.path.display()))
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.picker = None;
+            None
+        }
+        _ => None,
+    }
+}
+
 fn handle_normal_key(app: &mut App, key: KeyEvent) -> Option<String> {
     match key.code {
         // ESC cancels running agent (signaled via return value in main loop)
@@ -245,7 +362,7 @@ fn handle_normal_key(app: &mut App, key: KeyEvent) -> Option<String> {
             None
         }
         KeyCode::Char('$') => {
-            app.cursor_col = app.current_line_char_len().saturating_sub(1).max(0);
+            app.cursor_col = app.current_line_char_len().saturating_sub(1);
             None
         }
         KeyCode::Char('w') => {
@@ -405,7 +522,10 @@ pub fn draw(frame: &mut Frame, app: &App) {
         ])
         .split(size);
 
-    draw_output(frame, app, chunks[0]);
+    match &app.picker {
+        Some(picker) => draw_picker(frame, picker, chunks[0]),
+        None => draw_output(frame, app, chunks[0]),
+    }
     draw_editor(frame, app, chunks[1]);
     draw_status(frame, app, chunks[2]);
 }
@@ -453,6 +573,44 @@ fn draw_output(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(paragraph, area);
 }
 
+fn draw_picker(frame: &mut Frame, picker: &Picker, area: Rect) {
+    let visible_height = area.height.saturating_sub(2) as usize;
+    // Keep the selection in view
+    let start = (picker.selected + 1).saturating_sub(visible_height);
+    let lines: Vec<Line> = picker
+        .hits
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(visible_height)
+        .map(|(i, hit)| {
+            let text = format!(
+                "{}  {}  [{}]  {}",
+                hit.timestamp.get(..19).unwrap_or(&hit.timestamp),
+                hit.session,
+                hit.role,
+                hit.snippet
+            );
+            let style = if i == picker.selected {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            Line::from(Span::styled(text, style))
+        })
+        .collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " Search: {} result(s) — j/k move, Enter open, Esc close ",
+            picker.hits.len()
+        ))
+        .border_style(Style::default().fg(Color::Magenta));
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
 fn draw_editor(frame: &mut Frame, app: &App, area: Rect) {
     let lines: Vec<Line> = app
         .editor_lines
@@ -536,22 +694,25 @@ pub async fn run_tui(
     model: Option<String>,
     resume: bool,
 ) -> anyhow::Result<()> {
-    let api_key = std::env::var("ANTHROPIC_API_KEY")
-        .map_err(|_| anyhow::anyhow!("ANTHROPIC_API_KEY not set"))?;
+    let oc = OpenclawConfig::discover();
+    let provider = crate::server::provider_from_config(&oc, None)?;
 
     let workspace_root = workspace
         .or_else(|| std::env::var("RUSTCLAW_WORKSPACE").ok().map(PathBuf::from))
         .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
 
     let default_model = model.unwrap_or_else(|| {
-        std::env::var("RUSTCLAW_MODEL").unwrap_or_else(|_| "claude-opus-4-6".to_string())
+        std::env::var("RUSTCLAW_MODEL")
+            .ok()
+            .or_else(|| oc.default_model().filter(|_| oc.uses_openai()))
+            .unwrap_or_else(|| "claude-opus-4-6".to_string())
     });
 
     // Load bootstrap identity files into system prompt
     let bootstrap = openclaw_config::load_bootstrap_files(&workspace_root);
     let system_prompt = openclaw_config::bootstrap_to_system_prompt(&bootstrap);
 
-    let tools = create_default_registry(&workspace_root);
+    let tools = crate::server::tools_from_config(&oc, &workspace_root)?;
     let config = AgentConfig {
         default_model: default_model.clone(),
         max_tool_iterations: 25,
@@ -559,7 +720,11 @@ pub async fn run_tui(
         workspace_root: workspace_root.clone(),
         sleep_threshold_pct: 1.0,
     };
-    let runtime = Arc::new(AgentRuntime::new(&api_key, tools, config));
+    let mut runtime = AgentRuntime::with_provider(provider, tools, config);
+    if let Some(policy) = crate::server::compaction_from_config(&oc) {
+        runtime = runtime.with_compaction(policy);
+    }
+    let runtime = Arc::new(runtime);
 
     // Resume or create new session
     let (session_key, ctx_path) = if resume {
@@ -584,6 +749,7 @@ pub async fn run_tui(
 
     let session_id = session_key.as_str().to_string();
     let mut app = App::new(&default_model, &session_id, &ctx_path.to_string_lossy());
+    app.context_max = runtime.model_info(&default_model).context_window;
 
     // Setup terminal with panic hook to restore on crash
     let original_hook = std::panic::take_hook();
@@ -628,7 +794,7 @@ async fn run_event_loop(
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     app: &mut App,
     runtime: Arc<AgentRuntime>,
-    session_key: SessionKey,
+    mut session_key: SessionKey,
     agent_event_rx: &mut mpsc::Receiver<AgentEvent>,
     agent_event_tx: mpsc::Sender<AgentEvent>,
     abort_tx: watch::Sender<bool>,
@@ -644,14 +810,21 @@ async fn run_event_loop(
         if event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
                 // ESC in normal mode while agent running = abort
-                if key.code == KeyCode::Esc && app.mode == VimMode::Normal && app.agent_running {
+                if key.code == KeyCode::Esc
+                    && app.mode == VimMode::Normal
+                    && app.agent_running
+                    && app.picker.is_none()
+                {
                     let _ = abort_tx.send(true);
                     app.agent_running = false;
                     app.push_output("\n[cancelled]\n");
                     continue;
                 }
 
-                if let Some(message) = handle_key(app, key) {
+                let message = handle_key(app, key);
+                if let Some(command) = message.as_deref().and_then(parse_command) {
+                    run_command(app, &runtime, &mut session_key, command).await;
+                } else if let Some(message) = message {
                     // Send message to agent
                     app.agent_running = true;
                     let rt = runtime.clone();
@@ -719,6 +892,18 @@ async fn run_event_loop(
                         app.context_used = sess.token_count().await;
                     }
                 }
+                AgentEvent::Failover { to, .. } => {
+                    app.push_output(&format!("\n[failover → {}]\n", to));
+                }
+                AgentEvent::Compacted {
+                    summarized,
+                    tokens_before,
+                    tokens_after,
+                    ..
+                } => {
+                    app.push_output(&compacted_note(summarized, tokens_before, tokens_after));
+                    app.context_used = tokens_after;
+                }
                 AgentEvent::Error(e) => {
                     app.push_output(&format!("\nError: {}\n", e));
                     app.agent_running = false;
@@ -730,3 +915,109 @@ async fn run_event_loop(
 
     Ok(())
 }
+
+async fn run_command(
+    app: &mut App,
+    runtime: &AgentRuntime,
+    session_key: &mut SessionKey,
+    command: Command,
+) {
+    match command {
+        Command::Checkpoints => {
+            let checkpoints = runtime.checkpoints(session_key);
+            if checkpoints.is_empty() {
+                app.push_output("No checkpoints\n");
+            }
+            for checkpoint in checkpoints {
+                app.push_output(&format!(
+                    "turn {}  {}  {} file(s)\n",
+                    checkpoint.turn,
+                    checkpoint.created_at,
+                    checkpoint.files.len()
+                ));
+            }
+        }
+        Command::Rewind(turn) => match runtime.restore_checkpoint(session_key, turn).await {
+            Ok(report) => app.push_output(&format!(
+                "[rewound to before turn {}: {} restored, {} removed]\n",
+                report.turn,
+                report.restored.len(),
+                report.removed.len()
+            )),
+            Err(e) => app.push_output(&format!("Error: {}\n", e)),
+        },
+        Command::Fork { turn, name } => {
+            let Some(parent) = runtime.sessions().get(session_key) else {
+                app.push_output("Error: nothing to fork yet\n");
+                return;
+            };
+            let name = name.unwrap_or_else(|| {
+                format!(
+                    "{}-fork-{}",
+                    session_key,
+                    &uuid::Uuid::new_v4().to_string()[..8]
+                )
+            });
+            let key = SessionKey::new(&name);
+            match runtime.sessions().fork(&parent, turn, &key).await {
+                Ok(fork) => {
+                    app.push_output(&format!(
+                        "[forked {} after turn {} → {}]\n",
+                        session_key, turn, name
+                    ));
+                    app.session_id = name;
+                    if let Some(path) = fork.ctx_path() {
+                        app.ctx_path = path.to_string_lossy().into_owned();
+                    }
+                    app.context_used = fork.token_count().await;
+                    *session_key = key;
+                }
+                Err(e) => app.push_output(&format!("Error: {}\n", e)),
+            }
+        }
+        Command::Search(query) => match search::search(runtime.workspace(), &query) {
+            Ok(hits) if hits.is_empty() => app.push_output("No matches\n"),
+            Ok(hits) => app.picker = Some(Picker { hits, selected: 0 }),
+            Err(e) => app.push_output(&format!("Error: {}\n", e)),
+        },
+        Command::Open(path) => {
+            let resumed = match agenticlaw_agent::ctx_file::parse_for_resume(&path) {
+                Ok(resumed) => resumed,
+                Err(e) => {
+                    app.push_output(&format!("Error: {}: {}\n", path.display(), e));
+                    return;
+                }
+            };
+            let session = runtime.sessions().resume_from_ctx(&resumed, None);
+            app.push_output(&format!("[switched to {}]\n", session.key));
+            app.session_id = session.key.as_str().to_string();
+            if let Some(path) = session.ctx_path() {
+                app.ctx_path = path.to_string_lossy().into_owned();
+            }
+            app.context_used = session.token_count().await;
+            *session_key = session.key.clone();
+        }
+        Command::Compact => match runtime.compact_session(session_key).await {
+            Ok(report) => {
+                app.push_output(&compacted_note(
+                    report.summarized,
+                    report.tokens_before,
+                    report.tokens_after,
+                ));
+                app.context_used = report.tokens_after;
+            }
+            Err(e) => app.push_output(&format!("Error: {}\n", e)),
+        },
+        Command::Invalid(usage) => app.push_output(&format!("{}\n", usage)),
+    }
+}
+
+/// `[compacted 12 messages: ~150k → ~40k tokens]`
+pub fn compacted_note(summarized: usize, tokens_before: usize, tokens_after: usize) -> String {
+    format!(
+        "\n[compacted {} messages: ~{}k → ~{}k tokens]\n",
+        summarized,
+        tokens_before / 1000,
+        tokens_after / 1000
+    )
+}
@@ -3,7 +3,8 @@
 //! Shares rendering and key handling with tui.rs but uses a WS connection
 //! instead of an embedded AgentRuntime.
 
-use crate::tui::{draw, handle_key, App, VimMode};
+use crate::tui::{compacted_note, draw, handle_key, parse_command, App, Command, Picker, VimMode};
+use agenticlaw_agent::search::SearchHit;
 use crossterm::{
     event::{self, Event, KeyCode, KeyModifiers},
     terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
@@ -16,7 +17,7 @@ use tokio_tungstenite::{connect_async, tungstenite::Message as WsMsg};
 
 pub async fn run_tui_client(
     port: u16,
-    session: String,
+    mut session: String,
     token: Option<String>,
 ) -> anyhow::Result<()> {
     let url = format!("ws://127.0.0.1:{}/ws", port);
@@ -82,7 +83,11 @@ pub async fn run_tui_client(
                     break;
                 }
 
-                if key.code == KeyCode::Esc && app.mode == VimMode::Normal && app.agent_running {
+                if key.code == KeyCode::Esc
+                    && app.mode == VimMode::Normal
+                    && app.agent_running
+                    && app.picker.is_none()
+                {
                     req_id += 1;
                     let abort = serde_json::json!({
                         "id": format!("req-{}", req_id),
@@ -95,7 +100,54 @@ pub async fn run_tui_client(
                     continue;
                 }
 
-                if let Some(message) = handle_key(&mut app, key) {
+                let message = handle_key(&mut app, key);
+                if let Some(command) = message.as_deref().and_then(parse_command) {
+                    let (method, params) = match command {
+                        Command::Checkpoints => (
+                            "checkpoints.list",
+                            serde_json::json!({ "session": session }),
+                        ),
+                        Command::Rewind(turn) => (
+                            "checkpoints.restore",
+                            serde_json::json!({ "session": session, "turn": turn }),
+                        ),
+                        Command::Fork { turn, name } => (
+                            "sessions.fork",
+                            serde_json::json!({ "session": session, "turn": turn, "name": name }),
+                        ),
+                        Command::Compact => {
+                            ("chat.compact", serde_json::json!({ "session": session }))
+                        }
+                        Command::Search(query) => (
+                            "sessions.search",
+                            serde_json::json!({
+                                "query": query.text,
+                                "since": query.since.map(|t| t.to_rfc3339()),
+                                "until": query.until.map(|t| t.to_rfc3339()),
+                                "role": query.role.map(|r| r.to_string()),
+                                "tool": query.tool,
+                                "limit": query.limit,
+                            }),
+                        ),
+                        Command::Open(_) => {
+                            app.push_output(
+                                "Opening a session needs embedded mode (agenticlaw chat --embedded)\n",
+                            );
+                            continue;
+                        }
+                        Command::Invalid(usage) => {
+                            app.push_output(&format!("{}\n", usage));
+                            continue;
+                        }
+                    };
+                    req_id += 1;
+                    let rpc = serde_json::json!({
+                        "id": format!("req-{}", req_id),
+                        "method": method,
+                        "params": params
+                    });
+                    ws_tx.send(WsMsg::Text(rpc.to_string())).await?;
+                } else if let Some(message) = message {
                     req_id += 1;
                     let rpc = serde_json::json!({
                         "id": format!("req-{}", req_id),
@@ -120,6 +172,8 @@ pub async fn run_tui_client(
             match ws_rx.next().now_or_never() {
                 Some(Some(Ok(WsMsg::Text(text)))) => {
                     handle_ws_event(&mut app, &text);
+                    // A fork switches the client over to the new session
+                    session.clone_from(&app.session_id);
                 }
                 Some(Some(Ok(WsMsg::Close(_)))) | Some(None) => {
                     app.push_output("\n[connection closed]\n");
@@ -153,6 +207,64 @@ fn handle_ws_event(app: &mut App, text: &str) {
         Err(_) => return,
     };
 
+    // Responses to our own requests; only command results are shown
+    if let Some(message) = v["error"]["message"].as_str() {
+        app.push_output(&format!("Error: {}\n", message));
+        return;
+    }
+    let result = &v["result"];
+    if let Some(checkpoints) = result["checkpoints"].as_array() {
+        if checkpoints.is_empty() {
+            app.push_output("No checkpoints\n");
+        }
+        for checkpoint in checkpoints {
+            app.push_output(&format!(
+                "turn {}  {}  {} file(s)\n",
+                checkpoint["turn"],
+                checkpoint["created_at"].as_str().unwrap_or("?"),
+                checkpoint["files"].as_array().map_or(0, Vec::len)
+            ));
+        }
+        return;
+    }
+    if let Some(restored) = result["restored"].as_array() {
+        app.push_output(&format!(
+            "[rewound to before turn {}: {} restored, {} removed]\n",
+            result["turn"],
+            restored.len(),
+            result["removed"].as_array().map_or(0, Vec::len)
+        ));
+        return;
+    }
+
+    if let Some(results) = result.get("results") {
+        match serde_json::from_value::<Vec<SearchHit>>(results.clone()) {
+            Ok(hits) if hits.is_empty() => app.push_output("No matches\n"),
+            Ok(hits) => app.picker = Some(Picker { hits, selected: 0 }),
+            Err(e) => app.push_output(&format!("Error: {}\n", e)),
+        }
+        return;
+    }
+
+    if let Some(tokens_after) = result["tokens_after"].as_u64() {
+        app.push_output(&compacted_note(
+            result["summarized"].as_u64().unwrap_or(0) as usize,
+            result["tokens_before"].as_u64().unwrap_or(0) as usize,
+            tokens_after as usize,
+        ));
+        return;
+    }
+
+    if let Some(parent) = result["parent"].as_str() {
+        let fork = result["session"].as_str().unwrap_or("?");
+        app.push_output(&format!(
+            "[forked {} after turn {} → {}]\n",
+            parent, result["turn"], fork
+        ));
+        app.session_id = fork.to_string();
+        return;
+    }
+
     let event_type = v.get("event").and_then(|e| e.as_str()).unwrap_or("");
     let data = &v["data"];
 
@@ -181,6 +293,17 @@ fn handle_ws_event(app: &mut App, text: &str) {
                         app.push_output(&format!("  done ({} chars)\n", content.len()));
                     }
                 }
+                "failover" => {
+                    let to = data["to"].as_str().unwrap_or("?");
+                    app.push_output(&format!("\n[failover → {}]\n", to));
+                }
+                "compacted" => {
+                    app.push_output(&compacted_note(
+                        data["summarized"].as_u64().unwrap_or(0) as usize,
+                        data["tokens_before"].as_u64().unwrap_or(0) as usize,
+                        data["tokens_after"].as_u64().unwrap_or(0) as usize,
+                    ));
+                }
                 "done" => {
                     app.push_output("\n");
                     app.agent_running = false;
@@ -251,6 +251,7 @@ async fn handle_legacy_message(
             let session_key = agenticlaw_agent::SessionKey::new(&session);
             if let Some(sess) = state.agent.sessions().get(&session_key) {
                 sess.abort().await;
+                state.agent.end_session(&session_key).await;
             }
         }
         ClientMessage::Call { id, method, params } => {
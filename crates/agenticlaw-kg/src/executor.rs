@@ -19,12 +19,17 @@ use crate::registry::{NodeType, NodeTypeRegistry, TemplateVars};
 use crate::resource::{Artifact, GraphAddress, KgEvent, ResourceDriver};
 use agenticlaw_agent::runtime::{AgentEvent, AgentRuntime};
 use agenticlaw_agent::session::SessionKey;
+use agenticlaw_tools::{SpawnableRuntime, ToolContext};
 use anyhow::Result;
 use chrono::Utc;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{info, warn};
 
+/// Re-prompts a leaf with an output schema gets before it fails.
+const STRUCTURED_RETRIES: usize = 2;
+
 /// Simple hash for logging (not cryptographic).
 #[allow(dead_code)]
 fn short_hash(s: &str) -> u64 {
@@ -72,6 +77,8 @@ pub struct NodeResult {
     pub success: bool,
     /// For parents: concatenated child outputs.
     pub child_outputs: Vec<(String, String)>, // (node_id, output)
+    /// For leaves with an output schema: the validated answer.
+    pub answer: Option<serde_json::Value>,
 }
 
 /// The KG Executor. Drives recursive agent tree with structural observability.
@@ -311,7 +318,13 @@ impl Executor {
         };
 
         // Spawn agent: system preloaded, one user message, ≤N tool calls, done.
-        let result = self.run_agent(run_id, addr, &system, &user_msg).await?;
+        let result = match &node_type.output_schema {
+            Some(schema) => {
+                self.run_structured_agent(run_id, addr, &system, &user_msg, schema)
+                    .await
+            }
+            None => self.run_agent(run_id, addr, &system, &user_msg).await?,
+        };
 
         // Write output (code captures it)
         self.driver
@@ -346,13 +359,7 @@ impl Executor {
         );
         self.write_manifest(run_id, manifest).await?;
 
-        Ok(NodeResult {
-            output: result.output,
-            tokens: result.tokens,
-            wall_ms: result.wall_ms,
-            success,
-            child_outputs: vec![],
-        })
+        Ok(NodeResult { success, ..result })
     }
 
     /// Execute a parent node: descend into children sequentially, aggregating context.
@@ -382,6 +389,8 @@ impl Executor {
             .await?;
 
         let mut child_outputs: Vec<(String, String)> = Vec::new();
+        // Typed answers of children with an output schema, by node id
+        let mut child_answers: HashMap<String, serde_json::Value> = HashMap::new();
         // Seed with parent-gathered context so first child gets it
         if !parent_context.is_empty() {
             child_outputs.push(("_parent_context".into(), parent_context));
@@ -413,8 +422,14 @@ impl Executor {
                 child_vars.plan = plan_text.clone();
             }
 
-            // If we have a branch name, extract it
-            if let Some((_, branch_text)) =
+            // If we have a branch name, take it from the typed answer, or
+            // extract it from the output of an untyped create-branch
+            if let Some(branch) = child_answers
+                .get("create-branch")
+                .and_then(|answer| answer["branch"].as_str())
+            {
+                child_vars.branch = branch.to_string();
+            } else if let Some((_, branch_text)) =
                 child_outputs.iter().find(|(id, _)| id == "create-branch")
             {
                 // Try to extract branch name from output
@@ -448,6 +463,9 @@ impl Executor {
                 .await?;
 
             child_outputs.push((child_id.clone(), child_result.output.clone()));
+            if let Some(answer) = child_result.answer {
+                child_answers.insert(child_id.clone(), answer);
+            }
             total_tokens += child_result.tokens;
             total_wall_ms += child_result.wall_ms;
 
@@ -514,6 +532,7 @@ impl Executor {
             wall_ms: total_wall_ms,
             success: all_success,
             child_outputs,
+            answer: None,
         })
     }
 
@@ -719,9 +738,60 @@ impl Executor {
             wall_ms,
             success,
             child_outputs: vec![],
+            answer: None,
         })
     }
 
+    /// Run a leaf that must answer through `final_answer`, validated against
+    /// `schema`. The answer (pretty JSON) doubles as the node's text output.
+    async fn run_structured_agent(
+        &self,
+        run_id: &str,
+        addr: &GraphAddress,
+        system_prompt: &str,
+        user_prompt: &str,
+        schema: &serde_json::Value,
+    ) -> NodeResult {
+        let session_id = format!("kg:{}:{}", run_id, addr.as_str());
+        let parent = ToolContext::new(session_id.clone());
+        let start = std::time::Instant::now();
+        let result = self
+            .runtime
+            .spawn_child_structured(
+                &parent,
+                &session_id,
+                system_prompt,
+                user_prompt,
+                self.runtime.config().max_tool_iterations,
+                schema,
+                STRUCTURED_RETRIES,
+            )
+            .await;
+        let wall_ms = start.elapsed().as_millis() as u64;
+
+        match result {
+            Ok((answer, tokens)) => NodeResult {
+                output: serde_json::to_string_pretty(&answer).unwrap_or_default(),
+                tokens,
+                wall_ms,
+                success: true,
+                child_outputs: vec![],
+                answer: Some(answer),
+            },
+            Err(e) => {
+                warn!(run_id, error = %e, "structured agent failed");
+                NodeResult {
+                    output: format!("ERROR: {}", e),
+                    tokens: 0,
+                    wall_ms,
+                    success: false,
+                    child_outputs: vec![],
+                    answer: None,
+                }
+            }
+        }
+    }
+
     fn build_report(&self, run_id: &str, manifest: &RunManifest, _result: &NodeResult) -> String {
         let mut report = format!(
             "# KG Run Report: {}\n\n## Purpose\n{}\n\n## Target\n{}\n\n## Outcome: {}\n\n",
@@ -832,3 +902,140 @@ impl Executor {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::OperatorRole;
+    use crate::resource::LocalFsDriver;
+    use agenticlaw_agent::AgentConfig;
+    use agenticlaw_llm::provider::{LlmError, LlmStream};
+    use agenticlaw_llm::{LlmProvider, LlmRequest, StreamDelta};
+    use std::sync::Mutex;
+
+    /// Answers `final_answer` when offered it; otherwise records the prompt.
+    struct BranchProvider {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for BranchProvider {
+        fn name(&self) -> &str {
+            "branch"
+        }
+        fn models(&self) -> &[&str] {
+            &["claude-sonnet-4"]
+        }
+        async fn complete_stream(
+            &self,
+            request: LlmRequest,
+            _cancel: Option<agenticlaw_llm::CancellationToken>,
+        ) -> Result<LlmStream, LlmError> {
+            let structured = request
+                .tools
+                .iter()
+                .flatten()
+                .any(|t| t.name == "final_answer");
+            let deltas = if structured {
+                vec![
+                    StreamDelta::ToolCallStart {
+                        id: "fa-1".into(),
+                        name: "final_answer".into(),
+                    },
+                    StreamDelta::ToolCallDelta {
+                        id: "fa-1".into(),
+                        arguments: r#"{"branch": "fix-7-typed", "head": "abc123"}"#.into(),
+                    },
+                    StreamDelta::ToolCallEnd { id: "fa-1".into() },
+                ]
+            } else {
+                let prompt = request.messages.last().unwrap().content.to_text();
+                self.prompts.lock().unwrap().push(prompt);
+                vec![StreamDelta::Text("pushed".into())]
+            };
+            let done = StreamDelta::Done {
+                stop_reason: None,
+                usage: None,
+            };
+            Ok(Box::pin(futures::stream::iter(
+                deltas.into_iter().chain([done]).map(Ok),
+            )))
+        }
+    }
+
+    fn leaf(id: &str, prompt: &str, output_schema: Option<serde_json::Value>) -> NodeType {
+        NodeType {
+            id: id.into(),
+            name: id.into(),
+            taxonomy_ref: None,
+            purpose_template: "Test.".into(),
+            fear_template: String::new(),
+            prompt_template: prompt.into(),
+            role: OperatorRole::Local,
+            is_leaf: true,
+            children: vec![],
+            success_criterion: None,
+            max_tool_calls: 3,
+            output_schema,
+        }
+    }
+
+    #[tokio::test]
+    async fn parent_reads_typed_child_answers() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut registry = NodeTypeRegistry::new();
+        registry.register(NodeType {
+            is_leaf: false,
+            children: vec!["create-branch".into(), "push".into()],
+            ..leaf("issue", "", None)
+        });
+        registry.register(leaf(
+            "create-branch",
+            "Create a branch.",
+            Some(serde_json::json!({
+                "type": "object",
+                "properties": {"branch": {"type": "string"}, "head": {"type": "string"}},
+                "required": ["branch", "head"],
+            })),
+        ));
+        registry.register(leaf("push", "Push {branch}.", None));
+
+        let provider = Arc::new(BranchProvider {
+            prompts: Mutex::new(Vec::new()),
+        });
+        let config = AgentConfig {
+            default_model: "claude-sonnet-4".into(),
+            workspace_root: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let runtime = Arc::new(AgentRuntime::with_provider(
+            provider.clone(),
+            agenticlaw_tools::ToolRegistry::new(),
+            config,
+        ));
+        let driver = Arc::new(LocalFsDriver::new(dir.path().join("runs")));
+        let executor = Executor::with_registry(runtime, driver, registry);
+
+        let manifest = executor
+            .run_issue(RunConfig {
+                purpose: "Test".into(),
+                target: "github.com/o/r/issues/7".into(),
+                issue: 7,
+                system_prompt: String::new(),
+                analysis_prompt: String::new(),
+                context_summary: String::new(),
+                max_iterations: 3,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(manifest.outcome, Outcome::Success);
+        // The branch came from the typed answer, not the fix-7-auto fallback
+        let prompts = provider.prompts.lock().unwrap();
+        assert!(
+            prompts[0].ends_with("Push fix-7-typed."),
+            "unexpected prompt: {}",
+            prompts[0]
+        );
+    }
+}
@@ -6,6 +6,7 @@
 
 use agenticlaw_agent::runtime::{AgentConfig, AgentRuntime};
 use agenticlaw_kg::{Executor, LocalFsDriver, ResourceDriver, RunConfig};
+use agenticlaw_llm::{replay_or_record, AnthropicProvider, LlmProvider, Route, RoutingProvider};
 use agenticlaw_tools::create_default_registry;
 use clap::Parser;
 use std::path::PathBuf;
@@ -87,7 +88,13 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let api_key = std::env::var("ANTHROPIC_API_KEY").expect("ANTHROPIC_API_KEY must be set");
+    // RUSTCLAW_LLM_REPLAY / RUSTCLAW_LLM_RECORD replay or record the run
+    let provider = replay_or_record(|| -> anyhow::Result<Arc<dyn LlmProvider>> {
+        let api_key = std::env::var("ANTHROPIC_API_KEY")
+            .map_err(|_| anyhow::anyhow!("ANTHROPIC_API_KEY must be set"))?;
+        let anthropic: Arc<dyn LlmProvider> = Arc::new(AnthropicProvider::new(&api_key));
+        Ok(Arc::new(RoutingProvider::new(Route::new(anthropic))))
+    })?;
 
     let workspace = cli.workspace.canonicalize().unwrap_or(cli.workspace);
     let tools = create_default_registry(&workspace);
@@ -96,7 +103,7 @@ async fn main() -> anyhow::Result<()> {
         ..Default::default()
     };
 
-    let runtime = Arc::new(AgentRuntime::new(&api_key, tools, config));
+    let runtime = Arc::new(AgentRuntime::with_provider(provider, tools, config));
     let driver = Arc::new(LocalFsDriver::new(&cli.runs_dir));
     let driver_trait: Arc<dyn agenticlaw_kg::ResourceDriver> = driver.clone();
     let executor = Executor::new(runtime, driver_trait);
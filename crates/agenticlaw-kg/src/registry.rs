@@ -67,6 +67,9 @@ pub struct NodeType {
     /// Max tool calls for leaf nodes. Default: 3.
     /// A leaf that can't do its job in this many calls is too broad — decompose further.
     pub max_tool_calls: u32,
+    /// JSON Schema for a leaf's answer. When set, the leaf must answer through
+    /// `final_answer` and its parent reads the typed result instead of scraping text.
+    pub output_schema: Option<serde_json::Value>,
 }
 
 /// The registry: holds all node types and resolves them by ID.
@@ -199,6 +202,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec!["analysis".into(), "impl".into(), "pr".into()],
         success_criterion: Some("All children succeed: analysis produced plan, impl produced passing code, PR is created and CI green.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── PARENT: analysis ───
@@ -222,6 +226,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         ],
         success_criterion: Some("A numbered, falsifiable implementation plan exists.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── LEAF: read-issue ───
@@ -244,6 +249,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("Output contains ## PROBLEM, ## EXPECTED, and ## REPRODUCTION sections.".into()),
         max_tool_calls: 0, // pure reasoning — no tools
+        output_schema: None,
     });
 
     // ─── LEAF: trace-entrypoints ───
@@ -266,6 +272,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("Output contains at least one traced path from entrypoint to affected code.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── LEAF: identify-files ───
@@ -287,6 +294,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("Output is a numbered list of file paths with change descriptions.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── LEAF: check-conflicts ───
@@ -317,6 +325,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
             "Output contains either ## CONFLICTS or ## NO CONFLICTS section.".into(),
         ),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── LEAF: synthesize-plan ───
@@ -346,6 +355,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("Output contains ## IMPLEMENTATION PLAN with numbered changes and tests.".into()),
         max_tool_calls: 0, // pure reasoning
+        output_schema: None,
     });
 
     // ─── PARENT: impl ───
@@ -367,6 +377,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         ],
         success_criterion: Some("All changes applied, tests written, tests pass.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── LEAF: create-branch ───
@@ -381,12 +392,20 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
                          1. `git checkout main && git pull origin main`\n\
                          2. `git checkout -b {branch}`\n\
                          3. Verify: `git log --oneline -3`\n\n\
-                         Output the branch name and the HEAD commit.".into(),
+                         Answer with the branch name and the HEAD commit.".into(),
         role: OperatorRole::Local,
         is_leaf: true,
         children: vec![],
         success_criterion: Some("Branch exists, based on latest main.".into()),
         max_tool_calls: 3,
+        output_schema: Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "branch": {"type": "string", "minLength": 1},
+                "head": {"type": "string"},
+            },
+            "required": ["branch", "head"],
+        })),
     });
 
     // ─── LEAF: apply-changes ───
@@ -410,6 +429,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("All plan changes applied with commits.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── LEAF: write-tests ───
@@ -432,6 +452,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("New test files/cases committed that cover the change.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── LEAF: run-tests ───
@@ -452,6 +473,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("Output contains 'TESTS PASS' with count.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── PARENT: pr ───
@@ -472,6 +494,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         ],
         success_criterion: Some("PR created targeting main, template filled, CI green.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── LEAF: fill-template ───
@@ -492,6 +515,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("PR body text exists with all template sections filled.".into()),
         max_tool_calls: 1, // read template, then pure reasoning
+        output_schema: None,
     });
 
     // ─── LEAF: push-and-create ───
@@ -511,6 +535,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("PR URL output from gh pr create.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     // ─── LEAF: verify-ci ───
@@ -530,6 +555,7 @@ pub fn default_issue_registry() -> NodeTypeRegistry {
         children: vec![],
         success_criterion: Some("Output contains 'CI PASS' or 'CI FAIL' with details.".into()),
         max_tool_calls: 3,
+        output_schema: None,
     });
 
     reg
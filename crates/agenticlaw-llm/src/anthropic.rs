@@ -1,7 +1,8 @@
 //! Anthropic Claude API provider with SSE streaming
 
+use crate::models::{ModelCatalog, ModelInfo};
 use crate::provider::{LlmError, LlmProvider, LlmResult, LlmStream};
-use crate::types::{LlmRequest, StreamDelta, Usage};
+use crate::types::{LlmRequest, StreamDelta, ToolChoice, Usage};
 use futures::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -15,6 +16,7 @@ pub struct AnthropicProvider {
     client: Client,
     api_key: String,
     base_url: String,
+    catalog: ModelCatalog,
 }
 
 impl AnthropicProvider {
@@ -23,6 +25,7 @@ impl AnthropicProvider {
             client: Client::new(),
             api_key: api_key.into(),
             base_url: ANTHROPIC_API_URL.to_string(),
+            catalog: ModelCatalog::default(),
         }
     }
 
@@ -30,6 +33,12 @@ impl AnthropicProvider {
         self.base_url = url.into();
         self
     }
+
+    /// Model metadata overriding the built-in table (from openclaw.json).
+    pub fn with_catalog(mut self, catalog: ModelCatalog) -> Self {
+        self.catalog = catalog;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -46,15 +55,21 @@ impl LlmProvider for AnthropicProvider {
         ]
     }
 
+    fn model_info(&self, model: &str) -> ModelInfo {
+        self.catalog.get(model)
+    }
+
     async fn complete_stream(
         &self,
         request: LlmRequest,
         cancel: Option<CancellationToken>,
     ) -> LlmResult<LlmStream> {
         // Heal any orphaned tool_use blocks before sending
-        let healed_messages = crate::types::validate_and_heal_messages(&request.messages);
+        let mut healed_messages = crate::types::validate_and_heal_messages(&request.messages);
+        crate::types::inline_media(&mut healed_messages).await;
 
-        let body = AnthropicRequest {
+        let cache = request.cache.clone().unwrap_or_default();
+        let mut body = AnthropicRequest {
             model: request.model.clone(),
             messages: healed_messages
                 .iter()
@@ -70,7 +85,21 @@ impl LlmProvider for AnthropicProvider {
                 .collect(),
             max_tokens: request.max_tokens.unwrap_or(8192),
             stream: true,
-            system: request.system.clone(),
+            system: request.system.as_ref().map(|system| {
+                if cache.system {
+                    serde_json::json!([{
+                        "type": "text",
+                        "text": system,
+                        "cache_control": CacheControl::ephemeral(),
+                    }])
+                } else {
+                    serde_json::json!(system)
+                }
+            }),
+            thinking: request.thinking.map(|t| AnthropicThinking {
+                kind: "enabled",
+                budget_tokens: t.budget_tokens,
+            }),
             tools: request.tools.as_ref().map(|tools| {
                 tools
                     .iter()
@@ -78,16 +107,29 @@ impl LlmProvider for AnthropicProvider {
                         name: t.name.clone(),
                         description: t.description.clone(),
                         input_schema: t.input_schema.clone(),
+                        cache_control: None,
                     })
                     .collect()
             }),
+            tool_choice: request.tool_choice.clone(),
         };
+        if cache.tools {
+            if let Some(last) = body.tools.as_mut().and_then(|t| t.last_mut()) {
+                last.cache_control = Some(CacheControl::ephemeral());
+            }
+        }
+        if cache.last_message {
+            if let Some(last) = body.messages.last_mut() {
+                mark_cache_breakpoint(&mut last.content);
+            }
+        }
 
         info!(
             model = %body.model,
             messages = body.messages.len(),
             max_tokens = body.max_tokens,
             has_tools = body.tools.is_some(),
+            thinking_budget = body.thinking.as_ref().map(|t| t.budget_tokens).unwrap_or(0),
             tool_count = body.tools.as_ref().map(|t| t.len()).unwrap_or(0),
             "Anthropic API request"
         );
@@ -105,6 +147,12 @@ impl LlmProvider for AnthropicProvider {
         let status = response.status();
 
         if !status.is_success() {
+            let retry_after_ms = response
+                .headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(|secs| secs * 1000);
             let error_text = response.text().await.unwrap_or_default();
             error!("Anthropic error {}: {}", status, error_text);
 
@@ -112,7 +160,12 @@ impl LlmProvider for AnthropicProvider {
                 return Err(LlmError::AuthFailed(error_text));
             } else if status.as_u16() == 429 {
                 return Err(LlmError::RateLimited {
-                    retry_after_ms: 60000,
+                    retry_after_ms: retry_after_ms.unwrap_or(60000),
+                });
+            } else if status.is_server_error() {
+                return Err(LlmError::ServerError {
+                    status: status.as_u16(),
+                    message: error_text,
                 });
             } else {
                 return Err(LlmError::RequestFailed(format!(
@@ -134,6 +187,8 @@ fn parse_sse_stream(
     async_stream::stream! {
         let mut buffer = String::new();
         let mut current_tool_id: Option<String> = None;
+        // Prompt-side counts arrive in message_start; output in message_delta
+        let mut usage = Usage::default();
 
         tokio::pin!(bytes_stream);
 
@@ -188,6 +243,13 @@ fn parse_sse_stream(
                 if event_data.is_empty() { continue; }
 
                 match event_type.as_str() {
+                    "message_start" => {
+                        if let Ok(data) = serde_json::from_str::<MessageStart>(&event_data) {
+                            if let Some(u) = data.message.usage {
+                                usage.merge(&u);
+                            }
+                        }
+                    }
                     "content_block_start" => {
                         if let Ok(data) = serde_json::from_str::<ContentBlockStart>(&event_data) {
                             match data.content_block {
@@ -195,7 +257,10 @@ fn parse_sse_stream(
                                     current_tool_id = Some(id.clone());
                                     yield Ok(StreamDelta::ToolCallStart { id, name });
                                 }
-                                ContentBlockType::Text { .. } => {}
+                                ContentBlockType::RedactedThinking { data } => {
+                                    yield Ok(StreamDelta::RedactedThinking(data));
+                                }
+                                ContentBlockType::Text { .. } | ContentBlockType::Thinking { .. } => {}
                             }
                         }
                     }
@@ -208,6 +273,9 @@ fn parse_sse_stream(
                                 DeltaType::ThinkingDelta { thinking } => {
                                     yield Ok(StreamDelta::Thinking(thinking));
                                 }
+                                DeltaType::SignatureDelta { signature } => {
+                                    yield Ok(StreamDelta::ThinkingSignature(signature));
+                                }
                                 DeltaType::InputJsonDelta { partial_json } => {
                                     if let Some(id) = &current_tool_id {
                                         yield Ok(StreamDelta::ToolCallDelta {
@@ -227,16 +295,22 @@ fn parse_sse_stream(
                     "message_delta" => {
                         if let Ok(data) = serde_json::from_str::<MessageDelta>(&event_data) {
                             let stop_reason = data.delta.stop_reason.clone();
-                            let usage = data.usage.clone();
                             if let Some(ref sr) = stop_reason {
                                 debug!(stop_reason = %sr, "Message complete");
                             }
-                            if let Some(ref u) = usage {
-                                info!(input_tokens = u.input_tokens, output_tokens = u.output_tokens, "Token usage");
+                            if let Some(ref u) = data.usage {
+                                usage.merge(u);
                             }
+                            info!(
+                                input_tokens = usage.input_tokens,
+                                output_tokens = usage.output_tokens,
+                                cache_creation_input_tokens = usage.cache_creation_input_tokens,
+                                cache_read_input_tokens = usage.cache_read_input_tokens,
+                                "Token usage"
+                            );
                             yield Ok(StreamDelta::Done {
                                 stop_reason,
-                                usage,
+                                usage: Some(usage.clone()),
                             });
                         }
                     }
@@ -261,10 +335,55 @@ struct AnthropicRequest {
     messages: Vec<AnthropicMessage>,
     max_tokens: u32,
     stream: bool,
+    /// Plain string, or a text block array when the system prompt is cached.
     #[serde(skip_serializing_if = "Option::is_none")]
-    system: Option<String>,
+    system: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<AnthropicThinking>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<AnthropicTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<ToolChoice>,
+}
+
+#[derive(Serialize)]
+struct AnthropicThinking {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    budget_tokens: u32,
+}
+
+/// Put a `cache_control` marker on the last content block of a message,
+/// expanding plain string content into a single text block first.
+fn mark_cache_breakpoint(content: &mut serde_json::Value) {
+    if let Some(text) = content.as_str() {
+        if text.is_empty() {
+            return; // empty text blocks can't carry cache_control
+        }
+        *content = serde_json::json!([{ "type": "text", "text": text }]);
+    }
+    if let Some(block) = content
+        .as_array_mut()
+        .and_then(|blocks| blocks.last_mut())
+        .and_then(|b| b.as_object_mut())
+    {
+        block.insert(
+            "cache_control".into(),
+            serde_json::json!(CacheControl::ephemeral()),
+        );
+    }
+}
+
+#[derive(Serialize)]
+struct CacheControl {
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+impl CacheControl {
+    fn ephemeral() -> Self {
+        Self { kind: "ephemeral" }
+    }
 }
 
 #[derive(Serialize)]
@@ -278,6 +397,8 @@ struct AnthropicTool {
     name: String,
     description: String,
     input_schema: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_control: Option<CacheControl>,
 }
 
 #[derive(Deserialize)]
@@ -295,6 +416,11 @@ enum ContentBlockType {
     #[serde(rename = "text")]
     #[allow(dead_code)]
     Text { text: String },
+    #[serde(rename = "thinking")]
+    #[allow(dead_code)]
+    Thinking { thinking: String },
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
 }
 
 #[derive(Deserialize)]
@@ -312,14 +438,25 @@ enum DeltaType {
     TextDelta { text: String },
     #[serde(rename = "thinking_delta")]
     ThinkingDelta { thinking: String },
+    #[serde(rename = "signature_delta")]
+    SignatureDelta { signature: String },
     #[serde(rename = "input_json_delta")]
     InputJsonDelta { partial_json: String },
 }
 
+#[derive(Deserialize)]
+struct MessageStart {
+    message: MessageStartContent,
+}
+
+#[derive(Deserialize)]
+struct MessageStartContent {
+    usage: Option<Usage>,
+}
+
 #[derive(Deserialize)]
 struct MessageDelta {
     delta: MessageDeltaContent,
-    #[allow(dead_code)]
     usage: Option<Usage>,
 }
 
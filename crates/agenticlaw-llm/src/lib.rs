@@ -1,10 +1,20 @@
 //! Agenticlaw LLM - Provider adapters with streaming support
 
 pub mod anthropic;
+pub mod models;
+pub mod openai;
+pub mod pricing;
 pub mod provider;
+pub mod replay;
+pub mod router;
 pub mod types;
 
 pub use anthropic::AnthropicProvider;
+pub use models::{ModelCatalog, ModelInfo};
+pub use openai::OpenAiCompatProvider;
+pub use pricing::ModelPricing;
 pub use provider::{LlmError, LlmProvider};
+pub use replay::{replay_or_record, Fixture, RecordingProvider, ReplayProvider};
+pub use router::{RetryPolicy, Route, RoutingProvider};
 pub use tokio_util::sync::CancellationToken;
 pub use types::*;
@@ -0,0 +1,169 @@
+//! Model capabilities — context window, output limit, feature support, pricing
+//!
+//! Built-in defaults cover the Claude models we run. Anything else (local
+//! servers, new releases) is described in openclaw.json under
+//! `models.providers.<provider>.models`, which overrides the built-ins field
+//! by field.
+
+use crate::pricing::ModelPricing;
+use crate::types::ThinkingConfig;
+use agenticlaw_core::openclaw_config::OcModelEntry;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct ModelInfo {
+    /// Max prompt + output tokens per request.
+    pub context_window: usize,
+    /// Max tokens the model can generate in one response.
+    pub max_output_tokens: usize,
+    pub supports_thinking: bool,
+    pub supports_tools: bool,
+    /// `None` for models without known pricing (e.g. self-hosted).
+    pub pricing: Option<ModelPricing>,
+}
+
+impl ModelInfo {
+    /// Conservative fallback for models we know nothing about.
+    pub const DEFAULT: ModelInfo = ModelInfo {
+        context_window: 128_000,
+        max_output_tokens: 8192,
+        supports_thinking: false,
+        supports_tools: true,
+        pricing: None,
+    };
+
+    const fn claude(
+        context_window: usize,
+        max_output_tokens: usize,
+        supports_thinking: bool,
+        pricing: ModelPricing,
+    ) -> Self {
+        Self {
+            context_window,
+            max_output_tokens,
+            supports_thinking,
+            supports_tools: true,
+            pricing: Some(pricing),
+        }
+    }
+
+    /// Built-in capabilities, matched by model id prefix.
+    pub fn builtin(model: &str) -> Option<Self> {
+        BUILTIN_MODELS
+            .iter()
+            .find(|(prefix, _)| model.starts_with(prefix))
+            .map(|(_, info)| *info)
+    }
+
+    /// Built-in capabilities, or `DEFAULT` for unknown models.
+    pub fn for_model(model: &str) -> Self {
+        Self::builtin(model).unwrap_or(Self::DEFAULT)
+    }
+
+    /// `max_tokens` and thinking settings for one request: `answer_tokens`
+    /// for the visible reply plus the thinking budget, capped at the model's
+    /// output limit. Thinking is dropped for models without it, and the budget
+    /// shrinks to fit when the cap bites.
+    pub fn output_limits(
+        &self,
+        answer_tokens: usize,
+        thinking: Option<ThinkingConfig>,
+    ) -> (u32, Option<ThinkingConfig>) {
+        let thinking = thinking.filter(|_| self.supports_thinking);
+        let budget = thinking.map_or(0, |t| t.budget_tokens as usize);
+        let max_tokens = (answer_tokens + budget).min(self.max_output_tokens);
+        let thinking = thinking.and_then(|t| {
+            let room = max_tokens - answer_tokens.min(max_tokens / 2);
+            let budget = (t.budget_tokens as usize).min(room) as u32;
+            (budget >= ThinkingConfig::MIN_BUDGET_TOKENS).then_some(ThinkingConfig::new(budget))
+        });
+        (max_tokens as u32, thinking)
+    }
+
+    /// Apply the fields set in an openclaw.json model entry.
+    pub fn with_entry(mut self, entry: &OcModelEntry) -> Self {
+        if let Some(n) = entry.context_window {
+            self.context_window = n;
+        }
+        if let Some(n) = entry.max_tokens {
+            self.max_output_tokens = n;
+        }
+        if let Some(b) = entry.reasoning {
+            self.supports_thinking = b;
+        }
+        if let Some(b) = entry.tools {
+            self.supports_tools = b;
+        }
+        if let Some(cost) = &entry.cost {
+            self.pricing = Some(ModelPricing {
+                input_per_mtok: cost.input,
+                output_per_mtok: cost.output,
+                cache_write_per_mtok: cost.cache_write,
+                cache_read_per_mtok: cost.cache_read,
+            });
+        }
+        self
+    }
+}
+
+/// Longest prefixes first so e.g. `claude-opus-4-6` wins over `claude-opus-4`.
+const BUILTIN_MODELS: &[(&str, ModelInfo)] = &[
+    (
+        "claude-opus-4-6",
+        ModelInfo::claude(200_000, 128_000, true, ModelPricing::anthropic(5.0, 25.0)),
+    ),
+    (
+        "claude-opus-4-5",
+        ModelInfo::claude(200_000, 64_000, true, ModelPricing::anthropic(5.0, 25.0)),
+    ),
+    (
+        "claude-opus-4",
+        ModelInfo::claude(200_000, 32_000, true, ModelPricing::anthropic(15.0, 75.0)),
+    ),
+    (
+        "claude-sonnet-4",
+        ModelInfo::claude(200_000, 64_000, true, ModelPricing::anthropic(3.0, 15.0)),
+    ),
+    (
+        "claude-3-7-sonnet",
+        ModelInfo::claude(200_000, 64_000, true, ModelPricing::anthropic(3.0, 15.0)),
+    ),
+    (
+        "claude-haiku-4-5",
+        ModelInfo::claude(200_000, 64_000, true, ModelPricing::anthropic(1.0, 5.0)),
+    ),
+    (
+        "claude-3-5-haiku",
+        ModelInfo::claude(200_000, 8192, false, ModelPricing::anthropic(0.8, 4.0)),
+    ),
+];
+
+/// Per-provider model table: configured entries layered over the built-ins.
+#[derive(Clone, Debug, Default)]
+pub struct ModelCatalog {
+    configured: HashMap<String, ModelInfo>,
+}
+
+impl ModelCatalog {
+    pub fn from_entries(entries: &[OcModelEntry]) -> Self {
+        let configured = entries
+            .iter()
+            .filter(|e| !e.id.is_empty())
+            .map(|e| (e.id.clone(), ModelInfo::for_model(&e.id).with_entry(e)))
+            .collect();
+        Self { configured }
+    }
+
+    pub fn insert(&mut self, model: impl Into<String>, info: ModelInfo) {
+        self.configured.insert(model.into(), info);
+    }
+
+    /// Configured entry for `model`, else the built-in table, else `DEFAULT`.
+    pub fn get(&self, model: &str) -> ModelInfo {
+        self.configured
+            .get(model)
+            .copied()
+            .unwrap_or_else(|| ModelInfo::for_model(model))
+    }
+}
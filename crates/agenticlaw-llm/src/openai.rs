@@ -0,0 +1,549 @@
+//! OpenAI-compatible chat completions provider with SSE streaming
+//!
+//! Speaks the `/v1/chat/completions` streaming dialect served by OpenAI and by
+//! local inference servers (vLLM, llama.cpp, Ollama). Messages and tool calls
+//! are translated to and from the OpenAI function-calling shapes so the rest of
+//! the runtime keeps working in Anthropic-style content blocks.
+
+use crate::models::{ModelCatalog, ModelInfo};
+use crate::provider::{LlmError, LlmProvider, LlmResult, LlmStream};
+use crate::types::{
+    ContentBlock, LlmContent, LlmMessage, LlmRequest, MediaSource, StreamDelta, ToolChoice, Usage,
+};
+use agenticlaw_core::openclaw_config::OcOpenAiProvider;
+use futures::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+
+const OPENAI_API_URL: &str = "https://api.openai.com/v1";
+
+pub struct OpenAiCompatProvider {
+    client: Client,
+    api_key: Option<String>,
+    base_url: String,
+    models: Vec<String>,
+    catalog: ModelCatalog,
+}
+
+impl OpenAiCompatProvider {
+    /// `base_url` is the API root (e.g. `http://localhost:8000/v1`);
+    /// `/chat/completions` is appended per request.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            client: Client::new(),
+            api_key: None,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            models: Vec::new(),
+            catalog: ModelCatalog::default(),
+        }
+    }
+
+    /// Hosted OpenAI endpoint with a bearer key.
+    pub fn openai(api_key: impl Into<String>) -> Self {
+        Self::new(OPENAI_API_URL).with_api_key(api_key)
+    }
+
+    /// Build from the `models.providers.openai` section of openclaw.json.
+    pub fn from_config(config: &OcOpenAiProvider) -> Self {
+        let mut provider = Self::new(config.base_url.as_deref().unwrap_or(OPENAI_API_URL))
+            .with_models(config.models.iter().map(|m| m.id.clone()))
+            .with_catalog(ModelCatalog::from_entries(&config.models));
+        if let Some(key) = &config.api_key {
+            provider = provider.with_api_key(key.clone());
+        }
+        provider
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Restrict `supports_model` to these ids. With no models configured the
+    /// provider accepts any model name, since local servers pick their own ids.
+    pub fn with_models(mut self, models: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.models = models.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Model metadata overriding the built-in table (from openclaw.json).
+    pub fn with_catalog(mut self, catalog: ModelCatalog) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn model_ids(&self) -> &[String] {
+        &self.models
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for OpenAiCompatProvider {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    /// Configured ids are owned strings, so they are exposed via `model_ids()`.
+    fn models(&self) -> &[&str] {
+        &[]
+    }
+
+    fn supports_model(&self, model: &str) -> bool {
+        self.models.is_empty()
+            || self
+                .models
+                .iter()
+                .any(|m| m == model || model.starts_with(m.as_str()))
+    }
+
+    fn model_info(&self, model: &str) -> ModelInfo {
+        self.catalog.get(model)
+    }
+
+    async fn complete_stream(
+        &self,
+        request: LlmRequest,
+        cancel: Option<CancellationToken>,
+    ) -> LlmResult<LlmStream> {
+        let mut healed_messages = crate::types::validate_and_heal_messages(&request.messages);
+        crate::types::inline_media(&mut healed_messages).await;
+
+        let body = OpenAiRequest {
+            model: request.model.clone(),
+            messages: to_openai_messages(request.system.as_deref(), &healed_messages),
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+            stream: true,
+            stream_options: StreamOptions {
+                include_usage: true,
+            },
+            tools: request.tools.as_ref().map(|tools| {
+                tools
+                    .iter()
+                    .map(|t| OpenAiTool {
+                        tool_type: "function",
+                        function: OpenAiFunction {
+                            name: t.name.clone(),
+                            description: t.description.clone(),
+                            parameters: t.input_schema.clone(),
+                        },
+                    })
+                    .collect()
+            }),
+            tool_choice: request.tool_choice.as_ref().map(|choice| match choice {
+                ToolChoice::Auto => json!("auto"),
+                ToolChoice::Any => json!("required"),
+                ToolChoice::Tool { name } => {
+                    json!({ "type": "function", "function": { "name": name } })
+                }
+            }),
+        };
+
+        info!(
+            model = %body.model,
+            messages = body.messages.len(),
+            has_tools = body.tools.is_some(),
+            tool_count = body.tools.as_ref().map(|t| t.len()).unwrap_or(0),
+            base_url = %self.base_url,
+            "OpenAI-compatible API request"
+        );
+
+        let mut builder = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("content-type", "application/json")
+            .json(&body);
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+        let response = builder.send().await?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            let retry_after_ms = response
+                .headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(|secs| secs * 1000);
+            let error_text = response.text().await.unwrap_or_default();
+            error!("OpenAI-compatible error {}: {}", status, error_text);
+
+            return Err(match status.as_u16() {
+                401 | 403 => LlmError::AuthFailed(error_text),
+                429 => LlmError::RateLimited {
+                    retry_after_ms: retry_after_ms.unwrap_or(60000),
+                },
+                code if status.is_server_error() => LlmError::ServerError {
+                    status: code,
+                    message: error_text,
+                },
+                _ => LlmError::RequestFailed(format!("{}: {}", status, error_text)),
+            });
+        }
+
+        let stream = parse_sse_stream(response.bytes_stream(), cancel);
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Translate runtime messages into OpenAI chat messages.
+///
+/// Assistant `tool_use` blocks become `tool_calls`; user `tool_result` blocks
+/// become one `role: "tool"` message each, emitted before any user text that
+/// shared the same turn.
+fn to_openai_messages(system: Option<&str>, messages: &[LlmMessage]) -> Vec<Value> {
+    let mut out = Vec::new();
+    if let Some(system) = system {
+        out.push(json!({ "role": "system", "content": system }));
+    }
+
+    for msg in messages {
+        let blocks = match &msg.content {
+            LlmContent::Text(text) => {
+                out.push(json!({ "role": msg.role, "content": text }));
+                continue;
+            }
+            LlmContent::Blocks(blocks) => blocks,
+        };
+
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+        let mut media = Vec::new();
+        for block in blocks {
+            match block {
+                ContentBlock::Text { text: t } => {
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    text.push_str(t);
+                }
+                ContentBlock::ToolUse { id, name, input } => {
+                    tool_calls.push(json!({
+                        "id": id,
+                        "type": "function",
+                        "function": { "name": name, "arguments": input.to_string() },
+                    }));
+                }
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    ..
+                } => {
+                    out.push(json!({
+                        "role": "tool",
+                        "tool_call_id": tool_use_id,
+                        "content": content.to_text(),
+                    }));
+                    // Tool messages are text-only: images ride along in the
+                    // user message that follows them
+                    if let LlmContent::Blocks(inner) = content {
+                        media.extend(inner.iter().filter_map(media_part));
+                    }
+                }
+                // Chat completions has no way to send reasoning back
+                ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {}
+                ContentBlock::Image { .. } | ContentBlock::Document { .. } => {
+                    media.extend(media_part(block));
+                }
+            }
+        }
+
+        if !tool_calls.is_empty() {
+            let content = if text.is_empty() {
+                Value::Null
+            } else {
+                Value::String(text)
+            };
+            out.push(json!({ "role": msg.role, "content": content, "tool_calls": tool_calls }));
+        } else if !media.is_empty() {
+            let mut parts = Vec::new();
+            if !text.is_empty() {
+                parts.push(json!({ "type": "text", "text": text }));
+            }
+            parts.extend(media);
+            out.push(json!({ "role": msg.role, "content": parts }));
+        } else if !text.is_empty() {
+            out.push(json!({ "role": msg.role, "content": text }));
+        }
+    }
+
+    out
+}
+
+/// Content part for an inlined image or document, as a data URL.
+fn media_part(block: &ContentBlock) -> Option<Value> {
+    let (source, title) = match block {
+        ContentBlock::Image { source } => (source, None),
+        ContentBlock::Document { source, title } => (source, Some(title)),
+        _ => return None,
+    };
+    let MediaSource::Base64 { media_type, data } = source else {
+        return None; // inline_media ran first
+    };
+    let url = format!("data:{};base64,{}", media_type, data);
+    Some(match title {
+        None => json!({ "type": "image_url", "image_url": { "url": url } }),
+        Some(title) => json!({
+            "type": "file",
+            "file": {
+                "filename": title.as_deref().unwrap_or("document.pdf"),
+                "file_data": url,
+            },
+        }),
+    })
+}
+
+/// Map OpenAI `finish_reason` onto the Anthropic stop reasons the runtime checks.
+fn map_finish_reason(reason: &str) -> String {
+    match reason {
+        "stop" => "end_turn",
+        "tool_calls" | "function_call" => "tool_use",
+        "length" => "max_tokens",
+        other => other,
+    }
+    .to_string()
+}
+
+fn parse_sse_stream(
+    bytes_stream: impl futures::Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send + 'static,
+    cancel: Option<CancellationToken>,
+) -> impl futures::Stream<Item = LlmResult<StreamDelta>> + Send {
+    async_stream::stream! {
+        let mut buffer = String::new();
+        // (index, call id) of the tool call still streaming arguments
+        let mut current_tool: Option<(u32, String)> = None;
+        let mut stop_reason: Option<String> = None;
+        let mut usage: Option<Usage> = None;
+        let mut done = false;
+
+        tokio::pin!(bytes_stream);
+
+        'outer: loop {
+            let chunk_result = if let Some(ref token) = cancel {
+                tokio::select! {
+                    biased;
+                    _ = token.cancelled() => {
+                        yield Err(LlmError::Cancelled);
+                        return;
+                    }
+                    next = bytes_stream.next() => {
+                        match next {
+                            Some(r) => r,
+                            None => break,
+                        }
+                    }
+                }
+            } else {
+                match bytes_stream.next().await {
+                    Some(r) => r,
+                    None => break,
+                }
+            };
+
+            let chunk = match chunk_result {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(LlmError::StreamError(e.to_string()));
+                    continue;
+                }
+            };
+
+            buffer.push_str(&String::from_utf8_lossy(&chunk).replace('\r', ""));
+
+            while let Some(event_end) = buffer.find("\n\n") {
+                let event_str = buffer[..event_end].to_string();
+                buffer = buffer[event_end + 2..].to_string();
+
+                let event_data: String = event_str
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("data:"))
+                    .map(str::trim_start)
+                    .collect();
+
+                if event_data.is_empty() { continue; }
+                if event_data == "[DONE]" {
+                    done = true;
+                    break 'outer;
+                }
+
+                let data = match serde_json::from_str::<ChatChunk>(&event_data) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        debug!(error = %e, data = %event_data, "Skipping unparseable chunk");
+                        continue;
+                    }
+                };
+
+                if let Some(err) = data.error {
+                    yield Err(LlmError::StreamError(err.message));
+                    continue;
+                }
+
+                if let Some(u) = data.usage {
+                    // OpenAI's prompt_tokens includes cached tokens; split them out
+                    let cached = u
+                        .prompt_tokens_details
+                        .and_then(|d| d.cached_tokens)
+                        .unwrap_or(0);
+                    usage = Some(Usage {
+                        input_tokens: u.prompt_tokens.saturating_sub(cached),
+                        output_tokens: u.completion_tokens,
+                        cache_read_input_tokens: cached,
+                        ..Default::default()
+                    });
+                }
+
+                for choice in data.choices {
+                    let delta = choice.delta.unwrap_or_default();
+
+                    if let Some(reasoning) = delta.reasoning_content.filter(|s| !s.is_empty()) {
+                        yield Ok(StreamDelta::Thinking(reasoning));
+                    }
+                    if let Some(text) = delta.content.filter(|s| !s.is_empty()) {
+                        yield Ok(StreamDelta::Text(text));
+                    }
+
+                    for call in delta.tool_calls.unwrap_or_default() {
+                        let function = call.function.unwrap_or_default();
+                        let id = match &current_tool {
+                            Some((index, id)) if *index == call.index => id.clone(),
+                            _ => {
+                                // Calls stream one after another; a new index closes the previous one
+                                if let Some((_, id)) = current_tool.take() {
+                                    yield Ok(StreamDelta::ToolCallEnd { id });
+                                }
+                                let id = call
+                                    .id
+                                    .unwrap_or_else(|| format!("call_{}", call.index));
+                                current_tool = Some((call.index, id.clone()));
+                                yield Ok(StreamDelta::ToolCallStart {
+                                    id: id.clone(),
+                                    name: function.name.unwrap_or_default(),
+                                });
+                                id
+                            }
+                        };
+                        if let Some(arguments) = function.arguments.filter(|a| !a.is_empty()) {
+                            yield Ok(StreamDelta::ToolCallDelta { id, arguments });
+                        }
+                    }
+
+                    if let Some(reason) = choice.finish_reason {
+                        debug!(finish_reason = %reason, "Message complete");
+                        if let Some((_, id)) = current_tool.take() {
+                            yield Ok(StreamDelta::ToolCallEnd { id });
+                        }
+                        stop_reason = Some(map_finish_reason(&reason));
+                    }
+                }
+            }
+        }
+
+        // Servers that drop the connection without [DONE] still get a clean end
+        if let Some((_, id)) = current_tool.take() {
+            yield Ok(StreamDelta::ToolCallEnd { id });
+        }
+        if done || stop_reason.is_some() {
+            if let Some(ref u) = usage {
+                info!(input_tokens = u.input_tokens, output_tokens = u.output_tokens, "Token usage");
+            }
+            yield Ok(StreamDelta::Done { stop_reason, usage });
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAiRequest {
+    model: String,
+    messages: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    stream: bool,
+    stream_options: StreamOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<OpenAiTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Serialize)]
+struct OpenAiTool {
+    #[serde(rename = "type")]
+    tool_type: &'static str,
+    function: OpenAiFunction,
+}
+
+#[derive(Serialize)]
+struct OpenAiFunction {
+    name: String,
+    description: String,
+    parameters: Value,
+}
+
+#[derive(Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    usage: Option<ChunkUsage>,
+    error: Option<ChunkError>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    delta: Option<ChunkDelta>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct ChunkDelta {
+    content: Option<String>,
+    reasoning_content: Option<String>,
+    tool_calls: Option<Vec<ChunkToolCall>>,
+}
+
+#[derive(Deserialize)]
+struct ChunkToolCall {
+    #[serde(default)]
+    index: u32,
+    id: Option<String>,
+    function: Option<ChunkFunction>,
+}
+
+#[derive(Deserialize, Default)]
+struct ChunkFunction {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChunkUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Deserialize)]
+struct PromptTokensDetails {
+    cached_tokens: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ChunkError {
+    message: String,
+}
@@ -0,0 +1,43 @@
+//! Per-model token pricing for cost estimates
+//!
+//! Prices are list prices in USD per million tokens. Estimates only — they
+//! ignore batch discounts, long-context surcharges and regional pricing.
+
+use crate::models::ModelInfo;
+use crate::types::Usage;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct ModelPricing {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+    pub cache_write_per_mtok: f64,
+    pub cache_read_per_mtok: f64,
+}
+
+impl ModelPricing {
+    /// Anthropic-style pricing: cache writes cost 1.25x input, reads 0.1x.
+    pub const fn anthropic(input_per_mtok: f64, output_per_mtok: f64) -> Self {
+        Self {
+            input_per_mtok,
+            output_per_mtok,
+            cache_write_per_mtok: input_per_mtok * 1.25,
+            cache_read_per_mtok: input_per_mtok * 0.1,
+        }
+    }
+
+    /// Built-in list prices (see `ModelInfo::builtin`). `None` for unknown
+    /// (e.g. self-hosted) models.
+    pub fn for_model(model: &str) -> Option<Self> {
+        ModelInfo::builtin(model).and_then(|info| info.pricing)
+    }
+
+    /// Estimated cost in USD for one request's usage.
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.input_tokens as f64 * self.input_per_mtok
+            + usage.output_tokens as f64 * self.output_per_mtok
+            + usage.cache_creation_input_tokens as f64 * self.cache_write_per_mtok
+            + usage.cache_read_input_tokens as f64 * self.cache_read_per_mtok)
+            / 1_000_000.0
+    }
+}
@@ -1,5 +1,6 @@
 //! LLM Provider trait
 
+use crate::models::ModelInfo;
 use crate::types::{LlmRequest, StreamDelta};
 use futures::Stream;
 use std::pin::Pin;
@@ -20,6 +21,10 @@ pub enum LlmError {
     #[error("rate limited: retry after {retry_after_ms}ms")]
     RateLimited { retry_after_ms: u64 },
 
+    /// 5xx from the provider (including Anthropic's 529 "overloaded").
+    #[error("server error {status}: {message}")]
+    ServerError { status: u16, message: String },
+
     #[error("context overflow: {0}")]
     ContextOverflow(String),
 
@@ -36,6 +41,16 @@ pub enum LlmError {
     NetworkError(#[from] reqwest::Error),
 }
 
+impl LlmError {
+    /// Transient failures worth retrying against the same provider.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            LlmError::RateLimited { .. } | LlmError::ServerError { .. } | LlmError::NetworkError(_)
+        )
+    }
+}
+
 /// Stream type for LLM responses
 pub type LlmStream = Pin<Box<dyn Stream<Item = LlmResult<StreamDelta>> + Send>>;
 
@@ -51,6 +66,11 @@ pub trait LlmProvider: Send + Sync {
             .any(|m| *m == model || model.starts_with(m))
     }
 
+    /// Capabilities of `model` on this provider. Defaults to the built-in table.
+    fn model_info(&self, model: &str) -> ModelInfo {
+        ModelInfo::for_model(model)
+    }
+
     /// Stream a completion response. If `cancel` is provided and triggered,
     /// the underlying HTTP connection is dropped and the stream yields `LlmError::Cancelled`.
     async fn complete_stream(
@@ -0,0 +1,303 @@
+//! Record/replay providers — deterministic LLM traffic for offline runs
+//!
+//! `RecordingProvider` wraps a real provider and appends every exchange to a
+//! JSON Lines fixture: the request, its key, the stream deltas, and the error
+//! that ended the stream, if any. `ReplayProvider` loads such a file and serves
+//! the recorded deltas for requests with the same key, without touching the
+//! network.
+//!
+//! The key is a hash of the serialized request, so anything that changes the
+//! request (model, prompt, tool results) misses. The temp directory is written
+//! as `$TMPDIR` in keys and fixture files and restored on replay, so recordings
+//! made under one temp directory replay under another. Identical requests
+//! recorded more than once are served in recording order.
+//!
+//! `replay_or_record` wires both up from the environment for binaries.
+
+use crate::models::ModelInfo;
+use crate::provider::{LlmError, LlmProvider, LlmResult, LlmStream};
+use crate::types::{LlmRequest, StreamDelta};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+
+/// One recorded request/response exchange (one line of a fixture file).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Fixture {
+    pub key: String,
+    /// The request as sent, for humans reading the fixture. Not used on replay.
+    #[serde(default)]
+    pub request: serde_json::Value,
+    pub deltas: Vec<StreamDelta>,
+    /// Set when the stream ended with an error after `deltas`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Stands in for the temp directory inside fixtures.
+const TEMP_DIR_TOKEN: &str = "$TMPDIR";
+
+/// The temp directory as it appears inside a JSON string.
+fn temp_dir_json() -> Option<String> {
+    let dir = std::env::temp_dir();
+    let dir = dir.to_str()?.trim_end_matches(['/', '\\']);
+    if dir.is_empty() {
+        return None;
+    }
+    let quoted = serde_json::to_string(dir).ok()?;
+    Some(quoted[1..quoted.len() - 1].to_string())
+}
+
+/// `json` with this machine's temp directory replaced by `TEMP_DIR_TOKEN`.
+fn portable(json: String) -> String {
+    match temp_dir_json() {
+        Some(dir) => json.replace(&dir, TEMP_DIR_TOKEN),
+        None => json,
+    }
+}
+
+/// Inverse of `portable`.
+fn localize(json: &str) -> String {
+    match temp_dir_json() {
+        Some(dir) => json.replace(TEMP_DIR_TOKEN, &dir),
+        None => json.to_string(),
+    }
+}
+
+/// Stable fixture key for a request: FNV-1a over its portable JSON form, as hex.
+pub fn fixture_key(request: &LlmRequest) -> String {
+    let json = portable(serde_json::to_string(request).unwrap_or_default());
+    let hash = json.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Wraps a provider and appends each exchange to a fixture file.
+///
+/// An exchange is written when its stream reaches `Done`, an error or its
+/// end; streams dropped before that are not recorded. Errors opening a stream
+/// are passed through unrecorded.
+pub struct RecordingProvider {
+    inner: Arc<dyn LlmProvider>,
+    path: PathBuf,
+    write_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl RecordingProvider {
+    pub fn new(inner: Arc<dyn LlmProvider>, path: impl AsRef<Path>) -> Self {
+        Self {
+            inner,
+            path: path.as_ref().to_path_buf(),
+            write_lock: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for RecordingProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn models(&self) -> &[&str] {
+        self.inner.models()
+    }
+
+    fn supports_model(&self, model: &str) -> bool {
+        self.inner.supports_model(model)
+    }
+
+    fn model_info(&self, model: &str) -> ModelInfo {
+        self.inner.model_info(model)
+    }
+
+    async fn complete_stream(
+        &self,
+        request: LlmRequest,
+        cancel: Option<CancellationToken>,
+    ) -> LlmResult<LlmStream> {
+        let key = fixture_key(&request);
+        let request_json = serde_json::to_value(&request).unwrap_or_default();
+        let mut inner = self.inner.complete_stream(request, cancel).await?;
+        let path = self.path.clone();
+        let write_lock = self.write_lock.clone();
+
+        Ok(Box::pin(async_stream::stream! {
+            let mut deltas = Vec::new();
+            let mut error = None;
+            let mut last = None;
+            while let Some(item) = inner.next().await {
+                match &item {
+                    Ok(delta) => deltas.push(delta.clone()),
+                    Err(e) => error = Some(e.to_string()),
+                }
+                if matches!(item, Ok(StreamDelta::Done { .. }) | Err(_)) {
+                    last = Some(item);
+                    break;
+                }
+                yield item;
+            }
+            // Consumers often stop reading at Done, so record before handing it over
+            let fixture = Fixture { key, request: request_json, deltas, error };
+            {
+                let _guard = write_lock.lock().await;
+                if let Err(e) = append_fixture(&path, &fixture).await {
+                    tracing::warn!(path = %path.display(), error = %e, "failed to record fixture");
+                }
+            }
+            if let Some(item) = last {
+                yield item;
+            }
+        }))
+    }
+}
+
+async fn append_fixture(path: &Path, fixture: &Fixture) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut line = portable(serde_json::to_string(fixture)?);
+    line.push('\n');
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+    // tokio hands the write to a blocking thread; wait for it to land
+    file.flush().await
+}
+
+/// Serves recorded exchanges by request key. Unknown requests fail with
+/// `RequestFailed` naming the key, so a stale fixture is easy to spot.
+pub struct ReplayProvider {
+    fixtures: HashMap<String, Vec<Fixture>>,
+    /// How many times each key has been served.
+    served: Mutex<HashMap<String, usize>>,
+}
+
+impl ReplayProvider {
+    pub fn new(fixtures: impl IntoIterator<Item = Fixture>) -> Self {
+        let mut by_key: HashMap<String, Vec<Fixture>> = HashMap::new();
+        for fixture in fixtures {
+            by_key.entry(fixture.key.clone()).or_default().push(fixture);
+        }
+        Self {
+            fixtures: by_key,
+            served: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Load a fixture file written by `RecordingProvider`.
+    pub fn load(path: impl AsRef<Path>) -> LlmResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| LlmError::RequestFailed(format!("fixture {}: {}", path.display(), e)))?;
+        let fixtures = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(&localize(line)).map_err(|e| {
+                    LlmError::InvalidResponse(format!(
+                        "fixture {}:{}: {}",
+                        path.display(),
+                        i + 1,
+                        e
+                    ))
+                })
+            })
+            .collect::<LlmResult<Vec<Fixture>>>()?;
+        Ok(Self::new(fixtures))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fixtures.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fixtures.is_empty()
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for ReplayProvider {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    fn models(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Any model may have been recorded; misses surface per request.
+    fn supports_model(&self, _model: &str) -> bool {
+        true
+    }
+
+    async fn complete_stream(
+        &self,
+        request: LlmRequest,
+        cancel: Option<CancellationToken>,
+    ) -> LlmResult<LlmStream> {
+        if cancel.as_ref().is_some_and(CancellationToken::is_cancelled) {
+            return Err(LlmError::Cancelled);
+        }
+        let key = fixture_key(&request);
+        let recorded = self.fixtures.get(&key).ok_or_else(|| {
+            LlmError::RequestFailed(format!(
+                "no recorded response for request {} (model {})",
+                key, request.model
+            ))
+        })?;
+        let fixture = {
+            let mut served = self.served.lock().unwrap();
+            let n = served.entry(key).or_insert(0);
+            // Past the end, keep serving the last recording
+            let fixture = &recorded[(*n).min(recorded.len() - 1)];
+            *n += 1;
+            fixture.clone()
+        };
+
+        let mut items: Vec<LlmResult<StreamDelta>> = fixture.deltas.into_iter().map(Ok).collect();
+        if let Some(e) = fixture.error {
+            items.push(Err(LlmError::StreamError(e)));
+        }
+        Ok(Box::pin(futures::stream::iter(items)))
+    }
+}
+
+/// Provider for a binary run, per the environment:
+/// `RUSTCLAW_LLM_REPLAY=<fixture>` serves recorded responses instead of
+/// calling `live` (no network, no key needed); `RUSTCLAW_LLM_RECORD=<fixture>`
+/// appends every exchange with the live provider to that file.
+pub fn replay_or_record<E: From<LlmError>>(
+    live: impl FnOnce() -> Result<Arc<dyn LlmProvider>, E>,
+) -> Result<Arc<dyn LlmProvider>, E> {
+    if let Ok(path) = std::env::var("RUSTCLAW_LLM_REPLAY") {
+        let replay = ReplayProvider::load(&path)?;
+        tracing::info!(
+            "Replaying {} recorded LLM responses from {}",
+            replay.len(),
+            path
+        );
+        return Ok(Arc::new(replay));
+    }
+    let provider = live()?;
+    match std::env::var("RUSTCLAW_LLM_RECORD") {
+        Ok(path) => {
+            tracing::info!("Recording LLM traffic to {}", path);
+            Ok(Arc::new(RecordingProvider::new(provider, path)))
+        }
+        Err(_) => Ok(provider),
+    }
+}
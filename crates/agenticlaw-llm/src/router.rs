@@ -0,0 +1,249 @@
+//! Routing provider — retry, backoff and failover across providers
+//!
+//! Wraps an ordered list of routes (provider + optional model override).
+//! Transient errors (`RateLimited`, `ServerError`, `NetworkError`) are retried
+//! against the same route: rate limits wait out `retry_after_ms`, everything
+//! else uses jittered exponential backoff. Once a route's retries are
+//! exhausted the next route is tried, and the returned stream starts with a
+//! `StreamDelta::Failover` so the runtime can tell clients.
+//!
+//! Retries only cover opening the stream. Errors after the first delta has
+//! been yielded are passed through, since the caller has already seen output.
+
+use crate::models::ModelInfo;
+use crate::provider::{LlmError, LlmProvider, LlmResult, LlmStream};
+use crate::types::{LlmRequest, StreamDelta};
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// One provider/model pair the router may send a request to.
+#[derive(Clone)]
+pub struct Route {
+    pub provider: Arc<dyn LlmProvider>,
+    /// Model to use instead of the request's model (e.g. a cheaper fallback).
+    pub model: Option<String>,
+}
+
+impl Route {
+    pub fn new(provider: Arc<dyn LlmProvider>) -> Self {
+        Self {
+            provider,
+            model: None,
+        }
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    fn label(&self, request_model: &str) -> String {
+        format!(
+            "{}/{}",
+            self.provider.name(),
+            self.model.as_deref().unwrap_or(request_model)
+        )
+    }
+}
+
+/// How hard to retry a single route before failing over.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries per route after the first attempt.
+    pub max_retries: u32,
+    /// First backoff delay; doubles per attempt.
+    pub base_delay_ms: u64,
+    /// Upper bound for a single backoff delay.
+    pub max_delay_ms: u64,
+    /// Rate limits asking us to wait longer than this fail over immediately.
+    pub max_retry_after_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+            max_retry_after_ms: 60_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (0-based) for the given error,
+    /// or `None` if the error should not be retried on this route.
+    pub fn delay_for(&self, attempt: u32, error: &LlmError) -> Option<Duration> {
+        if attempt >= self.max_retries || !error.is_retryable() {
+            return None;
+        }
+        match error {
+            LlmError::RateLimited { retry_after_ms } => (*retry_after_ms
+                <= self.max_retry_after_ms)
+                .then(|| Duration::from_millis(*retry_after_ms)),
+            _ => {
+                let exp = self
+                    .base_delay_ms
+                    .saturating_mul(1u64 << attempt.min(20))
+                    .min(self.max_delay_ms);
+                // Full jitter in [exp/2, exp] keeps concurrent clients from retrying in lockstep
+                let half = exp / 2;
+                Some(Duration::from_millis(half + jitter(exp - half)))
+            }
+        }
+    }
+}
+
+fn jitter(range_ms: u64) -> u64 {
+    if range_ms == 0 {
+        return 0;
+    }
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    nanos % (range_ms + 1)
+}
+
+pub struct RoutingProvider {
+    routes: Vec<Route>,
+    policy: RetryPolicy,
+}
+
+impl RoutingProvider {
+    /// `primary` is tried first; fallbacks are added with `with_fallback`.
+    pub fn new(primary: Route) -> Self {
+        Self {
+            routes: vec![primary],
+            policy: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_fallback(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for RoutingProvider {
+    fn name(&self) -> &str {
+        self.routes[0].provider.name()
+    }
+
+    fn models(&self) -> &[&str] {
+        self.routes[0].provider.models()
+    }
+
+    fn supports_model(&self, model: &str) -> bool {
+        self.routes[0].provider.supports_model(model)
+    }
+
+    /// Sizing follows the primary route; fallbacks are expected to fit the
+    /// same context (pick fallback models accordingly). A fallback's own
+    /// model, as named in a `Failover`, resolves on that fallback's provider
+    /// so its usage is priced right.
+    fn model_info(&self, model: &str) -> ModelInfo {
+        let route = self.routes[1..]
+            .iter()
+            .find(|r| r.model.as_deref() == Some(model))
+            .unwrap_or(&self.routes[0]);
+        route
+            .provider
+            .model_info(route.model.as_deref().unwrap_or(model))
+    }
+
+    async fn complete_stream(
+        &self,
+        request: LlmRequest,
+        cancel: Option<CancellationToken>,
+    ) -> LlmResult<LlmStream> {
+        let primary_label = self.routes[0].label(&request.model);
+        let mut last_error = None;
+
+        for (index, route) in self.routes.iter().enumerate() {
+            let mut routed = request.clone();
+            if let Some(model) = &route.model {
+                routed.model = model.clone();
+            }
+            let label = route.label(&request.model);
+
+            let mut attempt = 0;
+            let error = loop {
+                match route
+                    .provider
+                    .complete_stream(routed.clone(), cancel.clone())
+                    .await
+                {
+                    Ok(stream) => {
+                        if index == 0 {
+                            return Ok(stream);
+                        }
+                        let reason = last_error
+                            .as_ref()
+                            .map(LlmError::to_string)
+                            .unwrap_or_default();
+                        info!(from = %primary_label, to = %label, "Provider failover");
+                        let notice = StreamDelta::Failover {
+                            from: primary_label,
+                            to: label,
+                            reason,
+                        };
+                        return Ok(Box::pin(
+                            futures::stream::once(async move { Ok(notice) }).chain(stream),
+                        ));
+                    }
+                    Err(LlmError::Cancelled) => return Err(LlmError::Cancelled),
+                    Err(e) => match self.policy.delay_for(attempt, &e) {
+                        Some(delay) => {
+                            warn!(
+                                route = %label,
+                                attempt = attempt + 1,
+                                delay_ms = delay.as_millis() as u64,
+                                error = %e,
+                                "LLM request failed, retrying"
+                            );
+                            sleep_or_cancel(delay, cancel.as_ref()).await?;
+                            attempt += 1;
+                        }
+                        None => break e,
+                    },
+                }
+            };
+
+            if !error.is_retryable() {
+                // Bad requests and auth failures won't get better on another route
+                return Err(error);
+            }
+            warn!(route = %label, error = %error, "Route exhausted");
+            last_error = Some(error);
+        }
+
+        Err(last_error.unwrap_or_else(|| LlmError::RequestFailed("no routes configured".into())))
+    }
+}
+
+async fn sleep_or_cancel(delay: Duration, cancel: Option<&CancellationToken>) -> LlmResult<()> {
+    match cancel {
+        Some(token) => tokio::select! {
+            _ = token.cancelled() => Err(LlmError::Cancelled),
+            _ = tokio::time::sleep(delay) => Ok(()),
+        },
+        None => {
+            tokio::time::sleep(delay).await;
+            Ok(())
+        }
+    }
+}
@@ -1,6 +1,8 @@
 //! LLM types for requests and streaming responses
 
+use base64::Engine;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 /// LLM request
 #[derive(Clone, Debug, Serialize)]
@@ -15,6 +17,15 @@ pub struct LlmRequest {
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub system: Option<String>,
+    /// Prompt cache breakpoints. Providers without prompt caching ignore this.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache: Option<PromptCache>,
+    /// Extended thinking. `max_tokens` must exceed the budget.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+    /// How the model must use `tools`. `None` leaves it to the model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
 }
 
 impl Default for LlmRequest {
@@ -26,6 +37,64 @@ impl Default for LlmRequest {
             max_tokens: Some(8192),
             temperature: None,
             system: None,
+            cache: None,
+            thinking: None,
+            tool_choice: None,
+        }
+    }
+}
+
+/// Constraint on tool use for one request.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ToolChoice {
+    /// Model decides whether to call a tool.
+    Auto,
+    /// Model must call some tool.
+    Any,
+    /// Model must call this tool.
+    Tool { name: String },
+}
+
+/// Extended thinking settings for a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThinkingConfig {
+    /// Tokens the model may spend thinking before it answers.
+    pub budget_tokens: u32,
+}
+
+impl ThinkingConfig {
+    /// Smallest budget Anthropic accepts.
+    pub const MIN_BUDGET_TOKENS: u32 = 1024;
+
+    pub fn new(budget_tokens: u32) -> Self {
+        Self { budget_tokens }
+    }
+}
+
+/// Where to place prompt cache breakpoints (Anthropic `cache_control`).
+///
+/// The cached prefix is tools → system → messages, so each breakpoint caches
+/// everything before it. Prefixes shorter than the model's minimum (1024
+/// tokens for most models) are silently not cached.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PromptCache {
+    /// Breakpoint after the system prompt (SOUL.md, AGENTS.md preload).
+    pub system: bool,
+    /// Breakpoint after the last tool definition.
+    pub tools: bool,
+    /// Rolling breakpoint on the last message, so the next turn reads this
+    /// turn's conversation prefix from cache.
+    pub last_message: bool,
+}
+
+impl PromptCache {
+    /// Breakpoints on system prompt, tools and the rolling message boundary.
+    pub fn all() -> Self {
+        Self {
+            system: true,
+            tools: true,
+            last_message: true,
         }
     }
 }
@@ -57,6 +126,30 @@ impl From<&str> for LlmContent {
     }
 }
 
+impl LlmContent {
+    /// Readable text of the content; images and documents become
+    /// `[image: <media type>]` / `[document: <media type>]` placeholders.
+    pub fn to_text(&self) -> String {
+        match self {
+            LlmContent::Text(s) => s.clone(),
+            LlmContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::Text { text } => Some(text.clone()),
+                    ContentBlock::Image { source } => {
+                        Some(format!("[image: {}]", source.media_type()))
+                    }
+                    ContentBlock::Document { source, .. } => {
+                        Some(format!("[document: {}]", source.media_type()))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
 /// Content block types
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -71,13 +164,188 @@ pub enum ContentBlock {
         input: serde_json::Value,
     },
 
+    /// `content` is a plain string or a list of text/image/document blocks.
     #[serde(rename = "tool_result")]
     ToolResult {
         tool_use_id: String,
-        content: String,
+        content: LlmContent,
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
     },
+
+    /// Must be sent back unchanged (signature included) ahead of the
+    /// tool_use blocks of the same assistant turn.
+    #[serde(rename = "thinking")]
+    Thinking { thinking: String, signature: String },
+
+    /// Thinking the provider flagged and encrypted; `data` is opaque.
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
+
+    #[serde(rename = "image")]
+    Image { source: MediaSource },
+
+    /// PDF or plain-text document.
+    #[serde(rename = "document")]
+    Document {
+        source: MediaSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+    },
+}
+
+impl ContentBlock {
+    pub fn is_thinking(&self) -> bool {
+        matches!(
+            self,
+            ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. }
+        )
+    }
+
+    /// Image or document block for a file on disk, by extension.
+    /// `None` for file types no provider accepts.
+    pub fn from_path(path: impl Into<PathBuf>) -> Option<Self> {
+        let source = MediaSource::from_path(path)?;
+        Some(if source.media_type().starts_with("image/") {
+            ContentBlock::Image { source }
+        } else {
+            ContentBlock::Document {
+                source,
+                title: None,
+            }
+        })
+    }
+
+    /// The source of an image or document block.
+    pub fn media_source(&self) -> Option<&MediaSource> {
+        match self {
+            ContentBlock::Image { source } | ContentBlock::Document { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Bytes of an image or document block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum MediaSource {
+    #[serde(rename = "base64")]
+    Base64 { media_type: String, data: String },
+
+    /// A file on local disk. Never sent as-is: providers read it and send
+    /// base64 at request time (see `inline_media`), so histories and .ctx
+    /// files stay small.
+    #[serde(rename = "path")]
+    Path { media_type: String, path: PathBuf },
+}
+
+impl MediaSource {
+    pub fn from_path(path: impl Into<PathBuf>) -> Option<Self> {
+        let path = path.into();
+        let media_type = media_type_for_path(&path)?.to_string();
+        Some(MediaSource::Path { media_type, path })
+    }
+
+    pub fn media_type(&self) -> &str {
+        match self {
+            MediaSource::Base64 { media_type, .. } | MediaSource::Path { media_type, .. } => {
+                media_type
+            }
+        }
+    }
+
+    /// Base64 payload, reading the file for `Path` sources.
+    pub async fn to_base64(&self) -> std::io::Result<String> {
+        match self {
+            MediaSource::Base64 { data, .. } => Ok(data.clone()),
+            MediaSource::Path { path, .. } => {
+                let bytes = tokio::fs::read(path).await?;
+                Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+            }
+        }
+    }
+}
+
+/// Media type of the images and documents providers accept, by extension.
+pub fn media_type_for_path(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        _ => return None,
+    })
+}
+
+/// File extension for a media type accepted by `media_type_for_path`.
+pub fn extension_for_media_type(media_type: &str) -> Option<&'static str> {
+    Some(match media_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "application/pdf" => "pdf",
+        _ => return None,
+    })
+}
+
+/// Replace every `MediaSource::Path` in `messages` (including inside tool
+/// results) with its base64 contents, ready to send. A file that can no
+/// longer be read becomes a text placeholder, so one lost attachment
+/// doesn't make the whole history unsendable.
+pub async fn inline_media(messages: &mut [LlmMessage]) {
+    for block in media_blocks_mut(messages) {
+        let Some(MediaSource::Path { media_type, path }) = block.media_source() else {
+            continue;
+        };
+        *block = match tokio::fs::read(path).await {
+            Ok(bytes) => with_media_source(
+                block,
+                MediaSource::Base64 {
+                    media_type: media_type.clone(),
+                    data: base64::engine::general_purpose::STANDARD.encode(bytes),
+                },
+            ),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "attachment unavailable");
+                ContentBlock::Text {
+                    text: format!("[attachment unavailable: {}]", path.display()),
+                }
+            }
+        };
+    }
+}
+
+fn with_media_source(block: &ContentBlock, source: MediaSource) -> ContentBlock {
+    match block {
+        ContentBlock::Document { title, .. } => ContentBlock::Document {
+            source,
+            title: title.clone(),
+        },
+        _ => ContentBlock::Image { source },
+    }
+}
+
+fn media_blocks_mut(messages: &mut [LlmMessage]) -> Vec<&mut ContentBlock> {
+    let mut media = Vec::new();
+    for msg in messages {
+        let LlmContent::Blocks(blocks) = &mut msg.content else {
+            continue;
+        };
+        for block in blocks {
+            match block {
+                ContentBlock::Image { .. } | ContentBlock::Document { .. } => media.push(block),
+                ContentBlock::ToolResult {
+                    content: LlmContent::Blocks(inner),
+                    ..
+                } => media.extend(inner.iter_mut().filter(|b| b.media_source().is_some())),
+                _ => {}
+            }
+        }
+    }
+    media
 }
 
 /// Tool definition
@@ -89,10 +357,15 @@ pub struct LlmTool {
 }
 
 /// Streaming delta from LLM
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum StreamDelta {
     Text(String),
     Thinking(String),
+    /// Closes the current thinking block; arrives after its last `Thinking` delta.
+    ThinkingSignature(String),
+    /// A whole redacted thinking block.
+    RedactedThinking(String),
     ToolCallStart {
         id: String,
         name: String,
@@ -109,13 +382,57 @@ pub enum StreamDelta {
         usage: Option<Usage>,
     },
     Error(String),
+    /// Emitted first by `RoutingProvider` when the response comes from a
+    /// fallback route. `from`/`to` are `provider/model`.
+    Failover {
+        from: String,
+        to: String,
+        reason: String,
+    },
 }
 
-/// Token usage
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+/// Token usage as reported by the provider for one request.
+///
+/// `input_tokens` counts only uncached prompt tokens; cache writes and reads
+/// are reported separately (Anthropic semantics, which OpenAI usage is mapped onto).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Usage {
     pub input_tokens: u32,
     pub output_tokens: u32,
+    pub cache_creation_input_tokens: u32,
+    pub cache_read_input_tokens: u32,
+}
+
+impl Usage {
+    /// Full prompt size: uncached input plus cache writes and reads.
+    pub fn prompt_tokens(&self) -> u32 {
+        self.input_tokens + self.cache_creation_input_tokens + self.cache_read_input_tokens
+    }
+
+    pub fn total_tokens(&self) -> u32 {
+        self.prompt_tokens() + self.output_tokens
+    }
+
+    /// Fold a later partial report into this one. Streaming APIs send prompt
+    /// counts up front and output counts at the end; non-zero fields win.
+    pub fn merge(&mut self, later: &Usage) {
+        fn pick(current: &mut u32, later: u32) {
+            if later > 0 {
+                *current = later;
+            }
+        }
+        pick(&mut self.input_tokens, later.input_tokens);
+        pick(&mut self.output_tokens, later.output_tokens);
+        pick(
+            &mut self.cache_creation_input_tokens,
+            later.cache_creation_input_tokens,
+        );
+        pick(
+            &mut self.cache_read_input_tokens,
+            later.cache_read_input_tokens,
+        );
+    }
 }
 
 /// Accumulated tool call from streaming
@@ -127,11 +444,46 @@ pub struct AccumulatedToolCall {
 }
 
 impl AccumulatedToolCall {
+    /// The arguments as JSON. A call streamed with no argument text at all
+    /// (a tool without parameters) has empty arguments: `{}`.
     pub fn parse_arguments(&self) -> Result<serde_json::Value, serde_json::Error> {
+        if self.arguments.trim().is_empty() {
+            return Ok(serde_json::Value::Object(Default::default()));
+        }
         serde_json::from_str(&self.arguments)
     }
 }
 
+/// Thinking blocks reassembled from a stream, in order.
+#[derive(Clone, Debug, Default)]
+pub struct AccumulatedThinking {
+    blocks: Vec<ContentBlock>,
+    current: String,
+}
+
+impl AccumulatedThinking {
+    pub fn push_delta(&mut self, thinking: &str) {
+        self.current.push_str(thinking);
+    }
+
+    pub fn push_signature(&mut self, signature: String) {
+        self.blocks.push(ContentBlock::Thinking {
+            thinking: std::mem::take(&mut self.current),
+            signature,
+        });
+    }
+
+    pub fn push_redacted(&mut self, data: String) {
+        self.blocks.push(ContentBlock::RedactedThinking { data });
+    }
+
+    /// Signed and redacted blocks. Unsigned thinking (e.g. OpenAI-compatible
+    /// `reasoning_content`) can't be replayed to the provider and is dropped.
+    pub fn into_blocks(self) -> Vec<ContentBlock> {
+        self.blocks
+    }
+}
+
 /// Validate and heal message history before sending to Anthropic API.
 ///
 /// Anthropic requires that every `tool_use` block in an assistant message
@@ -185,8 +537,7 @@ pub fn validate_and_heal_messages(messages: &[LlmMessage]) -> Vec<LlmMessage> {
                             for id in &missing {
                                 blocks.push(ContentBlock::ToolResult {
                                     tool_use_id: id.clone(),
-                                    content: "[cancelled] Tool execution was interrupted."
-                                        .to_string(),
+                                    content: "[cancelled] Tool execution was interrupted.".into(),
                                     is_error: Some(true),
                                 });
                             }
@@ -203,7 +554,7 @@ pub fn validate_and_heal_messages(messages: &[LlmMessage]) -> Vec<LlmMessage> {
                         .iter()
                         .map(|id| ContentBlock::ToolResult {
                             tool_use_id: id.clone(),
-                            content: "[cancelled] Tool execution was interrupted.".to_string(),
+                            content: "[cancelled] Tool execution was interrupted.".into(),
                             is_error: Some(true),
                         })
                         .collect();
@@ -124,13 +124,84 @@ fn content_block_tool_result_serde() {
             is_error,
         } => {
             assert_eq!(tool_use_id, "tc-1");
-            assert_eq!(content, "file contents");
+            assert_eq!(content.to_text(), "file contents");
             assert_eq!(is_error, Some(false));
         }
         _ => panic!("Expected ToolResult"),
     }
 }
 
+#[test]
+fn image_and_document_blocks_serde() {
+    let image = ContentBlock::Image {
+        source: MediaSource::Base64 {
+            media_type: "image/png".into(),
+            data: "iVBORw0K".into(),
+        },
+    };
+    assert_eq!(
+        serde_json::to_value(&image).unwrap(),
+        serde_json::json!({
+            "type": "image",
+            "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0K"}
+        })
+    );
+
+    let doc: ContentBlock = serde_json::from_value(serde_json::json!({
+        "type": "document",
+        "source": {"type": "path", "media_type": "application/pdf", "path": "/tmp/spec.pdf"}
+    }))
+    .unwrap();
+    assert_eq!(
+        doc.media_source().unwrap(),
+        &MediaSource::Path {
+            media_type: "application/pdf".into(),
+            path: "/tmp/spec.pdf".into(),
+        }
+    );
+
+    // Tool results may carry blocks; text() keeps a placeholder for the media
+    let result = ContentBlock::ToolResult {
+        tool_use_id: "t1".into(),
+        content: LlmContent::Blocks(vec![
+            ContentBlock::Text {
+                text: "shot".into(),
+            },
+            image,
+        ]),
+        is_error: None,
+    };
+    let back: ContentBlock =
+        serde_json::from_value(serde_json::to_value(&result).unwrap()).unwrap();
+    let ContentBlock::ToolResult { content, .. } = back else {
+        panic!("Expected ToolResult");
+    };
+    assert_eq!(content.to_text(), "shot\n[image: image/png]");
+}
+
+#[test]
+fn media_types_by_extension() {
+    use std::path::Path;
+    assert_eq!(media_type_for_path(Path::new("a.PNG")), Some("image/png"));
+    assert_eq!(media_type_for_path(Path::new("a.jpeg")), Some("image/jpeg"));
+    assert_eq!(
+        media_type_for_path(Path::new("a.pdf")),
+        Some("application/pdf")
+    );
+    assert_eq!(media_type_for_path(Path::new("a.txt")), None);
+    assert_eq!(extension_for_media_type("image/jpeg"), Some("jpg"));
+
+    assert!(matches!(
+        ContentBlock::from_path("x.webp"),
+        Some(ContentBlock::Image { .. })
+    ));
+    assert!(matches!(
+        ContentBlock::from_path("x.pdf"),
+        Some(ContentBlock::Document { .. })
+    ));
+    assert!(ContentBlock::from_path("x.rs").is_none());
+}
+
 #[test]
 fn content_block_tool_result_no_error_skipped() {
     let b = ContentBlock::ToolResult {
@@ -198,6 +269,16 @@ fn accumulated_tool_call_parse_invalid() {
     assert!(tc.parse_arguments().is_err());
 }
 
+#[test]
+fn accumulated_tool_call_parse_empty_is_object() {
+    let tc = AccumulatedToolCall {
+        id: "tc-1".into(),
+        name: "list".into(),
+        arguments: " ".into(),
+    };
+    assert_eq!(tc.parse_arguments().unwrap(), serde_json::json!({}));
+}
+
 #[test]
 fn accumulated_tool_call_default() {
     let tc = AccumulatedToolCall::default();
@@ -222,11 +303,59 @@ fn usage_serde() {
     let u = Usage {
         input_tokens: 100,
         output_tokens: 50,
+        cache_read_input_tokens: 20,
+        ..Default::default()
     };
     let json = serde_json::to_string(&u).unwrap();
     let back: Usage = serde_json::from_str(&json).unwrap();
     assert_eq!(back.input_tokens, 100);
     assert_eq!(back.output_tokens, 50);
+    assert_eq!(back.cache_read_input_tokens, 20);
+}
+
+#[test]
+fn usage_partial_report_deserializes() {
+    // Anthropic message_delta only carries output_tokens
+    let u: Usage = serde_json::from_str(r#"{"output_tokens": 15}"#).unwrap();
+    assert_eq!(u.output_tokens, 15);
+    assert_eq!(u.input_tokens, 0);
+}
+
+#[test]
+fn usage_merge_and_totals() {
+    let mut u = Usage {
+        input_tokens: 10,
+        output_tokens: 1,
+        cache_creation_input_tokens: 100,
+        cache_read_input_tokens: 1000,
+    };
+    u.merge(&Usage {
+        output_tokens: 42,
+        ..Default::default()
+    });
+    assert_eq!(u.input_tokens, 10);
+    assert_eq!(u.output_tokens, 42);
+    assert_eq!(u.prompt_tokens(), 1110);
+    assert_eq!(u.total_tokens(), 1152);
+}
+
+#[test]
+fn model_pricing_builtin_and_cost() {
+    let opus = ModelPricing::for_model("claude-opus-4-6-20250929").unwrap();
+    assert_eq!(opus.input_per_mtok, 5.0);
+    let old_opus = ModelPricing::for_model("claude-opus-4-1-20250805").unwrap();
+    assert_eq!(old_opus.input_per_mtok, 15.0);
+    assert!(ModelPricing::for_model("qwen2.5-coder").is_none());
+
+    let haiku = ModelPricing::for_model("claude-haiku-4-5-20251001").unwrap();
+    let cost = haiku.cost(&Usage {
+        input_tokens: 1_000_000,
+        output_tokens: 1_000_000,
+        cache_creation_input_tokens: 1_000_000,
+        cache_read_input_tokens: 1_000_000,
+    });
+    // 1 + 5 + 1.25 + 0.1
+    assert!((cost - 7.35).abs() < 1e-9, "cost {}", cost);
 }
 
 // ===========================================================================
@@ -261,7 +390,7 @@ async fn anthropic_provider_simple_text_response() {
 
     let provider = AnthropicProvider::new(&api_key);
     assert_eq!(provider.name(), "anthropic");
-    assert!(provider.models().len() > 0);
+    assert!(!provider.models().is_empty());
 
     let request = LlmRequest {
         model: "claude-haiku-4-5-20251001".into(),
@@ -396,9 +525,1372 @@ async fn anthropic_provider_bad_key_fails() {
     assert!(result.is_err(), "Expected error with bad API key");
 }
 
+#[tokio::test]
+async fn anthropic_provider_reports_merged_usage() {
+    let events = [
+        (
+            "message_start",
+            serde_json::json!({"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":12,"cache_creation_input_tokens":0,"cache_read_input_tokens":3000,"output_tokens":1}}}),
+        ),
+        (
+            "content_block_start",
+            serde_json::json!({"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}),
+        ),
+        (
+            "content_block_delta",
+            serde_json::json!({"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"pong"}}),
+        ),
+        (
+            "content_block_stop",
+            serde_json::json!({"type":"content_block_stop","index":0}),
+        ),
+        (
+            "message_delta",
+            serde_json::json!({"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":5}}),
+        ),
+        ("message_stop", serde_json::json!({"type":"message_stop"})),
+    ];
+    let body: String = events
+        .iter()
+        .map(|(event, data)| format!("event: {}\ndata: {}\n\n", event, data))
+        .collect();
+    let (base_url, _server) =
+        mock_server("200 OK", "content-type: text/event-stream\r\n", body).await;
+
+    let provider =
+        AnthropicProvider::new("sk-test").with_base_url(format!("{}/messages", base_url));
+    let request = LlmRequest {
+        messages: vec![LlmMessage {
+            role: "user".into(),
+            content: LlmContent::Text("ping".into()),
+        }],
+        ..Default::default()
+    };
+    let deltas = collect_deltas(provider.complete_stream(request, None).await.unwrap()).await;
+    match deltas.last().unwrap() {
+        StreamDelta::Done { usage: Some(u), .. } => {
+            assert_eq!(u.input_tokens, 12);
+            assert_eq!(u.cache_read_input_tokens, 3000);
+            assert_eq!(u.output_tokens, 5);
+        }
+        other => panic!("Expected Done with usage, got {:?}", other),
+    }
+}
+
+async fn anthropic_request_body(request: LlmRequest) -> serde_json::Value {
+    let body = format!(
+        "event: message_stop\ndata: {}\n\n",
+        serde_json::json!({"type": "message_stop"})
+    );
+    let (base_url, server) =
+        mock_server("200 OK", "content-type: text/event-stream\r\n", body).await;
+    let provider =
+        AnthropicProvider::new("sk-test").with_base_url(format!("{}/messages", base_url));
+    collect_deltas(provider.complete_stream(request, None).await.unwrap()).await;
+    serde_json::from_str(&server.await.unwrap()).unwrap()
+}
+
+fn cacheable_request(cache: Option<PromptCache>) -> LlmRequest {
+    LlmRequest {
+        messages: vec![
+            LlmMessage {
+                role: "user".into(),
+                content: LlmContent::Text("first".into()),
+            },
+            LlmMessage {
+                role: "assistant".into(),
+                content: LlmContent::Text("reply".into()),
+            },
+            LlmMessage {
+                role: "user".into(),
+                content: LlmContent::Text("second".into()),
+            },
+        ],
+        system: Some("You are L0.".into()),
+        tools: Some(vec![
+            LlmTool {
+                name: "read".into(),
+                description: "Read a file".into(),
+                input_schema: serde_json::json!({"type": "object"}),
+            },
+            LlmTool {
+                name: "write".into(),
+                description: "Write a file".into(),
+                input_schema: serde_json::json!({"type": "object"}),
+            },
+        ]),
+        cache,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn anthropic_provider_sends_tool_choice() {
+    let mut request = cacheable_request(None);
+    request.tool_choice = Some(ToolChoice::Tool {
+        name: "write".into(),
+    });
+    let sent = anthropic_request_body(request).await;
+    assert_eq!(
+        sent["tool_choice"],
+        serde_json::json!({"type": "tool", "name": "write"})
+    );
+
+    let sent = anthropic_request_body(cacheable_request(None)).await;
+    assert!(sent.get("tool_choice").is_none());
+}
+
+#[tokio::test]
+async fn anthropic_provider_sends_cache_breakpoints() {
+    let sent = anthropic_request_body(cacheable_request(Some(PromptCache::all()))).await;
+    let ephemeral = serde_json::json!({"type": "ephemeral"});
+
+    assert_eq!(sent["system"][0]["text"], "You are L0.");
+    assert_eq!(sent["system"][0]["cache_control"], ephemeral);
+
+    assert!(sent["tools"][0].get("cache_control").is_none());
+    assert_eq!(sent["tools"][1]["cache_control"], ephemeral);
+
+    // Only the last message carries the rolling breakpoint
+    assert_eq!(sent["messages"][0]["content"], "first");
+    assert_eq!(sent["messages"][2]["content"][0]["text"], "second");
+    assert_eq!(
+        sent["messages"][2]["content"][0]["cache_control"],
+        ephemeral
+    );
+}
+
+#[tokio::test]
+async fn anthropic_provider_marks_last_block_of_block_message() {
+    let mut request = cacheable_request(Some(PromptCache {
+        last_message: true,
+        ..Default::default()
+    }));
+    request.messages.push(LlmMessage {
+        role: "user".into(),
+        content: LlmContent::Blocks(vec![
+            ContentBlock::ToolResult {
+                tool_use_id: "t1".into(),
+                content: "a".into(),
+                is_error: None,
+            },
+            ContentBlock::ToolResult {
+                tool_use_id: "t2".into(),
+                content: "b".into(),
+                is_error: None,
+            },
+        ]),
+    });
+    let sent = anthropic_request_body(request).await;
+    let last = &sent["messages"].as_array().unwrap().last().unwrap()["content"];
+    assert!(last[0].get("cache_control").is_none());
+    assert_eq!(last[1]["cache_control"]["type"], "ephemeral");
+    // System and tools untouched when not requested
+    assert_eq!(sent["system"], "You are L0.");
+    assert!(sent["tools"][1].get("cache_control").is_none());
+}
+
+#[tokio::test]
+async fn anthropic_provider_without_cache_sends_plain_request() {
+    let sent = anthropic_request_body(cacheable_request(None)).await;
+    assert_eq!(sent["system"], "You are L0.");
+    assert_eq!(sent["messages"][2]["content"], "second");
+    assert!(!sent.to_string().contains("cache_control"));
+}
+
+#[tokio::test]
+async fn anthropic_provider_sends_thinking_budget() {
+    let mut request = cacheable_request(None);
+    request.thinking = Some(ThinkingConfig::new(4096));
+    let sent = anthropic_request_body(request).await;
+    assert_eq!(
+        sent["thinking"],
+        serde_json::json!({"type": "enabled", "budget_tokens": 4096})
+    );
+
+    let sent = anthropic_request_body(cacheable_request(None)).await;
+    assert!(sent.get("thinking").is_none());
+}
+
+#[tokio::test]
+async fn anthropic_provider_streams_signed_and_redacted_thinking() {
+    let events = [
+        serde_json::json!({"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":""}}),
+        serde_json::json!({"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Let me "}}),
+        serde_json::json!({"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"check."}}),
+        serde_json::json!({"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"c2ln"}}),
+        serde_json::json!({"type":"content_block_stop","index":0}),
+        serde_json::json!({"type":"content_block_start","index":1,"content_block":{"type":"redacted_thinking","data":"b3BhcXVl"}}),
+        serde_json::json!({"type":"content_block_stop","index":1}),
+        serde_json::json!({"type":"content_block_start","index":2,"content_block":{"type":"text","text":""}}),
+        serde_json::json!({"type":"content_block_delta","index":2,"delta":{"type":"text_delta","text":"42"}}),
+        serde_json::json!({"type":"content_block_stop","index":2}),
+        serde_json::json!({"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":9}}),
+        serde_json::json!({"type":"message_stop"}),
+    ];
+    let body: String = events
+        .iter()
+        .map(|data| {
+            format!(
+                "event: {}\ndata: {}\n\n",
+                data["type"].as_str().unwrap(),
+                data
+            )
+        })
+        .collect();
+    let (base_url, _server) =
+        mock_server("200 OK", "content-type: text/event-stream\r\n", body).await;
+    let provider =
+        AnthropicProvider::new("sk-test").with_base_url(format!("{}/messages", base_url));
+    let deltas = collect_deltas(
+        provider
+            .complete_stream(cacheable_request(None), None)
+            .await
+            .unwrap(),
+    )
+    .await;
+
+    let mut thinking = AccumulatedThinking::default();
+    for delta in &deltas {
+        match delta {
+            StreamDelta::Thinking(t) => thinking.push_delta(t),
+            StreamDelta::ThinkingSignature(s) => thinking.push_signature(s.clone()),
+            StreamDelta::RedactedThinking(d) => thinking.push_redacted(d.clone()),
+            _ => {}
+        }
+    }
+    let blocks = thinking.into_blocks();
+    assert_eq!(blocks.len(), 2);
+    assert!(
+        matches!(&blocks[0], ContentBlock::Thinking { thinking, signature }
+        if thinking == "Let me check." && signature == "c2ln")
+    );
+    assert!(matches!(&blocks[1], ContentBlock::RedactedThinking { data } if data == "b3BhcXVl"));
+    assert!(deltas
+        .iter()
+        .any(|d| matches!(d, StreamDelta::Text(t) if t == "42")));
+}
+
+#[test]
+fn thinking_blocks_serde() {
+    let block = ContentBlock::Thinking {
+        thinking: "hmm".into(),
+        signature: "sig".into(),
+    };
+    assert!(block.is_thinking());
+    let json = serde_json::to_value(&block).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"type": "thinking", "thinking": "hmm", "signature": "sig"})
+    );
+    let redacted: ContentBlock =
+        serde_json::from_value(serde_json::json!({"type": "redacted_thinking", "data": "x"}))
+            .unwrap();
+    assert!(redacted.is_thinking());
+    assert!(!ContentBlock::Text { text: "t".into() }.is_thinking());
+}
+
+#[test]
+fn accumulated_thinking_drops_unsigned_text() {
+    let mut thinking = AccumulatedThinking::default();
+    thinking.push_delta("reasoning without a signature");
+    assert!(thinking.into_blocks().is_empty());
+}
+
+fn temp_media_file(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "agenticlaw-llm-test-{}-{}",
+        std::process::id(),
+        name
+    ));
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn anthropic_provider_inlines_path_media() {
+    let png = temp_media_file("inline.png", b"png-bytes");
+    let image = ContentBlock::from_path(&png).unwrap();
+    let mut request = cacheable_request(None);
+    request.messages[2].content = LlmContent::Blocks(vec![
+        ContentBlock::Text {
+            text: "What is this?".into(),
+        },
+        image.clone(),
+    ]);
+    request.messages.push(LlmMessage {
+        role: "assistant".into(),
+        content: LlmContent::Blocks(vec![ContentBlock::ToolUse {
+            id: "t1".into(),
+            name: "read".into(),
+            input: serde_json::json!({"path": "inline.png"}),
+        }]),
+    });
+    request.messages.push(LlmMessage {
+        role: "user".into(),
+        content: LlmContent::Blocks(vec![ContentBlock::ToolResult {
+            tool_use_id: "t1".into(),
+            content: LlmContent::Blocks(vec![image]),
+            is_error: None,
+        }]),
+    });
+    let sent = anthropic_request_body(request).await;
+    let expected = serde_json::json!({
+        "type": "base64",
+        "media_type": "image/png",
+        "data": "cG5nLWJ5dGVz",
+    });
+    assert_eq!(sent["messages"][2]["content"][1]["source"], expected);
+    assert_eq!(
+        sent["messages"][4]["content"][0]["content"][0]["source"],
+        expected
+    );
+    let _ = std::fs::remove_file(png);
+}
+
+#[tokio::test]
+async fn anthropic_provider_sends_a_placeholder_for_a_missing_attachment() {
+    let mut request = cacheable_request(None);
+    request.messages[2].content = LlmContent::Blocks(vec![
+        ContentBlock::Text {
+            text: "What is this?".into(),
+        },
+        ContentBlock::from_path("/nonexistent/gone.png").unwrap(),
+    ]);
+    let sent = anthropic_request_body(request).await;
+    assert_eq!(
+        sent["messages"][2]["content"][1],
+        serde_json::json!({
+            "type": "text",
+            "text": "[attachment unavailable: /nonexistent/gone.png]",
+        })
+    );
+}
+
 #[test]
 fn anthropic_provider_supports_model() {
     let provider = AnthropicProvider::new("fake");
     assert!(provider.supports_model("claude-haiku-4-5-20251001"));
     assert!(!provider.supports_model("gpt-4"));
 }
+
+// ===========================================================================
+// OpenAiCompatProvider — local mock SSE server
+// ===========================================================================
+
+/// Serve one HTTP request with a canned response. Resolves to the request body.
+async fn mock_server(
+    status: &'static str,
+    headers: &'static str,
+    body: String,
+) -> (String, tokio::task::JoinHandle<String>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut raw = Vec::new();
+        let mut buf = [0u8; 4096];
+        let header_end = loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            raw.extend_from_slice(&buf[..n]);
+            if let Some(pos) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&raw[..header_end]).to_lowercase();
+        let content_length: usize = head
+            .lines()
+            .find_map(|l| l.strip_prefix("content-length:"))
+            .map(|v| v.trim().parse().unwrap())
+            .unwrap_or(0);
+        while raw.len() < header_end + content_length {
+            let n = socket.read(&mut buf).await.unwrap();
+            raw.extend_from_slice(&buf[..n]);
+        }
+        let request_body = String::from_utf8_lossy(&raw[header_end..]).to_string();
+
+        let response = format!(
+            "HTTP/1.1 {}\r\n{}connection: close\r\ncontent-length: {}\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        socket.shutdown().await.ok();
+        request_body
+    });
+
+    (base_url, handle)
+}
+
+fn sse(chunks: &[serde_json::Value]) -> String {
+    let mut out = String::new();
+    for c in chunks {
+        out.push_str(&format!("data: {}\n\n", c));
+    }
+    out.push_str("data: [DONE]\n\n");
+    out
+}
+
+async fn collect_deltas(stream: agenticlaw_llm::provider::LlmStream) -> Vec<StreamDelta> {
+    use futures::StreamExt;
+    stream.map(|r| r.expect("Stream error")).collect().await
+}
+
+#[tokio::test]
+async fn openai_provider_streams_text_and_usage() {
+    let body = sse(&[
+        serde_json::json!({"choices":[{"index":0,"delta":{"role":"assistant","content":"po"},"finish_reason":null}]}),
+        serde_json::json!({"choices":[{"index":0,"delta":{"content":"ng"},"finish_reason":null}]}),
+        serde_json::json!({"choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}),
+        serde_json::json!({"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":2,"total_tokens":14,"prompt_tokens_details":{"cached_tokens":4}}}),
+    ]);
+    let (base_url, server) =
+        mock_server("200 OK", "content-type: text/event-stream\r\n", body).await;
+
+    let provider = OpenAiCompatProvider::new(base_url).with_api_key("sk-local");
+    assert_eq!(provider.name(), "openai");
+    let request = LlmRequest {
+        model: "qwen2.5-coder".into(),
+        system: Some("be terse".into()),
+        messages: vec![LlmMessage {
+            role: "user".into(),
+            content: LlmContent::Text("ping".into()),
+        }],
+        max_tokens: Some(32),
+        ..Default::default()
+    };
+    let deltas = collect_deltas(provider.complete_stream(request, None).await.unwrap()).await;
+
+    let text: String = deltas
+        .iter()
+        .filter_map(|d| match d {
+            StreamDelta::Text(t) => Some(t.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(text, "pong");
+    match deltas.last().unwrap() {
+        StreamDelta::Done { stop_reason, usage } => {
+            assert_eq!(stop_reason.as_deref(), Some("end_turn"));
+            let usage = usage.as_ref().expect("usage");
+            assert_eq!(usage.input_tokens, 8);
+            assert_eq!(usage.cache_read_input_tokens, 4);
+            assert_eq!(usage.output_tokens, 2);
+        }
+        other => panic!("Expected Done last, got {:?}", other),
+    }
+
+    let sent: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+    assert_eq!(sent["model"], "qwen2.5-coder");
+    assert_eq!(sent["stream"], true);
+    assert_eq!(sent["max_tokens"], 32);
+    assert_eq!(sent["messages"][0]["role"], "system");
+    assert_eq!(sent["messages"][0]["content"], "be terse");
+    assert_eq!(sent["messages"][1]["role"], "user");
+    assert_eq!(sent["messages"][1]["content"], "ping");
+}
+
+#[tokio::test]
+async fn openai_provider_streams_tool_calls() {
+    let body = sse(&[
+        serde_json::json!({"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_abc","type":"function","function":{"name":"get_weather","arguments":""}}]},"finish_reason":null}]}),
+        serde_json::json!({"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"city\":"}}]},"finish_reason":null}]}),
+        serde_json::json!({"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"Paris\"}"}}]},"finish_reason":null}]}),
+        serde_json::json!({"choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}),
+    ]);
+    let (base_url, server) =
+        mock_server("200 OK", "content-type: text/event-stream\r\n", body).await;
+
+    let provider = OpenAiCompatProvider::new(base_url);
+    let request = LlmRequest {
+        model: "llama3.1".into(),
+        messages: vec![LlmMessage {
+            role: "user".into(),
+            content: LlmContent::Text("Weather in Paris?".into()),
+        }],
+        tools: Some(vec![LlmTool {
+            name: "get_weather".into(),
+            description: "Get weather for a city".into(),
+            input_schema: serde_json::json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+        }]),
+        ..Default::default()
+    };
+    let deltas = collect_deltas(provider.complete_stream(request, None).await.unwrap()).await;
+
+    let mut args = String::new();
+    let mut started = None;
+    let mut ended = None;
+    let mut stop = None;
+    for d in deltas {
+        match d {
+            StreamDelta::ToolCallStart { id, name } => started = Some((id, name)),
+            StreamDelta::ToolCallDelta { arguments, .. } => args.push_str(&arguments),
+            StreamDelta::ToolCallEnd { id } => ended = Some(id),
+            StreamDelta::Done { stop_reason, .. } => stop = stop_reason,
+            _ => {}
+        }
+    }
+    assert_eq!(
+        started,
+        Some(("call_abc".to_string(), "get_weather".to_string()))
+    );
+    assert_eq!(ended.as_deref(), Some("call_abc"));
+    assert_eq!(stop.as_deref(), Some("tool_use"));
+    let parsed: serde_json::Value = serde_json::from_str(&args).unwrap();
+    assert_eq!(parsed["city"], "Paris");
+
+    let sent: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+    assert_eq!(sent["tools"][0]["type"], "function");
+    assert_eq!(sent["tools"][0]["function"]["name"], "get_weather");
+    assert_eq!(
+        sent["tools"][0]["function"]["parameters"]["properties"]["city"]["type"],
+        "string"
+    );
+}
+
+#[tokio::test]
+async fn openai_provider_closes_each_parallel_tool_call() {
+    let body = sse(&[
+        serde_json::json!({"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_a","function":{"name":"read","arguments":"{\"path\":\"a\"}"}}]},"finish_reason":null}]}),
+        serde_json::json!({"choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"read","arguments":"{\"path\":\"b\"}"}}]},"finish_reason":null}]}),
+        serde_json::json!({"choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}),
+    ]);
+    let (base_url, _server) =
+        mock_server("200 OK", "content-type: text/event-stream\r\n", body).await;
+
+    let provider = OpenAiCompatProvider::new(base_url);
+    let request = LlmRequest {
+        model: "llama3.1".into(),
+        messages: vec![LlmMessage {
+            role: "user".into(),
+            content: LlmContent::Text("Read a and b".into()),
+        }],
+        ..Default::default()
+    };
+    let deltas = collect_deltas(provider.complete_stream(request, None).await.unwrap()).await;
+
+    let order: Vec<String> = deltas
+        .iter()
+        .filter_map(|d| match d {
+            StreamDelta::ToolCallStart { id, .. } => Some(format!("start:{}", id)),
+            StreamDelta::ToolCallEnd { id } => Some(format!("end:{}", id)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        order,
+        vec!["start:call_a", "end:call_a", "start:call_b", "end:call_b"]
+    );
+}
+
+#[tokio::test]
+async fn openai_provider_translates_tool_history() {
+    let body = sse(&[
+        serde_json::json!({"choices":[{"index":0,"delta":{"content":"Sunny."},"finish_reason":"stop"}]}),
+    ]);
+    let (base_url, server) =
+        mock_server("200 OK", "content-type: text/event-stream\r\n", body).await;
+
+    let provider = OpenAiCompatProvider::new(base_url);
+    let request = LlmRequest {
+        model: "llama3.1".into(),
+        messages: vec![
+            LlmMessage {
+                role: "user".into(),
+                content: LlmContent::Text("Weather in Paris?".into()),
+            },
+            LlmMessage {
+                role: "assistant".into(),
+                content: LlmContent::Blocks(vec![
+                    ContentBlock::Text {
+                        text: "Checking.".into(),
+                    },
+                    ContentBlock::ToolUse {
+                        id: "call_1".into(),
+                        name: "get_weather".into(),
+                        input: serde_json::json!({"city": "Paris"}),
+                    },
+                ]),
+            },
+            LlmMessage {
+                role: "user".into(),
+                content: LlmContent::Blocks(vec![ContentBlock::ToolResult {
+                    tool_use_id: "call_1".into(),
+                    content: "sunny, 21C".into(),
+                    is_error: None,
+                }]),
+            },
+        ],
+        ..Default::default()
+    };
+    let deltas = collect_deltas(provider.complete_stream(request, None).await.unwrap()).await;
+    assert!(matches!(deltas.last(), Some(StreamDelta::Done { .. })));
+
+    let sent: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+    let messages = sent["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[1]["role"], "assistant");
+    assert_eq!(messages[1]["content"], "Checking.");
+    assert_eq!(messages[1]["tool_calls"][0]["id"], "call_1");
+    assert_eq!(
+        messages[1]["tool_calls"][0]["function"]["name"],
+        "get_weather"
+    );
+    let arguments: serde_json::Value = serde_json::from_str(
+        messages[1]["tool_calls"][0]["function"]["arguments"]
+            .as_str()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(arguments["city"], "Paris");
+    assert_eq!(messages[2]["role"], "tool");
+    assert_eq!(messages[2]["tool_call_id"], "call_1");
+    assert_eq!(messages[2]["content"], "sunny, 21C");
+}
+
+#[tokio::test]
+async fn openai_provider_maps_tool_choice() {
+    for (choice, expected) in [
+        (ToolChoice::Any, serde_json::json!("required")),
+        (
+            ToolChoice::Tool {
+                name: "final_answer".into(),
+            },
+            serde_json::json!({"type": "function", "function": {"name": "final_answer"}}),
+        ),
+    ] {
+        let body =
+            sse(&[serde_json::json!({"choices":[{"index":0,"delta":{},"finish_reason":"stop"}]})]);
+        let (base_url, server) =
+            mock_server("200 OK", "content-type: text/event-stream\r\n", body).await;
+        let provider = OpenAiCompatProvider::new(base_url);
+        let request = LlmRequest {
+            model: "llama3.1".into(),
+            tool_choice: Some(choice),
+            ..cacheable_request(None)
+        };
+        collect_deltas(provider.complete_stream(request, None).await.unwrap()).await;
+        let sent: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+        assert_eq!(sent["tool_choice"], expected);
+    }
+}
+
+#[tokio::test]
+async fn openai_provider_sends_images_as_content_parts() {
+    let body = sse(&[
+        serde_json::json!({"choices":[{"index":0,"delta":{"content":"A cat."},"finish_reason":"stop"}]}),
+    ]);
+    let (base_url, server) =
+        mock_server("200 OK", "content-type: text/event-stream\r\n", body).await;
+    let image = ContentBlock::Image {
+        source: MediaSource::Base64 {
+            media_type: "image/png".into(),
+            data: "aGk=".into(),
+        },
+    };
+
+    let provider = OpenAiCompatProvider::new(base_url);
+    let request = LlmRequest {
+        model: "llava".into(),
+        messages: vec![
+            LlmMessage {
+                role: "user".into(),
+                content: LlmContent::Blocks(vec![
+                    ContentBlock::Text {
+                        text: "What is this?".into(),
+                    },
+                    image.clone(),
+                ]),
+            },
+            LlmMessage {
+                role: "assistant".into(),
+                content: LlmContent::Blocks(vec![ContentBlock::ToolUse {
+                    id: "call_1".into(),
+                    name: "read".into(),
+                    input: serde_json::json!({"path": "b.png"}),
+                }]),
+            },
+            LlmMessage {
+                role: "user".into(),
+                content: LlmContent::Blocks(vec![ContentBlock::ToolResult {
+                    tool_use_id: "call_1".into(),
+                    content: LlmContent::Blocks(vec![image]),
+                    is_error: None,
+                }]),
+            },
+        ],
+        ..Default::default()
+    };
+    collect_deltas(provider.complete_stream(request, None).await.unwrap()).await;
+
+    let sent: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+    let messages = sent["messages"].as_array().unwrap();
+    assert_eq!(messages[0]["content"][0]["text"], "What is this?");
+    assert_eq!(
+        messages[0]["content"][1]["image_url"]["url"],
+        "data:image/png;base64,aGk="
+    );
+    // Tool messages are text-only; the image follows as a user message
+    assert_eq!(messages[2]["role"], "tool");
+    assert_eq!(messages[2]["content"], "[image: image/png]");
+    assert_eq!(messages[3]["role"], "user");
+    assert_eq!(messages[3]["content"][0]["type"], "image_url");
+}
+
+#[tokio::test]
+async fn openai_provider_maps_rate_limit() {
+    let (base_url, _server) = mock_server(
+        "429 Too Many Requests",
+        "retry-after: 7\r\n",
+        r#"{"error":{"message":"slow down"}}"#.to_string(),
+    )
+    .await;
+
+    let provider = OpenAiCompatProvider::new(base_url);
+    let request = LlmRequest {
+        model: "llama3.1".into(),
+        messages: vec![LlmMessage {
+            role: "user".into(),
+            content: LlmContent::Text("hi".into()),
+        }],
+        ..Default::default()
+    };
+    match provider.complete_stream(request, None).await {
+        Err(LlmError::RateLimited { retry_after_ms }) => assert_eq!(retry_after_ms, 7000),
+        Err(e) => panic!("Expected RateLimited, got {}", e),
+        Ok(_) => panic!("Expected RateLimited, got a stream"),
+    }
+}
+
+#[tokio::test]
+async fn openai_provider_maps_auth_failure() {
+    let (base_url, _server) = mock_server("401 Unauthorized", "", "bad key".to_string()).await;
+
+    let provider = OpenAiCompatProvider::new(base_url).with_api_key("sk-bad");
+    let request = LlmRequest {
+        model: "gpt-4o".into(),
+        messages: vec![LlmMessage {
+            role: "user".into(),
+            content: LlmContent::Text("hi".into()),
+        }],
+        ..Default::default()
+    };
+    assert!(matches!(
+        provider.complete_stream(request, None).await,
+        Err(LlmError::AuthFailed(_))
+    ));
+}
+
+#[test]
+fn openai_provider_supports_model() {
+    let open = OpenAiCompatProvider::new("http://localhost:11434/v1");
+    assert!(open.supports_model("anything-local"));
+
+    let pinned =
+        OpenAiCompatProvider::new("http://localhost:8000/v1/").with_models(["qwen2.5-coder"]);
+    assert_eq!(pinned.base_url(), "http://localhost:8000/v1");
+    assert!(pinned.supports_model("qwen2.5-coder"));
+    assert!(!pinned.supports_model("claude-opus-4-6"));
+}
+
+// ===========================================================================
+// RoutingProvider — retry, backoff, failover
+// ===========================================================================
+
+/// Fails with the queued errors in order, then streams its name as text.
+struct ScriptedProvider {
+    name: &'static str,
+    failures: std::sync::Mutex<std::collections::VecDeque<LlmError>>,
+    calls: std::sync::atomic::AtomicUsize,
+    models_seen: std::sync::Mutex<Vec<String>>,
+}
+
+impl ScriptedProvider {
+    fn new(name: &'static str, failures: Vec<LlmError>) -> std::sync::Arc<Self> {
+        std::sync::Arc::new(Self {
+            name,
+            failures: std::sync::Mutex::new(failures.into()),
+            calls: std::sync::atomic::AtomicUsize::new(0),
+            models_seen: std::sync::Mutex::new(Vec::new()),
+        })
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for ScriptedProvider {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn models(&self) -> &[&str] {
+        &["scripted"]
+    }
+    async fn complete_stream(
+        &self,
+        request: LlmRequest,
+        _cancel: Option<CancellationToken>,
+    ) -> Result<agenticlaw_llm::provider::LlmStream, LlmError> {
+        self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.models_seen.lock().unwrap().push(request.model);
+        if let Some(e) = self.failures.lock().unwrap().pop_front() {
+            return Err(e);
+        }
+        let deltas = vec![
+            Ok(StreamDelta::Text(self.name.to_string())),
+            Ok(StreamDelta::Done {
+                stop_reason: Some("end_turn".into()),
+                usage: None,
+            }),
+        ];
+        Ok(Box::pin(futures::stream::iter(deltas)))
+    }
+}
+
+fn fast_policy(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        base_delay_ms: 1,
+        max_delay_ms: 5,
+        max_retry_after_ms: 50,
+    }
+}
+
+fn server_error() -> LlmError {
+    LlmError::ServerError {
+        status: 529,
+        message: "overloaded".into(),
+    }
+}
+
+#[tokio::test]
+async fn router_retries_transient_errors_on_primary() {
+    let primary = ScriptedProvider::new(
+        "primary",
+        vec![server_error(), LlmError::RateLimited { retry_after_ms: 1 }],
+    );
+    let router = RoutingProvider::new(Route::new(primary.clone())).with_policy(fast_policy(3));
+
+    let deltas = collect_deltas(
+        router
+            .complete_stream(LlmRequest::default(), None)
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(primary.calls(), 3);
+    assert!(matches!(&deltas[0], StreamDelta::Text(t) if t == "primary"));
+    assert!(!deltas
+        .iter()
+        .any(|d| matches!(d, StreamDelta::Failover { .. })));
+}
+
+#[tokio::test]
+async fn router_fails_over_when_primary_exhausted() {
+    let primary = ScriptedProvider::new("primary", vec![server_error(), server_error()]);
+    let secondary = ScriptedProvider::new("secondary", vec![]);
+    let router = RoutingProvider::new(Route::new(primary.clone()))
+        .with_fallback(Route::new(secondary.clone()).with_model("small-model"))
+        .with_policy(fast_policy(1));
+
+    let request = LlmRequest {
+        model: "big-model".into(),
+        ..Default::default()
+    };
+    let deltas = collect_deltas(router.complete_stream(request, None).await.unwrap()).await;
+
+    assert_eq!(primary.calls(), 2);
+    assert_eq!(secondary.calls(), 1);
+    assert_eq!(*secondary.models_seen.lock().unwrap(), vec!["small-model"]);
+    match &deltas[0] {
+        StreamDelta::Failover { from, to, reason } => {
+            assert_eq!(from, "primary/big-model");
+            assert_eq!(to, "secondary/small-model");
+            assert!(reason.contains("529"), "reason: {}", reason);
+        }
+        other => panic!("Expected Failover first, got {:?}", other),
+    }
+    assert!(matches!(&deltas[1], StreamDelta::Text(t) if t == "secondary"));
+}
+
+#[tokio::test]
+async fn router_long_rate_limit_fails_over_without_waiting() {
+    let primary = ScriptedProvider::new(
+        "primary",
+        vec![LlmError::RateLimited {
+            retry_after_ms: 60_000,
+        }],
+    );
+    let secondary = ScriptedProvider::new("secondary", vec![]);
+    let router = RoutingProvider::new(Route::new(primary.clone()))
+        .with_fallback(Route::new(secondary.clone()))
+        .with_policy(fast_policy(3));
+
+    let started = std::time::Instant::now();
+    let deltas = collect_deltas(
+        router
+            .complete_stream(LlmRequest::default(), None)
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert_eq!(primary.calls(), 1);
+    assert!(matches!(&deltas[0], StreamDelta::Failover { .. }));
+}
+
+#[tokio::test]
+async fn router_does_not_retry_auth_failures() {
+    let primary = ScriptedProvider::new("primary", vec![LlmError::AuthFailed("bad key".into())]);
+    let secondary = ScriptedProvider::new("secondary", vec![]);
+    let router = RoutingProvider::new(Route::new(primary.clone()))
+        .with_fallback(Route::new(secondary.clone()))
+        .with_policy(fast_policy(3));
+
+    let result = router.complete_stream(LlmRequest::default(), None).await;
+    assert!(matches!(result, Err(LlmError::AuthFailed(_))));
+    assert_eq!(primary.calls(), 1);
+    assert_eq!(secondary.calls(), 0);
+}
+
+#[tokio::test]
+async fn router_returns_last_error_when_all_routes_exhausted() {
+    let primary = ScriptedProvider::new("primary", vec![server_error(), server_error()]);
+    let secondary = ScriptedProvider::new(
+        "secondary",
+        vec![
+            LlmError::RateLimited { retry_after_ms: 1 },
+            LlmError::RateLimited { retry_after_ms: 1 },
+        ],
+    );
+    let router = RoutingProvider::new(Route::new(primary))
+        .with_fallback(Route::new(secondary.clone()))
+        .with_policy(fast_policy(1));
+
+    let result = router.complete_stream(LlmRequest::default(), None).await;
+    assert!(matches!(result, Err(LlmError::RateLimited { .. })));
+    assert_eq!(secondary.calls(), 2);
+}
+
+#[tokio::test]
+async fn router_backoff_sleep_is_cancellable() {
+    let primary = ScriptedProvider::new("primary", vec![server_error()]);
+    let router = RoutingProvider::new(Route::new(primary)).with_policy(RetryPolicy {
+        base_delay_ms: 60_000,
+        max_delay_ms: 60_000,
+        ..RetryPolicy::default()
+    });
+
+    let cancel = CancellationToken::new();
+    let trigger = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        trigger.cancel();
+    });
+    let result = router
+        .complete_stream(LlmRequest::default(), Some(cancel))
+        .await;
+    assert!(matches!(result, Err(LlmError::Cancelled)));
+}
+
+#[test]
+fn retry_policy_backoff_is_bounded_and_jittered() {
+    let policy = RetryPolicy {
+        max_retries: 10,
+        base_delay_ms: 100,
+        max_delay_ms: 1_000,
+        max_retry_after_ms: 60_000,
+    };
+    let err = server_error();
+    let first = policy.delay_for(0, &err).unwrap().as_millis();
+    assert!((50..=100).contains(&first), "first delay {}", first);
+    let late = policy.delay_for(9, &err).unwrap().as_millis();
+    assert!((500..=1_000).contains(&late), "late delay {}", late);
+    assert!(policy.delay_for(10, &err).is_none());
+    assert!(policy
+        .delay_for(0, &LlmError::ContextOverflow("too long".into()))
+        .is_none());
+    assert_eq!(
+        policy
+            .delay_for(
+                0,
+                &LlmError::RateLimited {
+                    retry_after_ms: 7_000
+                }
+            )
+            .unwrap()
+            .as_millis(),
+        7_000
+    );
+}
+
+// ===========================================================================
+// ModelInfo / ModelCatalog — capability table
+// ===========================================================================
+
+fn model_entry(json: serde_json::Value) -> agenticlaw_core::openclaw_config::OcModelEntry {
+    serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn model_info_builtin_table() {
+    let opus = ModelInfo::for_model("claude-opus-4-6-20250929");
+    assert_eq!(opus.context_window, 200_000);
+    assert_eq!(opus.max_output_tokens, 128_000);
+    assert!(opus.supports_thinking);
+    assert!(opus.supports_tools);
+    assert_eq!(opus.pricing, ModelPricing::for_model("claude-opus-4-6"));
+
+    let haiku = ModelInfo::for_model("claude-3-5-haiku-20241022");
+    assert_eq!(haiku.max_output_tokens, 8192);
+    assert!(!haiku.supports_thinking);
+
+    assert!(ModelInfo::builtin("qwen2.5-coder").is_none());
+    assert_eq!(ModelInfo::for_model("qwen2.5-coder"), ModelInfo::DEFAULT);
+}
+
+#[test]
+fn model_catalog_overrides_builtins_field_by_field() {
+    let catalog = ModelCatalog::from_entries(&[
+        model_entry(serde_json::json!({"id": "claude-opus-4-6", "contextWindow": 1_000_000})),
+        model_entry(serde_json::json!({
+            "id": "qwen2.5-coder",
+            "contextWindow": 32768,
+            "maxTokens": 4096,
+            "tools": false,
+            "cost": {"input": 0.1, "output": 0.2}
+        })),
+    ]);
+
+    let opus = catalog.get("claude-opus-4-6");
+    assert_eq!(opus.context_window, 1_000_000);
+    assert_eq!(opus.max_output_tokens, 128_000); // kept from built-in
+
+    let qwen = catalog.get("qwen2.5-coder");
+    assert_eq!(qwen.context_window, 32768);
+    assert_eq!(qwen.max_output_tokens, 4096);
+    assert!(!qwen.supports_tools);
+    assert_eq!(qwen.pricing.unwrap().output_per_mtok, 0.2);
+
+    // Unconfigured models fall through to the built-in table
+    assert_eq!(
+        catalog.get("claude-haiku-4-5"),
+        ModelInfo::for_model("claude-haiku-4-5")
+    );
+}
+
+#[test]
+fn providers_report_configured_model_info() {
+    use agenticlaw_core::openclaw_config::OcOpenAiProvider;
+    use std::sync::Arc;
+
+    let openai = OpenAiCompatProvider::from_config(&OcOpenAiProvider {
+        base_url: Some("http://localhost:8000/v1".into()),
+        api_key: None,
+        models: vec![model_entry(
+            serde_json::json!({"id": "qwen2.5-coder", "contextWindow": 32768}),
+        )],
+    });
+    assert_eq!(openai.model_info("qwen2.5-coder").context_window, 32768);
+
+    let anthropic =
+        AnthropicProvider::new("k").with_catalog(ModelCatalog::from_entries(&[model_entry(
+            serde_json::json!({"id": "claude-opus-4-6", "contextWindow": 1_000_000}),
+        )]));
+    assert_eq!(
+        anthropic.model_info("claude-opus-4-6").context_window,
+        1_000_000
+    );
+
+    // The router sizes requests by its primary route, honouring a model override
+    let router = RoutingProvider::new(Route::new(Arc::new(openai)).with_model("qwen2.5-coder"))
+        .with_fallback(Route::new(Arc::new(anthropic)));
+    assert_eq!(router.model_info("anything").context_window, 32768);
+}
+
+#[test]
+fn model_info_output_limits_fit_thinking_budget() {
+    let opus = ModelInfo::for_model("claude-opus-4-6");
+    assert_eq!(opus.output_limits(16384, None), (16384, None));
+    assert_eq!(
+        opus.output_limits(16384, Some(ThinkingConfig::new(10_000))),
+        (26384, Some(ThinkingConfig::new(10_000)))
+    );
+
+    // The cap bites: budget shrinks but half the output stays for the answer
+    let opus4 = ModelInfo::for_model("claude-opus-4-1");
+    assert_eq!(
+        opus4.output_limits(16384, Some(ThinkingConfig::new(30_000))),
+        (32_000, Some(ThinkingConfig::new(16_000)))
+    );
+
+    // Models without thinking never get a budget
+    let haiku = ModelInfo::for_model("claude-3-5-haiku-20241022");
+    assert_eq!(
+        haiku.output_limits(16384, Some(ThinkingConfig::new(4096))),
+        (8192, None)
+    );
+
+    // A budget squeezed below the API minimum is dropped
+    let small = ModelInfo {
+        max_output_tokens: 1500,
+        supports_thinking: true,
+        ..ModelInfo::DEFAULT
+    };
+    assert_eq!(
+        small.output_limits(8192, Some(ThinkingConfig::new(4096))),
+        (1500, None)
+    );
+}
+
+// ===========================================================================
+// RecordingProvider / ReplayProvider
+// ===========================================================================
+
+/// Answers with the request's last user text, numbered by call.
+struct EchoProvider {
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for EchoProvider {
+    fn name(&self) -> &str {
+        "echo"
+    }
+    fn models(&self) -> &[&str] {
+        &["echo-1"]
+    }
+    async fn complete_stream(
+        &self,
+        request: LlmRequest,
+        _cancel: Option<CancellationToken>,
+    ) -> Result<agenticlaw_llm::provider::LlmStream, LlmError> {
+        let n = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let text = request.messages.last().unwrap().content.to_text();
+        let mut items = vec![
+            Ok(StreamDelta::ToolCallStart {
+                id: "t1".into(),
+                name: "echo".into(),
+            }),
+            Ok(StreamDelta::Text(format!("{} #{}", text, n))),
+        ];
+        if text == "fail" {
+            items.push(Err(LlmError::StreamError("connection reset".into())));
+        } else {
+            items.push(Ok(StreamDelta::Done {
+                stop_reason: Some("end_turn".into()),
+                usage: Some(Usage {
+                    input_tokens: 3,
+                    output_tokens: 2,
+                    ..Default::default()
+                }),
+            }));
+        }
+        Ok(Box::pin(futures::stream::iter(items)))
+    }
+}
+
+fn echo_request(text: &str) -> LlmRequest {
+    LlmRequest {
+        model: "echo-1".into(),
+        messages: vec![LlmMessage {
+            role: "user".into(),
+            content: LlmContent::Text(text.into()),
+        }],
+        ..Default::default()
+    }
+}
+
+fn fixture_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "agenticlaw-llm-fixture-{}-{}.jsonl",
+        std::process::id(),
+        name
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+async fn collect_results(
+    stream: agenticlaw_llm::provider::LlmStream,
+) -> Vec<Result<StreamDelta, LlmError>> {
+    use futures::StreamExt;
+    stream.collect().await
+}
+
+#[tokio::test]
+async fn recorded_exchanges_replay_without_inner_provider() {
+    let path = fixture_path("roundtrip");
+    let echo = std::sync::Arc::new(EchoProvider {
+        calls: Default::default(),
+    });
+    let recorder = RecordingProvider::new(echo, &path);
+    for text in ["hello", "hello", "other"] {
+        collect_deltas(
+            recorder
+                .complete_stream(echo_request(text), None)
+                .await
+                .unwrap(),
+        )
+        .await;
+    }
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+    let replay = ReplayProvider::load(&path).unwrap();
+    assert_eq!(replay.len(), 3);
+    assert!(replay.supports_model("anything"));
+
+    let text_of = |deltas: &[StreamDelta]| match &deltas[1] {
+        StreamDelta::Text(t) => t.clone(),
+        other => panic!("Expected Text, got {:?}", other),
+    };
+    // Repeated requests come back in recording order, then stick on the last
+    for expected in ["hello #0", "hello #1", "hello #1"] {
+        let deltas = collect_deltas(
+            replay
+                .complete_stream(echo_request("hello"), None)
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(text_of(&deltas), expected);
+    }
+    let deltas = collect_deltas(
+        replay
+            .complete_stream(echo_request("other"), None)
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(text_of(&deltas), "other #2");
+    assert!(
+        matches!(&deltas[0], StreamDelta::ToolCallStart { id, name } if id == "t1" && name == "echo")
+    );
+    match deltas.last().unwrap() {
+        StreamDelta::Done {
+            stop_reason,
+            usage: Some(u),
+        } => {
+            assert_eq!(stop_reason.as_deref(), Some("end_turn"));
+            assert_eq!(u.total_tokens(), 5);
+        }
+        other => panic!("Expected Done with usage, got {:?}", other),
+    }
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn replay_reproduces_stream_errors() {
+    let path = fixture_path("error");
+    let recorder = RecordingProvider::new(
+        std::sync::Arc::new(EchoProvider {
+            calls: Default::default(),
+        }),
+        &path,
+    );
+    let live = collect_results(
+        recorder
+            .complete_stream(echo_request("fail"), None)
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert!(live.last().unwrap().is_err());
+
+    let replay = ReplayProvider::load(&path).unwrap();
+    let replayed = collect_results(
+        replay
+            .complete_stream(echo_request("fail"), None)
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(replayed.len(), 3);
+    assert!(
+        matches!(replayed.last(), Some(Err(LlmError::StreamError(e))) if e.contains("connection reset"))
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn replay_rejects_unrecorded_requests() {
+    let replay = ReplayProvider::new(Vec::new());
+    let err = match replay.complete_stream(echo_request("new"), None).await {
+        Err(e) => e,
+        Ok(_) => panic!("Expected a miss"),
+    };
+    let key = agenticlaw_llm::replay::fixture_key(&echo_request("new"));
+    assert!(
+        matches!(&err, LlmError::RequestFailed(msg) if msg.contains(&key)),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn fixtures_are_recorded_at_done_and_independent_of_the_temp_dir() {
+    use futures::StreamExt;
+
+    let path = fixture_path("portable");
+    let recorder = RecordingProvider::new(
+        std::sync::Arc::new(EchoProvider {
+            calls: Default::default(),
+        }),
+        &path,
+    );
+    let text = std::env::temp_dir()
+        .join("agenticlaw-notes.txt")
+        .display()
+        .to_string();
+    // Stop reading at Done, as most consumers do
+    let mut stream = recorder
+        .complete_stream(echo_request(&text), None)
+        .await
+        .unwrap();
+    while let Some(Ok(delta)) = stream.next().await {
+        if matches!(delta, StreamDelta::Done { .. }) {
+            break;
+        }
+    }
+    drop(stream);
+
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    assert!(recorded.contains("$TMPDIR"), "{}", recorded);
+    assert!(!recorded.contains(&text), "{}", recorded);
+
+    // Replay puts this machine's temp dir back
+    let replay = ReplayProvider::load(&path).unwrap();
+    let deltas = collect_deltas(
+        replay
+            .complete_stream(echo_request(&text), None)
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert!(matches!(&deltas[1], StreamDelta::Text(t) if *t == format!("{} #0", text)));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn fixture_key_tracks_request_content() {
+    use agenticlaw_llm::replay::fixture_key;
+    assert_eq!(
+        fixture_key(&echo_request("a")),
+        fixture_key(&echo_request("a"))
+    );
+    assert_ne!(
+        fixture_key(&echo_request("a")),
+        fixture_key(&echo_request("b"))
+    );
+    assert_eq!(fixture_key(&echo_request("a")).len(), 16);
+}
@@ -0,0 +1,221 @@
+//! Checkpoints — undo for agent file edits
+//!
+//! Before a mutating file tool (`write`, `edit`, `multi_edit`, `apply_patch`)
+//! touches a path, the prior content is snapshotted under the current turn.
+//! Only the first snapshot of a path per turn is kept, so each checkpoint
+//! holds the workspace as it was when the turn started. Restoring turn N
+//! replays the checkpoints of N and every later turn, newest first.
+//!
+//! Snapshots are keyed by `ToolContext::lineage`, so edits made by spawned
+//! children land in the parent's checkpoints. Changes made through `bash` or
+//! `shell` are not captured.
+//!
+//! Only the last `MAX_CHECKPOINTS` turns that edited files are kept. Restoring
+//! to a turn older than that fails rather than rewinding only part of the way.
+
+use crate::registry::ToolContext;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// Checkpoints kept per session; the oldest turns are dropped first.
+pub const MAX_CHECKPOINTS: usize = 100;
+/// Files larger than this are not snapshotted.
+const MAX_SNAPSHOT_BYTES: u64 = 10 * 1024 * 1024;
+
+struct Checkpoint {
+    turn: usize,
+    created_at: String,
+    /// Path → content before the turn touched it; `None` if it did not exist.
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CheckpointSummary {
+    pub turn: usize,
+    pub created_at: String,
+    pub files: Vec<PathBuf>,
+}
+
+/// What `CheckpointStore::restore` changed on disk.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RestoreReport {
+    pub turn: usize,
+    /// Files written back to their earlier content.
+    pub restored: Vec<PathBuf>,
+    /// Files the rewound turns created, now deleted.
+    pub removed: Vec<PathBuf>,
+}
+
+/// One session's checkpoints, oldest first.
+#[derive(Default)]
+struct History {
+    checkpoints: Vec<Checkpoint>,
+    /// Latest turn whose checkpoint was dropped to stay under the limit.
+    evicted_through: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct CheckpointStore {
+    sessions: Mutex<HashMap<String, History>>,
+}
+
+impl CheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Save the current content of `path` unless this turn already has it.
+    pub async fn snapshot(&self, ctx: &ToolContext, path: &Path) {
+        if ctx.lineage.is_empty() || self.has(ctx, path) {
+            return;
+        }
+        let prior = match tokio::fs::metadata(path).await {
+            Ok(meta) if meta.len() > MAX_SNAPSHOT_BYTES => {
+                warn!("checkpoint: {} too large to snapshot", path.display());
+                return;
+            }
+            Ok(_) => match tokio::fs::read(path).await {
+                Ok(content) => Some(content),
+                Err(e) => {
+                    warn!("checkpoint: cannot snapshot {}: {}", path.display(), e);
+                    return;
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!("checkpoint: cannot snapshot {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let history = sessions.entry(ctx.lineage.clone()).or_default();
+        if history
+            .checkpoints
+            .last()
+            .is_none_or(|c| c.turn != ctx.turn)
+        {
+            history.checkpoints.push(Checkpoint {
+                turn: ctx.turn,
+                created_at: chrono::Utc::now().to_rfc3339(),
+                files: Vec::new(),
+            });
+            if history.checkpoints.len() > MAX_CHECKPOINTS {
+                let dropped = history.checkpoints.remove(0);
+                history.evicted_through = Some(dropped.turn);
+            }
+        }
+        let current = history.checkpoints.last_mut().unwrap();
+        if !current.files.iter().any(|(p, _)| p == path) {
+            current.files.push((path.to_path_buf(), prior));
+        }
+    }
+
+    fn has(&self, ctx: &ToolContext, path: &Path) -> bool {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(&ctx.lineage)
+            .and_then(|h| h.checkpoints.last())
+            .is_some_and(|c| c.turn == ctx.turn && c.files.iter().any(|(p, _)| p == path))
+    }
+
+    /// Checkpoints for `session`, oldest first.
+    pub fn list(&self, session: &str) -> Vec<CheckpointSummary> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session)
+            .map(|history| {
+                history
+                    .checkpoints
+                    .iter()
+                    .map(|c| CheckpointSummary {
+                        turn: c.turn,
+                        created_at: c.created_at.clone(),
+                        files: c.files.iter().map(|(p, _)| p.clone()).collect(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Put every file touched in `turn` or later back the way it was before
+    /// `turn`. The rewound checkpoints are dropped once all files are back.
+    /// Fails, changing nothing, if `turn`'s checkpoint was dropped for the
+    /// `MAX_CHECKPOINTS` limit.
+    pub async fn restore(&self, session: &str, turn: usize) -> Result<RestoreReport, String> {
+        // Earliest snapshot of each path wins: that is its state before `turn`.
+        let mut before: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+        {
+            let sessions = self.sessions.lock().unwrap();
+            let history = sessions.get(session);
+            if let Some(evicted) = history.and_then(|h| h.evicted_through) {
+                if turn <= evicted {
+                    return Err(format!(
+                        "Turn {} of session {} is too old to restore: only the last {} \
+                         checkpoints are kept, and turns up to {} were dropped",
+                        turn, session, MAX_CHECKPOINTS, evicted
+                    ));
+                }
+            }
+            let checkpoints = history.map(|h| h.checkpoints.as_slice()).unwrap_or(&[]);
+            for checkpoint in checkpoints.iter().filter(|c| c.turn >= turn) {
+                for (path, prior) in &checkpoint.files {
+                    if !before.iter().any(|(p, _)| p == path) {
+                        before.push((path.clone(), prior.clone()));
+                    }
+                }
+            }
+        }
+        if before.is_empty() {
+            return Err(format!(
+                "No checkpoints for session {} at or after turn {}",
+                session, turn
+            ));
+        }
+
+        let mut report = RestoreReport {
+            turn,
+            ..Default::default()
+        };
+        let mut failures = Vec::new();
+        for (path, prior) in before {
+            let result = match &prior {
+                Some(content) => write_back(&path, content).await,
+                None => match tokio::fs::remove_file(&path).await {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                    other => other,
+                },
+            };
+            match (result, prior.is_some()) {
+                (Ok(()), true) => report.restored.push(path),
+                (Ok(()), false) => report.removed.push(path),
+                (Err(e), _) => failures.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(format!("Restore incomplete: {}", failures.join("; ")));
+        }
+
+        if let Some(history) = self.sessions.lock().unwrap().get_mut(session) {
+            history.checkpoints.retain(|c| c.turn < turn);
+        }
+        Ok(report)
+    }
+
+    /// Drop all checkpoints for `session`.
+    pub fn forget(&self, session: &str) {
+        self.sessions.lock().unwrap().remove(session);
+    }
+}
+
+async fn write_back(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, content).await
+}
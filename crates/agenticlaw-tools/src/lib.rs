@@ -4,15 +4,25 @@
 //! To add a tool: create the file, implement Tool trait, register below.
 //! To remove a tool: delete the file, remove from mod.rs and registry below.
 
+pub mod checkpoint;
+pub mod network;
 pub mod registry;
+pub mod sandbox;
+pub mod schedule;
 pub mod tools;
+pub mod tracker;
 
-pub use registry::{Tool, ToolRegistry, ToolResult};
+pub use checkpoint::{CheckpointStore, CheckpointSummary, RestoreReport};
+pub use network::NetworkPolicy;
+pub use registry::{Tool, ToolContext, ToolRegistry, ToolResult};
+pub use sandbox::{Access, SandboxConfig, WorkspaceSandbox};
+pub use schedule::{CallAccess, Concurrency};
 pub use tools::spawn::{
     RuntimeHandle, SpawnTool, SpawnableRuntime, SubagentControl, SubagentInfoSnapshot,
     SubagentRegistryHandle,
 };
 pub use tools::subagent::SubagentTool;
+pub use tracker::FileTracker;
 
 use std::path::Path;
 use std::sync::Arc;
@@ -27,31 +37,75 @@ pub fn create_runtime_handle() -> RuntimeHandle {
     Arc::new(RwLock::new(None))
 }
 
+/// web_fetch here refuses every URL; use `create_sandboxed_registry` to give
+/// it a `NetworkPolicy`.
 pub fn create_default_registry(workspace_root: impl AsRef<Path>) -> ToolRegistry {
     create_default_registry_with_spawn(workspace_root, create_runtime_handle())
 }
 
 /// Create registry with a shared runtime handle for the spawn tool.
 /// After constructing AgentRuntime, call `runtime_handle.write().await = Some(runtime)`.
+/// web_fetch refuses every URL, as in `create_default_registry`.
 pub fn create_default_registry_with_spawn(
     workspace_root: impl AsRef<Path>,
     runtime_handle: RuntimeHandle,
+) -> ToolRegistry {
+    create_sandboxed_registry(
+        WorkspaceSandbox::new(workspace_root),
+        Arc::new(NetworkPolicy::deny_all()),
+        runtime_handle,
+    )
+}
+
+/// Create the default registry with every filesystem tool routed through
+/// `sandbox` and web_fetch limited to what `network` allows.
+pub fn create_sandboxed_registry(
+    sandbox: WorkspaceSandbox,
+    network: Arc<NetworkPolicy>,
+    runtime_handle: RuntimeHandle,
 ) -> ToolRegistry {
     let mut registry = ToolRegistry::new();
-    let root = workspace_root.as_ref();
+    let sandbox = Arc::new(sandbox);
+    let tracker = Arc::new(FileTracker::new());
+    let checkpoints = registry.checkpoints().clone();
+    let root = sandbox.workspace().to_path_buf();
 
     // --- Core tools (read-only) ---
-    registry.register(tools::read::ReadTool::new(root));
-    registry.register(tools::glob::GlobTool::new(root));
-    registry.register(tools::grep::GrepTool::new(root));
+    registry.register(
+        tools::read::ReadTool::with_sandbox(sandbox.clone()).with_tracker(tracker.clone()),
+    );
+    registry.register(tools::glob::GlobTool::with_sandbox(sandbox.clone()));
+    registry.register(tools::grep::GrepTool::with_sandbox(sandbox.clone()));
 
     // --- Mutation tools ---
-    registry.register(tools::write::WriteTool::new(root));
-    registry.register(tools::edit::EditTool::new(root));
-    registry.register(tools::bash::BashTool::new(root));
+    registry.register(
+        tools::write::WriteTool::with_sandbox(sandbox.clone())
+            .with_tracker(tracker.clone())
+            .with_checkpoints(checkpoints.clone()),
+    );
+    registry.register(
+        tools::edit::EditTool::with_sandbox(sandbox.clone())
+            .with_tracker(tracker.clone())
+            .with_checkpoints(checkpoints.clone()),
+    );
+    registry.register(
+        tools::multi_edit::MultiEditTool::with_sandbox(sandbox.clone())
+            .with_tracker(tracker.clone())
+            .with_checkpoints(checkpoints.clone()),
+    );
+    registry.register(
+        tools::apply_patch::ApplyPatchTool::with_sandbox(sandbox)
+            .with_tracker(tracker)
+            .with_checkpoints(checkpoints),
+    );
+    registry.register(tools::bash::BashTool::new(&root));
+    registry.register(tools::shell::ShellTool::new(&root));
+
+    // --- Network ---
+    registry.register(tools::web_fetch::WebFetchTool::with_policy(network));
 
     // --- KG primitive: recursive sub-agent spawning ---
-    registry.register(tools::spawn::SpawnTool::new(root, runtime_handle));
+    registry.register(tools::spawn::SpawnTool::new(&root, runtime_handle));
 
     registry
 }
@@ -61,21 +115,50 @@ pub fn create_default_registry_with_spawn(
 /// Only registers tools whose names appear in `allowed_tools`.
 /// Used by operator containers to enforce policy at the tool registration level.
 /// If a tool isn't registered, the LLM never sees it and can't call it.
+/// web_fetch, when allowed, connects only where `network` allows.
 pub fn create_policy_registry(
     workspace_root: impl AsRef<Path>,
     allowed_tools: &[&str],
+    network: Arc<NetworkPolicy>,
 ) -> ToolRegistry {
     let mut registry = ToolRegistry::new();
     let root = workspace_root.as_ref();
+    let sandbox = Arc::new(WorkspaceSandbox::new(root));
+    let tracker = Arc::new(FileTracker::new());
+    let checkpoints = registry.checkpoints().clone();
 
     for name in allowed_tools {
         match *name {
-            "read" => registry.register(tools::read::ReadTool::new(root)),
-            "glob" => registry.register(tools::glob::GlobTool::new(root)),
-            "grep" => registry.register(tools::grep::GrepTool::new(root)),
-            "write" => registry.register(tools::write::WriteTool::new(root)),
-            "edit" => registry.register(tools::edit::EditTool::new(root)),
+            "read" => registry.register(
+                tools::read::ReadTool::with_sandbox(sandbox.clone()).with_tracker(tracker.clone()),
+            ),
+            "glob" => registry.register(tools::glob::GlobTool::with_sandbox(sandbox.clone())),
+            "grep" => registry.register(tools::grep::GrepTool::with_sandbox(sandbox.clone())),
+            "write" => registry.register(
+                tools::write::WriteTool::with_sandbox(sandbox.clone())
+                    .with_tracker(tracker.clone())
+                    .with_checkpoints(checkpoints.clone()),
+            ),
+            "edit" => registry.register(
+                tools::edit::EditTool::with_sandbox(sandbox.clone())
+                    .with_tracker(tracker.clone())
+                    .with_checkpoints(checkpoints.clone()),
+            ),
+            "multi_edit" => registry.register(
+                tools::multi_edit::MultiEditTool::with_sandbox(sandbox.clone())
+                    .with_tracker(tracker.clone())
+                    .with_checkpoints(checkpoints.clone()),
+            ),
+            "apply_patch" => registry.register(
+                tools::apply_patch::ApplyPatchTool::with_sandbox(sandbox.clone())
+                    .with_tracker(tracker.clone())
+                    .with_checkpoints(checkpoints.clone()),
+            ),
             "bash" => registry.register(tools::bash::BashTool::new(root)),
+            "shell" => registry.register(tools::shell::ShellTool::new(root)),
+            "web_fetch" => {
+                registry.register(tools::web_fetch::WebFetchTool::with_policy(network.clone()))
+            }
             _ => tracing::warn!("Unknown tool in policy: {}", name),
         }
     }
@@ -0,0 +1,267 @@
+//! Network policy — which URLs the web tools may connect to
+//!
+//! Rules use the operator policy's `network` tier syntax: `connect:<url glob>`
+//! where `*` matches within one path segment and `**` matches anything, so
+//! `connect:https:**` allows every HTTPS URL and
+//! `connect:https://*.example.com/**` one domain's subdomains. Deny rules win
+//! over allow rules; a URL no allow rule matches is refused.
+//!
+//! Operator policy files compile in via `NetworkPolicy::from_policy_file`,
+//! which reads the same `network` tier `Policy::check_network` enforces.
+//!
+//! Rules see the URL, not where it leads. So whatever the rules say, internal
+//! addresses (loopback, private, link-local, unique-local) are refused unless
+//! the policy allows the private network: an IP host in `check`, and a
+//! hostname's resolved addresses in `Resolver`, which the web tools' client
+//! uses for every connection, redirects included.
+
+use crate::sandbox::SandboxConfig;
+use agenticlaw_core::openclaw_config::OcFetchConfig;
+use regex::Regex;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Plain HTTP(S) anywhere, unless configured otherwise.
+pub const DEFAULT_ALLOW: &[&str] = &["connect:http:**", "connect:https:**"];
+
+/// Cloud metadata hostnames, in any scheme and on any port; fetching them
+/// from an agent leaks credentials. Metadata IPs are internal addresses.
+pub const DEFAULT_DENY: &[&str] = &[
+    "connect:*://metadata.google.internal/**",
+    "connect:*://metadata.google.internal:*/**",
+    "connect:*://metadata/**",
+    "connect:*://metadata:*/**",
+];
+
+#[derive(Debug, Clone)]
+pub struct NetworkPolicy {
+    allow: Vec<Rule>,
+    deny: Vec<Rule>,
+    /// Whether internal addresses may be reached.
+    private_network: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: String,
+    regex: Regex,
+}
+
+impl Default for NetworkPolicy {
+    fn default() -> Self {
+        Self::from(&OcFetchConfig::default())
+    }
+}
+
+impl From<&OcFetchConfig> for NetworkPolicy {
+    fn from(oc: &OcFetchConfig) -> Self {
+        let defaults = |r: &[&str]| r.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        Self::from_rules(
+            &oc.allow.clone().unwrap_or_else(|| defaults(DEFAULT_ALLOW)),
+            &oc.deny.clone().unwrap_or_else(|| defaults(DEFAULT_DENY)),
+        )
+        .with_private_network(oc.allow_private_network)
+    }
+}
+
+impl NetworkPolicy {
+    /// Compile `connect:` rules. Rules for other operations (`dns:`, `listen:`)
+    /// are ignored; the web tools only connect.
+    pub fn from_rules(allow: &[String], deny: &[String]) -> Self {
+        let compile = |rules: &[String]| {
+            rules
+                .iter()
+                .filter(|r| r.starts_with("connect:"))
+                .filter_map(|r| match glob_regex(r) {
+                    Ok(regex) => Some(Rule {
+                        pattern: r.clone(),
+                        regex,
+                    }),
+                    Err(e) => {
+                        tracing::warn!(rule = %r, error = %e, "invalid network rule");
+                        None
+                    }
+                })
+                .collect()
+        };
+        Self {
+            allow: compile(allow),
+            deny: compile(deny),
+            private_network: false,
+        }
+    }
+
+    /// No rules: every URL is refused. What registries give web_fetch when
+    /// their caller names no policy.
+    pub fn deny_all() -> Self {
+        Self::from_rules(&[], &[])
+    }
+
+    /// Let the rules alone decide about internal addresses.
+    pub fn with_private_network(mut self, allow: bool) -> Self {
+        self.private_network = allow;
+        self
+    }
+
+    /// Rules from an operator policy file's `network` tier.
+    pub fn from_policy_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("policy {}: {}", path.display(), e))?;
+        let policy: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| format!("policy {}: {}", path.display(), e))?;
+        let rules = |tier: &str| -> Vec<String> {
+            policy["network"][tier]
+                .as_array()
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Ok(Self::from_rules(&rules("allow"), &rules("deny")))
+    }
+
+    /// The `network` tier of a sandbox's operator policy file, or `deny_all`
+    /// for a sandbox without one.
+    pub fn from_sandbox_config(config: &SandboxConfig) -> Result<Self, String> {
+        match &config.policy {
+            Some(path) => Self::from_policy_file(path),
+            None => Ok(Self::deny_all()),
+        }
+    }
+
+    /// Whether connecting to `url` is allowed; the error names the reason.
+    /// A hostname's addresses are only known, and checked, on connecting.
+    pub fn check(&self, url: &str) -> Result<(), String> {
+        // IP hosts are checked as parsed, whatever their spelling
+        let ip = reqwest::Url::parse(url).ok().and_then(|u| {
+            let host = u.host_str()?.trim_start_matches('[').trim_end_matches(']');
+            host.parse::<IpAddr>().ok()
+        });
+        if let Some(ip) = ip {
+            self.check_addr(ip)?;
+        }
+        let operation = format!("connect:{}", url);
+        if let Some(rule) = self.deny.iter().find(|r| r.regex.is_match(&operation)) {
+            return Err(format!(
+                "{} is denied by network rule {}",
+                url, rule.pattern
+            ));
+        }
+        if self.allow.iter().any(|r| r.regex.is_match(&operation)) {
+            Ok(())
+        } else {
+            Err(format!("{} is not allowed by any network rule", url))
+        }
+    }
+
+    /// Whether connecting to `ip` is allowed.
+    pub fn check_addr(&self, ip: IpAddr) -> Result<(), String> {
+        if !self.private_network && is_internal(ip) {
+            return Err(format!(
+                "{} is an internal address; the private network is not allowed",
+                ip
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Loopback, private, shared (CGNAT), link-local, unique-local and
+/// unspecified addresses, IPv4 ones also in their IPv6 forms (mapped,
+/// compatible, NAT64).
+pub fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(ip) => {
+            let s = ip.segments();
+            let embedded = match s {
+                [0, 0, 0, 0, 0, 0 | 0xffff, ..] | [0x64, 0xff9b, 0, 0, 0, 0, ..] => {
+                    let [.., hi, lo] = s;
+                    Some(Ipv4Addr::from((u32::from(hi) << 16) | u32::from(lo)))
+                }
+                _ => None,
+            };
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || (s[0] & 0xfe00) == 0xfc00
+                || (s[0] & 0xffc0) == 0xfe80
+                || embedded.is_some_and(|v4| is_internal(IpAddr::V4(v4)))
+        }
+    }
+}
+
+/// DNS for the web tools' client: the system resolver, minus the addresses
+/// the policy refuses. A name left with none fails to resolve.
+pub struct Resolver {
+    policy: Arc<NetworkPolicy>,
+}
+
+impl Resolver {
+    pub fn new(policy: Arc<NetworkPolicy>) -> Self {
+        Self { policy }
+    }
+
+    /// Resolve `host` to the addresses that may be connected to.
+    pub async fn lookup(policy: &NetworkPolicy, host: &str) -> Result<Vec<SocketAddr>, String> {
+        let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+            .await
+            .map_err(|e| format!("{}: {}", host, e))?
+            .collect();
+        let mut refused = None;
+        let addrs: Vec<SocketAddr> = resolved
+            .into_iter()
+            .filter(|a| match policy.check_addr(a.ip()) {
+                Ok(()) => true,
+                Err(e) => {
+                    refused = Some(e);
+                    false
+                }
+            })
+            .collect();
+        match (addrs.is_empty(), refused) {
+            (true, Some(e)) => Err(format!("{} resolves to {}", host, e)),
+            _ => Ok(addrs),
+        }
+    }
+}
+
+impl reqwest::dns::Resolve for Resolver {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> reqwest::dns::Resolving {
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let addrs = Self::lookup(&policy, name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Same translation as the operator's policy globs: `**` matches anything,
+/// `*` and `?` stop at `/`.
+fn glob_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut re = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                re.push_str(".*");
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re)
+}
@@ -4,17 +4,28 @@
 //! Tools can be added/removed by editing the tools/ directory and
 //! the create_default_registry() function in lib.rs.
 
-use agenticlaw_llm::LlmTool;
+use crate::checkpoint::CheckpointStore;
+use crate::schedule::{self, CallAccess, Concurrency};
+use agenticlaw_llm::{ContentBlock, LlmContent, LlmTool};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 
+/// How long a timed-out tool gets to clean up after its cancellation.
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
 #[derive(Clone, Debug)]
 pub enum ToolResult {
     Text(String),
     Json(Value),
     Error(String),
+    /// Text plus images/documents, e.g. `read` on a PNG.
+    Content(Vec<ContentBlock>),
 }
 
 impl ToolResult {
@@ -30,6 +41,19 @@ impl ToolResult {
             Self::Text(s) => s.clone(),
             Self::Json(v) => serde_json::to_string_pretty(v).unwrap_or_default(),
             Self::Error(e) => format!("Error: {}", e),
+            Self::Content(blocks) => LlmContent::Blocks(blocks.clone()).to_text(),
+        }
+    }
+
+    /// Image and document blocks to send alongside the text.
+    pub fn media(&self) -> Vec<ContentBlock> {
+        match self {
+            Self::Content(blocks) => blocks
+                .iter()
+                .filter(|b| b.media_source().is_some())
+                .cloned()
+                .collect(),
+            _ => Vec::new(),
         }
     }
 
@@ -38,6 +62,47 @@ impl ToolResult {
     }
 }
 
+/// Who a tool call runs for. Tools that keep state between calls (shell
+/// sessions, file snapshots) key it by `session` and drop it in `end_session`.
+#[derive(Clone, Debug)]
+pub struct ToolContext {
+    /// Owning agent session key.
+    pub session: String,
+    /// Root of the spawn tree `session` belongs to; children inherit their
+    /// parent's. State shared between parent and children is keyed by this.
+    pub lineage: String,
+    /// User turn of the session the call belongs to; checkpoints key on it.
+    pub turn: usize,
+    pub cancel: CancellationToken,
+}
+
+impl ToolContext {
+    pub fn new(session: impl Into<String>) -> Self {
+        let session = session.into();
+        Self {
+            lineage: session.clone(),
+            session,
+            turn: 0,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    pub fn with_lineage(mut self, lineage: impl Into<String>) -> Self {
+        self.lineage = lineage.into();
+        self
+    }
+
+    pub fn with_turn(mut self, turn: usize) -> Self {
+        self.turn = turn;
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+}
+
 /// The Tool trait — implement this to add a new capability.
 ///
 /// Each tool is a standalone unit that can be registered with a ToolRegistry.
@@ -69,6 +134,27 @@ pub trait Tool: Send + Sync {
         true
     }
 
+    /// How calls to this tool may overlap with other calls from the same
+    /// turn. Default: read-only tools read, everything else runs alone.
+    fn concurrency(&self) -> Concurrency {
+        if self.is_read_only() {
+            Concurrency::Read
+        } else {
+            Concurrency::Exclusive
+        }
+    }
+
+    /// Files or directories a call with `args` reads or writes.
+    fn resources(&self, _args: &Value) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// How long a call with `args` may run before it is cancelled and fails
+    /// with a timeout error. `None` for tools bounded some other way.
+    fn timeout(&self, _args: &Value) -> Option<Duration> {
+        Some(schedule::DEFAULT_TIMEOUT)
+    }
+
     /// Execute the tool with the given arguments.
     async fn execute(&self, args: Value) -> ToolResult;
 
@@ -82,6 +168,19 @@ pub trait Tool: Send + Sync {
         }
     }
 
+    /// Execute on behalf of a session. Default: `execute_cancellable` with the
+    /// context's token. Override to keep per-session state.
+    async fn execute_in(&self, args: Value, ctx: &ToolContext) -> ToolResult {
+        self.execute_cancellable(args, ctx.cancel.clone()).await
+    }
+
+    /// The session is gone (deleted or aborted): release anything held for it.
+    async fn end_session(&self, _session: &str) {}
+
+    /// Every session of `lineage` is gone for good (its root was deleted):
+    /// drop state shared across the spawn tree. Not called on abort.
+    async fn end_lineage(&self, _lineage: &str) {}
+
     /// Convert to the LLM tool definition format.
     fn to_llm_tool(&self) -> LlmTool {
         LlmTool {
@@ -93,7 +192,10 @@ pub trait Tool: Send + Sync {
 }
 
 pub struct ToolRegistry {
-    tools: HashMap<String, Arc<dyn Tool>>,
+    /// By name, so definitions come out in the same order on every run and
+    /// requests built from them hash the same for replay.
+    tools: BTreeMap<String, Arc<dyn Tool>>,
+    checkpoints: Arc<CheckpointStore>,
 }
 
 impl Default for ToolRegistry {
@@ -105,10 +207,16 @@ impl Default for ToolRegistry {
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
-            tools: HashMap::new(),
+            tools: BTreeMap::new(),
+            checkpoints: Arc::new(CheckpointStore::new()),
         }
     }
 
+    /// Snapshots of files changed by this registry's tools, per session.
+    pub fn checkpoints(&self) -> &Arc<CheckpointStore> {
+        &self.checkpoints
+    }
+
     /// Register a tool. Replaces any existing tool with the same name.
     pub fn register(&mut self, tool: impl Tool + 'static) {
         let name = tool.name().to_string();
@@ -124,9 +232,14 @@ impl ToolRegistry {
         self.tools.get(name).cloned()
     }
 
+    /// Execute a tool. Arguments that don't match the tool's input schema
+    /// fail with every violation listed, without running the tool.
     pub async fn execute(&self, name: &str, args: Value) -> ToolResult {
         match self.tools.get(name) {
-            Some(tool) if tool.is_enabled() => tool.execute(args).await,
+            Some(tool) if tool.is_enabled() => match checked_args(tool.as_ref(), args) {
+                Ok(args) => tool.execute(args).await,
+                Err(invalid) => invalid,
+            },
             Some(_) => ToolResult::Error(format!("Tool '{}' is disabled", name)),
             None => ToolResult::Error(format!("Tool not found: {}", name)),
         }
@@ -140,12 +253,78 @@ impl ToolRegistry {
         cancel: CancellationToken,
     ) -> ToolResult {
         match self.tools.get(name) {
-            Some(tool) if tool.is_enabled() => tool.execute_cancellable(args, cancel).await,
+            Some(tool) if tool.is_enabled() => {
+                let args = match checked_args(tool.as_ref(), args) {
+                    Ok(args) => args,
+                    Err(invalid) => return invalid,
+                };
+                let limit = tool.timeout(&args);
+                let run_cancel = cancel.child_token();
+                let run = tool.execute_cancellable(args, run_cancel.clone());
+                with_timeout(name, limit, &run_cancel, run).await
+            }
+            Some(_) => ToolResult::Error(format!("Tool '{}' is disabled", name)),
+            None => ToolResult::Error(format!("Tool not found: {}", name)),
+        }
+    }
+
+    /// Execute a tool on behalf of a session.
+    pub async fn execute_in(&self, name: &str, args: Value, ctx: &ToolContext) -> ToolResult {
+        match self.tools.get(name) {
+            Some(tool) if tool.is_enabled() => {
+                let args = match checked_args(tool.as_ref(), args) {
+                    Ok(args) => args,
+                    Err(invalid) => return invalid,
+                };
+                let limit = tool.timeout(&args);
+                let ctx = ctx.clone().with_cancel(ctx.cancel.child_token());
+                let run = tool.execute_in(args, &ctx);
+                with_timeout(name, limit, &ctx.cancel, run).await
+            }
             Some(_) => ToolResult::Error(format!("Tool '{}' is disabled", name)),
             None => ToolResult::Error(format!("Tool not found: {}", name)),
         }
     }
 
+    /// What a call to `name` with `args` touches. Unknown tools fail without
+    /// touching anything.
+    pub fn access(&self, name: &str, args: &Value) -> CallAccess {
+        match self.tools.get(name) {
+            Some(tool) => CallAccess {
+                concurrency: tool.concurrency(),
+                resources: tool.resources(args),
+            },
+            None => CallAccess {
+                concurrency: Concurrency::Independent,
+                resources: Vec::new(),
+            },
+        }
+    }
+
+    /// Group `(name, args)` calls into batches that may run concurrently;
+    /// see `schedule::batches`.
+    pub fn batches(&self, calls: &[(&str, &Value)]) -> Vec<Range<usize>> {
+        let accesses: Vec<CallAccess> = calls
+            .iter()
+            .map(|(name, args)| self.access(name, args))
+            .collect();
+        schedule::batches(&accesses)
+    }
+
+    /// Release every tool's state for `session`.
+    pub async fn end_session(&self, session: &str) {
+        for tool in self.tools.values() {
+            tool.end_session(session).await;
+        }
+    }
+
+    /// Release every tool's state for `lineage`.
+    pub async fn end_lineage(&self, lineage: &str) {
+        for tool in self.tools.values() {
+            tool.end_lineage(lineage).await;
+        }
+    }
+
     /// Get LLM tool definitions for all enabled tools.
     pub fn get_definitions(&self) -> Vec<LlmTool> {
         self.tools
@@ -179,3 +358,56 @@ impl ToolRegistry {
             .collect()
     }
 }
+
+/// `args` checked against `tool`'s input schema, or an error listing every
+/// violation. A `path` argument stands in for an undeclared `file_path`, as
+/// the file tools accept either; it is renamed before checking.
+fn checked_args(tool: &dyn Tool, mut args: Value) -> Result<Value, ToolResult> {
+    let schema = tool.input_schema();
+    let declared = |key: &str| schema["properties"].get(key).is_some();
+    if declared("file_path") && !declared("path") {
+        if let Some(obj) = args.as_object_mut() {
+            if !obj.contains_key("file_path") {
+                if let Some(path) = obj.remove("path") {
+                    obj.insert("file_path".into(), path);
+                }
+            }
+        }
+    }
+    match agenticlaw_core::schema::validate(&schema, &args) {
+        Ok(()) => Ok(args),
+        Err(violations) => Err(ToolResult::error(format!(
+            "Invalid arguments for {}:\n- {}",
+            tool.name(),
+            violations.join("\n- ")
+        ))),
+    }
+}
+
+/// Run a tool call, cancelling it through `cancel` once `limit` passes. The
+/// tool gets `CANCEL_GRACE` to stop (kill its process, say) before the
+/// timeout error is returned.
+async fn with_timeout(
+    name: &str,
+    limit: Option<Duration>,
+    cancel: &CancellationToken,
+    run: impl Future<Output = ToolResult>,
+) -> ToolResult {
+    let Some(limit) = limit else {
+        return run.await;
+    };
+    tokio::pin!(run);
+    tokio::select! {
+        result = &mut run => result,
+        _ = tokio::time::sleep(limit) => {
+            cancel.cancel();
+            let _ = tokio::time::timeout(CANCEL_GRACE, run).await;
+            tracing::warn!(tool = %name, limit_secs = limit.as_secs_f64(), "Tool timed out");
+            ToolResult::error(format!(
+                "Tool '{}' timed out after {}s",
+                name,
+                limit.as_secs_f64()
+            ))
+        }
+    }
+}
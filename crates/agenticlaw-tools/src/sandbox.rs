@@ -0,0 +1,431 @@
+//! Workspace sandbox — the one place filesystem tools decide which paths they may touch
+//!
+//! A path is allowed when, after expanding `~/`, resolving `..` and following
+//! symlinks, it lies under a root: writable roots for writes, writable or
+//! read-only roots for reads. Deny globs win over roots. A path that is inside
+//! a root lexically but escapes it through a symlink is rejected as such.
+//!
+//! Deny globs without a `/` match any path component (`.env`, `*.pem`); globs
+//! starting with `/` match the absolute path; other globs match the path
+//! relative to the root it falls under.
+//!
+//! Operator policies (`filesystem` tier, `"read:/path/**"` / `"write:/path/**"`
+//! rules) compile into the same structure via `SandboxConfig::from_policy_rules`.
+
+use agenticlaw_core::openclaw_config::{expand_tilde, OcSandbox};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+/// Files no tool reads or writes unless the config replaces this list.
+pub const DEFAULT_DENY: &[&str] = &[".env", ".env.*", "*.pem", "*.key", "id_rsa", "id_ed25519"];
+
+/// Dangling links followed before giving up, as the kernel's SYMLOOP_MAX.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Serializable sandbox settings, as found in the consciousness TOML and
+/// (via `OcSandbox`) in openclaw.json.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxConfig {
+    /// Writable roots. Empty means the workspace, unless a policy supplies roots.
+    pub roots: Vec<PathBuf>,
+    /// Roots tools may read but not modify.
+    pub read_only_roots: Vec<PathBuf>,
+    /// Globs denied for any access.
+    pub deny: Vec<String>,
+    /// Globs denied for writes only.
+    pub deny_write: Vec<String>,
+    /// Operator policy JSON whose `filesystem` rules are compiled in.
+    pub policy: Option<PathBuf>,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            read_only_roots: Vec::new(),
+            deny: DEFAULT_DENY.iter().map(|s| s.to_string()).collect(),
+            deny_write: Vec::new(),
+            policy: None,
+        }
+    }
+}
+
+impl SandboxConfig {
+    /// Compile operator `filesystem` rules (`"<read|write>:<glob>"`).
+    ///
+    /// Allow rules become roots: the glob up to its first wildcard component,
+    /// so `write:/workspace/**` is the writable root `/workspace` and
+    /// `read:**` the read-only root `/`. Deny rules become deny globs;
+    /// `read:` denials block all access, `write:` denials only writes.
+    pub fn from_policy_rules(allow: &[String], deny: &[String]) -> Self {
+        let mut config = Self {
+            deny: Vec::new(),
+            ..Self::default()
+        };
+        for rule in allow {
+            let Some((action, pattern)) = rule.split_once(':') else {
+                continue;
+            };
+            let root = literal_prefix(pattern);
+            match action {
+                "write" => push_unique(&mut config.roots, root),
+                "read" => push_unique(&mut config.read_only_roots, root),
+                _ => {}
+            }
+        }
+        for rule in deny {
+            let Some((action, pattern)) = rule.split_once(':') else {
+                continue;
+            };
+            match action {
+                "read" => config.deny.push(pattern.to_string()),
+                "write" => config.deny_write.push(pattern.to_string()),
+                _ => {}
+            }
+        }
+        config
+    }
+
+    /// Rules from an operator policy file's `filesystem` tier.
+    pub fn from_policy_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("policy {}: {}", path.display(), e))?;
+        let policy: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| format!("policy {}: {}", path.display(), e))?;
+        let rules = |tier: &str| -> Vec<String> {
+            policy["filesystem"][tier]
+                .as_array()
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Ok(Self::from_policy_rules(&rules("allow"), &rules("deny")))
+    }
+}
+
+impl From<&OcSandbox> for SandboxConfig {
+    fn from(oc: &OcSandbox) -> Self {
+        let paths = |v: &[String]| v.iter().map(|p| expand_tilde(p)).collect();
+        let defaults = Self::default();
+        Self {
+            roots: paths(&oc.roots),
+            read_only_roots: paths(&oc.read_only_roots),
+            deny: oc.deny.clone().unwrap_or(defaults.deny),
+            deny_write: oc.deny_write.clone(),
+            policy: oc.policy.as_deref().map(expand_tilde),
+        }
+    }
+}
+
+/// `/a/b/**/*.rs` → `/a/b`; `**` → `/`.
+fn literal_prefix(pattern: &str) -> PathBuf {
+    let mut root = PathBuf::from("/");
+    for component in Path::new(pattern).components() {
+        match component {
+            Component::Normal(c) => {
+                let c = c.to_string_lossy();
+                if c.contains(['*', '?', '[', '{']) {
+                    break;
+                }
+                root.push(c.as_ref());
+            }
+            Component::RootDir | Component::CurDir => {}
+            _ => break,
+        }
+    }
+    root
+}
+
+fn push_unique(paths: &mut Vec<PathBuf>, path: PathBuf) {
+    if !paths.contains(&path) {
+        paths.push(path);
+    }
+}
+
+/// Deny globs, split by how they are matched.
+#[derive(Debug, Clone)]
+struct DenyList {
+    patterns: Vec<String>,
+    /// No `/`: matched against each path component.
+    components: GlobSet,
+    /// Leading `/`: matched against the absolute path.
+    absolute: GlobSet,
+    /// Anything else: matched against the path relative to its root.
+    relative: GlobSet,
+}
+
+impl DenyList {
+    fn new(patterns: &[String]) -> Self {
+        let mut components = GlobSetBuilder::new();
+        let mut absolute = GlobSetBuilder::new();
+        let mut relative = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = match GlobBuilder::new(pattern).literal_separator(true).build() {
+                Ok(g) => g,
+                Err(e) => {
+                    tracing::warn!(pattern = %pattern, error = %e, "invalid sandbox deny glob");
+                    continue;
+                }
+            };
+            if !pattern.contains('/') {
+                components.add(glob);
+            } else if pattern.starts_with('/') {
+                absolute.add(glob);
+            } else {
+                relative.add(glob);
+            }
+        }
+        let build = |b: GlobSetBuilder| b.build().unwrap_or_else(|_| GlobSet::empty());
+        Self {
+            patterns: patterns.to_vec(),
+            components: build(components),
+            absolute: build(absolute),
+            relative: build(relative),
+        }
+    }
+
+    fn matches(&self, path: &Path, root: Option<&Path>) -> bool {
+        if self.absolute.is_match(path) {
+            return true;
+        }
+        let rel = root.and_then(|r| path.strip_prefix(r).ok());
+        if rel.is_some_and(|rel| self.relative.is_match(rel)) {
+            return true;
+        }
+        // Components above the root are the operator's business, not the agent's
+        rel.unwrap_or(path)
+            .components()
+            .any(|c| matches!(c, Component::Normal(c) if self.components.is_match(c)))
+    }
+}
+
+/// A root as configured (lexically normalized) and with symlinks resolved.
+#[derive(Debug, Clone)]
+struct Root {
+    path: PathBuf,
+    real: PathBuf,
+}
+
+impl Root {
+    fn new(path: &Path) -> Self {
+        let path = normalize(path);
+        Self {
+            real: real_path(&path),
+            path,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkspaceSandbox {
+    workspace: PathBuf,
+    roots: Vec<Root>,
+    read_only_roots: Vec<Root>,
+    deny: DenyList,
+    deny_write: DenyList,
+}
+
+impl WorkspaceSandbox {
+    /// The workspace as the only root, with `DEFAULT_DENY`.
+    pub fn new(workspace: impl AsRef<Path>) -> Self {
+        Self::from_config(workspace, &SandboxConfig::default())
+            .expect("default sandbox config loads no files")
+    }
+
+    /// Build from config, loading `config.policy` if set.
+    pub fn from_config(
+        workspace: impl AsRef<Path>,
+        config: &SandboxConfig,
+    ) -> Result<Self, String> {
+        let mut config = config.clone();
+        if let Some(policy) = config.policy.take() {
+            let rules = SandboxConfig::from_policy_file(&policy)?;
+            config.roots.extend(rules.roots);
+            config.read_only_roots.extend(rules.read_only_roots);
+            config.deny.extend(rules.deny);
+            config.deny_write.extend(rules.deny_write);
+        } else if config.roots.is_empty() {
+            config.roots.push(workspace.as_ref().to_path_buf());
+        }
+
+        let workspace = workspace.as_ref().to_path_buf();
+        let root = |p: &PathBuf| Root::new(&expand_home(&p.to_string_lossy(), &workspace));
+        Ok(Self {
+            roots: config.roots.iter().map(root).collect(),
+            read_only_roots: config.read_only_roots.iter().map(root).collect(),
+            deny: DenyList::new(&config.deny),
+            deny_write: DenyList::new(&config.deny_write),
+            workspace,
+        })
+    }
+
+    pub fn with_root(mut self, root: impl AsRef<Path>) -> Self {
+        self.roots.push(Root::new(root.as_ref()));
+        self
+    }
+
+    pub fn with_read_only_root(mut self, root: impl AsRef<Path>) -> Self {
+        self.read_only_roots.push(Root::new(root.as_ref()));
+        self
+    }
+
+    pub fn with_deny(mut self, pattern: impl Into<String>) -> Self {
+        let mut patterns = self.deny.patterns.clone();
+        patterns.push(pattern.into());
+        self.deny = DenyList::new(&patterns);
+        self
+    }
+
+    /// Relative paths resolve against this; bash runs here.
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Resolve a tool's path argument and check `access` to it.
+    pub fn resolve(&self, path: &str, access: Access) -> Result<PathBuf, String> {
+        let resolved = normalize(&expand_home(path, &self.workspace));
+        self.check(&resolved, access)
+            .map_err(|reason| match reason {
+                Denied::Outside
+                    if access == Access::Write && self.check(&resolved, Access::Read).is_ok() =>
+                {
+                    format!("Write blocked: '{}' is in a read-only root", path)
+                }
+                Denied::Outside => format!(
+                    "Path escape blocked: '{}' resolves outside the workspace",
+                    path
+                ),
+                Denied::Symlink(target) => format!(
+                    "Path escape blocked: '{}' follows a symlink to {}, outside the workspace",
+                    path,
+                    target.display()
+                ),
+                Denied::Pattern => format!("Access denied: '{}' matches a sandbox deny rule", path),
+            })?;
+        Ok(resolved)
+    }
+
+    /// Where `path` points, without checking access; for scheduling.
+    pub fn absolute(&self, path: &str) -> PathBuf {
+        normalize(&expand_home(path, &self.workspace))
+    }
+
+    /// Whether `path` (absolute) may be accessed; for filtering directory walks.
+    pub fn allows(&self, path: &Path, access: Access) -> bool {
+        self.check(&normalize(path), access).is_ok()
+    }
+
+    fn check(&self, path: &Path, access: Access) -> Result<(), Denied> {
+        let real = real_path(path);
+        let Some(real_root) = self.root_for(&real, access, |r| &r.real) else {
+            return Err(match self.root_for(path, access, |r| &r.path) {
+                Some(_) => Denied::Symlink(real),
+                None => Denied::Outside,
+            });
+        };
+        let root = self.root_for(path, access, |r| &r.path);
+        let denied =
+            |list: &DenyList| list.matches(&real, Some(real_root)) || list.matches(path, root);
+        if denied(&self.deny) || (access == Access::Write && denied(&self.deny_write)) {
+            return Err(Denied::Pattern);
+        }
+        Ok(())
+    }
+
+    /// The deepest root containing `path` that grants `access`, compared on
+    /// the root's configured or real form.
+    fn root_for<'a>(
+        &'a self,
+        path: &Path,
+        access: Access,
+        form: impl Fn(&'a Root) -> &'a PathBuf,
+    ) -> Option<&'a Path> {
+        let read_only = match access {
+            Access::Read => self.read_only_roots.as_slice(),
+            Access::Write => &[],
+        };
+        self.roots
+            .iter()
+            .chain(read_only)
+            .map(form)
+            .filter(|r| path.starts_with(r))
+            .max_by_key(|r| r.components().count())
+            .map(PathBuf::as_path)
+    }
+}
+
+enum Denied {
+    Outside,
+    Symlink(PathBuf),
+    Pattern,
+}
+
+/// `~/x` → home, relative → under `base`, absolute unchanged.
+fn expand_home(path: &str, base: &Path) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        dirs::home_dir().unwrap_or_default().join(rest)
+    } else if path == "~" {
+        dirs::home_dir().unwrap_or_default()
+    } else {
+        base.join(path)
+    }
+}
+
+/// Resolve `.` and `..` lexically, without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Follow symlinks through the longest existing ancestor; the rest is kept
+/// as-is (it does not exist yet, e.g. a file about to be written). A dangling
+/// link is followed to where it points, since writing through it creates
+/// its target.
+fn real_path(path: &Path) -> PathBuf {
+    real_path_within(path, MAX_SYMLINK_HOPS)
+}
+
+fn real_path_within(path: &Path, hops: usize) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(real) = existing.canonicalize() {
+            return rest.iter().rev().fold(real, |p, c| p.join(c));
+        }
+        let dangling = std::fs::symlink_metadata(existing).is_ok_and(|m| m.is_symlink());
+        if dangling && hops > 0 {
+            if let (Ok(target), Some(parent)) = (std::fs::read_link(existing), existing.parent()) {
+                let target = normalize(&parent.join(target));
+                let target = rest.iter().rev().fold(target, |p, c| p.join(c));
+                return real_path_within(&target, hops - 1);
+            }
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
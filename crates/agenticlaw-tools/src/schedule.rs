@@ -0,0 +1,84 @@
+//! Tool call scheduling — which calls from one assistant turn may overlap
+//!
+//! Every call has a `Concurrency` class and the paths it touches. Calls are
+//! grouped, in the order the model made them, into batches of calls that
+//! don't conflict: batches run one after another, the calls in a batch all
+//! at once. Reads share a batch with reads of anything and with writes of
+//! other files; a write waits for every earlier call touching its file; an
+//! exclusive call (a shell command) runs alone.
+
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Time limit for tools that don't set their own.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Added to a limit a tool enforces itself (bash's `timeout` argument), so
+/// the tool's own, more specific error wins the race.
+pub const TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Concurrency {
+    /// Only reads its resources.
+    Read,
+    /// Changes its resources. Declaring none makes it exclusive.
+    Write,
+    /// Side effects that can't be scoped, such as shell commands.
+    Exclusive,
+    /// Runs alongside anything, e.g. a call to an unknown tool, which fails
+    /// without touching anything.
+    Independent,
+}
+
+/// What one call will touch, from `Tool::concurrency` and `Tool::resources`.
+#[derive(Clone, Debug)]
+pub struct CallAccess {
+    pub concurrency: Concurrency,
+    /// Files or directories; a directory covers everything below it.
+    pub resources: Vec<PathBuf>,
+}
+
+impl CallAccess {
+    /// Whether the two calls must not run at the same time.
+    pub fn conflicts_with(&self, other: &CallAccess) -> bool {
+        use Concurrency::*;
+        match (self.concurrency, other.concurrency) {
+            (Independent, _) | (_, Independent) => false,
+            (Exclusive, _) | (_, Exclusive) => true,
+            (Read, Read) => false,
+            (Write, _) if self.resources.is_empty() => true,
+            (_, Write) if other.resources.is_empty() => true,
+            _ => self.resources.iter().any(|a| {
+                other
+                    .resources
+                    .iter()
+                    .any(|b| a.starts_with(b) || b.starts_with(a))
+            }),
+        }
+    }
+}
+
+/// Split `calls` into consecutive batches with no conflicts inside a batch.
+/// A call never starts before an earlier call it conflicts with finishes.
+pub fn batches(calls: &[CallAccess]) -> Vec<Range<usize>> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    for (i, call) in calls.iter().enumerate() {
+        if calls[start..i].iter().any(|c| c.conflicts_with(call)) {
+            batches.push(start..i);
+            start = i;
+        }
+    }
+    if start < calls.len() {
+        batches.push(start..calls.len());
+    }
+    batches
+}
+
+/// A tool's `file_path` (or `path`) argument, for `Tool::resources`.
+pub(crate) fn path_arg(args: &serde_json::Value) -> Option<&str> {
+    args.get("file_path")
+        .or(args.get("path"))
+        .and_then(|v| v.as_str())
+}
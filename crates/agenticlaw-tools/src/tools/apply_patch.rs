@@ -0,0 +1,670 @@
+//! Apply-patch tool — unified diffs across one or more files
+//!
+//! Accepts `diff -u` / `git diff` output. Each hunk is placed at its stated
+//! line if the context matches there, otherwise at the nearest position where
+//! it does. When nothing matches exactly, the hunk is retried ignoring
+//! whitespace, then with up to `fuzz` context lines dropped from each end
+//! (as GNU patch does). Nothing is written unless every hunk of every file
+//! applies; `dry_run` reports where each hunk would land or why it fails.
+
+use super::edit::write_atomic;
+use crate::checkpoint::CheckpointStore;
+use crate::registry::{Tool, ToolContext, ToolResult};
+use crate::sandbox::{Access, WorkspaceSandbox};
+use crate::schedule::Concurrency;
+use crate::tracker::FileTracker;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tracing::debug;
+
+const DEFAULT_FUZZ: usize = 2;
+
+pub struct ApplyPatchTool {
+    sandbox: Arc<WorkspaceSandbox>,
+    tracker: Arc<FileTracker>,
+    checkpoints: Arc<CheckpointStore>,
+}
+
+impl ApplyPatchTool {
+    pub fn new(workspace_root: impl AsRef<Path>) -> Self {
+        Self::with_sandbox(Arc::new(WorkspaceSandbox::new(workspace_root)))
+    }
+
+    pub fn with_sandbox(sandbox: Arc<WorkspaceSandbox>) -> Self {
+        Self {
+            sandbox,
+            tracker: Arc::new(FileTracker::new()),
+            checkpoints: Arc::new(CheckpointStore::new()),
+        }
+    }
+
+    /// Share read/write records with the other file tools.
+    pub fn with_tracker(mut self, tracker: Arc<FileTracker>) -> Self {
+        self.tracker = tracker;
+        self
+    }
+
+    /// Snapshot files into `checkpoints` before changing them.
+    pub fn with_checkpoints(mut self, checkpoints: Arc<CheckpointStore>) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for ApplyPatchTool {
+    fn name(&self) -> &str {
+        "apply_patch"
+    }
+
+    fn description(&self) -> &str {
+        "Apply a unified diff (diff -u or git diff format) to one or more files. Creates and \
+         deletes files via /dev/null headers. Tolerates shifted line numbers and small \
+         context drift. Applies all hunks or none; use dry_run to check first."
+    }
+
+    fn prompt(&self) -> &str {
+        "For changes spanning several files or many hunks, send one apply_patch call with a \
+         unified diff instead of many edit calls."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "patch": {
+                    "type": "string",
+                    "description": "Unified diff text"
+                },
+                "dry_run": {
+                    "type": "boolean",
+                    "description": "Report which hunks apply without writing anything (default: false)"
+                },
+                "fuzz": {
+                    "type": "integer",
+                    "description": "Context lines that may be ignored at each end of a hunk (default: 2)"
+                },
+                "force": {
+                    "type": "boolean",
+                    "description": "Write even if the file changed since you last read it (default: false)"
+                }
+            },
+            "required": ["patch"]
+        })
+    }
+
+    fn concurrency(&self) -> Concurrency {
+        Concurrency::Write
+    }
+
+    /// Every file the patch names; none (so exclusive) if it doesn't parse.
+    fn resources(&self, args: &Value) -> Vec<PathBuf> {
+        let Some(Ok(files)) = args["patch"].as_str().map(parse_patch) else {
+            return Vec::new();
+        };
+        files
+            .iter()
+            .flat_map(|f| [&f.old_path, &f.new_path])
+            .flatten()
+            .map(|p| self.sandbox.absolute(p))
+            .collect()
+    }
+
+    async fn execute(&self, args: Value) -> ToolResult {
+        self.execute_in(args, &ToolContext::new("")).await
+    }
+
+    async fn execute_in(&self, args: Value, ctx: &ToolContext) -> ToolResult {
+        let patch = match args["patch"].as_str() {
+            Some(p) => p,
+            None => return ToolResult::error("Missing required parameter: patch"),
+        };
+        let dry_run = args["dry_run"].as_bool().unwrap_or(false);
+        let force = args["force"].as_bool().unwrap_or(false);
+        let fuzz = args["fuzz"]
+            .as_u64()
+            .map(|f| f as usize)
+            .unwrap_or(DEFAULT_FUZZ);
+
+        let files = match parse_patch(patch) {
+            Ok(f) if !f.is_empty() => f,
+            Ok(_) => return ToolResult::error("No file diffs found in patch"),
+            Err(e) => return ToolResult::error(format!("Invalid patch: {}", e)),
+        };
+
+        let mut report = Vec::new();
+        let mut planned = Vec::new();
+        let mut failed = false;
+        for file in &files {
+            match self.plan(file, fuzz, (!force).then_some(ctx)).await {
+                Ok((plan, notes)) => {
+                    let failures = notes.iter().filter(|n| n.failed).count();
+                    failed |= failures > 0;
+                    report.push(format!(
+                        "{}: {}",
+                        file.display_path(),
+                        if failures > 0 {
+                            format!("{} of {} hunks FAILED", failures, notes.len())
+                        } else {
+                            format!(
+                                "{} hunk{} ok",
+                                notes.len(),
+                                if notes.len() == 1 { "" } else { "s" }
+                            )
+                        }
+                    ));
+                    report.extend(
+                        notes
+                            .iter()
+                            .filter(|n| n.failed || n.detail.is_some() || dry_run)
+                            .map(|n| format!("  {}", n)),
+                    );
+                    planned.push(plan);
+                }
+                Err(e) => {
+                    failed = true;
+                    report.push(format!("{}: FAILED — {}", file.display_path(), e));
+                }
+            }
+        }
+
+        if dry_run {
+            let verdict = if failed {
+                "Dry run: patch would NOT apply."
+            } else {
+                "Dry run: patch applies cleanly."
+            };
+            return ToolResult::text(format!("{}\n{}", verdict, report.join("\n")));
+        }
+        if failed {
+            return ToolResult::error(format!(
+                "Patch not applied; no files changed.\n{}",
+                report.join("\n")
+            ));
+        }
+
+        for plan in &planned {
+            for path in plan.source.iter().chain(&plan.target) {
+                self.checkpoints.snapshot(ctx, path).await;
+            }
+        }
+        if let Err(e) = commit(&planned).await {
+            return ToolResult::error(format!("Failed to write patch: {}", e));
+        }
+        for plan in &planned {
+            if let (Some(target), Some(content)) = (&plan.target, &plan.content) {
+                self.tracker.record_write(ctx, target, content.as_bytes());
+            }
+        }
+        debug!("apply_patch: {} files", planned.len());
+        ToolResult::text(format!("Patch applied.\n{}", report.join("\n")))
+    }
+
+    async fn end_lineage(&self, lineage: &str) {
+        self.tracker.forget(lineage);
+    }
+}
+
+impl ApplyPatchTool {
+    /// Resolve paths, read the original and apply every hunk in memory.
+    /// With `stale_check`, a source changed since that session read it fails.
+    async fn plan(
+        &self,
+        file: &FileDiff,
+        fuzz: usize,
+        stale_check: Option<&ToolContext>,
+    ) -> Result<(FilePlan, Vec<HunkNote>), String> {
+        let source = match &file.old_path {
+            Some(p) => Some(self.sandbox.resolve(p, Access::Write)?),
+            None => None,
+        };
+        let target = match &file.new_path {
+            Some(p) => Some(self.sandbox.resolve(p, Access::Write)?),
+            None => None,
+        };
+
+        if let (Some(ctx), Some(path)) = (stale_check, &source) {
+            self.tracker.check(ctx, path).await?;
+        }
+        let original = match &source {
+            Some(path) => Some(
+                fs::read_to_string(path)
+                    .await
+                    .map_err(|e| format!("cannot read {}: {}", path.display(), e))?,
+            ),
+            None => {
+                if let Some(t) = target.as_ref().filter(|t| t.exists()) {
+                    return Err(format!("{} already exists", t.display()));
+                }
+                None
+            }
+        };
+
+        let mut text = TextFile::parse(original.as_deref().unwrap_or(""));
+        if original.is_none() {
+            text.trailing_newline = true;
+        }
+        let notes = text.apply(&file.hunks, fuzz);
+
+        let content = match &target {
+            Some(_) => Some(text.render()),
+            None if text.lines.is_empty() || notes.iter().any(|n| n.failed) => None,
+            None => return Err("deletion patch leaves content behind".into()),
+        };
+        Ok((
+            FilePlan {
+                source,
+                target,
+                original,
+                content,
+            },
+            notes,
+        ))
+    }
+}
+
+// ── Parsing ─────────────────────────────────────────────────────────────
+
+#[derive(Debug)]
+struct FileDiff {
+    /// `None` for `/dev/null` (file creation).
+    old_path: Option<String>,
+    /// `None` for `/dev/null` (file deletion).
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+impl FileDiff {
+    fn display_path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or("?")
+    }
+}
+
+#[derive(Debug)]
+struct Hunk {
+    /// 1-based line in the original file, as stated in the header.
+    old_start: usize,
+    lines: Vec<HunkLine>,
+    /// The new side ends without a newline (`\ No newline at end of file`).
+    no_newline_at_end: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+fn parse_patch(patch: &str) -> Result<Vec<FileDiff>, String> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut lines = patch.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if let Some(old) = line.strip_prefix("--- ") {
+            let Some(new) = lines.next().and_then(|l| l.strip_prefix("+++ ")) else {
+                return Err(format!("'--- {}' not followed by a '+++' line", old));
+            };
+            files.push(FileDiff {
+                old_path: header_path(old),
+                new_path: header_path(new),
+                hunks: Vec::new(),
+            });
+        } else if line.starts_with("@@") {
+            let file = files
+                .last_mut()
+                .ok_or("hunk before any '---'/'+++' file header")?;
+            let (old_start, old_len, new_len) = parse_hunk_header(line)?;
+            let mut hunk = Hunk {
+                old_start,
+                lines: Vec::new(),
+                no_newline_at_end: false,
+            };
+            let (mut old_seen, mut new_seen) = (0, 0);
+            while old_seen < old_len || new_seen < new_len {
+                let Some(body) = lines.next() else {
+                    return Err(format!("hunk '{}' ends early", line));
+                };
+                match body.chars().next() {
+                    Some('+') => {
+                        new_seen += 1;
+                        hunk.lines.push(HunkLine::Add(body[1..].to_string()));
+                    }
+                    Some('-') => {
+                        old_seen += 1;
+                        hunk.lines.push(HunkLine::Remove(body[1..].to_string()));
+                    }
+                    Some(' ') => {
+                        old_seen += 1;
+                        new_seen += 1;
+                        hunk.lines.push(HunkLine::Context(body[1..].to_string()));
+                    }
+                    // Editors strip the space off blank context lines
+                    None => {
+                        old_seen += 1;
+                        new_seen += 1;
+                        hunk.lines.push(HunkLine::Context(String::new()));
+                    }
+                    Some('\\') => {}
+                    _ => return Err(format!("unexpected line in hunk: '{}'", body)),
+                }
+            }
+            if lines.peek().is_some_and(|l| l.starts_with('\\')) {
+                lines.next();
+                // After a removed line it only describes the old file
+                hunk.no_newline_at_end = !matches!(hunk.lines.last(), Some(HunkLine::Remove(_)));
+            }
+            file.hunks.push(hunk);
+        }
+        // Anything else (diff --git, index, mode lines, prose) is ignored
+    }
+
+    if let Some(empty) = files.iter().find(|f| f.hunks.is_empty()) {
+        return Err(format!("no hunks for {}", empty.display_path()));
+    }
+    Ok(files)
+}
+
+/// `a/src/lib.rs\t2024-01-01 ...` → `src/lib.rs`; `/dev/null` → `None`.
+fn header_path(header: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or("").trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// `@@ -12,5 +12,7 @@ fn main()` → (12, 5, 7). A missing length means 1.
+fn parse_hunk_header(line: &str) -> Result<(usize, usize, usize), String> {
+    let bad = || format!("bad hunk header: '{}'", line);
+    let mut parts = line.split_whitespace().skip(1);
+    let range = |part: Option<&str>, sign: char| -> Result<(usize, usize), String> {
+        let spec = part.and_then(|p| p.strip_prefix(sign)).ok_or_else(bad)?;
+        let (start, len) = spec.split_once(',').unwrap_or((spec, "1"));
+        Ok((
+            start.parse().map_err(|_| bad())?,
+            len.parse().map_err(|_| bad())?,
+        ))
+    };
+    let (old_start, old_len) = range(parts.next(), '-')?;
+    let (_, new_len) = range(parts.next(), '+')?;
+    Ok((old_start, old_len, new_len))
+}
+
+// ── Applying ────────────────────────────────────────────────────────────
+
+struct TextFile {
+    lines: Vec<String>,
+    trailing_newline: bool,
+}
+
+/// Outcome of one hunk, for the report.
+struct HunkNote {
+    index: usize,
+    failed: bool,
+    /// Line (1-based) where the hunk landed, or was expected if it failed.
+    line: usize,
+    /// Offset/fuzz notes, or why it failed.
+    detail: Option<String>,
+}
+
+impl std::fmt::Display for HunkNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.failed {
+            write!(
+                f,
+                "hunk {} FAILED near line {}: {}",
+                self.index,
+                self.line,
+                self.detail.as_deref().unwrap_or("context not found")
+            )
+        } else {
+            write!(f, "hunk {} at line {}", self.index, self.line)?;
+            match &self.detail {
+                Some(d) => write!(f, " ({})", d),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+impl TextFile {
+    fn parse(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(String::from).collect(),
+            trailing_newline: content.ends_with('\n'),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = self.lines.join("\n");
+        if self.trailing_newline && !self.lines.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Apply hunks in order; a failed hunk is noted and skipped.
+    fn apply(&mut self, hunks: &[Hunk], fuzz: usize) -> Vec<HunkNote> {
+        let mut notes = Vec::new();
+        // Lines added minus lines removed by earlier hunks
+        let mut offset: isize = 0;
+        // Hunks may not overlap: each starts after the previous one ended
+        let mut floor = 0;
+
+        for (i, hunk) in hunks.iter().enumerate() {
+            let expected = (hunk.old_start.saturating_sub(1) as isize + offset).max(0) as usize;
+            match self.locate(hunk, expected, floor, fuzz) {
+                Some(found) => {
+                    let new: Vec<String> = hunk.lines
+                        [found.skip_front..hunk.lines.len() - found.skip_back]
+                        .iter()
+                        .filter_map(|l| match l {
+                            HunkLine::Context(t) | HunkLine::Add(t) => Some(t.clone()),
+                            HunkLine::Remove(_) => None,
+                        })
+                        .collect();
+                    let new_len = new.len();
+                    self.lines.splice(found.at..found.at + found.len, new);
+                    offset += new_len as isize - found.len as isize;
+                    floor = found.at + new_len;
+                    if floor == self.lines.len() {
+                        self.trailing_newline = !hunk.no_newline_at_end;
+                    }
+
+                    let mut detail = Vec::new();
+                    let shift = found.at as isize - (expected + found.skip_front) as isize;
+                    if shift != 0 {
+                        detail.push(format!("offset {:+}", shift));
+                    }
+                    if found.fuzz > 0 {
+                        detail.push(format!("fuzz {}", found.fuzz));
+                    }
+                    if found.loose {
+                        detail.push("whitespace ignored".into());
+                    }
+                    notes.push(HunkNote {
+                        index: i + 1,
+                        failed: false,
+                        line: found.at + 1,
+                        detail: Some(detail.join(", ")).filter(|d| !d.is_empty()),
+                    });
+                }
+                None => notes.push(HunkNote {
+                    index: i + 1,
+                    failed: true,
+                    line: hunk.old_start,
+                    detail: None,
+                }),
+            }
+        }
+        notes
+    }
+
+    /// Find where `hunk` applies: nearest match to `expected`, strict before
+    /// loose, less fuzz before more.
+    fn locate(&self, hunk: &Hunk, expected: usize, floor: usize, max_fuzz: usize) -> Option<Found> {
+        let leading = hunk
+            .lines
+            .iter()
+            .take_while(|l| matches!(l, HunkLine::Context(_)))
+            .count();
+        let trailing = hunk
+            .lines
+            .iter()
+            .rev()
+            .take_while(|l| matches!(l, HunkLine::Context(_)))
+            .count()
+            .min(hunk.lines.len() - leading);
+
+        for fuzz in 0..=max_fuzz {
+            let skip_front = fuzz.min(leading);
+            let skip_back = fuzz.min(trailing);
+            if fuzz > 0 && skip_front + skip_back == 0 {
+                break;
+            }
+            let old: Vec<&str> = hunk.lines[skip_front..hunk.lines.len() - skip_back]
+                .iter()
+                .filter_map(|l| match l {
+                    HunkLine::Context(t) | HunkLine::Remove(t) => Some(t.as_str()),
+                    HunkLine::Add(_) => None,
+                })
+                .collect();
+            let expected = expected + skip_front;
+            if old.is_empty() {
+                // Pure insertion: trust the line number
+                return Some(Found {
+                    at: expected.clamp(floor, self.lines.len().max(floor)),
+                    len: 0,
+                    skip_front,
+                    skip_back,
+                    fuzz,
+                    loose: false,
+                });
+            }
+            for loose in [false, true] {
+                if let Some(at) = self.search(&old, expected, floor, loose) {
+                    return Some(Found {
+                        at,
+                        len: old.len(),
+                        skip_front,
+                        skip_back,
+                        fuzz,
+                        loose,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Closest position at or after `floor` where `old` matches.
+    fn search(&self, old: &[&str], expected: usize, floor: usize, loose: bool) -> Option<usize> {
+        if self.lines.len() < old.len() {
+            return None;
+        }
+        let last = self.lines.len() - old.len();
+        if floor > last {
+            return None;
+        }
+        let matches_at = |at: usize| {
+            self.lines[at..at + old.len()]
+                .iter()
+                .zip(old)
+                .all(|(have, want)| {
+                    if loose {
+                        have.split_whitespace().eq(want.split_whitespace())
+                    } else {
+                        have == want
+                    }
+                })
+        };
+        let expected = expected.clamp(floor, last);
+        for distance in 0..=(last - floor).max(expected - floor) {
+            let after = expected + distance;
+            if after <= last && matches_at(after) {
+                return Some(after);
+            }
+            if distance > 0 && distance <= expected - floor && matches_at(expected - distance) {
+                return Some(expected - distance);
+            }
+        }
+        None
+    }
+}
+
+struct Found {
+    at: usize,
+    len: usize,
+    skip_front: usize,
+    skip_back: usize,
+    fuzz: usize,
+    loose: bool,
+}
+
+// ── Writing ─────────────────────────────────────────────────────────────
+
+struct FilePlan {
+    source: Option<PathBuf>,
+    target: Option<PathBuf>,
+    original: Option<String>,
+    /// New content for `target`; `None` deletes `source`.
+    content: Option<String>,
+}
+
+/// Write every planned file; on failure, restore the ones already written.
+async fn commit(plans: &[FilePlan]) -> Result<(), String> {
+    for (done, plan) in plans.iter().enumerate() {
+        if let Err(e) = write_plan(plan).await {
+            for written in plans[..done].iter().rev() {
+                rollback(written).await;
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+async fn write_plan(plan: &FilePlan) -> Result<(), String> {
+    if let (Some(target), Some(content)) = (&plan.target, &plan.content) {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        write_atomic(target, content)
+            .await
+            .map_err(|e| format!("{}: {}", target.display(), e))?;
+    }
+    // Deletion, or the old side of a rename
+    if let Some(source) = plan
+        .source
+        .as_ref()
+        .filter(|s| Some(*s) != plan.target.as_ref())
+    {
+        fs::remove_file(source)
+            .await
+            .map_err(|e| format!("{}: {}", source.display(), e))?;
+    }
+    Ok(())
+}
+
+async fn rollback(plan: &FilePlan) {
+    if let Some(target) = &plan.target {
+        if plan.source.as_ref() != Some(target) {
+            let _ = fs::remove_file(target).await;
+        }
+    }
+    if let (Some(source), Some(original)) = (&plan.source, &plan.original) {
+        let _ = write_atomic(source, original).await;
+    }
+}
@@ -1,8 +1,10 @@
 //! Bash tool — execute shell commands with timeout, background support, and cancellation
 
 use crate::registry::{Tool, ToolResult};
+use crate::schedule::TIMEOUT_GRACE;
 use serde_json::{json, Value};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::process::Command;
 use tokio_util::sync::CancellationToken;
 use tracing::debug;
@@ -19,6 +21,13 @@ impl BashTool {
             default_timeout_secs: 120,
         }
     }
+
+    fn timeout_secs(&self, args: &Value) -> u64 {
+        args["timeout"]
+            .as_u64()
+            .unwrap_or(self.default_timeout_secs)
+            .min(600)
+    }
 }
 
 #[async_trait::async_trait]
@@ -59,16 +68,19 @@ impl Tool for BashTool {
         })
     }
 
+    /// Bash enforces its own `timeout` argument; the registry's limit only
+    /// backs it up.
+    fn timeout(&self, args: &Value) -> Option<Duration> {
+        Some(Duration::from_secs(self.timeout_secs(args)) + TIMEOUT_GRACE)
+    }
+
     async fn execute(&self, args: Value) -> ToolResult {
         let command = match args["command"].as_str() {
             Some(c) => c,
             None => return ToolResult::error("Missing required parameter: command"),
         };
 
-        let timeout_secs = args["timeout"]
-            .as_u64()
-            .unwrap_or(self.default_timeout_secs)
-            .min(600);
+        let timeout_secs = self.timeout_secs(&args);
 
         if let Some(desc) = args["description"].as_str() {
             debug!("bash [{}]: {}", desc, command);
@@ -105,10 +117,7 @@ impl Tool for BashTool {
             None => return ToolResult::error("Missing required parameter: command"),
         };
 
-        let timeout_secs = args["timeout"]
-            .as_u64()
-            .unwrap_or(self.default_timeout_secs)
-            .min(600);
+        let timeout_secs = self.timeout_secs(&args);
 
         if let Some(desc) = args["description"].as_str() {
             debug!("bash (cancellable) [{}]: {}", desc, command);
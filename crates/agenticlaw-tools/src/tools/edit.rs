@@ -1,21 +1,46 @@
 //! Edit tool — find and replace exact strings in files
 
-use crate::registry::{Tool, ToolResult};
+use crate::checkpoint::CheckpointStore;
+use crate::registry::{Tool, ToolContext, ToolResult};
+use crate::sandbox::{Access, WorkspaceSandbox};
+use crate::schedule::{path_arg, Concurrency};
+use crate::tracker::FileTracker;
 use serde_json::{json, Value};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
 use tracing::debug;
 
 pub struct EditTool {
-    workspace_root: PathBuf,
+    sandbox: Arc<WorkspaceSandbox>,
+    tracker: Arc<FileTracker>,
+    checkpoints: Arc<CheckpointStore>,
 }
 
 impl EditTool {
     pub fn new(workspace_root: impl AsRef<Path>) -> Self {
+        Self::with_sandbox(Arc::new(WorkspaceSandbox::new(workspace_root)))
+    }
+
+    pub fn with_sandbox(sandbox: Arc<WorkspaceSandbox>) -> Self {
         Self {
-            workspace_root: workspace_root.as_ref().to_path_buf(),
+            sandbox,
+            tracker: Arc::new(FileTracker::new()),
+            checkpoints: Arc::new(CheckpointStore::new()),
         }
     }
+
+    /// Share read/write records with the other file tools.
+    pub fn with_tracker(mut self, tracker: Arc<FileTracker>) -> Self {
+        self.tracker = tracker;
+        self
+    }
+
+    /// Snapshot files into `checkpoints` before changing them.
+    pub fn with_checkpoints(mut self, checkpoints: Arc<CheckpointStore>) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -53,13 +78,31 @@ impl Tool for EditTool {
                 "replace_all": {
                     "type": "boolean",
                     "description": "Replace all occurrences (default: false)"
+                },
+                "force": {
+                    "type": "boolean",
+                    "description": "Write even if the file changed since you last read it (default: false)"
                 }
             },
             "required": ["file_path", "old_string", "new_string"]
         })
     }
 
+    fn concurrency(&self) -> Concurrency {
+        Concurrency::Write
+    }
+
+    fn resources(&self, args: &Value) -> Vec<PathBuf> {
+        path_arg(args)
+            .map(|p| vec![self.sandbox.absolute(p)])
+            .unwrap_or_default()
+    }
+
     async fn execute(&self, args: Value) -> ToolResult {
+        self.execute_in(args, &ToolContext::new("")).await
+    }
+
+    async fn execute_in(&self, args: Value, ctx: &ToolContext) -> ToolResult {
         let path = match args
             .get("file_path")
             .or(args.get("path"))
@@ -78,40 +121,87 @@ impl Tool for EditTool {
         };
         let replace_all = args["replace_all"].as_bool().unwrap_or(false);
 
-        let full_path = if Path::new(path).is_absolute() {
-            PathBuf::from(path)
-        } else {
-            self.workspace_root.join(path)
+        let full_path = match self.sandbox.resolve(path, Access::Write) {
+            Ok(p) => p,
+            Err(e) => return ToolResult::error(e),
         };
+        if !args["force"].as_bool().unwrap_or(false) {
+            if let Err(e) = self.tracker.check(ctx, &full_path).await {
+                return ToolResult::error(e);
+            }
+        }
 
         let content = match fs::read_to_string(&full_path).await {
             Ok(c) => c,
             Err(e) => return ToolResult::error(format!("Failed to read file: {}", e)),
         };
 
-        if !content.contains(old) {
-            return ToolResult::error("old_string not found in file");
-        }
-
-        let new_content = if replace_all {
-            content.replace(old, new)
-        } else {
-            let count = content.matches(old).count();
-            if count > 1 {
-                return ToolResult::error(format!(
-                    "old_string found {} times — must be unique. Use replace_all or provide more context.",
-                    count
-                ));
-            }
-            content.replacen(old, new, 1)
+        let new_content = match apply_edit(&content, old, new, replace_all) {
+            Ok(c) => c,
+            Err(e) => return ToolResult::error(e),
         };
 
-        match fs::write(&full_path, &new_content).await {
+        self.checkpoints.snapshot(ctx, &full_path).await;
+        match write_atomic(&full_path, &new_content).await {
             Ok(()) => {
+                self.tracker
+                    .record_write(ctx, &full_path, new_content.as_bytes());
                 debug!("edit: {}", path);
                 ToolResult::text(format!("Edited {}", path))
             }
             Err(e) => ToolResult::error(format!("Failed to write: {}", e)),
         }
     }
+
+    async fn end_lineage(&self, lineage: &str) {
+        self.tracker.forget(lineage);
+    }
+}
+
+/// Replace `old` with `new` in `content`; `old` must be unique unless `replace_all`.
+pub(crate) fn apply_edit(
+    content: &str,
+    old: &str,
+    new: &str,
+    replace_all: bool,
+) -> Result<String, String> {
+    if !content.contains(old) {
+        return Err("old_string not found in file".into());
+    }
+    if replace_all {
+        return Ok(content.replace(old, new));
+    }
+    let count = content.matches(old).count();
+    if count > 1 {
+        return Err(format!(
+            "old_string found {} times — must be unique. Use replace_all or provide more context.",
+            count
+        ));
+    }
+    Ok(content.replacen(old, new, 1))
+}
+
+/// Write via a temp file and rename, so readers never see a half-written file.
+/// Keeps the permissions of the file being replaced. A symlink is followed:
+/// its target is replaced and the link left in place.
+pub(crate) async fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let path = match fs::symlink_metadata(path).await {
+        Ok(meta) if meta.file_type().is_symlink() => fs::canonicalize(path).await?,
+        _ => path.to_path_buf(),
+    };
+    let path = path.as_path();
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+    fs::write(&tmp, content).await?;
+    if let Ok(meta) = fs::metadata(path).await {
+        let _ = fs::set_permissions(&tmp, meta.permissions()).await;
+    }
+    if let Err(e) = fs::rename(&tmp, path).await {
+        let _ = fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    Ok(())
 }
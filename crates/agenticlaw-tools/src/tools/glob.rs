@@ -1,21 +1,26 @@
 //! Glob tool — fast file pattern matching
 
+use super::search::{walk_files, Page};
 use crate::registry::{Tool, ToolResult};
+use crate::sandbox::{Access, WorkspaceSandbox};
 use globset::GlobBuilder;
 use serde_json::{json, Value};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 use tracing::debug;
-use walkdir::WalkDir;
 
 pub struct GlobTool {
-    workspace_root: PathBuf,
+    sandbox: Arc<WorkspaceSandbox>,
 }
 
 impl GlobTool {
     pub fn new(workspace_root: impl AsRef<Path>) -> Self {
-        Self {
-            workspace_root: workspace_root.as_ref().to_path_buf(),
-        }
+        Self::with_sandbox(Arc::new(WorkspaceSandbox::new(workspace_root)))
+    }
+
+    pub fn with_sandbox(sandbox: Arc<WorkspaceSandbox>) -> Self {
+        Self { sandbox }
     }
 }
 
@@ -27,7 +32,8 @@ impl Tool for GlobTool {
 
     fn description(&self) -> &str {
         "Find files matching a glob pattern. Supports ** for recursive matching. \
-         Returns file paths sorted by modification time (newest first)."
+         Respects .gitignore and skips hidden files. Returns file paths sorted by \
+         modification time (newest first) or by path; page with head_limit and offset."
     }
 
     fn is_read_only(&self) -> bool {
@@ -45,75 +51,96 @@ impl Tool for GlobTool {
                 "path": {
                     "type": "string",
                     "description": "Directory to search in (default: workspace root)"
+                },
+                "sort": {
+                    "type": "string",
+                    "enum": ["mtime", "path"],
+                    "description": "Order of results (default: mtime, newest first)"
+                },
+                "head_limit": {
+                    "type": "integer",
+                    "description": "Return at most this many paths"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "Skip this many paths first (default: 0)"
                 }
             },
             "required": ["pattern"]
         })
     }
 
+    fn resources(&self, args: &Value) -> Vec<PathBuf> {
+        vec![self.sandbox.absolute(args["path"].as_str().unwrap_or("."))]
+    }
+
     async fn execute(&self, args: Value) -> ToolResult {
         let pattern = match args["pattern"].as_str() {
             Some(p) => p,
             None => return ToolResult::error("Missing required parameter: pattern"),
         };
 
-        let search_root = args["path"]
-            .as_str()
-            .map(|p| {
-                if Path::new(p).is_absolute() {
-                    PathBuf::from(p)
-                } else {
-                    self.workspace_root.join(p)
-                }
-            })
-            .unwrap_or_else(|| self.workspace_root.clone());
+        let search_root = match self
+            .sandbox
+            .resolve(args["path"].as_str().unwrap_or("."), Access::Read)
+        {
+            Ok(p) => p,
+            Err(e) => return ToolResult::error(e),
+        };
 
         let glob = match GlobBuilder::new(pattern).literal_separator(false).build() {
             Ok(g) => g.compile_matcher(),
             Err(e) => return ToolResult::error(format!("Invalid glob pattern: {}", e)),
         };
 
-        let mut matches: Vec<(PathBuf, std::time::SystemTime)> = Vec::new();
+        let by_path = match args["sort"].as_str().unwrap_or("mtime") {
+            "mtime" => false,
+            "path" => true,
+            other => return ToolResult::error(format!("Unknown sort: {}", other)),
+        };
 
-        for entry in WalkDir::new(&search_root)
-            .follow_links(true)
-            .into_iter()
-            .filter_entry(|e| {
-                let name = e.file_name().to_string_lossy();
-                !name.starts_with('.') && name != "node_modules" && name != "target"
-            })
-            .filter_map(|e| e.ok())
-        {
-            if entry.file_type().is_file() {
+        let sandbox = self.sandbox.clone();
+        let walk = tokio::task::spawn_blocking(move || {
+            walk_files(&search_root, &sandbox, None, |entry| {
                 let rel_path = entry
                     .path()
                     .strip_prefix(&search_root)
                     .unwrap_or(entry.path());
-                if glob.is_match(rel_path) {
-                    let mtime = entry
-                        .metadata()
-                        .ok()
-                        .and_then(|m| m.modified().ok())
-                        .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                    matches.push((entry.path().to_path_buf(), mtime));
+                if !glob.is_match(rel_path) {
+                    return None;
                 }
-            }
-        }
+                let mtime = entry
+                    .metadata()
+                    .ok()
+                    .and_then(|m| m.modified().ok())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((entry.path().to_path_buf(), mtime))
+            })
+        })
+        .await;
+        let mut matches = match walk {
+            Ok(Ok(matches)) => matches,
+            Ok(Err(e)) => return ToolResult::error(e),
+            Err(e) => return ToolResult::error(format!("Search failed: {}", e)),
+        };
 
-        // Sort by modification time, newest first
-        matches.sort_by_key(|m| std::cmp::Reverse(m.1));
+        if by_path {
+            matches.sort_by(|a, b| a.0.cmp(&b.0));
+        } else {
+            // Newest first; ties by path so pages are stable between calls
+            matches.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        }
 
         debug!("glob: '{}' → {} matches", pattern, matches.len());
 
         if matches.is_empty() {
             ToolResult::text("No files found")
         } else {
-            let result: Vec<String> = matches
+            let paths: Vec<String> = matches
                 .iter()
-                .take(1000) // cap output
                 .map(|(p, _)| p.to_string_lossy().to_string())
                 .collect();
-            ToolResult::text(result.join("\n"))
+            ToolResult::text(Page::from_args(&args).render(&paths))
         }
     }
 }
@@ -1,24 +1,45 @@
 //! Grep tool — content search with regex support
 
+use super::search::{walk_files, Page};
 use crate::registry::{Tool, ToolResult};
-use regex::Regex;
+use crate::sandbox::{Access, WorkspaceSandbox};
+use regex::{Regex, RegexBuilder};
 use serde_json::{json, Value};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tracing::debug;
-use walkdir::WalkDir;
+
+/// Files with a NUL byte in their first block are treated as binary.
+const BINARY_SNIFF_BYTES: usize = 8192;
 
 pub struct GrepTool {
-    workspace_root: PathBuf,
+    sandbox: Arc<WorkspaceSandbox>,
 }
 
 impl GrepTool {
     pub fn new(workspace_root: impl AsRef<Path>) -> Self {
-        Self {
-            workspace_root: workspace_root.as_ref().to_path_buf(),
-        }
+        Self::with_sandbox(Arc::new(WorkspaceSandbox::new(workspace_root)))
+    }
+
+    pub fn with_sandbox(sandbox: Arc<WorkspaceSandbox>) -> Self {
+        Self { sandbox }
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum OutputMode {
+    FilesWithMatches,
+    Content,
+    Count,
+}
+
+struct Search {
+    regex: Regex,
+    multiline: bool,
+    mode: OutputMode,
+    context: usize,
+}
+
 #[async_trait::async_trait]
 impl Tool for GrepTool {
     fn name(&self) -> &str {
@@ -27,7 +48,8 @@ impl Tool for GrepTool {
 
     fn description(&self) -> &str {
         "Search file contents using regex patterns. Returns matching file paths by default, \
-         or matching lines with context. Use glob parameter to filter files."
+         or matching lines with context. Respects .gitignore and skips hidden and binary \
+         files. Filter with glob or type; page through large results with head_limit and offset."
     }
 
     fn is_read_only(&self) -> bool {
@@ -50,6 +72,10 @@ impl Tool for GrepTool {
                     "type": "string",
                     "description": "Glob pattern to filter files (e.g. '*.rs', '*.{ts,tsx}')"
                 },
+                "type": {
+                    "type": "string",
+                    "description": "File type to search (e.g. 'rust', 'py', 'js')"
+                },
                 "output_mode": {
                     "type": "string",
                     "enum": ["files_with_matches", "content", "count"],
@@ -59,180 +85,188 @@ impl Tool for GrepTool {
                     "type": "boolean",
                     "description": "Case insensitive search (default: false)"
                 },
+                "multiline": {
+                    "type": "boolean",
+                    "description": "Let patterns span lines; '.' also matches newlines (default: false)"
+                },
                 "context": {
                     "type": "integer",
                     "description": "Lines of context around matches (for content mode)"
+                },
+                "head_limit": {
+                    "type": "integer",
+                    "description": "Return at most this many files, counts or lines"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "Skip this many files, counts or lines first (default: 0)"
                 }
             },
             "required": ["pattern"]
         })
     }
 
+    fn resources(&self, args: &Value) -> Vec<PathBuf> {
+        vec![self.sandbox.absolute(args["path"].as_str().unwrap_or("."))]
+    }
+
     async fn execute(&self, args: Value) -> ToolResult {
         let pattern_str = match args["pattern"].as_str() {
             Some(p) => p,
             None => return ToolResult::error("Missing required parameter: pattern"),
         };
 
-        let case_insensitive = args["case_insensitive"].as_bool().unwrap_or(false);
-        let regex_pattern = if case_insensitive {
-            format!("(?i){}", pattern_str)
-        } else {
-            pattern_str.to_string()
-        };
-
-        let regex = match Regex::new(&regex_pattern) {
+        let multiline = args["multiline"].as_bool().unwrap_or(false);
+        let regex = match RegexBuilder::new(pattern_str)
+            .case_insensitive(args["case_insensitive"].as_bool().unwrap_or(false))
+            .multi_line(multiline)
+            .dot_matches_new_line(multiline)
+            .build()
+        {
             Ok(r) => r,
             Err(e) => return ToolResult::error(format!("Invalid regex: {}", e)),
         };
 
-        let search_root = args["path"]
-            .as_str()
-            .map(|p| {
-                if Path::new(p).is_absolute() {
-                    PathBuf::from(p)
-                } else {
-                    self.workspace_root.join(p)
-                }
-            })
-            .unwrap_or_else(|| self.workspace_root.clone());
+        let search_root = match self
+            .sandbox
+            .resolve(args["path"].as_str().unwrap_or("."), Access::Read)
+        {
+            Ok(p) => p,
+            Err(e) => return ToolResult::error(e),
+        };
 
-        let output_mode = args["output_mode"].as_str().unwrap_or("files_with_matches");
-        let context_lines = args["context"].as_u64().unwrap_or(0) as usize;
+        let mode = match args["output_mode"].as_str().unwrap_or("files_with_matches") {
+            "files_with_matches" => OutputMode::FilesWithMatches,
+            "content" => OutputMode::Content,
+            "count" => OutputMode::Count,
+            other => return ToolResult::error(format!("Unknown output_mode: {}", other)),
+        };
 
-        let file_glob = args["glob"].as_str().and_then(|g| {
-            globset::GlobBuilder::new(g)
+        let file_glob = match args["glob"].as_str() {
+            Some(g) => match globset::GlobBuilder::new(g)
                 .literal_separator(false)
                 .build()
-                .ok()
-                .map(|g| g.compile_matcher())
-        });
-
-        // If searching a single file
-        if search_root.is_file() {
-            return search_file(&search_root, &regex, output_mode, context_lines);
-        }
-
-        let mut results = Vec::new();
-        let mut _match_count = 0;
-
-        for entry in WalkDir::new(&search_root)
-            .follow_links(true)
-            .into_iter()
-            .filter_entry(|e| {
-                let name = e.file_name().to_string_lossy();
-                !name.starts_with('.') && name != "node_modules" && name != "target"
-            })
-            .filter_map(|e| e.ok())
-        {
-            if !entry.file_type().is_file() {
-                continue;
-            }
-
-            // Apply glob filter
-            if let Some(ref glob) = file_glob {
-                let name = entry.file_name().to_string_lossy();
-                if !glob.is_match(name.as_ref()) {
-                    continue;
-                }
-            }
-
-            // Skip binary files (check first 512 bytes)
-            if let Ok(bytes) = std::fs::read(entry.path()) {
-                if bytes.len() > 512 && bytes[..512].contains(&0) {
-                    continue;
-                }
-            } else {
-                continue;
-            }
-
-            let content = match std::fs::read_to_string(entry.path()) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
+            {
+                Ok(g) => Some(g.compile_matcher()),
+                Err(e) => return ToolResult::error(format!("Invalid glob pattern: {}", e)),
+            },
+            None => None,
+        };
+        let file_type = args["type"].as_str().map(String::from);
 
-            if regex.is_match(&content) {
-                match output_mode {
-                    "files_with_matches" => {
-                        results.push(entry.path().to_string_lossy().to_string());
-                    }
-                    "count" => {
-                        let count = regex.find_iter(&content).count();
-                        results.push(format!("{}:{}", entry.path().display(), count));
-                        _match_count += count;
-                    }
-                    "content" => {
-                        let lines: Vec<&str> = content.lines().collect();
-                        for (i, line) in lines.iter().enumerate() {
-                            if regex.is_match(line) {
-                                let start = i.saturating_sub(context_lines);
-                                let end = (i + context_lines + 1).min(lines.len());
-                                for (j, line_text) in lines.iter().enumerate().take(end).skip(start)
-                                {
-                                    let prefix = if j == i { ">" } else { " " };
-                                    results.push(format!(
-                                        "{}{}:{}:{}",
-                                        prefix,
-                                        entry.path().display(),
-                                        j + 1,
-                                        line_text
-                                    ));
-                                }
-                                if context_lines > 0 && end < lines.len() {
-                                    results.push("--".to_string());
-                                }
-                            }
-                        }
+        let search = Search {
+            regex,
+            multiline,
+            mode,
+            context: args["context"].as_u64().unwrap_or(0) as usize,
+        };
+        let sandbox = self.sandbox.clone();
+        let walk = tokio::task::spawn_blocking(move || {
+            walk_files(&search_root, &sandbox, file_type.as_deref(), |entry| {
+                if let Some(glob) = &file_glob {
+                    if !glob.is_match(entry.file_name()) {
+                        return None;
                     }
-                    _ => {}
                 }
-            }
+                search_file(entry.path(), &search)
+            })
+        })
+        .await;
+        let mut found = match walk {
+            Ok(Ok(found)) => found,
+            Ok(Err(e)) => return ToolResult::error(e),
+            Err(e) => return ToolResult::error(format!("Search failed: {}", e)),
+        };
 
-            if results.len() > 5000 {
-                break;
-            } // cap output
-        }
+        // The walk is parallel; sort so pages are stable between calls
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        let entries: Vec<String> = found.into_iter().flat_map(|(_, lines)| lines).collect();
 
-        debug!("grep: '{}' → {} results", pattern_str, results.len());
+        debug!("grep: '{}' → {} results", pattern_str, entries.len());
 
-        if results.is_empty() {
+        if entries.is_empty() {
             ToolResult::text("No matches found")
         } else {
-            ToolResult::text(results.join("\n"))
+            ToolResult::text(Page::from_args(&args).render(&entries))
         }
     }
 }
 
-fn search_file(path: &Path, regex: &Regex, output_mode: &str, context_lines: usize) -> ToolResult {
-    let content = match std::fs::read_to_string(path) {
-        Ok(c) => c,
-        Err(e) => return ToolResult::error(format!("Failed to read: {}", e)),
-    };
+/// Result lines for one file, or `None` if it has no match or is binary.
+fn search_file(path: &Path, search: &Search) -> Option<(PathBuf, Vec<String>)> {
+    let bytes = std::fs::read(path).ok()?;
+    if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return None;
+    }
+    let content = String::from_utf8(bytes).ok()?;
+    let display = path.display();
 
-    if !regex.is_match(&content) {
-        return ToolResult::text("No matches found");
+    let lines: Vec<&str> = content.lines().collect();
+    let matched = matched_lines(&content, &lines, search);
+    if matched.is_empty() {
+        return None;
     }
 
-    match output_mode {
-        "files_with_matches" => ToolResult::text(path.to_string_lossy().to_string()),
-        "count" => ToolResult::text(format!("{}", regex.find_iter(&content).count())),
-        _ => {
-            let lines: Vec<&str> = content.lines().collect();
-            let mut results = Vec::new();
-            for (i, line) in lines.iter().enumerate() {
-                if regex.is_match(line) {
-                    let start = i.saturating_sub(context_lines);
-                    let end = (i + context_lines + 1).min(lines.len());
-                    for (j, line_text) in lines.iter().enumerate().take(end).skip(start) {
-                        let prefix = if j == i { ">" } else { " " };
-                        results.push(format!("{}{}:{}", prefix, j + 1, line_text));
-                    }
-                    if context_lines > 0 {
-                        results.push("--".to_string());
-                    }
+    let out = match search.mode {
+        OutputMode::FilesWithMatches => vec![display.to_string()],
+        OutputMode::Count => {
+            let count = if search.multiline {
+                search.regex.find_iter(&content).count()
+            } else {
+                lines
+                    .iter()
+                    .map(|line| search.regex.find_iter(line).count())
+                    .sum()
+            };
+            vec![format!("{}:{}", display, count)]
+        }
+        OutputMode::Content => {
+            let mut out = Vec::new();
+            let mut shown_to = 0;
+            for &i in &matched {
+                let start = i.saturating_sub(search.context).max(shown_to);
+                let end = (i + search.context + 1).min(lines.len());
+                if start >= end {
+                    continue;
+                }
+                if search.context > 0 && shown_to > 0 && start > shown_to {
+                    out.push("--".to_string());
+                }
+                for (j, line_text) in lines.iter().enumerate().take(end).skip(start) {
+                    let prefix = if matched.binary_search(&j).is_ok() {
+                        ">"
+                    } else {
+                        " "
+                    };
+                    out.push(format!("{}{}:{}:{}", prefix, display, j + 1, line_text));
                 }
+                shown_to = end;
             }
-            ToolResult::text(results.join("\n"))
+            out
         }
+    };
+    Some((path.to_path_buf(), out))
+}
+
+/// Indices of lines holding a match, ascending. Multiline matches mark every
+/// line they span.
+fn matched_lines(content: &str, lines: &[&str], search: &Search) -> Vec<usize> {
+    if !search.multiline {
+        return lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| search.regex.is_match(line))
+            .map(|(i, _)| i)
+            .collect();
+    }
+    let line_of = |offset: usize| content[..offset].matches('\n').count();
+    let mut matched = Vec::new();
+    for m in search.regex.find_iter(content) {
+        let first = line_of(m.start());
+        // A match ending in a newline does not reach into the next line
+        let last = line_of(m.end().saturating_sub(1).max(m.start()));
+        matched.extend(first..=last.min(lines.len().saturating_sub(1)));
     }
+    matched.dedup();
+    matched
 }
@@ -6,11 +6,16 @@
 //! 3. Add `pub mod <name>;` here
 //! 4. Register it in create_default_registry() in ../lib.rs
 
+pub mod apply_patch;
 pub mod bash;
 pub mod edit;
 pub mod glob;
 pub mod grep;
+pub mod multi_edit;
 pub mod read;
+mod search;
+pub mod shell;
 pub mod spawn;
 pub mod subagent;
+pub mod web_fetch;
 pub mod write;
@@ -0,0 +1,179 @@
+//! Multi-edit tool — several find/replace edits to one file, all or nothing
+
+use super::edit::{apply_edit, write_atomic};
+use crate::checkpoint::CheckpointStore;
+use crate::registry::{Tool, ToolContext, ToolResult};
+use crate::sandbox::{Access, WorkspaceSandbox};
+use crate::schedule::{path_arg, Concurrency};
+use crate::tracker::FileTracker;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tracing::debug;
+
+pub struct MultiEditTool {
+    sandbox: Arc<WorkspaceSandbox>,
+    tracker: Arc<FileTracker>,
+    checkpoints: Arc<CheckpointStore>,
+}
+
+impl MultiEditTool {
+    pub fn new(workspace_root: impl AsRef<Path>) -> Self {
+        Self::with_sandbox(Arc::new(WorkspaceSandbox::new(workspace_root)))
+    }
+
+    pub fn with_sandbox(sandbox: Arc<WorkspaceSandbox>) -> Self {
+        Self {
+            sandbox,
+            tracker: Arc::new(FileTracker::new()),
+            checkpoints: Arc::new(CheckpointStore::new()),
+        }
+    }
+
+    /// Share read/write records with the other file tools.
+    pub fn with_tracker(mut self, tracker: Arc<FileTracker>) -> Self {
+        self.tracker = tracker;
+        self
+    }
+
+    /// Snapshot files into `checkpoints` before changing them.
+    pub fn with_checkpoints(mut self, checkpoints: Arc<CheckpointStore>) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for MultiEditTool {
+    fn name(&self) -> &str {
+        "multi_edit"
+    }
+
+    fn description(&self) -> &str {
+        "Apply several exact-string edits to one file in a single call. Edits run in order, \
+         each on the result of the previous one. If any edit fails, none are applied."
+    }
+
+    fn prompt(&self) -> &str {
+        "Prefer multi_edit over repeated edit calls when changing several places in one file."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "file_path": {
+                    "type": "string",
+                    "description": "Path to the file to edit"
+                },
+                "edits": {
+                    "type": "array",
+                    "description": "Edits to apply in order",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "old_string": {
+                                "type": "string",
+                                "description": "Exact text to find and replace"
+                            },
+                            "new_string": {
+                                "type": "string",
+                                "description": "Replacement text"
+                            },
+                            "replace_all": {
+                                "type": "boolean",
+                                "description": "Replace all occurrences (default: false)"
+                            }
+                        },
+                        "required": ["old_string", "new_string"]
+                    }
+                },
+                "force": {
+                    "type": "boolean",
+                    "description": "Write even if the file changed since you last read it (default: false)"
+                }
+            },
+            "required": ["file_path", "edits"]
+        })
+    }
+
+    fn concurrency(&self) -> Concurrency {
+        Concurrency::Write
+    }
+
+    fn resources(&self, args: &Value) -> Vec<PathBuf> {
+        path_arg(args)
+            .map(|p| vec![self.sandbox.absolute(p)])
+            .unwrap_or_default()
+    }
+
+    async fn execute(&self, args: Value) -> ToolResult {
+        self.execute_in(args, &ToolContext::new("")).await
+    }
+
+    async fn execute_in(&self, args: Value, ctx: &ToolContext) -> ToolResult {
+        let path = match args
+            .get("file_path")
+            .or(args.get("path"))
+            .and_then(|v| v.as_str())
+        {
+            Some(p) => p,
+            None => return ToolResult::error("Missing required parameter: file_path"),
+        };
+        let edits = match args["edits"].as_array() {
+            Some(e) if !e.is_empty() => e,
+            _ => return ToolResult::error("Missing required parameter: edits"),
+        };
+
+        let full_path = match self.sandbox.resolve(path, Access::Write) {
+            Ok(p) => p,
+            Err(e) => return ToolResult::error(e),
+        };
+        if !args["force"].as_bool().unwrap_or(false) {
+            if let Err(e) = self.tracker.check(ctx, &full_path).await {
+                return ToolResult::error(e);
+            }
+        }
+        let original = match fs::read_to_string(&full_path).await {
+            Ok(c) => c,
+            Err(e) => return ToolResult::error(format!("Failed to read file: {}", e)),
+        };
+
+        let mut content = original.clone();
+        for (i, edit) in edits.iter().enumerate() {
+            let (Some(old), Some(new)) = (edit["old_string"].as_str(), edit["new_string"].as_str())
+            else {
+                return ToolResult::error(format!(
+                    "edit {}: old_string and new_string are required; no edits applied",
+                    i + 1
+                ));
+            };
+            let replace_all = edit["replace_all"].as_bool().unwrap_or(false);
+            content = match apply_edit(&content, old, new, replace_all) {
+                Ok(c) => c,
+                Err(e) => {
+                    return ToolResult::error(format!("edit {}: {}; no edits applied", i + 1, e))
+                }
+            };
+        }
+
+        if content == original {
+            return ToolResult::text(format!("No changes to {}", path));
+        }
+        self.checkpoints.snapshot(ctx, &full_path).await;
+        match write_atomic(&full_path, &content).await {
+            Ok(()) => {
+                self.tracker
+                    .record_write(ctx, &full_path, content.as_bytes());
+                debug!("multi_edit: {} ({} edits)", path, edits.len());
+                ToolResult::text(format!("Applied {} edits to {}", edits.len(), path))
+            }
+            Err(e) => ToolResult::error(format!("Failed to write: {}", e)),
+        }
+    }
+
+    async fn end_lineage(&self, lineage: &str) {
+        self.tracker.forget(lineage);
+    }
+}
@@ -1,51 +1,40 @@
 //! Read tool — read file contents with optional offset/limit
 
-use crate::registry::{Tool, ToolResult};
+use crate::registry::{Tool, ToolContext, ToolResult};
+use crate::sandbox::{Access, WorkspaceSandbox};
+use crate::schedule::path_arg;
+use crate::tracker::FileTracker;
+use agenticlaw_llm::{media_type_for_path, ContentBlock};
 use serde_json::{json, Value};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
 use tracing::debug;
 
+/// Anthropic rejects images over 5 MB.
+const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
 pub struct ReadTool {
-    workspace_root: PathBuf,
+    sandbox: Arc<WorkspaceSandbox>,
+    tracker: Arc<FileTracker>,
 }
 
 impl ReadTool {
     pub fn new(workspace_root: impl AsRef<Path>) -> Self {
-        Self {
-            workspace_root: workspace_root.as_ref().to_path_buf(),
-        }
+        Self::with_sandbox(Arc::new(WorkspaceSandbox::new(workspace_root)))
     }
 
-    fn resolve_path(&self, path: &str) -> Result<PathBuf, String> {
-        let p = Path::new(path);
-        let expanded = if path.starts_with("~/") {
-            dirs::home_dir()
-                .unwrap_or_default()
-                .join(path.strip_prefix("~/").unwrap())
-        } else if p.is_absolute() {
-            p.to_path_buf()
-        } else {
-            self.workspace_root.join(p)
-        };
-        // Resolve symlinks if the file exists, otherwise use the path as-is
-        let resolved = expanded.canonicalize().unwrap_or(expanded.clone());
-
-        // Security: block path traversal outside workspace for relative paths
-        if !p.is_absolute() && !path.starts_with("~/") {
-            let ws_canonical = self
-                .workspace_root
-                .canonicalize()
-                .unwrap_or_else(|_| self.workspace_root.clone());
-            if !resolved.starts_with(&ws_canonical) {
-                return Err(format!(
-                    "Path escape blocked: '{}' resolves outside workspace",
-                    path
-                ));
-            }
+    pub fn with_sandbox(sandbox: Arc<WorkspaceSandbox>) -> Self {
+        Self {
+            sandbox,
+            tracker: Arc::new(FileTracker::new()),
         }
+    }
 
-        Ok(resolved)
+    /// Share read/write records with the other file tools.
+    pub fn with_tracker(mut self, tracker: Arc<FileTracker>) -> Self {
+        self.tracker = tracker;
+        self
     }
 }
 
@@ -56,7 +45,8 @@ impl Tool for ReadTool {
     }
 
     fn description(&self) -> &str {
-        "Read the contents of a file. Returns numbered lines. Use offset/limit for large files."
+        "Read the contents of a file. Returns numbered lines. Use offset/limit for large files. \
+         Images (.png, .jpg, .gif, .webp) are returned as images you can see."
     }
 
     fn prompt(&self) -> &str {
@@ -88,7 +78,17 @@ impl Tool for ReadTool {
         })
     }
 
+    fn resources(&self, args: &Value) -> Vec<PathBuf> {
+        path_arg(args)
+            .map(|p| vec![self.sandbox.absolute(p)])
+            .unwrap_or_default()
+    }
+
     async fn execute(&self, args: Value) -> ToolResult {
+        self.execute_in(args, &ToolContext::new("")).await
+    }
+
+    async fn execute_in(&self, args: Value, ctx: &ToolContext) -> ToolResult {
         let path = match args
             .get("file_path")
             .or(args.get("path"))
@@ -98,15 +98,20 @@ impl Tool for ReadTool {
             None => return ToolResult::error("Missing required parameter: file_path"),
         };
 
-        let resolved = match self.resolve_path(path) {
+        let resolved = match self.sandbox.resolve(path, Access::Read) {
             Ok(p) => p,
             Err(e) => return ToolResult::error(e),
         };
 
+        if media_type_for_path(&resolved).is_some_and(|t| t.starts_with("image/")) {
+            return read_image(path, resolved).await;
+        }
+
         let content = match fs::read_to_string(&resolved).await {
             Ok(c) => c,
             Err(e) => return ToolResult::error(format!("Failed to read file: {}", e)),
         };
+        self.tracker.record_read(ctx, &resolved, content.as_bytes());
 
         let offset = args["offset"].as_u64().unwrap_or(1) as usize;
         let limit = args["limit"].as_u64().unwrap_or(2000) as usize;
@@ -130,4 +135,29 @@ impl Tool for ReadTool {
         );
         ToolResult::text(result.join("\n"))
     }
+
+    async fn end_lineage(&self, lineage: &str) {
+        self.tracker.forget(lineage);
+    }
+}
+
+/// Image mode: the file goes back as an image block that references the
+/// file on disk; the provider reads the bytes when it sends the request.
+async fn read_image(path: &str, resolved: PathBuf) -> ToolResult {
+    let size = match fs::metadata(&resolved).await {
+        Ok(m) => m.len(),
+        Err(e) => return ToolResult::error(format!("Failed to read file: {}", e)),
+    };
+    if size > MAX_IMAGE_BYTES {
+        return ToolResult::error(format!(
+            "Image too large: {} bytes (max {})",
+            size, MAX_IMAGE_BYTES
+        ));
+    }
+    debug!("read: {} (image, {} bytes)", path, size);
+    let text = format!("Image: {} ({} bytes)", path, size);
+    match ContentBlock::from_path(resolved) {
+        Some(image) => ToolResult::Content(vec![ContentBlock::Text { text }, image]),
+        None => ToolResult::error(format!("Unsupported image type: {}", path)),
+    }
 }
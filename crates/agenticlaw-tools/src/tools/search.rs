@@ -0,0 +1,110 @@
+//! Shared plumbing for grep and glob — ignore-aware parallel directory walks
+//! and paginated output that stays under a byte budget.
+
+use crate::sandbox::{Access, WorkspaceSandbox};
+use ignore::types::TypesBuilder;
+use ignore::{DirEntry, WalkBuilder, WalkState};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Output is cut at an entry boundary past this, well under the runtime's
+/// default 50,000-byte tool output budget so the pagination footer survives.
+pub(crate) const MAX_OUTPUT_BYTES: usize = 40_000;
+
+/// Walk `root` in parallel, honouring `.gitignore`, `.ignore` and git excludes
+/// (inside a git repo or not) and skipping hidden entries. `visit` runs on
+/// worker threads for every readable file and keeps what it returns.
+///
+/// `file_type` restricts the walk to a ripgrep file type such as `rust` or `py`.
+pub(crate) fn walk_files<T, F>(
+    root: &Path,
+    sandbox: &WorkspaceSandbox,
+    file_type: Option<&str>,
+    visit: F,
+) -> Result<Vec<T>, String>
+where
+    T: Send,
+    F: Fn(&DirEntry) -> Option<T> + Sync,
+{
+    let mut builder = WalkBuilder::new(root);
+    builder.follow_links(true).require_git(false);
+    if let Some(name) = file_type {
+        let mut types = TypesBuilder::new();
+        types.add_defaults();
+        types.select(name);
+        let types = types
+            .build()
+            .map_err(|e| format!("Invalid file type {:?}: {}", name, e))?;
+        builder.types(types);
+    }
+
+    let found = Mutex::new(Vec::new());
+    builder.build_parallel().run(|| {
+        Box::new(|entry| {
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
+            let is_file = entry.file_type().is_some_and(|t| t.is_file());
+            if is_file && sandbox.allows(entry.path(), Access::Read) {
+                if let Some(item) = visit(&entry) {
+                    found.lock().unwrap().push(item);
+                }
+            }
+            WalkState::Continue
+        })
+    });
+    Ok(found.into_inner().unwrap())
+}
+
+/// `offset` and `head_limit` tool arguments.
+pub(crate) struct Page {
+    pub(crate) offset: usize,
+    pub(crate) limit: Option<usize>,
+}
+
+impl Page {
+    pub(crate) fn from_args(args: &Value) -> Self {
+        Self {
+            offset: args["offset"].as_u64().unwrap_or(0) as usize,
+            limit: args["head_limit"]
+                .as_u64()
+                .filter(|&n| n > 0)
+                .map(|n| n as usize),
+        }
+    }
+
+    /// Join the entries on this page, one per line, stopping early at
+    /// `MAX_OUTPUT_BYTES`. A footer says how to fetch the rest.
+    pub(crate) fn render(&self, entries: &[String]) -> String {
+        let total = entries.len();
+        let start = self.offset.min(total);
+        let end = self
+            .limit
+            .map_or(total, |limit| start.saturating_add(limit).min(total));
+        if start == total {
+            return format!("[offset {} is past the last of {} results]", start, total);
+        }
+
+        let mut out = String::new();
+        let mut shown = start;
+        for entry in &entries[start..end] {
+            if !out.is_empty() && out.len() + entry.len() + 1 > MAX_OUTPUT_BYTES {
+                break;
+            }
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(entry);
+            shown += 1;
+        }
+        if start > 0 || shown < total {
+            out.push_str(&format!("\n[showing {}-{} of {}", start + 1, shown, total));
+            if shown < total {
+                out.push_str(&format!("; use offset={} for more", shown));
+            }
+            out.push(']');
+        }
+        out
+    }
+}
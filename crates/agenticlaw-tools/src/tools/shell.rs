@@ -0,0 +1,630 @@
+//! Shell tool — named processes that outlive a single tool call
+//!
+//! `bash` runs one command to completion. `shell` keeps processes alive
+//! between calls, in two shapes:
+//! - a persistent bash (`exec`), so `cd`, exports and functions carry over
+//! - a background command (`start`) such as a dev server, REPL or long build,
+//!   which the agent polls, feeds stdin, waits on and kills
+//!
+//! Processes belong to the agent session that started them and are killed
+//! (with their process group) when that session ends.
+
+use crate::registry::{Tool, ToolContext, ToolResult};
+use crate::schedule::TIMEOUT_GRACE;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+const MAX_SHELLS_PER_SESSION: usize = 8;
+/// Unread output kept per process; older output is dropped first.
+const MAX_LOG_BYTES: usize = 1_000_000;
+/// Output returned by one call; the rest stays for the next read.
+const MAX_READ_BYTES: usize = 30_000;
+const DEFAULT_WAIT_SECS: u64 = 30;
+const MAX_WAIT_SECS: u64 = 600;
+/// How long `start` waits for early output or an immediate failure.
+const START_GRACE: Duration = Duration::from_millis(500);
+
+type Shared = Arc<tokio::sync::Mutex<ShellProcess>>;
+
+pub struct ShellTool {
+    workspace_root: PathBuf,
+    /// session → shell name → process
+    sessions: Mutex<HashMap<String, HashMap<String, Shared>>>,
+}
+
+impl ShellTool {
+    pub fn new(workspace_root: impl AsRef<Path>) -> Self {
+        Self {
+            workspace_root: workspace_root.as_ref().to_path_buf(),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, session: &str, name: &str) -> Option<Shared> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session)
+            .and_then(|shells| shells.get(name).cloned())
+    }
+
+    fn take(&self, session: &str, name: &str) -> Option<Shared> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get_mut(session)
+            .and_then(|shells| shells.remove(name))
+    }
+
+    /// Spawn and register a process. `command: None` is a persistent bash fed
+    /// through stdin.
+    fn spawn(&self, session: &str, name: &str, command: Option<&str>) -> Result<Shared, String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let shells = sessions.entry(session.to_string()).or_default();
+        if shells.contains_key(name) {
+            return Err(format!(
+                "shell '{}' already exists; kill it first or pick another name",
+                name
+            ));
+        }
+        if shells.len() >= MAX_SHELLS_PER_SESSION {
+            return Err(format!(
+                "too many shells ({}); kill one first",
+                MAX_SHELLS_PER_SESSION
+            ));
+        }
+        let process = ShellProcess::spawn(&self.workspace_root, command)?;
+        info!(session, shell = name, pid = ?process.child.id(), "shell started");
+        let process: Shared = Arc::new(tokio::sync::Mutex::new(process));
+        shells.insert(name.to_string(), process.clone());
+        Ok(process)
+    }
+
+    async fn start(&self, session: &str, name: &str, args: &Value) -> ToolResult {
+        let Some(command) = args["command"].as_str() else {
+            return ToolResult::error("'start' requires 'command'");
+        };
+        let process = match self.spawn(session, name, Some(command)) {
+            Ok(p) => p,
+            Err(e) => return ToolResult::error(e),
+        };
+        let mut process = process.lock().await;
+        process
+            .wait_exit(START_GRACE, &CancellationToken::new())
+            .await;
+        ToolResult::text(process.report(name))
+    }
+
+    async fn exec(
+        &self,
+        session: &str,
+        name: &str,
+        args: &Value,
+        cancel: &CancellationToken,
+    ) -> ToolResult {
+        let Some(command) = args["command"].as_str() else {
+            return ToolResult::error("'exec' requires 'command'");
+        };
+        let process = match self.get(session, name) {
+            Some(p) => p,
+            None => match self.spawn(session, name, None) {
+                Ok(p) => p,
+                Err(e) => return ToolResult::error(e),
+            },
+        };
+        let mut process = process.lock().await;
+        if process.command.is_some() {
+            return ToolResult::error(format!(
+                "'{}' runs a background command; use write to send it input",
+                name
+            ));
+        }
+        match process.exec(command, wait_timeout(args), cancel).await {
+            Ok(Some((output, 0))) => ToolResult::text(or_no_output(output)),
+            Ok(Some((output, code))) => {
+                ToolResult::text(format!("Exit code: {}\n{}", code, output.trim_end()))
+            }
+            Ok(None) => ToolResult::text(format!(
+                "{}\n[still running in '{}'; use wait or poll for the rest]",
+                process.read_new(),
+                name
+            )),
+            Err(e) => ToolResult::error(e),
+        }
+    }
+
+    async fn end(&self, session: &str) {
+        let shells = self.sessions.lock().unwrap().remove(session);
+        for (name, process) in shells.into_iter().flatten() {
+            info!(session, shell = %name, "killing shell: session ended");
+            process.lock().await.kill().await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for ShellTool {
+    fn name(&self) -> &str {
+        "shell"
+    }
+
+    fn description(&self) -> &str {
+        "Named shells that persist across calls. exec runs a command in a persistent bash \
+         (cd and env carry over). start launches a background command (dev server, REPL, \
+         long build); then poll for new output, write to its stdin, wait for it to exit, \
+         or kill it. list shows your shells."
+    }
+
+    fn prompt(&self) -> &str {
+        "Use shell instead of bash when state must survive between commands or a process \
+         must keep running: shell(action='start', name='dev', command='npm run dev'), then \
+         poll. Kill background processes you no longer need."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "required": ["action"],
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["exec", "start", "poll", "write", "wait", "kill", "list"]
+                },
+                "name": {
+                    "type": "string",
+                    "description": "Shell name (default 'default')"
+                },
+                "command": {
+                    "type": "string",
+                    "description": "Command for exec or start"
+                },
+                "input": {
+                    "type": "string",
+                    "description": "Text to write to stdin (include a trailing newline to submit a line)"
+                },
+                "timeout": {
+                    "type": "integer",
+                    "description": "Seconds exec/wait block before returning partial output (default 30, max 600)"
+                }
+            }
+        })
+    }
+
+    /// Waiting actions stop at their own `timeout` and return what arrived.
+    fn timeout(&self, args: &Value) -> Option<Duration> {
+        Some(wait_timeout(args) + TIMEOUT_GRACE)
+    }
+
+    async fn execute(&self, args: Value) -> ToolResult {
+        self.execute_in(args, &ToolContext::new("")).await
+    }
+
+    async fn execute_in(&self, args: Value, ctx: &ToolContext) -> ToolResult {
+        let action = args["action"].as_str().unwrap_or("");
+        let name = args["name"].as_str().unwrap_or("default");
+        let session = ctx.session.as_str();
+        debug!(session, shell = name, action, "shell");
+
+        match action {
+            "exec" => self.exec(session, name, &args, &ctx.cancel).await,
+            "start" => self.start(session, name, &args).await,
+            "list" => {
+                let shells: Vec<_> = self
+                    .sessions
+                    .lock()
+                    .unwrap()
+                    .get(session)
+                    .map(|s| s.iter().map(|(n, p)| (n.clone(), p.clone())).collect())
+                    .unwrap_or_default();
+                if shells.is_empty() {
+                    return ToolResult::text("(no shells)");
+                }
+                let mut lines = Vec::new();
+                for (name, process) in shells {
+                    let mut process = process.lock().await;
+                    lines.push(format!(
+                        "{} — {}",
+                        process.status_line(&name),
+                        process.command.as_deref().unwrap_or("persistent bash")
+                    ));
+                }
+                lines.sort();
+                ToolResult::text(lines.join("\n"))
+            }
+            "poll" | "write" | "wait" | "kill" => {
+                let Some(process) = (if action == "kill" {
+                    self.take(session, name)
+                } else {
+                    self.get(session, name)
+                }) else {
+                    return ToolResult::error(format!("no shell named '{}'", name));
+                };
+                let mut process = process.lock().await;
+                match action {
+                    "write" => {
+                        let Some(input) = args["input"].as_str() else {
+                            return ToolResult::error("'write' requires 'input'");
+                        };
+                        if let Err(e) = process.write(input).await {
+                            return ToolResult::error(e);
+                        }
+                        // Give the process a moment to react
+                        process.wait_output(START_GRACE, &ctx.cancel).await;
+                    }
+                    "wait" => process.wait_exit(wait_timeout(&args), &ctx.cancel).await,
+                    "kill" => process.kill().await,
+                    _ => {}
+                }
+                ToolResult::text(process.report(name))
+            }
+            "" => ToolResult::error("Missing required parameter: action"),
+            other => ToolResult::error(format!("unknown action '{}'", other)),
+        }
+    }
+
+    async fn end_session(&self, session: &str) {
+        self.end(session).await;
+    }
+}
+
+fn wait_timeout(args: &Value) -> Duration {
+    Duration::from_secs(
+        args["timeout"]
+            .as_u64()
+            .unwrap_or(DEFAULT_WAIT_SECS)
+            .min(MAX_WAIT_SECS),
+    )
+}
+
+fn or_no_output(output: String) -> String {
+    let output = output.trim_end().to_string();
+    if output.is_empty() {
+        "(no output)".into()
+    } else {
+        output
+    }
+}
+
+// ── Process ─────────────────────────────────────────────────────────────
+
+/// Combined stdout/stderr with a read cursor.
+#[derive(Default)]
+struct OutputLog {
+    text: String,
+    /// Byte offset of the first unread byte.
+    read: usize,
+    /// Unread bytes discarded to stay under `MAX_LOG_BYTES`.
+    dropped: usize,
+}
+
+impl OutputLog {
+    fn push(&mut self, chunk: &str) {
+        self.text.push_str(chunk);
+        if self.text.len() > MAX_LOG_BYTES {
+            let mut cut = self.text.len() - MAX_LOG_BYTES;
+            while !self.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.dropped += cut.saturating_sub(self.read);
+            self.text.drain(..cut);
+            self.read = self.read.saturating_sub(cut);
+        }
+    }
+
+    fn unread(&self) -> &str {
+        &self.text[self.read..]
+    }
+}
+
+struct ShellProcess {
+    /// `None` for a persistent bash driven through `exec`.
+    command: Option<String>,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    log: Arc<Mutex<OutputLog>>,
+    /// Signalled on new output and when the pipes close.
+    output: Arc<Notify>,
+    started: Instant,
+    exit_code: Option<i32>,
+    /// Numbers `exec` completion markers.
+    execs: u64,
+    /// Marker of an `exec` that timed out and hasn't printed it yet.
+    pending: Option<String>,
+}
+
+impl ShellProcess {
+    fn spawn(cwd: &Path, command: Option<&str>) -> Result<Self, String> {
+        // stderr joins stdout so output stays in order; the persistent shell
+        // then reads its commands from stdin
+        let script = match command {
+            Some(c) => format!("exec 2>&1\n{}", c),
+            None => "exec 2>&1\nexec bash -s".to_string(),
+        };
+        let mut cmd = Command::new("bash");
+        cmd.arg("-c").arg(script);
+        cmd.current_dir(cwd)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn: {}", e))?;
+        let log = Arc::new(Mutex::new(OutputLog::default()));
+        let output = Arc::new(Notify::new());
+        if let Some(stdout) = child.stdout.take() {
+            pump(stdout, log.clone(), output.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            pump(stderr, log.clone(), output.clone());
+        }
+        Ok(Self {
+            command: command.map(String::from),
+            stdin: child.stdin.take(),
+            child,
+            log,
+            output,
+            started: Instant::now(),
+            exit_code: None,
+            execs: 0,
+            pending: None,
+        })
+    }
+
+    async fn write(&mut self, input: &str) -> Result<(), String> {
+        let stdin = self.stdin.as_mut().ok_or("stdin closed")?;
+        stdin
+            .write_all(input.as_bytes())
+            .await
+            .map_err(|e| format!("write failed: {}", e))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| format!("write failed: {}", e))
+    }
+
+    fn running(&mut self) -> bool {
+        if self.exit_code.is_none() {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                self.exit_code = Some(status.code().unwrap_or(-1));
+            }
+        }
+        self.exit_code.is_none()
+    }
+
+    /// Run `command` in the persistent shell; `Some((output, exit_code))` once
+    /// it finishes, `None` if `timeout` passed first (output stays unread).
+    async fn exec(
+        &mut self,
+        command: &str,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<Option<(String, i32)>, String> {
+        if !self.running() {
+            return Err(format!(
+                "shell exited with code {}",
+                self.exit_code.unwrap_or(-1)
+            ));
+        }
+        if !self.settle_pending() {
+            return Err(
+                "the previous command is still running; wait or poll for it, or kill the shell"
+                    .to_string(),
+            );
+        }
+        self.execs += 1;
+        let marker = format!("__agenticlaw_exec_{}_{}__", std::process::id(), self.execs);
+        self.write(&format!(
+            "{}\nprintf '\\n{} %s\\n' \"$?\"\n",
+            command, marker
+        ))
+        .await?;
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        let output = self.output.clone();
+        loop {
+            let notified = output.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            {
+                let mut log = self.log.lock().unwrap();
+                if let Some(pos) = log.unread().find(&marker) {
+                    let start = log.read;
+                    let rest = &log.text[start + pos + marker.len()..];
+                    if let Some(eol) = rest.find('\n') {
+                        let code = rest[..eol].trim().parse().unwrap_or(-1);
+                        let output = log.text[start..start + pos].to_string();
+                        // Drop the newline printf put ahead of the marker
+                        let output = output.strip_suffix('\n').unwrap_or(&output).to_string();
+                        log.read = start + pos + marker.len() + eol + 1;
+                        return Ok(Some((output, code)));
+                    }
+                }
+            }
+            if !self.running() {
+                return Err(format!(
+                    "shell exited with code {}\n{}",
+                    self.exit_code.unwrap_or(-1),
+                    self.read_new()
+                ));
+            }
+            tokio::select! {
+                _ = notified => {}
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+                _ = tokio::time::sleep_until(deadline) => break,
+                _ = cancel.cancelled() => break,
+            }
+        }
+        self.pending = Some(marker);
+        Ok(None)
+    }
+
+    /// Swap a timed-out `exec`'s marker line, once it shows up, for a note
+    /// with its exit code. True when no `exec` is outstanding.
+    fn settle_pending(&mut self) -> bool {
+        let Some(marker) = &self.pending else {
+            return true;
+        };
+        let mut log = self.log.lock().unwrap();
+        let read = log.read;
+        let Some(pos) = log.unread().find(marker.as_str()) else {
+            return false;
+        };
+        let start = read + pos;
+        let Some(eol) = log.text[start..].find('\n') else {
+            return false;
+        };
+        let code = log.text[start + marker.len()..start + eol]
+            .trim()
+            .to_string();
+        let note = format!("[previous command exited with code {}]", code);
+        log.text.replace_range(start..start + eol, &note);
+        drop(log);
+        self.pending = None;
+        true
+    }
+
+    /// Wait until the process exits, `timeout` passes or `cancel` fires.
+    /// A persistent bash stops waiting once its timed-out `exec` finishes.
+    async fn wait_exit(&mut self, timeout: Duration, cancel: &CancellationToken) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.running() {
+            if self.command.is_none() && self.settle_pending() {
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(50)) => {}
+                _ = tokio::time::sleep_until(deadline) => break,
+                _ = cancel.cancelled() => break,
+            }
+        }
+        // Let the pumps drain what the process wrote last
+        if !self.running() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Wait for any new output, up to `timeout`.
+    async fn wait_output(&mut self, timeout: Duration, cancel: &CancellationToken) {
+        let notified = self.output.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if !self.log.lock().unwrap().unread().is_empty() {
+            return;
+        }
+        tokio::select! {
+            _ = notified => {}
+            _ = tokio::time::sleep(timeout) => {}
+            _ = cancel.cancelled() => {}
+        }
+    }
+
+    /// Unread output (up to `MAX_READ_BYTES`), advancing the cursor.
+    fn read_new(&mut self) -> String {
+        self.settle_pending();
+        let mut log = self.log.lock().unwrap();
+        let mut out = String::new();
+        if log.dropped > 0 {
+            out.push_str(&format!(
+                "[{} bytes of older output dropped]\n",
+                log.dropped
+            ));
+            log.dropped = 0;
+        }
+        let unread = log.unread();
+        let mut end = unread.len().min(MAX_READ_BYTES);
+        while !unread.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.push_str(&unread[..end]);
+        let more = unread.len() - end;
+        log.read += end;
+        if more > 0 {
+            out.push_str(&format!("\n[{} more bytes; poll again]", more));
+        }
+        out
+    }
+
+    fn status_line(&mut self, name: &str) -> String {
+        if self.running() {
+            format!("[{}: running, {}s]", name, self.started.elapsed().as_secs())
+        } else {
+            format!(
+                "[{}: exited with code {}]",
+                name,
+                self.exit_code.unwrap_or(-1)
+            )
+        }
+    }
+
+    /// New output followed by the status line.
+    fn report(&mut self, name: &str) -> String {
+        let status = self.status_line(name);
+        let output = self.read_new();
+        if output.trim().is_empty() {
+            status
+        } else {
+            format!("{}\n{}", output.trim_end(), status)
+        }
+    }
+
+    /// Kill the whole process group, so children of the shell die too.
+    async fn kill(&mut self) {
+        if !self.running() {
+            return;
+        }
+        #[cfg(unix)]
+        if let Some(pid) = self.child.id() {
+            // SAFETY: plain syscall; the group was created by process_group(0) at spawn
+            unsafe {
+                libc::killpg(pid as i32, libc::SIGKILL);
+            }
+        }
+        let _ = self.child.kill().await;
+        self.running();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// Copy a pipe into the log until it closes, decoding UTF-8 across chunk edges.
+fn pump(
+    mut pipe: impl AsyncRead + Unpin + Send + 'static,
+    log: Arc<Mutex<OutputLog>>,
+    notify: Arc<Notify>,
+) {
+    tokio::spawn(async move {
+        let mut buf = [0u8; 8192];
+        let mut pending: Vec<u8> = Vec::new();
+        loop {
+            match pipe.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    pending.extend_from_slice(&buf[..n]);
+                    let valid = match std::str::from_utf8(&pending) {
+                        Ok(_) => pending.len(),
+                        // Incomplete sequence at the end: keep it for the next chunk
+                        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                        Err(_) => pending.len(),
+                    };
+                    let chunk: Vec<u8> = pending.drain(..valid).collect();
+                    log.lock().unwrap().push(&String::from_utf8_lossy(&chunk));
+                    notify.notify_waiters();
+                }
+            }
+        }
+        if !pending.is_empty() {
+            log.lock().unwrap().push(&String::from_utf8_lossy(&pending));
+        }
+        notify.notify_waiters();
+    });
+}
@@ -7,10 +7,12 @@
 //! The observability layer (resource driver) records everything structurally:
 //! prompt, fear, ego, transcript, output, metrics — all written by code, not the agent.
 
-use crate::registry::{Tool, ToolResult};
+use crate::registry::{Tool, ToolContext, ToolResult};
+use crate::schedule::Concurrency;
 use serde_json::{json, Value};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 /// Shared handle to the agent runtime, set after construction.
@@ -87,16 +89,36 @@ impl std::fmt::Display for SubagentInfoSnapshot {
 #[async_trait::async_trait]
 pub trait SpawnableRuntime: Send + Sync {
     /// Run a child agent turn with the given system prompt and user message.
+    /// The child shares `parent`'s lineage, so file-tool reads carry over.
     /// Returns (output_text, token_estimate).
     async fn spawn_child(
         &self,
+        parent: &ToolContext,
         session_id: &str,
         system_prompt: &str,
         user_message: &str,
         max_iterations: usize,
     ) -> Result<(String, usize), String>;
+
+    /// Run a child agent that must finish with an answer matching `schema`,
+    /// re-prompting up to `max_retries` times on violations.
+    /// Returns (answer, token_estimate).
+    #[allow(clippy::too_many_arguments)]
+    async fn spawn_child_structured(
+        &self,
+        parent: &ToolContext,
+        session_id: &str,
+        system_prompt: &str,
+        user_message: &str,
+        max_iterations: usize,
+        schema: &Value,
+        max_retries: usize,
+    ) -> Result<(Value, usize), String>;
 }
 
+/// Re-prompts a structured child gets before the spawn fails.
+const STRUCTURED_MAX_RETRIES: usize = 2;
+
 pub struct SpawnTool {
     #[allow(dead_code)]
     workspace_root: PathBuf,
@@ -198,12 +220,31 @@ impl Tool for SpawnTool {
                 "max_iterations": {
                     "type": "integer",
                     "description": "Max tool call iterations (default 25, max 50)"
+                },
+                "output_schema": {
+                    "type": "object",
+                    "description": "Optional JSON Schema for the child's answer. The child must finish by calling final_answer with matching input, and you get that JSON back instead of free text."
                 }
             }
         })
     }
 
+    /// A child may edit any file in the workspace, so it runs alone rather
+    /// than alongside the parent's own edits.
+    fn concurrency(&self) -> Concurrency {
+        Concurrency::Exclusive
+    }
+
+    /// Bounded by the child's iteration limit instead.
+    fn timeout(&self, _args: &Value) -> Option<Duration> {
+        None
+    }
+
     async fn execute(&self, args: Value) -> ToolResult {
+        self.execute_in(args, &ToolContext::new("")).await
+    }
+
+    async fn execute_in(&self, args: Value, ctx: &ToolContext) -> ToolResult {
         let purpose = args
             .get("purpose")
             .and_then(|v| v.as_str())
@@ -219,6 +260,11 @@ impl Tool for SpawnTool {
             .and_then(|v| v.as_u64())
             .unwrap_or(25)
             .min(50) as usize;
+        let output_schema = match args.get("output_schema") {
+            None | Some(Value::Null) => None,
+            Some(schema @ Value::Object(_)) => Some(schema),
+            Some(_) => return ToolResult::error("'output_schema' must be a JSON Schema object"),
+        };
 
         let child_id = self.next_child_id();
         let session_id = format!(
@@ -301,14 +347,33 @@ impl Tool for SpawnTool {
         };
         drop(runtime_guard); // release lock before async work
 
-        let result = runtime
-            .spawn_child(&session_id, &system_prompt, task, max_iter)
-            .await;
+        // Structured children hand back JSON; output.md keeps it pretty-printed.
+        let result = match output_schema {
+            Some(schema) => runtime
+                .spawn_child_structured(
+                    ctx,
+                    &session_id,
+                    &system_prompt,
+                    task,
+                    max_iter,
+                    schema,
+                    STRUCTURED_MAX_RETRIES,
+                )
+                .await
+                .map(|(answer, tokens)| {
+                    let output = serde_json::to_string_pretty(&answer).unwrap_or_default();
+                    (output, Some(answer), tokens)
+                }),
+            None => runtime
+                .spawn_child(ctx, &session_id, &system_prompt, task, max_iter)
+                .await
+                .map(|(output, tokens)| (output, None, tokens)),
+        };
         let wall_ms = start.elapsed().as_millis() as u64;
 
         // --- CODE: Write results AFTER spawn ---
         match &result {
-            Ok((output, tokens)) => {
+            Ok((output, answer, tokens)) => {
                 tracing::info!(
                     child = %session_id,
                     tokens = tokens,
@@ -344,6 +409,9 @@ impl Tool for SpawnTool {
                     )).await;
                 }
 
+                if let Some(answer) = answer {
+                    return ToolResult::Json(answer.clone());
+                }
                 let name_info = subagent_name.as_deref().unwrap_or(&session_id);
                 ToolResult::text(format!("[{}] {}", name_info, output))
             }
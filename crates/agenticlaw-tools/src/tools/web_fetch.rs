@@ -0,0 +1,293 @@
+//! Web fetch tool — fetch a URL and return it as readable markdown
+
+use crate::network::{NetworkPolicy, Resolver};
+use crate::registry::{Tool, ToolResult};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::debug;
+
+/// Returned content is cut here unless the call asks for more.
+pub const DEFAULT_MAX_BYTES: usize = 40_000;
+/// Upper bound for the `max_bytes` argument.
+const MAX_MAX_BYTES: usize = 1024 * 1024;
+/// Bodies are read up to this before conversion; the rest is dropped.
+const MAX_DOWNLOAD_BYTES: usize = 5 * 1024 * 1024;
+const MAX_REDIRECTS: usize = 10;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Elements with no readable content.
+const SKIP_TAGS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "iframe",
+];
+
+pub struct WebFetchTool {
+    client: reqwest::Client,
+    policy: Arc<NetworkPolicy>,
+}
+
+impl Default for WebFetchTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebFetchTool {
+    /// Fetch with the default network policy: HTTP(S), minus metadata endpoints.
+    pub fn new() -> Self {
+        Self::with_policy(Arc::new(NetworkPolicy::default()))
+    }
+
+    /// Fetch only what `policy` allows. Every redirect hop is checked too,
+    /// and every address connected to.
+    pub fn with_policy(policy: Arc<NetworkPolicy>) -> Self {
+        let redirect_policy = policy.clone();
+        let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(format!("more than {} redirects", MAX_REDIRECTS));
+            }
+            match redirect_policy.check(attempt.url().as_str()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(format!("redirect blocked: {}", e)),
+            }
+        });
+        let client = reqwest::Client::builder()
+            .redirect(redirect)
+            .dns_resolver(Arc::new(Resolver::new(policy.clone())))
+            // A proxy would resolve, and reach, hosts past the resolver
+            .no_proxy()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("agenticlaw/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("static reqwest client config");
+        Self { client, policy }
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for WebFetchTool {
+    fn name(&self) -> &str {
+        "web_fetch"
+    }
+
+    fn description(&self) -> &str {
+        "Fetch an http(s) URL. HTML pages are converted to markdown; other text \
+         (JSON, XML, plain text) is returned as is. Binary content is refused. \
+         Output is capped at max_bytes."
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The http:// or https:// URL to fetch"
+                },
+                "max_bytes": {
+                    "type": "integer",
+                    "description": format!(
+                        "Maximum bytes of content to return (default: {}, max: {})",
+                        DEFAULT_MAX_BYTES, MAX_MAX_BYTES
+                    )
+                },
+                "raw": {
+                    "type": "boolean",
+                    "description": "Return HTML as is instead of converting to markdown (default: false)"
+                }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, args: Value) -> ToolResult {
+        let url = match args["url"].as_str() {
+            Some(u) => u,
+            None => return ToolResult::error("Missing required parameter: url"),
+        };
+        let parsed = match reqwest::Url::parse(url) {
+            Ok(u) if matches!(u.scheme(), "http" | "https") => u,
+            Ok(u) => return ToolResult::error(format!("Unsupported URL scheme: {}", u.scheme())),
+            Err(e) => return ToolResult::error(format!("Invalid URL: {}", e)),
+        };
+        if let Err(e) = self.policy.check(parsed.as_str()) {
+            return ToolResult::error(e);
+        }
+        let max_bytes = args["max_bytes"]
+            .as_u64()
+            .map_or(DEFAULT_MAX_BYTES, |n| (n as usize).min(MAX_MAX_BYTES));
+        let raw = args["raw"].as_bool().unwrap_or(false);
+
+        debug!("web_fetch: {}", parsed);
+        let mut response = match self.client.get(parsed).send().await {
+            Ok(r) => r,
+            Err(e) => return ToolResult::error(format!("Fetch failed: {}", describe(&e))),
+        };
+        let status = response.status();
+        let final_url = response.url().to_string();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| {
+                v.split(';')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .to_ascii_lowercase()
+            });
+
+        let mut body = Vec::new();
+        let mut cut = false;
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    let room = MAX_DOWNLOAD_BYTES - body.len();
+                    body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+                    if chunk.len() >= room {
+                        cut = true;
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => return ToolResult::error(format!("Fetch failed: {}", describe(&e))),
+            }
+        }
+
+        let kind = match classify(content_type.as_deref(), &body) {
+            Some(kind) => kind,
+            None => {
+                return ToolResult::error(format!(
+                    "Unsupported content type {} ({} bytes) from {}; web_fetch returns text only",
+                    content_type.as_deref().unwrap_or("(none)"),
+                    body.len(),
+                    final_url
+                ))
+            }
+        };
+        let text = String::from_utf8_lossy(&body);
+        let mut title = None;
+        let content = if kind == Kind::Html && !raw {
+            title = html_title(&text);
+            html_to_markdown(&text)
+        } else {
+            text.into_owned()
+        };
+
+        let mut out = format!("URL: {}\n", final_url);
+        if let Some(ct) = &content_type {
+            out.push_str(&format!("Content-Type: {}\n", ct));
+        }
+        if let Some(title) = title {
+            out.push_str(&format!("Title: {}\n", title));
+        }
+        out.push('\n');
+        let total = content.len();
+        if total > max_bytes {
+            let end = floor_char_boundary(&content, max_bytes);
+            out.push_str(&content[..end]);
+            out.push_str(&format!(
+                "\n\n[truncated at {} of {} bytes; raise max_bytes for more]",
+                end, total
+            ));
+        } else {
+            out.push_str(&content);
+            if cut {
+                out.push_str(&format!(
+                    "\n\n[download stopped at {} bytes]",
+                    MAX_DOWNLOAD_BYTES
+                ));
+            }
+        }
+
+        if status.is_success() {
+            ToolResult::text(out)
+        } else {
+            ToolResult::error(format!("HTTP {} from {}\n\n{}", status, final_url, out))
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Html,
+    Text,
+}
+
+/// How to present a body, from its content type or, without one, its bytes.
+/// `None` for binary content.
+fn classify(content_type: Option<&str>, body: &[u8]) -> Option<Kind> {
+    match content_type {
+        Some("text/html" | "application/xhtml+xml") => Some(Kind::Html),
+        Some(ct)
+            if ct.starts_with("text/")
+                || ct.ends_with("+json")
+                || ct.ends_with("+xml")
+                || matches!(
+                    ct,
+                    "application/json"
+                        | "application/xml"
+                        | "application/javascript"
+                        | "application/x-ndjson"
+                        | "application/yaml"
+                        | "application/toml"
+                ) =>
+        {
+            Some(Kind::Text)
+        }
+        Some(_) => None,
+        None => {
+            let head = String::from_utf8_lossy(&body[..body.len().min(512)]).to_ascii_lowercase();
+            if body.contains(&0) || std::str::from_utf8(body).is_err() {
+                None
+            } else if head.trim_start().starts_with("<!doctype html") || head.contains("<html") {
+                Some(Kind::Html)
+            } else {
+                Some(Kind::Text)
+            }
+        }
+    }
+}
+
+fn html_to_markdown(html: &str) -> String {
+    let converter = htmd::HtmlToMarkdown::builder()
+        .skip_tags(SKIP_TAGS.to_vec())
+        .build();
+    match converter.convert(html) {
+        Ok(markdown) => markdown.trim().to_string(),
+        Err(_) => html.to_string(),
+    }
+}
+
+fn html_title(html: &str) -> Option<String> {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let re = TITLE.get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+    let captures = re.captures(html)?;
+    let title = captures[1].split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+fn floor_char_boundary(s: &str, max: usize) -> usize {
+    let mut end = max.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    end
+}
+
+/// reqwest hides the interesting part (e.g. a blocked redirect) in the source chain.
+fn describe(e: &reqwest::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(s) = source {
+        message.push_str(": ");
+        message.push_str(&s.to_string());
+        source = s.source();
+    }
+    message
+}
@@ -1,21 +1,46 @@
 //! Write tool — create or overwrite a file
 
-use crate::registry::{Tool, ToolResult};
+use crate::checkpoint::CheckpointStore;
+use crate::registry::{Tool, ToolContext, ToolResult};
+use crate::sandbox::{Access, WorkspaceSandbox};
+use crate::schedule::{path_arg, Concurrency};
+use crate::tracker::FileTracker;
 use serde_json::{json, Value};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
 use tracing::debug;
 
 pub struct WriteTool {
-    workspace_root: PathBuf,
+    sandbox: Arc<WorkspaceSandbox>,
+    tracker: Arc<FileTracker>,
+    checkpoints: Arc<CheckpointStore>,
 }
 
 impl WriteTool {
     pub fn new(workspace_root: impl AsRef<Path>) -> Self {
+        Self::with_sandbox(Arc::new(WorkspaceSandbox::new(workspace_root)))
+    }
+
+    pub fn with_sandbox(sandbox: Arc<WorkspaceSandbox>) -> Self {
         Self {
-            workspace_root: workspace_root.as_ref().to_path_buf(),
+            sandbox,
+            tracker: Arc::new(FileTracker::new()),
+            checkpoints: Arc::new(CheckpointStore::new()),
         }
     }
+
+    /// Share read/write records with the other file tools.
+    pub fn with_tracker(mut self, tracker: Arc<FileTracker>) -> Self {
+        self.tracker = tracker;
+        self
+    }
+
+    /// Snapshot files into `checkpoints` before changing them.
+    pub fn with_checkpoints(mut self, checkpoints: Arc<CheckpointStore>) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -40,13 +65,31 @@ impl Tool for WriteTool {
                 "content": {
                     "type": "string",
                     "description": "Content to write to the file"
+                },
+                "force": {
+                    "type": "boolean",
+                    "description": "Write even if the file changed since you last read it (default: false)"
                 }
             },
             "required": ["file_path", "content"]
         })
     }
 
+    fn concurrency(&self) -> Concurrency {
+        Concurrency::Write
+    }
+
+    fn resources(&self, args: &Value) -> Vec<PathBuf> {
+        path_arg(args)
+            .map(|p| vec![self.sandbox.absolute(p)])
+            .unwrap_or_default()
+    }
+
     async fn execute(&self, args: Value) -> ToolResult {
+        self.execute_in(args, &ToolContext::new("")).await
+    }
+
+    async fn execute_in(&self, args: Value, ctx: &ToolContext) -> ToolResult {
         let path = match args
             .get("file_path")
             .or(args.get("path"))
@@ -60,11 +103,15 @@ impl Tool for WriteTool {
             None => return ToolResult::error("Missing required parameter: content"),
         };
 
-        let full_path = if Path::new(path).is_absolute() {
-            PathBuf::from(path)
-        } else {
-            self.workspace_root.join(path)
+        let full_path = match self.sandbox.resolve(path, Access::Write) {
+            Ok(p) => p,
+            Err(e) => return ToolResult::error(e),
         };
+        if !args["force"].as_bool().unwrap_or(false) {
+            if let Err(e) = self.tracker.check(ctx, &full_path).await {
+                return ToolResult::error(e);
+            }
+        }
 
         if let Some(parent) = full_path.parent() {
             if let Err(e) = fs::create_dir_all(parent).await {
@@ -72,12 +119,19 @@ impl Tool for WriteTool {
             }
         }
 
+        self.checkpoints.snapshot(ctx, &full_path).await;
         match fs::write(&full_path, content).await {
             Ok(()) => {
+                self.tracker
+                    .record_write(ctx, &full_path, content.as_bytes());
                 debug!("write: {} ({} bytes)", path, content.len());
                 ToolResult::text(format!("Wrote {} bytes to {}", content.len(), path))
             }
             Err(e) => ToolResult::error(format!("Failed to write: {}", e)),
         }
     }
+
+    async fn end_lineage(&self, lineage: &str) {
+        self.tracker.forget(lineage);
+    }
 }
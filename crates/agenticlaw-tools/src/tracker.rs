@@ -0,0 +1,119 @@
+//! File tracker — stale-write protection for the file tools
+//!
+//! Records what each file looked like when a session last read or wrote it.
+//! Before a write or edit, the on-disk file is compared against that record;
+//! if someone else changed it in between (a human in their editor, a sibling
+//! subagent), the write is refused so the agent re-reads instead of
+//! clobbering the change. Files a session never read are not checked.
+//!
+//! Each session keeps its own records, so one sibling's write trips the
+//! others. A child works on its parent's behalf, though: its writes also
+//! keep the lineage root's record current rather than tripping it. Records
+//! are grouped by `ToolContext::lineage` and last until the lineage ends
+//! (`Tool::end_lineage`); aborting a turn keeps them.
+
+use crate::registry::ToolContext;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Content fingerprint of a file at one point in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stamp {
+    len: u64,
+    hash: u64,
+}
+
+impl Stamp {
+    fn of(content: &[u8]) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        content.hash(&mut hasher);
+        Self {
+            len: content.len() as u64,
+            hash: hasher.finish(),
+        }
+    }
+
+    async fn on_disk(path: &Path) -> Option<Self> {
+        tokio::fs::read(path).await.ok().map(|c| Self::of(&c))
+    }
+}
+
+/// session → path → stamp at last read/write
+type SessionStamps = HashMap<String, HashMap<PathBuf, Stamp>>;
+
+#[derive(Default)]
+pub struct FileTracker {
+    /// lineage → its sessions' records
+    seen: Mutex<HashMap<String, SessionStamps>>,
+}
+
+impl FileTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember `content` as what the calling session just read of `path`.
+    pub fn record_read(&self, ctx: &ToolContext, path: &Path, content: &[u8]) {
+        self.seen
+            .lock()
+            .unwrap()
+            .entry(ctx.lineage.clone())
+            .or_default()
+            .entry(ctx.session.clone())
+            .or_default()
+            .insert(path.to_path_buf(), Stamp::of(content));
+    }
+
+    /// Remember `content` as what the calling session just wrote to `path`.
+    /// A child's write also refreshes the lineage root's record of the file.
+    pub fn record_write(&self, ctx: &ToolContext, path: &Path, content: &[u8]) {
+        let stamp = Stamp::of(content);
+        let mut seen = self.seen.lock().unwrap();
+        let sessions = seen.entry(ctx.lineage.clone()).or_default();
+        sessions
+            .entry(ctx.session.clone())
+            .or_default()
+            .insert(path.to_path_buf(), stamp);
+        if let Some(root) = sessions
+            .get_mut(&ctx.lineage)
+            .and_then(|files| files.get_mut(path))
+        {
+            *root = stamp;
+        }
+    }
+
+    /// Err if `path` changed on disk since the calling session last read or
+    /// wrote it.
+    pub async fn check(&self, ctx: &ToolContext, path: &Path) -> Result<(), String> {
+        let Some(seen) = self
+            .seen
+            .lock()
+            .unwrap()
+            .get(&ctx.lineage)
+            .and_then(|sessions| sessions.get(&ctx.session))
+            .and_then(|files| files.get(path).copied())
+        else {
+            return Ok(());
+        };
+        match Stamp::on_disk(path).await {
+            Some(now) if now == seen => Ok(()),
+            Some(_) => Err(format!(
+                "{} changed on disk since you last read it. Read it again before editing, \
+                 or pass force: true to overwrite the other change.",
+                path.display()
+            )),
+            None => Err(format!(
+                "{} was deleted or moved since you last read it. Pass force: true to \
+                 recreate it anyway.",
+                path.display()
+            )),
+        }
+    }
+
+    /// Drop everything recorded for `lineage`.
+    pub fn forget(&self, lineage: &str) {
+        self.seen.lock().unwrap().remove(lineage);
+    }
+}
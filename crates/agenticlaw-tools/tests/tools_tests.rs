@@ -79,8 +79,26 @@ async fn create_default_registry_has_all_tools() {
     assert!(names.contains(&"bash"));
     assert!(names.contains(&"glob"));
     assert!(names.contains(&"grep"));
-    assert_eq!(names.len(), 7);
-    assert_eq!(reg.get_definitions().len(), 7);
+    assert!(names.contains(&"shell"));
+    assert!(names.contains(&"multi_edit"));
+    assert!(names.contains(&"apply_patch"));
+    assert!(names.contains(&"web_fetch"));
+    assert_eq!(names.len(), 11);
+    assert_eq!(reg.get_definitions().len(), 11);
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn registry_definitions_are_sorted_by_name() {
+    let ws = test_workspace();
+    let names: Vec<String> = create_default_registry(&ws)
+        .get_definitions()
+        .into_iter()
+        .map(|d| d.name)
+        .collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
     cleanup(&ws);
 }
 
@@ -106,6 +124,114 @@ async fn registry_tool_has_schema() {
     cleanup(&ws);
 }
 
+#[tokio::test]
+async fn registry_validates_arguments_against_schema() {
+    let ws = test_workspace();
+    let reg = create_default_registry(&ws);
+
+    let result = reg
+        .execute("read", json!({"offset": "ten", "limit": 5}))
+        .await;
+    assert!(result.is_error());
+    let text = result.to_content_string();
+    assert!(text.contains("Invalid arguments for read"), "{}", text);
+    assert!(
+        text.contains("missing required property \"file_path\""),
+        "{}",
+        text
+    );
+    assert!(text.contains("$.offset: expected integer"), "{}", text);
+
+    let result = reg.execute("glob", serde_json::Value::Null).await;
+    assert!(result.to_content_string().contains("expected object"));
+
+    // `path` still names the file for the file tools
+    std::fs::write(ws.join("alias.txt"), "aliased").unwrap();
+    let result = reg.execute("read", json!({"path": "alias.txt"})).await;
+    assert!(result.to_content_string().contains("aliased"));
+    cleanup(&ws);
+}
+
+// ===========================================================================
+// Scheduling — batches and timeouts
+// ===========================================================================
+
+#[tokio::test]
+async fn registry_batches_reads_together_and_orders_conflicts() {
+    let ws = test_workspace();
+    let reg = create_default_registry(&ws);
+    let calls = [
+        ("read", json!({"path": "a.txt"})),
+        ("read", json!({"file_path": "b.txt"})),
+        ("grep", json!({"pattern": "x", "path": "src"})),
+        // Different file from everything above: joins the batch
+        (
+            "edit",
+            json!({"path": "c.txt", "old_string": "a", "new_string": "b"}),
+        ),
+        // Inside the grep's directory
+        ("write", json!({"path": "src/lib.rs", "content": ""})),
+        ("write", json!({"path": "b.txt", "content": ""})),
+        ("read", json!({"path": "b.txt"})),
+        ("bash", json!({"command": "ls"})),
+        ("web_fetch", json!({"url": "https://example.com"})),
+        // A child agent may touch any file
+        (
+            "edit",
+            json!({"path": "d.txt", "old_string": "a", "new_string": "b"}),
+        ),
+        ("spawn", json!({"task": "edit d.txt"})),
+    ];
+    let named: Vec<_> = calls.iter().map(|(n, a)| (*n, a)).collect();
+    assert_eq!(
+        reg.batches(&named),
+        vec![0..4, 4..6, 6..7, 7..8, 8..10, 10..11]
+    );
+
+    let access = reg.access("write", &json!({"path": "./src/../a.txt"}));
+    assert_eq!(access.concurrency, Concurrency::Write);
+    assert_eq!(access.resources, vec![ws.join("a.txt")]);
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn registry_times_out_slow_tools() {
+    struct Slow;
+
+    #[async_trait::async_trait]
+    impl Tool for Slow {
+        fn name(&self) -> &str {
+            "slow"
+        }
+        fn description(&self) -> &str {
+            "Sleeps"
+        }
+        fn input_schema(&self) -> serde_json::Value {
+            json!({"type": "object"})
+        }
+        fn timeout(&self, _args: &serde_json::Value) -> Option<std::time::Duration> {
+            Some(std::time::Duration::from_millis(50))
+        }
+        async fn execute(&self, _args: serde_json::Value) -> ToolResult {
+            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            ToolResult::text("done")
+        }
+    }
+
+    let mut reg = ToolRegistry::new();
+    reg.register(Slow);
+    let start = std::time::Instant::now();
+    let r = reg
+        .execute_in("slow", json!({}), &ToolContext::new("s1"))
+        .await;
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    assert!(r.is_error());
+    assert_eq!(
+        r.to_content_string(),
+        "Error: Tool 'slow' timed out after 0.05s"
+    );
+}
+
 // ===========================================================================
 // WriteTool — real filesystem
 // ===========================================================================
@@ -205,6 +331,29 @@ async fn read_tool_with_offset_and_limit() {
     cleanup(&ws);
 }
 
+#[tokio::test]
+async fn read_tool_returns_images_as_blocks() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("shot.png"), b"\x89PNG\r\n\x1a\nfake").unwrap();
+    let reg = create_default_registry(&ws);
+    let result = reg.execute("read", json!({"path": "shot.png"})).await;
+    assert!(!result.is_error());
+    assert!(result.to_content_string().contains("[image: image/png]"));
+
+    let media = result.media();
+    assert_eq!(media.len(), 1);
+    match &media[0] {
+        agenticlaw_llm::ContentBlock::Image {
+            source: agenticlaw_llm::MediaSource::Path { media_type, path },
+        } => {
+            assert_eq!(media_type, "image/png");
+            assert!(path.ends_with("shot.png"));
+        }
+        other => panic!("Expected path-backed image, got {:?}", other),
+    }
+    cleanup(&ws);
+}
+
 #[tokio::test]
 async fn read_tool_missing_file() {
     let ws = test_workspace();
@@ -245,6 +394,319 @@ async fn read_tool_path_escape_blocked() {
     cleanup(&ws);
 }
 
+// ===========================================================================
+// WorkspaceSandbox
+// ===========================================================================
+
+#[tokio::test]
+async fn sandbox_blocks_absolute_paths_outside_workspace() {
+    let ws = test_workspace();
+    let outside = test_workspace();
+    let target = outside.join("owned.txt");
+    let reg = create_default_registry(&ws);
+
+    let result = reg
+        .execute(
+            "write",
+            json!({"path": target.to_string_lossy(), "content": "x"}),
+        )
+        .await;
+    assert!(result.is_error(), "{}", result.to_content_string());
+    assert!(!target.exists());
+
+    std::fs::write(&target, "old").unwrap();
+    let result = reg
+        .execute(
+            "edit",
+            json!({"path": target.to_string_lossy(), "old_string": "old", "new_string": "new"}),
+        )
+        .await;
+    assert!(result.is_error());
+    let result = reg
+        .execute(
+            "grep",
+            json!({"pattern": "old", "path": outside.to_string_lossy()}),
+        )
+        .await;
+    assert!(result.is_error());
+
+    cleanup(&ws);
+    cleanup(&outside);
+}
+
+#[tokio::test]
+async fn sandbox_denies_secret_files() {
+    let ws = test_workspace();
+    std::fs::write(ws.join(".env"), "API_KEY=hunter2").unwrap();
+    std::fs::write(ws.join("server.pem"), "PRIVATE KEY").unwrap();
+    std::fs::write(ws.join("notes.txt"), "PRIVATE KEY mentioned").unwrap();
+    let reg = create_default_registry(&ws);
+
+    let result = reg.execute("read", json!({"path": ".env"})).await;
+    assert!(result.to_content_string().contains("deny rule"));
+    let result = reg
+        .execute("write", json!({"path": "config/.env", "content": "x"}))
+        .await;
+    assert!(result.is_error());
+
+    let result = reg.execute("glob", json!({"pattern": "*"})).await;
+    assert!(!result.to_content_string().contains("server.pem"));
+    let result = reg.execute("grep", json!({"pattern": "PRIVATE"})).await;
+    let found = result.to_content_string();
+    assert!(found.contains("notes.txt") && !found.contains("server.pem"));
+
+    cleanup(&ws);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn sandbox_blocks_symlink_escape() {
+    let ws = test_workspace();
+    let outside = test_workspace();
+    std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+    std::os::unix::fs::symlink(&outside, ws.join("link")).unwrap();
+    let reg = create_default_registry(&ws);
+
+    let result = reg
+        .execute("read", json!({"path": "link/secret.txt"}))
+        .await;
+    assert!(result.to_content_string().contains("symlink"));
+    let result = reg
+        .execute("write", json!({"path": "link/new.txt", "content": "x"}))
+        .await;
+    assert!(result.is_error());
+    assert!(!outside.join("new.txt").exists());
+
+    cleanup(&ws);
+    cleanup(&outside);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn sandbox_blocks_writes_through_a_dangling_symlink() {
+    let ws = test_workspace();
+    let outside = test_workspace();
+    std::os::unix::fs::symlink(outside.join("planted.txt"), ws.join("planted.txt")).unwrap();
+    std::os::unix::fs::symlink("gone/planted.txt", ws.join("nested.txt")).unwrap();
+    std::os::unix::fs::symlink(&outside, ws.join("gone")).unwrap();
+    let reg = create_default_registry(&ws);
+
+    for path in ["planted.txt", "nested.txt"] {
+        let result = reg
+            .execute("write", json!({"path": path, "content": "x"}))
+            .await;
+        assert!(
+            result.is_error(),
+            "{}: {}",
+            path,
+            result.to_content_string()
+        );
+    }
+    assert!(!outside.join("planted.txt").exists());
+
+    // A dangling link that stays inside the workspace is fine
+    std::os::unix::fs::symlink("real.txt", ws.join("alias.txt")).unwrap();
+    let result = reg
+        .execute("write", json!({"path": "alias.txt", "content": "x"}))
+        .await;
+    assert!(!result.is_error(), "{}", result.to_content_string());
+    assert_eq!(std::fs::read_to_string(ws.join("real.txt")).unwrap(), "x");
+
+    cleanup(&ws);
+    cleanup(&outside);
+}
+
+#[tokio::test]
+async fn sandbox_read_only_roots() {
+    let ws = test_workspace();
+    let docs = test_workspace();
+    std::fs::write(docs.join("guide.md"), "read me").unwrap();
+    let sandbox = WorkspaceSandbox::new(&ws).with_read_only_root(&docs);
+    let reg = create_sandboxed_registry(
+        sandbox,
+        std::sync::Arc::new(NetworkPolicy::deny_all()),
+        create_runtime_handle(),
+    );
+    let guide = docs.join("guide.md").to_string_lossy().to_string();
+
+    let result = reg.execute("read", json!({"path": guide})).await;
+    assert!(result.to_content_string().contains("read me"));
+    let result = reg
+        .execute("write", json!({"path": guide, "content": "x"}))
+        .await;
+    assert!(result.to_content_string().contains("read-only"));
+
+    cleanup(&ws);
+    cleanup(&docs);
+}
+
+#[test]
+fn sandbox_compiles_operator_filesystem_rules() {
+    let rules = |r: &[&str]| r.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let config = SandboxConfig::from_policy_rules(
+        &rules(&["read:**", "write:/workspace/**", "write:/tmp/**"]),
+        &rules(&[
+            "read:/etc/shadow",
+            "write:/etc/agenticlaw/**",
+            "read:/proc/*/mem",
+        ]),
+    );
+    assert_eq!(
+        config.roots,
+        vec![PathBuf::from("/workspace"), PathBuf::from("/tmp")]
+    );
+    assert_eq!(config.read_only_roots, vec![PathBuf::from("/")]);
+
+    let sandbox = WorkspaceSandbox::from_config("/workspace", &config).unwrap();
+    assert!(sandbox.resolve("/usr/lib/os-release", Access::Read).is_ok());
+    assert!(sandbox
+        .resolve("/usr/lib/os-release", Access::Write)
+        .is_err());
+    assert!(sandbox.resolve("/tmp/scratch.txt", Access::Write).is_ok());
+    assert!(sandbox.resolve("/etc/shadow", Access::Read).is_err());
+    assert!(sandbox.resolve("/proc/1/mem", Access::Read).is_err());
+    assert!(sandbox
+        .resolve("/etc/agenticlaw/policy.json", Access::Read)
+        .is_ok());
+    assert!(sandbox
+        .resolve("/etc/agenticlaw/policy.json", Access::Write)
+        .is_err());
+}
+
+// ===========================================================================
+// GrepTool / GlobTool — ignore-aware search
+// ===========================================================================
+
+#[tokio::test]
+async fn search_honours_gitignore_and_ignore_files() {
+    let ws = test_workspace();
+    std::fs::write(ws.join(".gitignore"), "build/\n*.log\n").unwrap();
+    std::fs::write(ws.join(".ignore"), "vendor/\n").unwrap();
+    for dir in ["src", "build", "vendor"] {
+        std::fs::create_dir_all(ws.join(dir)).unwrap();
+        std::fs::write(ws.join(dir).join("lib.rs"), "needle\n").unwrap();
+    }
+    std::fs::write(ws.join("run.log"), "needle\n").unwrap();
+    let reg = create_default_registry(&ws);
+
+    let found = reg
+        .execute("grep", json!({"pattern": "needle"}))
+        .await
+        .to_content_string();
+    assert!(found.contains("src/lib.rs"), "{}", found);
+    assert!(!found.contains("build/") && !found.contains("vendor/") && !found.contains(".log"));
+
+    let found = reg
+        .execute("glob", json!({"pattern": "**/*.rs"}))
+        .await
+        .to_content_string();
+    assert_eq!(found, ws.join("src/lib.rs").to_string_lossy());
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn grep_filters_by_type_and_pages_results() {
+    let ws = test_workspace();
+    for name in ["a.rs", "b.rs", "c.rs", "d.py"] {
+        std::fs::write(ws.join(name), "match\n").unwrap();
+    }
+    let reg = create_default_registry(&ws);
+
+    let page = reg
+        .execute(
+            "grep",
+            json!({"pattern": "match", "type": "rust", "head_limit": 2, "offset": 1}),
+        )
+        .await
+        .to_content_string();
+    let lines: Vec<&str> = page.lines().collect();
+    assert!(
+        lines[0].ends_with("b.rs") && lines[1].ends_with("c.rs"),
+        "{}",
+        page
+    );
+    assert_eq!(lines[2], "[showing 2-3 of 3]");
+
+    let result = reg
+        .execute("grep", json!({"pattern": "match", "type": "nonsense"}))
+        .await;
+    assert!(result.is_error());
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn grep_multiline_matches_span_lines() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("f.rs"), "fn main(\n    x: u8,\n) {}\nother\n").unwrap();
+    let reg = create_default_registry(&ws);
+
+    let args = json!({"pattern": r"main\(.*?\)", "output_mode": "content"});
+    let single = reg.execute("grep", args.clone()).await.to_content_string();
+    assert_eq!(single, "No matches found");
+
+    let mut multi = args;
+    multi["multiline"] = json!(true);
+    let found = reg.execute("grep", multi).await.to_content_string();
+    let lines: Vec<&str> = found.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", found);
+    assert!(lines.iter().all(|l| l.starts_with('>')));
+    assert!(lines[2].ends_with(":3:) {}"));
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn grep_truncates_to_byte_budget_with_offset_hint() {
+    let ws = test_workspace();
+    let line = format!("hit {}\n", "x".repeat(200));
+    std::fs::write(ws.join("big.txt"), line.repeat(1000)).unwrap();
+    let reg = create_default_registry(&ws);
+
+    let found = reg
+        .execute("grep", json!({"pattern": "hit", "output_mode": "content"}))
+        .await
+        .to_content_string();
+    assert!(found.len() < 50_000, "{}", found.len());
+    let footer = found.lines().last().unwrap();
+    assert!(footer.starts_with("[showing 1-") && footer.contains("use offset="));
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn glob_sorts_by_mtime_or_path() {
+    let ws = test_workspace();
+    for name in ["b.txt", "a.txt", "c.txt"] {
+        std::fs::write(ws.join(name), name).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    let reg = create_default_registry(&ws);
+    let names = |out: String| {
+        out.lines()
+            .map(|l| l.rsplit('/').next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let newest_first = reg.execute("glob", json!({"pattern": "*.txt"})).await;
+    assert_eq!(
+        names(newest_first.to_content_string()),
+        ["c.txt", "a.txt", "b.txt"]
+    );
+    let by_path = reg
+        .execute(
+            "glob",
+            json!({"pattern": "*.txt", "sort": "path", "head_limit": 2}),
+        )
+        .await;
+    assert_eq!(
+        names(by_path.to_content_string()),
+        [
+            "a.txt",
+            "b.txt",
+            "[showing 1-2 of 3; use offset=2 for more]"
+        ]
+    );
+    cleanup(&ws);
+}
+
 // ===========================================================================
 // EditTool — real filesystem
 // ===========================================================================
@@ -270,6 +732,43 @@ async fn edit_tool_replaces_text() {
     cleanup(&ws);
 }
 
+#[cfg(unix)]
+#[tokio::test]
+async fn edits_through_a_symlink_change_its_target() {
+    let ws = test_workspace();
+    std::fs::create_dir(ws.join("real")).unwrap();
+    std::fs::write(ws.join("real/config.toml"), "port = 1\nhost = a\n").unwrap();
+    std::os::unix::fs::symlink("real/config.toml", ws.join("config.toml")).unwrap();
+    let reg = create_default_registry(&ws);
+
+    let result = reg
+        .execute(
+            "edit",
+            json!({"path": "config.toml", "old_string": "port = 1", "new_string": "port = 2"}),
+        )
+        .await;
+    assert!(!result.is_error(), "{}", result.to_content_string());
+    let result = reg
+        .execute(
+            "multi_edit",
+            json!({"path": "config.toml", "edits": [
+                {"old_string": "host = a", "new_string": "host = b"}
+            ]}),
+        )
+        .await;
+    assert!(!result.is_error(), "{}", result.to_content_string());
+
+    assert!(std::fs::symlink_metadata(ws.join("config.toml"))
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(
+        std::fs::read_to_string(ws.join("real/config.toml")).unwrap(),
+        "port = 2\nhost = b\n"
+    );
+    cleanup(&ws);
+}
+
 #[tokio::test]
 async fn edit_tool_old_string_not_found() {
     let ws = test_workspace();
@@ -393,6 +892,846 @@ async fn exec_tool_empty_output() {
     cleanup(&ws);
 }
 
+// ===========================================================================
+// MultiEditTool / ApplyPatchTool
+// ===========================================================================
+
+#[tokio::test]
+async fn multi_edit_applies_edits_in_order() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("lib.rs"), "fn old() {}\nfn caller() { old() }\n").unwrap();
+    let reg = create_default_registry(&ws);
+
+    let result = reg
+        .execute(
+            "multi_edit",
+            json!({"path": "lib.rs", "edits": [
+                {"old_string": "old", "new_string": "new", "replace_all": true},
+                {"old_string": "fn new() {}", "new_string": "fn new() -> u8 { 1 }"}
+            ]}),
+        )
+        .await;
+    assert!(!result.is_error(), "{}", result.to_content_string());
+    assert_eq!(
+        std::fs::read_to_string(ws.join("lib.rs")).unwrap(),
+        "fn new() -> u8 { 1 }\nfn caller() { new() }\n"
+    );
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn multi_edit_is_all_or_nothing() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("a.txt"), "one two two").unwrap();
+    let reg = create_default_registry(&ws);
+
+    let result = reg
+        .execute(
+            "multi_edit",
+            json!({"path": "a.txt", "edits": [
+                {"old_string": "one", "new_string": "1"},
+                {"old_string": "two", "new_string": "2"}
+            ]}),
+        )
+        .await;
+    let msg = result.to_content_string();
+    assert!(result.is_error());
+    assert!(msg.contains("edit 2") && msg.contains("2 times"), "{}", msg);
+    assert_eq!(
+        std::fs::read_to_string(ws.join("a.txt")).unwrap(),
+        "one two two"
+    );
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn apply_patch_multiple_files_with_offset_and_creation() {
+    let ws = test_workspace();
+    // Two extra lines at the top shift the hunk by +2
+    std::fs::write(ws.join("a.txt"), "x\ny\nalpha\nbeta\ngamma\n").unwrap();
+    std::fs::write(ws.join("gone.txt"), "bye\n").unwrap();
+    let reg = create_default_registry(&ws);
+
+    let patch = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,3 +1,3 @@
+ alpha
+-beta
++BETA
+ gamma
+--- /dev/null
++++ b/new/file.txt
+@@ -0,0 +1,2 @@
++hello
++world
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+";
+    let result = reg.execute("apply_patch", json!({"patch": patch})).await;
+    let msg = result.to_content_string();
+    assert!(!result.is_error(), "{}", msg);
+    assert!(msg.contains("offset +2"), "{}", msg);
+    assert_eq!(
+        std::fs::read_to_string(ws.join("a.txt")).unwrap(),
+        "x\ny\nalpha\nBETA\ngamma\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(ws.join("new/file.txt")).unwrap(),
+        "hello\nworld\n"
+    );
+    assert!(!ws.join("gone.txt").exists());
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn apply_patch_fuzz_tolerates_context_drift() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("f.txt"), "one\nTWO\nthree\nfour\n").unwrap();
+    let reg = create_default_registry(&ws);
+
+    // Leading context "two" no longer matches; fuzz 1 drops it
+    let patch = "--- a/f.txt\n+++ b/f.txt\n@@ -2,3 +2,3 @@\n two\n-three\n+3\n four\n";
+    let result = reg
+        .execute("apply_patch", json!({"patch": patch, "fuzz": 0}))
+        .await;
+    assert!(result.is_error());
+    let result = reg.execute("apply_patch", json!({"patch": patch})).await;
+    let msg = result.to_content_string();
+    assert!(msg.contains("fuzz 1"), "{}", msg);
+    assert_eq!(
+        std::fs::read_to_string(ws.join("f.txt")).unwrap(),
+        "one\nTWO\n3\nfour\n"
+    );
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn apply_patch_dry_run_reports_failing_hunks_and_writes_nothing() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("a.txt"), "a\nb\nc\n").unwrap();
+    std::fs::write(ws.join("b.txt"), "x\ny\nz\n").unwrap();
+    let reg = create_default_registry(&ws);
+
+    let patch = "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,3 +1,3 @@
+ a
+-b
++B
+ c
+--- a/b.txt
++++ b/b.txt
+@@ -1,3 +1,3 @@
+ x
+-nope
++NOPE
+ z
+";
+    let result = reg
+        .execute("apply_patch", json!({"patch": patch, "dry_run": true}))
+        .await;
+    let msg = result.to_content_string();
+    assert!(msg.contains("would NOT apply"), "{}", msg);
+    assert!(msg.contains("a.txt: 1 hunk ok"), "{}", msg);
+    assert!(msg.contains("hunk 1 FAILED"), "{}", msg);
+
+    // For real: still nothing written, since b.txt fails
+    let result = reg.execute("apply_patch", json!({"patch": patch})).await;
+    assert!(result.is_error());
+    assert_eq!(
+        std::fs::read_to_string(ws.join("a.txt")).unwrap(),
+        "a\nb\nc\n"
+    );
+    cleanup(&ws);
+}
+
+// ===========================================================================
+// FileTracker — stale-write protection
+// ===========================================================================
+
+#[tokio::test]
+async fn edit_refuses_file_changed_since_read() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("notes.txt"), "alpha\nbeta\n").unwrap();
+    let reg = create_default_registry(&ws);
+    let ctx = ToolContext::new("s1");
+
+    let read = reg
+        .execute_in("read", json!({"path": "notes.txt"}), &ctx)
+        .await;
+    assert!(!read.is_error());
+    std::fs::write(ws.join("notes.txt"), "alpha\nbeta\ngamma\n").unwrap();
+
+    let edit = json!({"path": "notes.txt", "old_string": "beta", "new_string": "BETA"});
+    let result = reg.execute_in("edit", edit.clone(), &ctx).await;
+    assert!(result.is_error());
+    assert!(result
+        .to_content_string()
+        .contains("changed on disk since you last read it"));
+    let write = reg
+        .execute_in("write", json!({"path": "notes.txt", "content": "x"}), &ctx)
+        .await;
+    assert!(write.is_error());
+    assert_eq!(
+        std::fs::read_to_string(ws.join("notes.txt")).unwrap(),
+        "alpha\nbeta\ngamma\n"
+    );
+
+    // A session that never read the file is not affected
+    let other = reg
+        .execute_in("edit", edit.clone(), &ToolContext::new("s2"))
+        .await;
+    assert!(!other.is_error(), "{}", other.to_content_string());
+
+    // force overrides the check
+    let mut forced = edit;
+    forced["old_string"] = json!("gamma");
+    forced["new_string"] = json!("GAMMA");
+    forced["force"] = json!(true);
+    let result = reg.execute_in("edit", forced, &ctx).await;
+    assert!(!result.is_error(), "{}", result.to_content_string());
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn own_writes_keep_tracking_current() {
+    let ws = test_workspace();
+    let reg = create_default_registry(&ws);
+    let ctx = ToolContext::new("s1");
+
+    for (args, tool) in [
+        (json!({"path": "a.txt", "content": "one\n"}), "write"),
+        (json!({"path": "a.txt"}), "read"),
+        (
+            json!({"path": "a.txt", "old_string": "one", "new_string": "two"}),
+            "edit",
+        ),
+        (
+            json!({"path": "a.txt", "edits": [{"old_string": "two", "new_string": "three"}]}),
+            "multi_edit",
+        ),
+        (json!({"path": "a.txt", "content": "four\n"}), "write"),
+    ] {
+        let result = reg.execute_in(tool, args, &ctx).await;
+        assert!(
+            !result.is_error(),
+            "{}: {}",
+            tool,
+            result.to_content_string()
+        );
+    }
+    assert_eq!(std::fs::read_to_string(ws.join("a.txt")).unwrap(), "four\n");
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn tracking_is_shared_across_a_lineage_and_dropped_on_end() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("shared.txt"), "v1\n").unwrap();
+    let reg = create_default_registry(&ws);
+    let parent = ToolContext::new("parent");
+    let child = ToolContext::new("child").with_lineage("parent");
+
+    reg.execute_in("read", json!({"path": "shared.txt"}), &parent)
+        .await;
+    // The child's edit updates the record the parent relies on ...
+    let result = reg
+        .execute_in(
+            "edit",
+            json!({"path": "shared.txt", "old_string": "v1", "new_string": "v2"}),
+            &child,
+        )
+        .await;
+    assert!(!result.is_error());
+    let result = reg
+        .execute_in(
+            "edit",
+            json!({"path": "shared.txt", "old_string": "v2", "new_string": "v3"}),
+            &parent,
+        )
+        .await;
+    assert!(!result.is_error(), "{}", result.to_content_string());
+
+    // ... and a change from outside the lineage trips both
+    std::fs::write(ws.join("shared.txt"), "outside\n").unwrap();
+    let stale = json!({"path": "shared.txt", "content": "mine\n"});
+    assert!(reg
+        .execute_in("write", stale.clone(), &child)
+        .await
+        .is_error());
+
+    // Ending a session (an abort) keeps the records; ending the lineage drops them
+    reg.end_session("parent").await;
+    assert!(reg
+        .execute_in("write", stale.clone(), &parent)
+        .await
+        .is_error());
+    reg.end_lineage("parent").await;
+    assert!(!reg.execute_in("write", stale, &parent).await.is_error());
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn a_sibling_write_trips_a_child_that_read_the_file_earlier() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("shared.txt"), "v1\n").unwrap();
+    let reg = create_default_registry(&ws);
+    let first = ToolContext::new("child-a").with_lineage("parent");
+    let second = ToolContext::new("child-b").with_lineage("parent");
+
+    reg.execute_in("read", json!({"path": "shared.txt"}), &first)
+        .await;
+    reg.execute_in("read", json!({"path": "shared.txt"}), &second)
+        .await;
+    let result = reg
+        .execute_in(
+            "edit",
+            json!({"path": "shared.txt", "old_string": "v1", "new_string": "v2"}),
+            &second,
+        )
+        .await;
+    assert!(!result.is_error(), "{}", result.to_content_string());
+
+    let result = reg
+        .execute_in(
+            "write",
+            json!({"path": "shared.txt", "content": "clobbered\n"}),
+            &first,
+        )
+        .await;
+    assert!(result.is_error());
+    assert!(result
+        .to_content_string()
+        .contains("changed on disk since you last read it"));
+    assert_eq!(
+        std::fs::read_to_string(ws.join("shared.txt")).unwrap(),
+        "v2\n"
+    );
+    cleanup(&ws);
+}
+
+// ===========================================================================
+// CheckpointStore — rewind of agent edits
+// ===========================================================================
+
+#[tokio::test]
+async fn restore_rewinds_edits_from_a_turn_onwards() {
+    let ws = test_workspace();
+    std::fs::write(ws.join("keep.txt"), "original\n").unwrap();
+    let reg = create_default_registry(&ws);
+    let turn1 = ToolContext::new("s1").with_turn(1);
+    let turn2 = ToolContext::new("s1").with_turn(2);
+
+    reg.execute_in("read", json!({"path": "keep.txt"}), &turn1)
+        .await;
+    for (tool, args, ctx) in [
+        (
+            "edit",
+            json!({"path": "keep.txt", "old_string": "original", "new_string": "one"}),
+            &turn1,
+        ),
+        (
+            "edit",
+            json!({"path": "keep.txt", "old_string": "one", "new_string": "two"}),
+            &turn2,
+        ),
+        (
+            "write",
+            json!({"path": "new/made.txt", "content": "x"}),
+            &turn2,
+        ),
+    ] {
+        let result = reg.execute_in(tool, args, ctx).await;
+        assert!(!result.is_error(), "{}", result.to_content_string());
+    }
+
+    let list = reg.checkpoints().list("s1");
+    assert_eq!(list.iter().map(|c| c.turn).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(list[1].files.len(), 2);
+
+    let report = reg.checkpoints().restore("s1", 2).await.unwrap();
+    assert_eq!(report.restored, vec![ws.join("keep.txt")]);
+    assert_eq!(report.removed, vec![ws.join("new/made.txt")]);
+    assert_eq!(
+        std::fs::read_to_string(ws.join("keep.txt")).unwrap(),
+        "one\n"
+    );
+    assert!(!ws.join("new/made.txt").exists());
+    assert_eq!(reg.checkpoints().list("s1").len(), 1);
+
+    reg.checkpoints().restore("s1", 1).await.unwrap();
+    assert_eq!(
+        std::fs::read_to_string(ws.join("keep.txt")).unwrap(),
+        "original\n"
+    );
+    assert!(reg.checkpoints().restore("s1", 1).await.is_err());
+    assert!(reg.checkpoints().list("other").is_empty());
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn restore_refuses_turns_past_the_checkpoint_limit() {
+    use agenticlaw_tools::checkpoint::MAX_CHECKPOINTS;
+
+    let ws = test_workspace();
+    let path = ws.join("log.txt");
+    let store = CheckpointStore::new();
+    // Two more turns than are kept: turns 1 and 2 are dropped
+    for turn in 1..=MAX_CHECKPOINTS + 2 {
+        store
+            .snapshot(&ToolContext::new("s1").with_turn(turn), &path)
+            .await;
+        std::fs::write(&path, format!("turn {}\n", turn)).unwrap();
+    }
+    assert_eq!(store.list("s1").len(), MAX_CHECKPOINTS);
+
+    let err = store.restore("s1", 2).await.unwrap_err();
+    assert!(err.contains("too old to restore"), "{}", err);
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        format!("turn {}\n", MAX_CHECKPOINTS + 2)
+    );
+
+    store.restore("s1", 3).await.unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "turn 2\n");
+    cleanup(&ws);
+}
+
+// ===========================================================================
+// WebFetchTool — local fixture server
+// ===========================================================================
+
+/// Serve fixed pages on an ephemeral port; returns the base URL.
+async fn fixture_server() -> String {
+    use axum::http::header;
+    use axum::response::{Html, IntoResponse, Redirect};
+    use axum::routing::get;
+
+    let app = axum::Router::new()
+        .route(
+            "/page",
+            get(|| async {
+                Html(
+                    "<html><head><title>Fixture  Page</title><style>p{}</style></head>\
+                     <body><h1>Hello</h1><p>Some <b>bold</b> text.</p>\
+                     <script>var hidden = 1;</script></body></html>",
+                )
+            }),
+        )
+        .route(
+            "/data.json",
+            get(|| async {
+                (
+                    [(header::CONTENT_TYPE, "application/json")],
+                    r#"{"ok":true}"#,
+                )
+            }),
+        )
+        .route(
+            "/image.png",
+            get(|| async {
+                (
+                    [(header::CONTENT_TYPE, "image/png")],
+                    vec![0x89u8, b'P', 0, 0],
+                )
+            }),
+        )
+        .route("/big", get(|| async { "x".repeat(10_000) }))
+        .route(
+            "/to-secret",
+            get(|| async { Redirect::temporary("/secret") }),
+        )
+        .route("/secret", get(|| async { "secret".into_response() }));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn fixture_fetch_tool() -> tools::web_fetch::WebFetchTool {
+    // The fixture server is on loopback
+    let policy = NetworkPolicy::from_rules(
+        &["connect:http://127.0.0.1:*/**".to_string()],
+        &["connect:http://127.0.0.1:*/secret".to_string()],
+    )
+    .with_private_network(true);
+    tools::web_fetch::WebFetchTool::with_policy(std::sync::Arc::new(policy))
+}
+
+#[test]
+fn network_policy_matches_connect_globs() {
+    let policy = NetworkPolicy::default();
+    assert!(policy.check("https://example.com/a/b").is_ok());
+    assert!(policy
+        .check("http://169.254.169.254/latest/meta-data")
+        .is_err());
+
+    let policy = NetworkPolicy::from_rules(
+        &["connect:https://*.example.com/**".to_string()],
+        &["connect:https://admin.example.com/**".to_string()],
+    );
+    assert!(policy.check("https://docs.example.com/guide").is_ok());
+    assert!(policy.check("https://example.org/").is_err());
+    let err = policy.check("https://admin.example.com/x").unwrap_err();
+    assert!(err.contains("denied"), "{}", err);
+}
+
+#[test]
+fn network_policy_refuses_internal_addresses_in_any_spelling() {
+    let policy = NetworkPolicy::default();
+    for url in [
+        "http://169.254.169.254/latest/meta-data",
+        "https://169.254.169.254/latest/meta-data",
+        "http://169.254.169.254:8080/",
+        "http://2852039166/",
+        "http://0xa9fea9fe/",
+        "http://0251.0376.0251.0376/",
+        "http://[::ffff:169.254.169.254]/",
+        "http://[64:ff9b::a9fe:a9fe]/",
+        "http://[fd00:ec2::254]/",
+        "http://[fe80::1]/",
+        "http://127.1/",
+        "http://[::1]/",
+        "http://0.0.0.0/",
+        "http://10.0.0.1/",
+        "http://172.16.5.5/",
+        "http://192.168.1.1/",
+        "http://100.100.100.200/",
+    ] {
+        let err = policy.check(url).unwrap_err();
+        assert!(err.contains("internal address"), "{}: {}", url, err);
+    }
+    for url in [
+        "https://metadata.google.internal/computeMetadata/v1/",
+        "http://metadata.google.internal:80/",
+        "http://metadata/",
+    ] {
+        let err = policy.check(url).unwrap_err();
+        assert!(err.contains("denied"), "{}: {}", url, err);
+    }
+    assert!(policy.check("http://93.184.216.34/").is_ok());
+    assert!(policy.check("http://[2606:2800:220:1::1]/").is_ok());
+
+    // Opting in leaves internal addresses to the rules
+    let policy = NetworkPolicy::default().with_private_network(true);
+    assert!(policy.check("http://10.0.0.1/").is_ok());
+}
+
+#[tokio::test]
+async fn network_resolver_drops_internal_addresses() {
+    use agenticlaw_tools::network::Resolver;
+
+    let policy = NetworkPolicy::default();
+    let err = Resolver::lookup(&policy, "localhost").await.unwrap_err();
+    assert!(err.contains("internal address"), "{}", err);
+    let policy = policy.with_private_network(true);
+    assert!(!Resolver::lookup(&policy, "localhost")
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn registries_without_a_network_policy_refuse_every_fetch() {
+    let ws = test_workspace();
+    let args = json!({"url": "https://example.com/"});
+    let policy = create_policy_registry(
+        &ws,
+        &["web_fetch"],
+        std::sync::Arc::new(NetworkPolicy::deny_all()),
+    );
+    for reg in [create_default_registry(&ws), policy] {
+        let r = reg.execute("web_fetch", args.clone()).await;
+        assert!(r.is_error());
+        let out = r.to_content_string();
+        assert!(out.contains("not allowed by any network rule"), "{}", out);
+    }
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn web_fetch_refuses_hostnames_that_resolve_inside() {
+    let base = fixture_server().await;
+    let port = base.rsplit(':').next().unwrap();
+    // The rules allow it; the resolver doesn't
+    let tool = tools::web_fetch::WebFetchTool::new();
+
+    let r = tool
+        .execute(json!({"url": format!("http://localhost:{}/page", port)}))
+        .await;
+    assert!(r.is_error());
+    let out = r.to_content_string();
+    assert!(out.contains("internal address"), "{}", out);
+}
+
+#[tokio::test]
+async fn web_fetch_converts_html_to_markdown() {
+    let base = fixture_server().await;
+    let tool = fixture_fetch_tool();
+
+    let r = tool.execute(json!({"url": format!("{}/page", base)})).await;
+    assert!(!r.is_error(), "{}", r.to_content_string());
+    let out = r.to_content_string();
+    assert!(out.contains("Title: Fixture Page"), "{}", out);
+    assert!(out.contains("Content-Type: text/html"), "{}", out);
+    assert!(out.contains("# Hello"), "{}", out);
+    assert!(out.contains("**bold**"), "{}", out);
+    assert!(!out.contains("hidden"), "script leaked: {}", out);
+
+    let r = tool
+        .execute(json!({"url": format!("{}/data.json", base)}))
+        .await;
+    assert!(r.to_content_string().contains(r#"{"ok":true}"#));
+}
+
+#[tokio::test]
+async fn web_fetch_caps_output_and_refuses_binary() {
+    let base = fixture_server().await;
+    let tool = fixture_fetch_tool();
+
+    let r = tool
+        .execute(json!({"url": format!("{}/big", base), "max_bytes": 100}))
+        .await;
+    let out = r.to_content_string();
+    assert!(out.contains(&"x".repeat(100)), "{}", out);
+    assert!(!out.contains(&"x".repeat(101)), "{}", out);
+    assert!(out.contains("truncated at 100 of 10000 bytes"), "{}", out);
+
+    let r = tool
+        .execute(json!({"url": format!("{}/image.png", base)}))
+        .await;
+    assert!(r.is_error());
+    assert!(r.to_content_string().contains("image/png"));
+}
+
+#[tokio::test]
+async fn web_fetch_enforces_network_policy() {
+    let base = fixture_server().await;
+    let tool = fixture_fetch_tool();
+
+    let r = tool
+        .execute(json!({"url": format!("{}/secret", base)}))
+        .await;
+    assert!(r.is_error());
+    assert!(r.to_content_string().contains("denied"));
+
+    // A redirect into a denied URL is caught at the hop
+    let r = tool
+        .execute(json!({"url": format!("{}/to-secret", base)}))
+        .await;
+    assert!(r.is_error());
+    assert!(
+        r.to_content_string().contains("redirect blocked"),
+        "{}",
+        r.to_content_string()
+    );
+
+    let r = tool.execute(json!({"url": "https://example.com/"})).await;
+    assert!(r.to_content_string().contains("not allowed"));
+    let r = tool.execute(json!({"url": "file:///etc/passwd"})).await;
+    assert!(r.to_content_string().contains("Unsupported URL scheme"));
+}
+
+// ===========================================================================
+// ShellTool — persistent sessions
+// ===========================================================================
+
+async fn shell(reg: &ToolRegistry, session: &str, args: serde_json::Value) -> String {
+    let result = reg
+        .execute_in("shell", args, &ToolContext::new(session))
+        .await;
+    assert!(!result.is_error(), "{}", result.to_content_string());
+    result.to_content_string()
+}
+
+#[tokio::test]
+async fn shell_exec_keeps_state_between_calls() {
+    let ws = test_workspace();
+    std::fs::create_dir_all(ws.join("sub")).unwrap();
+    let reg = create_default_registry(&ws);
+
+    shell(
+        &reg,
+        "s1",
+        json!({"action": "exec", "command": "cd sub && export GREETING=hi"}),
+    )
+    .await;
+    let out = shell(
+        &reg,
+        "s1",
+        json!({"action": "exec", "command": "pwd; echo $GREETING"}),
+    )
+    .await;
+    assert!(out.ends_with("sub\nhi"), "{}", out);
+
+    let out = shell(
+        &reg,
+        "s1",
+        json!({"action": "exec", "command": "echo oops >&2; false"}),
+    )
+    .await;
+    assert!(out.starts_with("Exit code: 1"), "{}", out);
+    assert!(out.contains("oops"));
+
+    reg.end_session("s1").await;
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn shell_exec_timeout_keeps_its_marker_out_of_later_output() {
+    let ws = test_workspace();
+    let reg = create_default_registry(&ws);
+
+    let out = shell(
+        &reg,
+        "s1",
+        json!({"action": "exec", "command": "sleep 1; echo late", "timeout": 0}),
+    )
+    .await;
+    assert!(out.contains("still running"), "{}", out);
+
+    // The shell is busy until the timed-out command finishes
+    let busy = reg
+        .execute_in(
+            "shell",
+            json!({"action": "exec", "command": "echo next"}),
+            &ToolContext::new("s1"),
+        )
+        .await;
+    assert!(busy.is_error());
+    assert!(busy.to_content_string().contains("still running"));
+
+    let out = shell(&reg, "s1", json!({"action": "wait", "timeout": 10})).await;
+    assert!(out.contains("late"), "{}", out);
+    assert!(
+        out.contains("[previous command exited with code 0]"),
+        "{}",
+        out
+    );
+    assert!(!out.contains("__agenticlaw_exec_"), "{}", out);
+
+    let out = shell(
+        &reg,
+        "s1",
+        json!({"action": "exec", "command": "echo next"}),
+    )
+    .await;
+    assert_eq!(out, "next");
+
+    reg.end_session("s1").await;
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn shell_background_process_poll_write_wait() {
+    let ws = test_workspace();
+    let reg = create_default_registry(&ws);
+
+    let out = shell(
+        &reg,
+        "s1",
+        json!({"action": "start", "name": "echo", "command": "echo ready; while read line; do echo got:$line; [ \"$line\" = quit ] && exit 3; done"}),
+    )
+    .await;
+    assert!(out.contains("ready"), "{}", out);
+    assert!(out.contains("[echo: running"), "{}", out);
+
+    let out = shell(
+        &reg,
+        "s1",
+        json!({"action": "write", "name": "echo", "input": "one\n"}),
+    )
+    .await;
+    assert!(out.contains("got:one"), "{}", out);
+    // Output is only returned once
+    let out = shell(&reg, "s1", json!({"action": "poll", "name": "echo"})).await;
+    assert!(!out.contains("got:one"), "{}", out);
+
+    shell(
+        &reg,
+        "s1",
+        json!({"action": "write", "name": "echo", "input": "quit\n"}),
+    )
+    .await;
+    let out = shell(
+        &reg,
+        "s1",
+        json!({"action": "wait", "name": "echo", "timeout": 5}),
+    )
+    .await;
+    assert!(out.contains("exited with code 3"), "{}", out);
+
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn shell_sessions_are_isolated_and_killed_on_end() {
+    let ws = test_workspace();
+    let reg = create_default_registry(&ws);
+
+    shell(
+        &reg,
+        "s1",
+        json!({"action": "start", "name": "sleeper", "command": "sleep 60"}),
+    )
+    .await;
+    let other = reg
+        .execute_in(
+            "shell",
+            json!({"action": "poll", "name": "sleeper"}),
+            &ToolContext::new("s2"),
+        )
+        .await;
+    assert!(other.is_error());
+
+    let out = shell(
+        &reg,
+        "s1",
+        json!({"action": "wait", "name": "sleeper", "timeout": 1}),
+    )
+    .await;
+    assert!(out.contains("running"), "{}", out);
+
+    reg.end_session("s1").await;
+    let out = shell(&reg, "s1", json!({"action": "list"})).await;
+    assert_eq!(out, "(no shells)");
+
+    cleanup(&ws);
+}
+
+#[tokio::test]
+async fn shell_kill_stops_process_group() {
+    let ws = test_workspace();
+    let reg = create_default_registry(&ws);
+
+    // The shell forks a child; killing must take the child down too
+    shell(
+        &reg,
+        "s1",
+        json!({"action": "start", "name": "tree", "command": "sleep 60 & echo $! > child.pid; wait"}),
+    )
+    .await;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let pid = std::fs::read_to_string(ws.join("child.pid")).unwrap();
+
+    let out = shell(&reg, "s1", json!({"action": "kill", "name": "tree"})).await;
+    assert!(out.contains("exited"), "{}", out);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    // Gone, or a zombie waiting for a reaper that isn't our concern
+    let alive = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+        .map(|stat| !stat.contains(") Z "))
+        .unwrap_or(false);
+    assert!(!alive, "background child survived kill");
+
+    cleanup(&ws);
+}
+
 // ===========================================================================
 // End-to-end: write then read then edit then read
 // ===========================================================================
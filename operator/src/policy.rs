@@ -218,6 +218,15 @@ impl Policy {
                     return most_restrictive(tool_decision, fs_decision);
                 }
             }
+            "web_fetch" => {
+                if let Some(url) = args.get("url").and_then(|v| v.as_str()) {
+                    let net_decision = self.check_network(&format!("connect:{}", url));
+                    if net_decision == Decision::Deny {
+                        return Decision::Deny;
+                    }
+                    return most_restrictive(tool_decision, net_decision);
+                }
+            }
             _ => {}
         }
 
@@ -689,6 +698,20 @@ mod tests {
         assert_eq!(p.check_tool_call("read", &args), Decision::Deny);
     }
 
+    #[test]
+    fn web_fetch_checks_network_tier() {
+        let args = json!({"url": "https://example.com/docs"});
+        assert_eq!(read_policy().check_tool_call("web_fetch", &args), Decision::Deny);
+        for role in ["POKE", "PROBE"] {
+            let p = Policy::load(&format!("policies/{}.json", role)).unwrap();
+            assert_eq!(p.check_tool_call("web_fetch", &args), Decision::Allow, "{}", role);
+        }
+        // Outbound fetch is scoped to the roles above
+        assert_eq!(operator_policy().check_tool_call("web_fetch", &args), Decision::Deny);
+        let agent = Policy::load("policies/AGENT.json").unwrap();
+        assert_eq!(agent.check_tool_call("web_fetch", &args), Decision::Deny);
+    }
+
     // ── Obfuscation detection ──
 
     #[test]
@@ -187,6 +187,7 @@ async fn main() -> anyhow::Result<()> {
                 anthropic_api_key: std::env::var("ANTHROPIC_API_KEY").ok(),
                 workspace_root,
                 system_prompt: merged_prompt,
+                consciousness: false,
             };
             start_gateway(config).await?;
         }
@@ -422,6 +423,7 @@ async fn start_gateway_only(cli: &Cli, oc: &OpenclawConfig) -> anyhow::Result<()
         anthropic_api_key: std::env::var("ANTHROPIC_API_KEY").ok(),
         workspace_root,
         system_prompt: merged_prompt,
+        consciousness: false,
     };
     start_gateway(config).await?;
     Ok(())
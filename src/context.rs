@@ -4,44 +4,13 @@
 //! agent-readable, and process-readable. JSONL is never persisted — it exists only
 //! as a wire format for LLM API calls.
 //!
-//! # Format spec
-//!
-//! ```text
-//! --- session: <id> ---
-//! started: <ISO 8601>
-//! cwd: <path>
-//!
-//! --- <ISO 8601> ---
-//! [contents of SOUL.md]
-//! [contents of AGENTS.md]
-//! ...concatenated context files as first turn...
-//!
-//! --- <ISO 8601> ---
-//! <up>
-//! User message text here.
-//! </up>
-//!
-//! --- <ISO 8601> ---
-//! Assistant response text here.
-//!
-//! [tool:read] /path/to/file
-//!   → file contents
-//!
-//! --- <ISO 8601> ---
-//! <up>
-//! [tool result contents — also <up> because it's input to the model]
-//! </up>
-//!
-//! --- <ISO 8601> ---
-//! The server runs on port 8080.
-//!
-//! --- <ISO 8601> [compaction] ---
-//! Summary of compacted context here.
-//! ```
+//! The format itself — typed events, writer, parser and its spec — lives in
+//! `agenticlaw_ctx`, shared with the agent runtime. This module converts
+//! between those events and the `SessionEvent`s the formatter works with.
 //!
 //! # Lifecycle
 //!
-//! 1. Session created → SOUL.md/AGENTS.md/etc concatenated as first turn in .ctx
+//! 1. Session created → SOUL.md/AGENTS.md/etc concatenated as a `[context]` turn
 //! 2. Uploaded to LLM API → .ctx JIT-wrapped into jsonl wire format (see `to_wire`)
 //! 3. LLM responds → jsonl wrapper stripped, clean content appended to .ctx
 //! 4. Tool results come back → appended as `<up>` blocks (input to model)
@@ -54,16 +23,13 @@
 //! - Tool call results
 //!
 //! Everything outside `<up>` is model output.
-//!
-//! # Design principles
-//!
-//! - No JSON, no binary, no escaping
-//! - Datetime lines (`--- <ts> ---`) are the only structural markers
-//! - `<up></up>` tags delimit external input; bare text is model output
-//! - Parsable back into SessionEvent for round-trip fidelity
 
 use crate::session::Session;
 use crate::transform::*;
+use crate::types::Usage;
+use agenticlaw_ctx::{Block, Event, Header, TokenUsage};
+
+pub use agenticlaw_ctx::ParseError;
 
 // ---------------------------------------------------------------------------
 // Emit: SessionEvent[] → clean context string
@@ -73,184 +39,159 @@ use crate::transform::*;
 pub struct EmitOptions {
     pub include_thinking: bool,
     pub include_usage: bool,
+    /// Keep long tool results whole instead of cutting out their middle.
     pub raw: bool,
 }
 
 const MAX_TOOL_RESULT_LINES: usize = 20;
 
 pub fn emit(events: &[SessionEvent], opts: &EmitOptions) -> String {
-    let mut out = String::new();
+    agenticlaw_ctx::render(&to_ctx_events(events, opts))
+}
 
+/// Session events as .ctx events. Each tool result follows the assistant
+/// turn that made the call, as it does in a live session.
+pub fn to_ctx_events(events: &[SessionEvent], opts: &EmitOptions) -> Vec<Event> {
+    let mut out = Vec::new();
     for event in events {
         match event {
             SessionEvent::Header {
                 id, timestamp, cwd, ..
-            } => {
-                out.push_str(&format!("--- session: {} ---\n", id));
-                out.push_str(&format!("started: {}\n", timestamp));
-                if let Some(cwd) = cwd {
-                    out.push_str(&format!("cwd: {}\n", cwd));
-                }
-                out.push('\n');
-            }
+            } => out.push(Event::Header(Header::new(id, timestamp, cwd.as_deref()))),
             SessionEvent::ModelChange {
                 timestamp,
                 provider,
                 model_id,
-            } => {
-                out.push_str(&format!(
-                    "--- {} [model: {} ({})] ---\n\n",
-                    timestamp, model_id, provider
-                ));
-            }
+            } => out.push(Event::ModelChange {
+                timestamp: timestamp.clone(),
+                model: model_id.clone(),
+                provider: provider.clone(),
+            }),
             SessionEvent::ThinkingLevelChange { timestamp, level } => {
-                out.push_str(&format!(
-                    "--- {} [thinking-level: {}] ---\n\n",
-                    timestamp, level
-                ));
-            }
-            SessionEvent::Turn(turn) => {
-                out.push_str(&format!("--- {} ---\n", turn.timestamp));
-                let is_user = turn.role == "user";
-                if is_user {
-                    out.push_str("<up>\n");
-                }
-
-                for content in &turn.contents {
-                    match content {
-                        TurnContent::Text(text) => {
-                            out.push_str(text);
-                            out.push('\n');
-                        }
-                        TurnContent::Thinking(thinking) => {
-                            if opts.include_thinking {
-                                out.push_str("[thinking] ");
-                                out.push_str(thinking);
-                                out.push('\n');
-                            }
-                        }
-                        TurnContent::Tool(interaction) => {
-                            emit_tool(&mut out, interaction, opts);
-                        }
-                    }
-                }
-
-                if is_user {
-                    out.push_str("</up>\n");
-                }
-
-                if opts.include_usage {
-                    if let Some(ref usage) = turn.usage {
-                        let input = usage.input.unwrap_or(0);
-                        let output = usage.output.unwrap_or(0);
-                        let cache = usage.cache_read.unwrap_or(0);
-                        let total = usage.total_tokens.unwrap_or(0);
-                        out.push_str(&format!(
-                            "[tokens: {} in, {} out, {} cached, {} total]\n",
-                            input, output, cache, total
-                        ));
-                    }
-                }
-                out.push('\n');
-            }
-            SessionEvent::Compaction { timestamp, summary } => {
-                out.push_str(&format!("--- {} [compaction] ---\n", timestamp));
-                for line in summary.lines() {
-                    out.push_str(line);
-                    out.push('\n');
-                }
-                out.push('\n');
+                out.push(Event::ThinkingLevel {
+                    timestamp: timestamp.clone(),
+                    level: level.clone(),
+                })
             }
+            SessionEvent::Turn(turn) => push_turn(&mut out, turn, opts),
+            SessionEvent::Compaction { timestamp, summary } => out.push(Event::Compaction {
+                timestamp: timestamp.clone(),
+                summary: summary.clone(),
+                kept: 0,
+            }),
         }
     }
-
     out
 }
 
-fn emit_tool(out: &mut String, interaction: &ToolInteraction, opts: &EmitOptions) {
-    let args_summary = summarize_tool_args(&interaction.name, &interaction.arguments);
-    out.push_str(&format!("[tool:{}] {}\n", interaction.name, args_summary));
-
-    if let Some(ref result) = interaction.result {
-        let prefix = if result.is_error { "error: " } else { "" };
-        let content = &result.content;
-
-        if opts.raw || content.lines().count() <= MAX_TOOL_RESULT_LINES {
-            out.push_str(&format!("  → {}{}\n", prefix, content.trim()));
-        } else {
-            let lines: Vec<&str> = content.lines().collect();
-            let show = MAX_TOOL_RESULT_LINES / 2;
-            for line in &lines[..show] {
-                out.push_str(&format!("  → {}\n", line));
-            }
-            out.push_str(&format!(
-                "  ... ({} lines omitted)\n",
-                lines.len() - MAX_TOOL_RESULT_LINES
-            ));
-            for line in &lines[lines.len() - show..] {
-                out.push_str(&format!("  → {}\n", line));
-            }
-        }
-    }
-}
-
-fn summarize_tool_args(tool_name: &str, args: &serde_json::Value) -> String {
-    match tool_name {
-        "read" | "write" => args
-            .get("path")
-            .or_else(|| args.get("file_path"))
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string(),
-        "bash" => args
-            .get("command")
-            .and_then(|v| v.as_str())
-            .map(|s| {
-                if s.len() > 120 {
-                    format!("{}...", &s[..120])
-                } else {
-                    s.to_string()
-                }
+fn push_turn(out: &mut Vec<Event>, turn: &Turn, opts: &EmitOptions) {
+    let timestamp = turn.timestamp.clone();
+    let text = || {
+        let texts: Vec<&str> = turn
+            .contents
+            .iter()
+            .filter_map(|c| match c {
+                TurnContent::Text(text) => Some(text.as_str()),
+                _ => None,
             })
-            .unwrap_or_default(),
-        "glob" => args
-            .get("pattern")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string(),
-        "grep" => {
-            let pattern = args.get("pattern").and_then(|v| v.as_str()).unwrap_or("");
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
-            format!("/{}/  in {}", pattern, path)
-        }
+            .collect();
+        texts.join("\n")
+    };
+    match turn.role.as_str() {
+        "user" => out.push(Event::User {
+            timestamp,
+            text: text(),
+            attachments: Vec::new(),
+        }),
+        "system" => out.push(Event::Context {
+            timestamp,
+            text: text(),
+        }),
         _ => {
-            if let Some(obj) = args.as_object() {
-                for (k, v) in obj {
-                    if let Some(s) = v.as_str() {
-                        let display = if s.len() > 100 {
-                            format!("{}...", &s[..100])
+            let mut blocks = Vec::new();
+            let mut results = Vec::new();
+            for content in &turn.contents {
+                match content {
+                    TurnContent::Text(text) => blocks.push(Block::Text(text.clone())),
+                    TurnContent::Thinking(thinking) => {
+                        if opts.include_thinking {
+                            blocks.push(Block::Thinking {
+                                text: thinking.clone(),
+                                signature: None,
+                            });
+                        }
+                    }
+                    TurnContent::Tool(interaction) => {
+                        // Calls read from old .ctx files have no id
+                        let id = if interaction.id.is_empty() {
+                            format!("tc-{}-{}", interaction.name, out.len() + results.len())
                         } else {
-                            s.to_string()
+                            interaction.id.clone()
                         };
-                        return format!("{}={}", k, display);
+                        blocks.push(Block::ToolCall {
+                            id: id.clone(),
+                            name: interaction.name.clone(),
+                            input: interaction.arguments.clone(),
+                        });
+                        if let Some(ref result) = interaction.result {
+                            results.push(Event::ToolResult {
+                                timestamp: timestamp.clone(),
+                                tool_use_id: id,
+                                content: if opts.raw {
+                                    result.content.clone()
+                                } else {
+                                    cut_long_result(&result.content)
+                                },
+                                attachments: Vec::new(),
+                                is_error: result.is_error,
+                            });
+                        }
                     }
                 }
             }
-            String::new()
+            let usage = turn
+                .usage
+                .as_ref()
+                .filter(|_| opts.include_usage)
+                .map(|u| TokenUsage {
+                    input: u.input.unwrap_or(0),
+                    output: u.output.unwrap_or(0),
+                    cache_read: u.cache_read.unwrap_or(0),
+                    cache_write: u.cache_write.unwrap_or(0),
+                    total: u.total_tokens.unwrap_or(0),
+                    model: None,
+                });
+            out.push(Event::Assistant {
+                timestamp,
+                blocks,
+                usage,
+            });
+            out.extend(results);
         }
     }
 }
 
+/// The first and last lines of a result longer than
+/// `MAX_TOOL_RESULT_LINES`, with a note of how many were left out.
+fn cut_long_result(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.len() <= MAX_TOOL_RESULT_LINES {
+        return content.to_string();
+    }
+    let show = MAX_TOOL_RESULT_LINES / 2;
+    let mut out: Vec<String> = lines[..show].iter().map(|l| l.to_string()).collect();
+    out.push(format!(
+        "... ({} lines omitted)",
+        lines.len() - MAX_TOOL_RESULT_LINES
+    ));
+    out.extend(lines[lines.len() - show..].iter().map(|l| l.to_string()));
+    out.join("\n")
+}
+
 // ---------------------------------------------------------------------------
 // Parse: clean context string → SessionEvent[]
 // ---------------------------------------------------------------------------
 
-#[derive(Debug)]
-pub struct ParseError {
-    pub line: usize,
-    pub message: String,
-}
-
 pub struct ParseResult {
     pub events: Vec<SessionEvent>,
     pub errors: Vec<ParseError>,
@@ -258,273 +199,153 @@ pub struct ParseResult {
 
 /// Parse a clean context file into session events.
 pub fn parse(content: &str) -> ParseResult {
-    let mut events = Vec::new();
-    let mut errors = Vec::new();
-    let lines: Vec<&str> = content.lines().collect();
-    let mut i = 0;
-
-    while i < lines.len() {
-        let line = lines[i];
-
-        // Session header: --- session: <id> ---
-        if let Some(id) = line
-            .strip_prefix("--- session: ")
-            .and_then(|s| s.strip_suffix(" ---"))
-        {
-            let id = id.to_string();
-            i += 1;
-            let mut timestamp = String::new();
-            let mut cwd = None;
-            while i < lines.len() && !lines[i].is_empty() {
-                if let Some(ts) = lines[i].strip_prefix("started: ") {
-                    timestamp = ts.to_string();
-                } else if let Some(c) = lines[i].strip_prefix("cwd: ") {
-                    cwd = Some(c.to_string());
-                }
-                i += 1;
-            }
-            events.push(SessionEvent::Header {
-                version: 0, // clean context format doesn't carry version
-                id,
-                timestamp,
-                cwd,
-            });
-            i += 1; // skip blank line
-            continue;
-        }
-
-        // Separator line: --- <timestamp> [optional annotation] ---
-        if line.starts_with("--- ") && line.ends_with(" ---") {
-            let inner = &line[4..line.len() - 4];
-
-            // Model change: --- <ts> [model: <id> (<provider>)] ---
-            if let Some(rest) = extract_bracket(inner, "model: ") {
-                let timestamp = inner[..inner.find('[').unwrap()].trim().to_string();
-                // Parse "model_id (provider)"
-                if let Some((model_id, provider)) = parse_model_annotation(rest) {
-                    events.push(SessionEvent::ModelChange {
-                        timestamp,
-                        provider,
-                        model_id,
-                    });
-                }
-                i += 1;
-                if i < lines.len() && lines[i].is_empty() {
-                    i += 1;
-                }
-                continue;
-            }
-
-            // Thinking level change: --- <ts> [thinking-level: <level>] ---
-            if let Some(rest) = extract_bracket(inner, "thinking-level: ") {
-                let timestamp = inner[..inner.find('[').unwrap()].trim().to_string();
-                events.push(SessionEvent::ThinkingLevelChange {
-                    timestamp,
-                    level: rest.to_string(),
-                });
-                i += 1;
-                if i < lines.len() && lines[i].is_empty() {
-                    i += 1;
-                }
-                continue;
-            }
+    let parsed = agenticlaw_ctx::parse(content);
+    ParseResult {
+        events: from_ctx_events(parsed.events),
+        errors: parsed.errors,
+    }
+}
 
-            // Compaction: --- <ts> [compaction] ---
-            if inner.ends_with("[compaction]") {
-                let timestamp = inner[..inner.find('[').unwrap()].trim().to_string();
-                i += 1;
-                let mut summary_lines = Vec::new();
-                while i < lines.len() && !lines[i].starts_with("--- ") {
-                    if lines[i].is_empty()
-                        && i + 1 < lines.len()
-                        && lines[i + 1].starts_with("--- ")
-                    {
-                        break;
-                    }
-                    summary_lines.push(lines[i]);
-                    i += 1;
+/// .ctx events as session events: each tool result is folded into the
+/// call it answers.
+pub fn from_ctx_events(events: Vec<Event>) -> Vec<SessionEvent> {
+    let mut out = Vec::new();
+    for event in events {
+        match event {
+            Event::Header(header) => out.push(SessionEvent::Header {
+                version: header.version,
+                id: header.id,
+                timestamp: header.started,
+                cwd: header.cwd,
+            }),
+            Event::Context { timestamp, text } => out.push(text_turn(timestamp, "system", text)),
+            Event::User {
+                timestamp,
+                mut text,
+                attachments,
+            } => {
+                for a in attachments {
+                    text.push_str(&format!(
+                        "\n[attachment:{}] {}",
+                        a.media_type,
+                        a.path.display()
+                    ));
                 }
-                // Skip trailing blank
-                if i < lines.len() && lines[i].is_empty() {
-                    i += 1;
+                if !text.is_empty() {
+                    out.push(text_turn(timestamp, "user", text));
                 }
-                events.push(SessionEvent::Compaction {
-                    timestamp,
-                    summary: summary_lines.join("\n"),
-                });
-                continue;
             }
-
-            // Regular turn: --- <timestamp> ---
-            let timestamp = inner.to_string();
-            i += 1;
-
-            // Check for <up> tag (user turn)
-            let is_user = i < lines.len() && lines[i] == "<up>";
-            if is_user {
-                i += 1;
-            }
-
-            let mut contents = Vec::new();
-            while i < lines.len() {
-                let l = lines[i];
-                // End of user turn
-                if is_user && l == "</up>" {
-                    i += 1;
-                    break;
-                }
-                // Next separator
-                if l.starts_with("--- ") && l.ends_with(" ---") {
-                    break;
-                }
-                // Blank line after non-user turn = end of turn
-                if !is_user
-                    && l.is_empty()
-                    && (i + 1 >= lines.len() || lines[i + 1].starts_with("--- "))
-                {
-                    i += 1;
-                    break;
-                }
-
-                // Thinking block
-                if let Some(thinking) = l.strip_prefix("[thinking] ") {
-                    contents.push(TurnContent::Thinking(thinking.to_string()));
-                    i += 1;
-                    continue;
-                }
-
-                // Tool interaction
-                if l.starts_with("[tool:") {
-                    let (interaction, next_i) = parse_tool_block(&lines, i);
-                    contents.push(TurnContent::Tool(interaction));
-                    i = next_i;
-                    continue;
-                }
-
-                // Token usage line (skip, metadata only)
-                if l.starts_with("[tokens: ") {
-                    i += 1;
-                    continue;
-                }
-
-                // Regular text
-                if !l.is_empty() || (!is_user && !contents.is_empty()) {
-                    // Append to last text block or create new one
-                    if let Some(TurnContent::Text(existing)) = contents.last_mut() {
-                        existing.push('\n');
-                        existing.push_str(l);
-                    } else if !l.is_empty() {
-                        contents.push(TurnContent::Text(l.to_string()));
-                    }
+            Event::Assistant {
+                timestamp,
+                blocks,
+                usage,
+            } => {
+                let contents: Vec<TurnContent> =
+                    blocks.into_iter().filter_map(turn_content).collect();
+                if !contents.is_empty() {
+                    out.push(SessionEvent::Turn(Turn {
+                        timestamp,
+                        role: "assistant".to_string(),
+                        contents,
+                        usage: usage.map(|u| Usage {
+                            input: Some(u.input),
+                            output: Some(u.output),
+                            cache_read: Some(u.cache_read),
+                            cache_write: Some(u.cache_write),
+                            total_tokens: Some(u.total),
+                            cost: None,
+                        }),
+                    }));
                 }
-                i += 1;
             }
-
-            // Skip trailing blank after turn
-            if i < lines.len() && lines[i].is_empty() {
-                i += 1;
-            }
-
-            if !contents.is_empty() {
-                let role = if is_user { "user" } else { "assistant" }.to_string();
-                events.push(SessionEvent::Turn(Turn {
-                    timestamp,
-                    role,
-                    contents,
-                    usage: None,
-                }));
+            Event::ToolResult {
+                timestamp,
+                tool_use_id,
+                content,
+                is_error,
+                ..
+            } => match pending_call(&mut out, &tool_use_id) {
+                Some(call) => call.result = Some(ToolResultInfo { content, is_error }),
+                None => out.push(text_turn(timestamp, "user", content)),
+            },
+            Event::ModelChange {
+                timestamp,
+                model,
+                provider,
+            } => out.push(SessionEvent::ModelChange {
+                timestamp,
+                provider,
+                model_id: model,
+            }),
+            Event::ThinkingLevel { timestamp, level } => {
+                out.push(SessionEvent::ThinkingLevelChange { timestamp, level })
             }
-            continue;
-        }
-
-        // Unrecognized line outside a turn
-        if !line.is_empty() {
-            errors.push(ParseError {
-                line: i + 1,
-                message: format!("Unexpected content outside turn: {}", line),
-            });
+            Event::Compaction {
+                timestamp, summary, ..
+            } => out.push(SessionEvent::Compaction { timestamp, summary }),
+            Event::Rewind { .. } => {}
         }
-        i += 1;
     }
+    out
+}
 
-    ParseResult { events, errors }
+fn text_turn(timestamp: String, role: &str, text: String) -> SessionEvent {
+    SessionEvent::Turn(Turn {
+        timestamp,
+        role: role.to_string(),
+        contents: vec![TurnContent::Text(text)],
+        usage: None,
+    })
 }
 
-fn extract_bracket<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
-    let start = s.find('[')?;
-    let end = s.rfind(']')?;
-    let inner = &s[start + 1..end];
-    inner.strip_prefix(prefix)
+fn turn_content(block: Block) -> Option<TurnContent> {
+    Some(match block {
+        Block::Text(text) => TurnContent::Text(text),
+        Block::Thinking { text, .. } => TurnContent::Thinking(text),
+        // Redacted thinking is opaque — nothing readable to keep
+        Block::RedactedThinking { .. } => return None,
+        Block::ToolCall { id, name, input } => TurnContent::Tool(ToolInteraction {
+            id,
+            name,
+            arguments: input,
+            result: None,
+        }),
+        Block::LegacyTool {
+            name,
+            summary,
+            result,
+            is_error,
+        } => TurnContent::Tool(ToolInteraction {
+            id: String::new(),
+            arguments: legacy_arguments(&name, &summary),
+            name,
+            result: result.map(|content| ToolResultInfo { content, is_error }),
+        }),
+    })
 }
 
-fn parse_model_annotation(s: &str) -> Option<(String, String)> {
-    // "claude-opus-4-6 (anthropic)" → ("claude-opus-4-6", "anthropic")
-    let paren_start = s.find('(')?;
-    let paren_end = s.find(')')?;
-    let model_id = s[..paren_start].trim().to_string();
-    let provider = s[paren_start + 1..paren_end].to_string();
-    Some((model_id, provider))
+/// The unanswered call with this id, latest turn first.
+fn pending_call<'a>(events: &'a mut [SessionEvent], id: &str) -> Option<&'a mut ToolInteraction> {
+    events.iter_mut().rev().find_map(|e| match e {
+        SessionEvent::Turn(turn) => turn.contents.iter_mut().find_map(|c| match c {
+            TurnContent::Tool(call) if call.id == id && call.result.is_none() => Some(call),
+            _ => None,
+        }),
+        _ => None,
+    })
 }
 
-fn parse_tool_block(lines: &[&str], start: usize) -> (ToolInteraction, usize) {
-    let line = lines[start];
-    // [tool:name] args_summary
-    let colon = line.find(':').unwrap_or(5);
-    let bracket_end = line.find(']').unwrap_or(line.len());
-    let name = line[colon + 1..bracket_end].to_string();
-    let args_text = line[bracket_end + 1..].trim().to_string();
-
-    let mut i = start + 1;
-    let mut result_lines = Vec::new();
-    let mut is_error = false;
-
-    while i < lines.len() {
-        let l = lines[i];
-        if let Some(content) = l.strip_prefix("  → ") {
-            if result_lines.is_empty() && content.starts_with("error: ") {
-                is_error = true;
-                result_lines.push(content.strip_prefix("error: ").unwrap().to_string());
-            } else {
-                result_lines.push(content.to_string());
-            }
-            i += 1;
-        } else if l.starts_with("  ... (") && l.ends_with(" lines omitted)") {
-            // Truncation marker — skip
-            i += 1;
-        } else {
-            break;
-        }
+/// Arguments rebuilt from an old `[tool:name] summary` line (best-effort).
+fn legacy_arguments(name: &str, summary: &str) -> serde_json::Value {
+    if summary.is_empty() {
+        return serde_json::json!({});
+    }
+    match name {
+        "read" | "write" => serde_json::json!({"file_path": summary}),
+        "bash" => serde_json::json!({"command": summary}),
+        "glob" => serde_json::json!({"pattern": summary}),
+        _ => serde_json::json!({"summary": summary}),
     }
-
-    let result = if result_lines.is_empty() {
-        None
-    } else {
-        Some(ToolResultInfo {
-            content: result_lines.join("\n"),
-            is_error,
-        })
-    };
-
-    // Build a simple args JSON from the summary (best-effort)
-    let arguments = if !args_text.is_empty() {
-        match name.as_str() {
-            "read" | "write" => serde_json::json!({"file_path": args_text}),
-            "bash" => serde_json::json!({"command": args_text}),
-            "glob" => serde_json::json!({"pattern": args_text}),
-            _ => serde_json::json!({"summary": args_text}),
-        }
-    } else {
-        serde_json::json!({})
-    };
-
-    (
-        ToolInteraction {
-            name,
-            arguments,
-            result,
-        },
-        i,
-    )
 }
 
 // ---------------------------------------------------------------------------
@@ -600,30 +421,18 @@ pub fn init_session(
     cwd: Option<&str>,
     context_files: &[&str], // file contents in order
 ) -> String {
-    let mut out = String::new();
-    out.push_str(&format!("--- session: {} ---\n", session_id));
-    out.push_str(&format!("started: {}\n", timestamp));
-    if let Some(cwd) = cwd {
-        out.push_str(&format!("cwd: {}\n", cwd));
-    }
-    out.push('\n');
-
-    // Context files become the first turn — plain text, no <up> tags
+    let mut events = vec![Event::Header(Header::new(session_id, timestamp, cwd))];
     if !context_files.is_empty() {
-        out.push_str(&format!("--- {} ---\n", timestamp));
-        for (i, content) in context_files.iter().enumerate() {
-            out.push_str(content);
-            if !content.ends_with('\n') {
-                out.push('\n');
-            }
-            if i < context_files.len() - 1 {
-                out.push('\n');
-            }
-        }
-        out.push('\n');
+        let text: Vec<&str> = context_files
+            .iter()
+            .map(|c| c.trim_end_matches('\n'))
+            .collect();
+        events.push(Event::Context {
+            timestamp: timestamp.to_string(),
+            text: text.join("\n\n"),
+        });
     }
-
-    out
+    agenticlaw_ctx::render(&events)
 }
 
 // ---------------------------------------------------------------------------
@@ -687,7 +496,7 @@ pub fn to_wire(events: &[SessionEvent]) -> String {
                         TurnContent::Tool(interaction) => {
                             content_blocks.push(serde_json::json!({
                                 "type": "toolCall",
-                                "id": format!("tc-{}", interaction.name),
+                                "id": interaction.id,
                                 "name": interaction.name,
                                 "arguments": interaction.arguments,
                             }));
@@ -711,11 +520,11 @@ pub fn to_wire(events: &[SessionEvent]) -> String {
                         if let Some(ref result) = interaction.result {
                             let obj = serde_json::json!({
                                 "type": "message",
-                                "id": format!("tr-{}", interaction.name),
+                                "id": format!("tr-{}", interaction.id),
                                 "timestamp": turn.timestamp,
                                 "message": {
                                     "role": "toolResult",
-                                    "toolCallId": format!("tc-{}", interaction.name),
+                                    "toolCallId": interaction.id,
                                     "toolName": interaction.name,
                                     "content": [{"type": "text", "text": result.content}],
                                     "isError": result.is_error,
@@ -765,39 +574,42 @@ pub fn from_wire_response(jsonl_line: &str) -> Option<String> {
     }
 
     let timestamp = record.get("timestamp")?.as_str()?;
-    let mut out = format!("--- {} ---\n", timestamp);
-
-    if let Some(blocks) = msg.get("content").and_then(|c| c.as_array()) {
-        for block in blocks {
+    let str_field = |block: &serde_json::Value, key: &str| {
+        block.get(key).and_then(|v| v.as_str()).map(String::from)
+    };
+    let mut blocks = Vec::new();
+    if let Some(content) = msg.get("content").and_then(|c| c.as_array()) {
+        for block in content {
             match block.get("type").and_then(|t| t.as_str()) {
                 Some("text") => {
-                    if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
-                        out.push_str(text);
-                        out.push('\n');
+                    if let Some(text) = str_field(block, "text") {
+                        blocks.push(Block::Text(text));
                     }
                 }
                 Some("thinking") => {
-                    if let Some(thinking) = block.get("thinking").and_then(|t| t.as_str()) {
-                        out.push_str(&format!("[thinking] {}\n", thinking));
+                    if let Some(text) = str_field(block, "thinking") {
+                        blocks.push(Block::Thinking {
+                            text,
+                            signature: str_field(block, "thinkingSignature"),
+                        });
                     }
                 }
-                Some("toolCall") => {
-                    let name = block
-                        .get("name")
-                        .and_then(|n| n.as_str())
-                        .unwrap_or("unknown");
-                    let args = block
+                Some("toolCall") => blocks.push(Block::ToolCall {
+                    id: str_field(block, "id").unwrap_or_default(),
+                    name: str_field(block, "name").unwrap_or_else(|| "unknown".to_string()),
+                    input: block
                         .get("arguments")
                         .cloned()
-                        .unwrap_or(serde_json::json!({}));
-                    let summary = summarize_tool_args(name, &args);
-                    out.push_str(&format!("[tool:{}] {}\n", name, summary));
-                }
+                        .unwrap_or(serde_json::json!({})),
+                }),
                 _ => {}
             }
         }
     }
 
-    out.push('\n');
-    Some(out)
+    Some(agenticlaw_ctx::render_event(&Event::Assistant {
+        timestamp: timestamp.to_string(),
+        blocks,
+        usage: None,
+    }))
 }
@@ -317,8 +317,8 @@ fn context_emit_assistant_turn_no_up_tags() {
 #[test]
 fn context_emit_tool_interaction() {
     let ctx = jsonl_to_context(sample_jsonl(), &EmitOptions::default());
-    assert!(ctx.contains("[tool:read] /etc/app/config.toml"));
-    assert!(ctx.contains("  → [server]"));
+    assert!(ctx.contains(r#"[tool:read id=tc1] {"file_path":"/etc/app/config.toml"}"#));
+    assert!(ctx.contains("<up>\n[result id=tc1]\n[server]"));
     assert!(ctx.contains("port = 8080"));
 }
 
@@ -338,7 +338,7 @@ fn context_emit_compaction() {
 #[test]
 fn context_emit_thinking_excluded_by_default() {
     let ctx = jsonl_to_context(sample_jsonl(), &EmitOptions::default());
-    assert!(!ctx.contains("[thinking]"));
+    assert!(!ctx.contains("<thinking>"));
     assert!(!ctx.contains("I need to read the config"));
 }
 
@@ -351,7 +351,7 @@ fn context_emit_thinking_included_with_flag() {
             ..Default::default()
         },
     );
-    assert!(ctx.contains("[thinking] I need to read the config file first."));
+    assert!(ctx.contains("<thinking>\nI need to read the config file first.\n</thinking>"));
 }
 
 #[test]
@@ -369,7 +369,32 @@ fn context_emit_usage_included_with_flag() {
             ..Default::default()
         },
     );
-    assert!(ctx.contains("[tokens: 100 in, 50 out, 200 cached, 350 total]"));
+    assert!(ctx.contains("[tokens: 100 in, 50 out, 200 cached, 0 cache-write, 350 total]"));
+}
+
+#[test]
+fn context_emit_cuts_long_tool_results_unless_raw() {
+    let output: String = (1..=30).map(|i| format!("line {}\\n", i)).collect();
+    let jsonl = format!(
+        r#"{{"type":"session","version":3,"id":"raw-001","timestamp":"2026-02-16T10:00:00.000Z"}}
+{{"type":"message","id":"m1","timestamp":"2026-02-16T10:00:01.000Z","message":{{"role":"assistant","content":[{{"type":"toolCall","id":"tc1","name":"bash","arguments":{{"command":"seq 30"}}}}]}}}}
+{{"type":"message","id":"m2","timestamp":"2026-02-16T10:00:02.000Z","message":{{"role":"toolResult","toolCallId":"tc1","toolName":"bash","content":[{{"type":"text","text":"{}"}}],"isError":false}}}}"#,
+        output
+    );
+
+    let ctx = jsonl_to_context(&jsonl, &EmitOptions::default());
+    assert!(ctx.contains("line 10\n... (10 lines omitted)\nline 21\n"));
+    assert!(!ctx.contains("line 15\n"));
+
+    let ctx = jsonl_to_context(
+        &jsonl,
+        &EmitOptions {
+            raw: true,
+            ..Default::default()
+        },
+    );
+    assert!(!ctx.contains("omitted"));
+    assert!(ctx.contains("line 15\n"));
 }
 
 #[test]
@@ -525,6 +550,26 @@ fn context_parse_thinking() {
     }
 }
 
+#[test]
+fn context_parse_thinking_block() {
+    let ctx = "--- session: s1 ---\nstarted: 2026-01-01T00:00:00Z\n\n--- 2026-01-01T00:02:00Z ---\n<thinking signature=\"sig\">\nFirst check the file.\nThen answer.\n</thinking>\n<thinking redacted=\"opaque\">\n</thinking>\nThe answer is 42.\n[tool:read] /tmp/a\n\n";
+    let result = context::parse(ctx);
+    assert!(result.errors.is_empty());
+    let crate::transform::SessionEvent::Turn(turn) = &result.events[1] else {
+        panic!("Expected Turn");
+    };
+    assert_eq!(turn.contents.len(), 3);
+    match &turn.contents[0] {
+        crate::transform::TurnContent::Thinking(t) => {
+            assert_eq!(t, "First check the file.\nThen answer.")
+        }
+        _ => panic!("Expected Thinking"),
+    }
+    assert!(
+        matches!(&turn.contents[1], crate::transform::TurnContent::Text(t) if t == "The answer is 42.")
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Round-trip tests: JSONL → clean context → parse → verify
 // ---------------------------------------------------------------------------
@@ -781,10 +826,11 @@ fn init_session_parses_back() {
     let ctx = context::init_session("parse-001", "2026-02-16T12:00:00Z", Some("/tmp"), &[soul]);
     let result = context::parse(&ctx);
     assert!(result.errors.is_empty());
-    // Header + one assistant turn (the preloaded context)
+    // Header + the preloaded context turn
     assert_eq!(result.events.len(), 2);
     if let crate::transform::SessionEvent::Turn(turn) = &result.events[1] {
-        assert_eq!(turn.role, "assistant"); // no <up>, so it's assistant
+        // Marked [context], so it isn't read as model output
+        assert_eq!(turn.role, "system");
         if let crate::transform::TurnContent::Text(text) = &turn.contents[0] {
             assert!(text.contains("You are an agent."));
         }
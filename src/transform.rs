@@ -4,6 +4,7 @@ use crate::types::*;
 
 /// A linked turn: an assistant tool call paired with its result.
 pub struct ToolInteraction {
+    pub id: String,
     pub name: String,
     pub arguments: serde_json::Value,
     pub result: Option<ToolResultInfo>,
@@ -125,6 +126,7 @@ pub fn transform(records: Vec<Record>) -> Vec<SessionEvent> {
                             } => {
                                 let result = tool_results.remove(id);
                                 contents.push(TurnContent::Tool(ToolInteraction {
+                                    id: id.clone(),
                                     name: name.clone(),
                                     arguments: arguments.clone(),
                                     result,
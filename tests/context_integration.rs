@@ -249,8 +249,8 @@ fn context_usable_as_memory_reference() {
     assert!(ctx.contains("HashMap"));
     assert!(ctx.contains("1c36ca9"));
     // Can find what tools were used
-    assert!(ctx.contains("[tool:read]"));
-    assert!(ctx.contains("[tool:bash]"));
+    assert!(ctx.contains("[tool:read id="));
+    assert!(ctx.contains("[tool:bash id="));
 }
 
 #[test]
@@ -286,7 +286,10 @@ fn context_distinguishes_input_from_output() {
     let up_count = ctx.matches("<up>").count();
     let close_count = ctx.matches("</up>").count();
     assert_eq!(up_count, close_count, "Mismatched <up>/</ up> tags");
-    assert_eq!(up_count, 2, "Expected 2 user turns");
+    // Tool results are input too, each in an <up> block of its own
+    let result_count = ctx.matches("<up>\n[result id=").count();
+    assert_eq!(result_count, 5, "Expected one result per tool call");
+    assert_eq!(up_count - result_count, 2, "Expected 2 user turns");
 
     // User content is inside tags, assistant content is not
     let in_up = extract_up_content(&ctx);
@@ -324,7 +327,7 @@ fn context_with_thinking_provides_reasoning_chain() {
         },
     );
 
-    assert!(ctx.contains("[thinking]"));
+    assert!(ctx.contains("<thinking>"));
     assert!(ctx.contains("I need to"));
 
     // Parse it back and verify thinking is preserved
@@ -351,9 +354,19 @@ fn context_format_is_grep_friendly() {
     let tool_lines: Vec<&str> = ctx.lines().filter(|l| l.starts_with("[tool:")).collect();
     assert_eq!(tool_lines.len(), 5);
 
-    // User input is greppable via <up> markers
-    let up_lines: Vec<&str> = ctx.lines().filter(|l| *l == "<up>").collect();
+    // User input is greppable via <up> markers, tool results by id
+    let lines: Vec<&str> = ctx.lines().collect();
+    let up_lines: Vec<&str> = lines
+        .windows(2)
+        .filter(|w| w[0] == "<up>" && !w[1].starts_with("[result id="))
+        .map(|w| w[0])
+        .collect();
     assert_eq!(up_lines.len(), 2);
+    let result_lines: Vec<&str> = ctx
+        .lines()
+        .filter(|l| l.starts_with("[result id="))
+        .collect();
+    assert_eq!(result_lines.len(), 5);
 
     // Timestamps are greppable
     let ts_lines: Vec<&str> = ctx.lines().filter(|l| l.starts_with("--- 2026-")).collect();
@@ -368,8 +381,13 @@ fn context_no_json_artifacts() {
     // No JSON structural characters from serialization
     assert!(!ctx.contains(r#""type":"#));
     assert!(!ctx.contains(r#""role":"#));
-    assert!(!ctx.contains(r#""content":"#));
     assert!(!ctx.contains(r#""parentId":"#));
+    // Tool call arguments are kept as JSON so calls can be replayed; no
+    // other line has any
+    assert!(!ctx
+        .lines()
+        .filter(|l| !l.starts_with("[tool:"))
+        .any(|l| l.contains(r#""content":"#)));
 }
 
 // ===========================================================================